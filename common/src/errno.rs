@@ -0,0 +1,76 @@
+//! Error numbers returned negated by syscalls
+
+/// Operation not permitted
+pub const EPERM: isize = 1;
+/// No such file or directory
+pub const ENOENT: isize = 2;
+/// Interrupted system call
+pub const EINTR: isize = 4;
+/// No such device or address
+pub const ENXIO: isize = 6;
+/// Bad file number
+pub const EBADF: isize = 9;
+/// Try again
+pub const EAGAIN: isize = 11;
+/// Out of memory
+pub const ENOMEM: isize = 12;
+/// Bad address
+pub const EFAULT: isize = 14;
+/// Device or resource busy
+pub const EBUSY: isize = 16;
+/// File exists
+pub const EEXIST: isize = 17;
+/// No such device
+pub const ENODEV: isize = 19;
+/// Not a directory
+pub const ENOTDIR: isize = 20;
+/// Is a directory
+pub const EISDIR: isize = 21;
+/// Invalid argument
+pub const EINVAL: isize = 22;
+/// Not a typewriter
+pub const ENOTTY: isize = 25;
+/// No space left on device
+pub const ENOSPC: isize = 28;
+/// Broken pipe
+pub const EPIPE: isize = 32;
+/// Resource deadlock would occur
+pub const EDEADLK: isize = 35;
+/// File name too long
+pub const ENAMETOOLONG: isize = 36;
+/// Directory not empty
+pub const ENOTEMPTY: isize = 39;
+/// Socket operation on non-socket
+pub const ENOTSOCK: isize = 88;
+/// Destination address required
+pub const EDESTADDRREQ: isize = 89;
+/// Message too long
+pub const EMSGSIZE: isize = 90;
+/// Protocol wrong type for socket
+pub const EPROTOTYPE: isize = 91;
+/// Protocol not supported
+pub const EPROTONOSUPPORT: isize = 93;
+/// Operation not supported on transport endpoint
+pub const EOPNOTSUPP: isize = 95;
+/// Address family not supported by protocol
+pub const EAFNOSUPPORT: isize = 97;
+/// Address already in use
+pub const EADDRINUSE: isize = 98;
+/// Cannot assign requested address
+pub const EADDRNOTAVAIL: isize = 99;
+/// Network is unreachable
+pub const ENETUNREACH: isize = 101;
+/// Connection reset by peer
+pub const ECONNRESET: isize = 104;
+/// Transport endpoint is already connected
+pub const EISCONN: isize = 106;
+/// Transport endpoint is not connected
+pub const ENOTCONN: isize = 107;
+/// Connection timed out
+pub const ETIMEDOUT: isize = 110;
+/// Connection refused
+pub const ECONNREFUSED: isize = 111;
+/// Operation already in progress
+pub const EALREADY: isize = 114;
+/// Operation now in progress
+pub const EINPROGRESS: isize = 115;
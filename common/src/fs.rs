@@ -0,0 +1,118 @@
+//! File metadata shared by the kernel and user programs
+
+/// Type of a file, the values are the `d_type` of directory entries.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    Fifo = 1,
+    CharDevice = 2,
+    Directory = 4,
+    BlockDevice = 6,
+    Regular = 8,
+    Symlink = 10,
+    Socket = 12,
+}
+
+/// Mask of the file type bits of `Stat::mode`.
+pub const S_IFMT: u32 = 0o170000;
+
+impl FileType {
+    pub fn from_dtype(dtype: u8) -> Option<Self> {
+        Some(match dtype {
+            1 => Self::Fifo,
+            2 => Self::CharDevice,
+            4 => Self::Directory,
+            6 => Self::BlockDevice,
+            8 => Self::Regular,
+            10 => Self::Symlink,
+            12 => Self::Socket,
+            _ => return None,
+        })
+    }
+
+    /// Return the file type bits of `Stat::mode`.
+    pub fn mode(self) -> u32 {
+        (self as u32) << 12
+    }
+}
+
+/// Metadata of a file returned by `fstat`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stat {
+    /// Identifies the filesystem.
+    pub dev: u64,
+    /// Identifies the file in its filesystem.
+    pub ino: u64,
+    /// File type and permission bits.
+    pub mode: u32,
+    pub nlink: u32,
+    pub size: u64,
+}
+
+impl Stat {
+    pub fn file_type(&self) -> Option<FileType> {
+        FileType::from_dtype(((self.mode & S_IFMT) >> 12) as u8)
+    }
+}
+
+/// Header of the records returned by `getdents64`, followed by the NUL-terminated name and
+/// padded to 8 bytes.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Dirent64 {
+    pub ino: u64,
+    /// Offset of the next record in the directory.
+    pub off: i64,
+    /// Length of the record.
+    pub reclen: u16,
+    pub dtype: u8,
+}
+
+/// Offset of the name in a `getdents64` record.
+pub const DIRENT64_NAME_OFFSET: usize = 19;
+
+/// `dirfd` of the *at syscalls resolving relative paths from the working directory.
+pub const AT_FDCWD: isize = -100;
+/// `unlinkat` flag to remove a directory.
+pub const AT_REMOVEDIR: u32 = 0x200;
+
+/// `fcntl` commands.
+pub const F_DUPFD: usize = 0;
+pub const F_GETFD: usize = 1;
+pub const F_SETFD: usize = 2;
+pub const F_GETFL: usize = 3;
+pub const F_SETFL: usize = 4;
+pub const F_DUPFD_CLOEXEC: usize = 1030;
+/// Set the capacity of a pipe, rounded up to pages, and return it.
+pub const F_SETPIPE_SZ: usize = 1031;
+pub const F_GETPIPE_SZ: usize = 1032;
+
+/// The file descriptor flag of `F_GETFD` and `F_SETFD`, closed by `exec`.
+pub const FD_CLOEXEC: usize = 1;
+
+/// Writes to a pipe of at most this many bytes are not interleaved with other writes.
+pub const PIPE_BUF: usize = 4096;
+
+/// `eventfd2` flag for reads to decrement the counter by one instead of resetting it.
+pub const EFD_SEMAPHORE: u32 = 1;
+
+#[cfg(all(unix, test))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_file_type_mode() {
+        assert_eq!(FileType::Directory.mode(), 0o040000);
+        assert_eq!(FileType::Regular.mode(), 0o100000);
+        let stat = Stat {
+            mode: FileType::Fifo.mode() | 0o644,
+            ..Default::default()
+        };
+        assert_eq!(stat.file_type(), Some(FileType::Fifo));
+        assert_eq!(
+            core::mem::offset_of!(Dirent64, dtype) + 1,
+            DIRENT64_NAME_OFFSET
+        );
+    }
+}
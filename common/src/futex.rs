@@ -0,0 +1,6 @@
+//! Operations of the futex syscall
+
+/// Block if the futex word still holds the expected value.
+pub const FUTEX_WAIT: usize = 0;
+/// Wake up at most the given number of waiters.
+pub const FUTEX_WAKE: usize = 1;
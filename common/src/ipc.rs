@@ -0,0 +1,35 @@
+//! Constants and structures of System V IPC
+
+/// The key of a segment which no other `shmget` finds.
+pub const IPC_PRIVATE: i32 = 0;
+/// Create the segment if the key has none.
+pub const IPC_CREAT: u32 = 0o1000;
+/// With `IPC_CREAT`, fail if the key already has a segment.
+pub const IPC_EXCL: u32 = 0o2000;
+
+/// `shmctl` command: remove the segment once it is detached everywhere.
+pub const IPC_RMID: usize = 0;
+/// `shmctl` command: set the permission bits from `ShmIdDs::mode`.
+pub const IPC_SET: usize = 1;
+/// `shmctl` command: return the state of the segment.
+pub const IPC_STAT: usize = 2;
+
+/// `shmat` flag: attach read-only.
+pub const SHM_RDONLY: u32 = 0o10000;
+/// `shmat` flag: round the address down to a page.
+pub const SHM_RND: u32 = 0o20000;
+
+/// The state of a shared memory segment, returned by `IPC_STAT`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShmIdDs {
+    pub key: i32,
+    /// Permission bits.
+    pub mode: u32,
+    /// Size in bytes, as given to `shmget`.
+    pub size: u64,
+    /// The process which has created the segment.
+    pub cpid: u32,
+    /// Number of attachments, counting those inherited by fork.
+    pub nattch: u32,
+}
@@ -2,8 +2,17 @@
 #![cfg_attr(not(unix), feature(custom_test_frameworks))]
 #![cfg_attr(not(unix), test_runner(test_runner))]
 
+pub mod errno;
+pub mod fs;
+pub mod futex;
+pub mod ipc;
+pub mod mqueue;
+pub mod poll;
 pub mod sig;
+pub mod socket;
 pub mod syscall_id;
+pub mod termios;
+pub mod time;
 
 #[cfg(all(not(unix), test))]
 fn test_runner(_tests: &[&dyn Fn()]) {
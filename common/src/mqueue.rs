@@ -0,0 +1,27 @@
+//! Attributes and limits of POSIX message queues
+
+/// Priorities are below this.
+pub const MQ_PRIO_MAX: u32 = 32768;
+/// Capacity of a queue created without attributes.
+pub const MQ_DEFAULT_MAXMSG: usize = 10;
+pub const MQ_DEFAULT_MSGSIZE: usize = 8192;
+/// Largest capacity of a queue.
+pub const MQ_MAXMSG_MAX: usize = 64;
+pub const MQ_MSGSIZE_MAX: usize = 8192;
+/// Number of queues which may exist at once.
+pub const MQ_QUEUES_MAX: usize = 256;
+
+/// The attributes of a queue, laid out as on Linux.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MqAttr {
+    /// `O_NONBLOCK` or 0, the only flag set by `mq_setattr`.
+    pub flags: i64,
+    /// Maximum number of messages queued.
+    pub maxmsg: i64,
+    /// Maximum size of a message.
+    pub msgsize: i64,
+    /// Number of messages queued, ignored when setting.
+    pub curmsgs: i64,
+    pub reserved: [i64; 4],
+}
@@ -0,0 +1,92 @@
+//! Structures of the poll and select syscalls
+
+use bitflags::bitflags;
+
+bitflags! {
+    /// Events of a file, requested in `PollFd::events` and returned in `PollFd::revents`.
+    #[repr(transparent)]
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct PollEvents: u16 {
+        /// There is data to read.
+        const IN = 0x1;
+        const PRI = 0x2;
+        /// Writing would not block.
+        const OUT = 0x4;
+        /// Only returned.
+        const ERR = 0x8;
+        /// The other end is closed, only returned.
+        const HUP = 0x10;
+        /// The fd is not open, only returned.
+        const NVAL = 0x20;
+    }
+}
+
+/// An fd to be polled, ignored if `fd` is negative.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PollFd {
+    pub fd: i32,
+    pub events: PollEvents,
+    pub revents: PollEvents,
+}
+
+/// Number of fds in an `FdSet`.
+pub const FD_SETSIZE: usize = 1024;
+const FD_SET_WORD_BITS: usize = u64::BITS as usize;
+
+/// A set of fds for select.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FdSet {
+    pub words: [u64; FD_SETSIZE / FD_SET_WORD_BITS],
+}
+
+impl FdSet {
+    pub const fn new() -> Self {
+        Self {
+            words: [0; FD_SETSIZE / FD_SET_WORD_BITS],
+        }
+    }
+
+    pub fn insert(&mut self, fd: usize) {
+        self.words[fd / FD_SET_WORD_BITS] |= 1 << (fd % FD_SET_WORD_BITS);
+    }
+
+    pub fn remove(&mut self, fd: usize) {
+        self.words[fd / FD_SET_WORD_BITS] &= !(1 << (fd % FD_SET_WORD_BITS));
+    }
+
+    pub fn contains(&self, fd: usize) -> bool {
+        self.words[fd / FD_SET_WORD_BITS] & (1 << (fd % FD_SET_WORD_BITS)) != 0
+    }
+
+    /// Return the index of the word holding `fd` and its bit in it.
+    pub fn position(fd: usize) -> (usize, u64) {
+        (fd / FD_SET_WORD_BITS, 1 << (fd % FD_SET_WORD_BITS))
+    }
+}
+
+impl Default for FdSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(unix, test))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fd_set() {
+        let mut set = FdSet::new();
+        set.insert(3);
+        set.insert(64);
+        set.insert(FD_SETSIZE - 1);
+        assert!(set.contains(3) && set.contains(64) && set.contains(FD_SETSIZE - 1));
+        assert!(!set.contains(4) && !set.contains(63));
+        assert_eq!(FdSet::position(64), (1, 1));
+        set.remove(64);
+        assert!(!set.contains(64));
+        assert_eq!(set.words[0], 1 << 3);
+    }
+}
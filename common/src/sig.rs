@@ -55,6 +55,33 @@ impl SignalFlags {
     }
 }
 
+/// `how` of `rt_sigprocmask`, which adds, removes or replaces the blocked signals.
+pub const SIG_BLOCK: usize = 0;
+pub const SIG_UNBLOCK: usize = 1;
+pub const SIG_SETMASK: usize = 2;
+
+/// A signal read from a signalfd, of the size of `struct signalfd_siginfo` on Linux. Only the
+/// signal number is known, the other fields are zero.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignalfdSiginfo {
+    pub signo: u32,
+    pub errno: i32,
+    pub code: i32,
+    pub reserved: [u8; 116],
+}
+
+impl SignalfdSiginfo {
+    pub fn new(signal: SignalFlags) -> Self {
+        Self {
+            signo: signal.to_number() as u32,
+            errno: 0,
+            code: 0,
+            reserved: [0; 116],
+        }
+    }
+}
+
 #[cfg(all(unix, test))]
 mod test {
     #[test]
@@ -70,4 +97,11 @@ mod test {
         assert_eq!(SignalFlags::from_number(2), SignalFlags::SIGINT);
         assert_eq!(SignalFlags::from_number(15), SignalFlags::SIGTERM);
     }
+
+    #[test]
+    fn test_signalfd_siginfo() {
+        use super::{SignalFlags, SignalfdSiginfo};
+        assert_eq!(size_of::<SignalfdSiginfo>(), 128);
+        assert_eq!(SignalfdSiginfo::new(SignalFlags::SIGPIPE).signo, 13);
+    }
 }
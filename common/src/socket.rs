@@ -0,0 +1,171 @@
+//! Constants and addresses of the socket syscalls, laid out as on Linux
+
+use bitflags::bitflags;
+
+/// Address family of local sockets.
+pub const AF_UNIX: u16 = 1;
+/// Address family of IPv4 sockets.
+pub const AF_INET: u16 = 2;
+
+/// The protocols of `AF_INET`, the default one of the socket type is also 0.
+pub const IPPROTO_TCP: usize = 6;
+pub const IPPROTO_UDP: usize = 17;
+
+pub const SOCK_STREAM: u32 = 1;
+pub const SOCK_DGRAM: u32 = 2;
+/// Mask of the socket type in the `type` argument of `socket`, the other bits are flags.
+pub const SOCK_TYPE_MASK: u32 = 0xf;
+/// The same bits as `O_NONBLOCK` and `O_CLOEXEC`.
+pub const SOCK_NONBLOCK: u32 = 0o4000;
+pub const SOCK_CLOEXEC: u32 = 0o2000000;
+
+bitflags! {
+    /// Flags of `sendto` and `recvfrom`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct MsgFlags: u32 {
+        /// Do not block, like `O_NONBLOCK` for this call only.
+        const DONTWAIT = 0x40;
+        /// Do not send `SIGPIPE` to a stream whose peer is gone, `EPIPE` is still returned.
+        const NOSIGNAL = 0x4000;
+    }
+}
+
+/// A socket address of some family, passed to the syscalls with its length.
+pub trait SockAddr {
+    /// The length passed with the address.
+    fn addr_len(&self) -> usize;
+}
+
+/// Size of `SockAddrUn::path`.
+pub const UNIX_PATH_MAX: usize = 108;
+
+/// The address of a local socket: a path in a filesystem, or a name in the abstract namespace
+/// starting with a NUL byte.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SockAddrUn {
+    pub family: u16,
+    pub path: [u8; UNIX_PATH_MAX],
+}
+
+impl SockAddrUn {
+    /// The address of the socket file at `path`.
+    pub fn new(path: &str) -> Self {
+        assert!(path.len() < UNIX_PATH_MAX, "path too long");
+        let mut addr = Self::default();
+        addr.path[..path.len()].copy_from_slice(path.as_bytes());
+        addr
+    }
+
+    /// The address `name` in the abstract namespace, which does not end with a NUL byte.
+    pub fn new_abstract(name: &str) -> Self {
+        assert!(name.len() < UNIX_PATH_MAX, "name too long");
+        let mut addr = Self::default();
+        addr.path[1..=name.len()].copy_from_slice(name.as_bytes());
+        addr
+    }
+
+    /// Return the path of a socket file, `None` for an abstract or unnamed address.
+    pub fn path(&self) -> Option<&str> {
+        let len = self.path.iter().position(|&byte| byte == 0)?;
+        if len == 0 {
+            return None;
+        }
+        core::str::from_utf8(&self.path[..len]).ok()
+    }
+}
+
+impl SockAddr for SockAddrUn {
+    /// The family and the path with its NUL, or the abstract name.
+    fn addr_len(&self) -> usize {
+        let len = match self.path.iter().rposition(|&byte| byte != 0) {
+            Some(last) if self.path[0] == 0 => last + 1,
+            Some(last) => last + 2,
+            None => 0,
+        };
+        SUN_PATH_OFFSET + len
+    }
+}
+
+impl Default for SockAddrUn {
+    /// An unnamed address.
+    fn default() -> Self {
+        Self {
+            family: AF_UNIX,
+            path: [0; UNIX_PATH_MAX],
+        }
+    }
+}
+
+/// Offset of `SockAddrUn::path`, an address of this length is unnamed.
+pub const SUN_PATH_OFFSET: usize = 2;
+
+/// Bind to every address of the host.
+pub const INADDR_ANY: [u8; 4] = [0, 0, 0, 0];
+pub const INADDR_LOOPBACK: [u8; 4] = [127, 0, 0, 1];
+
+/// The address of an IPv4 socket, whose port and address are in network byte order.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SockAddrIn {
+    pub family: u16,
+    pub port: u16,
+    pub addr: [u8; 4],
+    pub zero: [u8; 8],
+}
+
+impl SockAddrIn {
+    pub fn new(addr: [u8; 4], port: u16) -> Self {
+        Self {
+            family: AF_INET,
+            port: port.to_be(),
+            addr,
+            zero: [0; 8],
+        }
+    }
+
+    /// The port in host byte order.
+    pub fn port(&self) -> u16 {
+        u16::from_be(self.port)
+    }
+}
+
+impl SockAddr for SockAddrIn {
+    fn addr_len(&self) -> usize {
+        size_of::<Self>()
+    }
+}
+
+impl Default for SockAddrIn {
+    /// Any address and port.
+    fn default() -> Self {
+        Self::new(INADDR_ANY, 0)
+    }
+}
+
+#[cfg(all(unix, test))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sock_addr_un() {
+        assert_eq!(core::mem::offset_of!(SockAddrUn, path), SUN_PATH_OFFSET);
+        let addr = SockAddrUn::new("/socket");
+        assert_eq!(addr.path(), Some("/socket"));
+        assert_eq!(addr.addr_len(), SUN_PATH_OFFSET + "/socket".len() + 1);
+        let addr = SockAddrUn::new_abstract("name");
+        assert_eq!(addr.path(), None);
+        assert_eq!(&addr.path[..5], b"\0name");
+        assert_eq!(addr.addr_len(), SUN_PATH_OFFSET + 5);
+        assert_eq!(SockAddrUn::default().addr_len(), SUN_PATH_OFFSET);
+    }
+
+    #[test]
+    fn test_sock_addr_in() {
+        let addr = SockAddrIn::new(INADDR_LOOPBACK, 8080);
+        assert_eq!(addr.addr_len(), 16);
+        assert_eq!(addr.port(), 8080);
+        let bytes: [u8; 16] = unsafe { core::mem::transmute(addr) };
+        assert_eq!(bytes[..8], [2, 0, 0x1f, 0x90, 127, 0, 0, 1]);
+    }
+}
@@ -1,10 +1,62 @@
+pub const SYSCALL_EVENTFD2: usize = 19;
+pub const SYSCALL_DUP: usize = 23;
+pub const SYSCALL_DUP3: usize = 24;
+pub const SYSCALL_FCNTL: usize = 25;
+pub const SYSCALL_IOCTL: usize = 29;
+pub const SYSCALL_MKNODAT: usize = 33;
+pub const SYSCALL_MKDIRAT: usize = 34;
+pub const SYSCALL_UNLINKAT: usize = 35;
+pub const SYSCALL_UMOUNT2: usize = 39;
+pub const SYSCALL_MOUNT: usize = 40;
 pub const SYSCALL_OPEN: usize = 56;
 pub const SYSCALL_CLOSE: usize = 57;
-pub const SYSCALL_PIPE: usize = 59;
+pub const SYSCALL_PIPE2: usize = 59;
+pub const SYSCALL_GETDENTS64: usize = 61;
 pub const SYSCALL_READ: usize = 63;
 pub const SYSCALL_WRITE: usize = 64;
+pub const SYSCALL_PSELECT6: usize = 72;
+pub const SYSCALL_PPOLL: usize = 73;
+pub const SYSCALL_SIGNALFD4: usize = 74;
+pub const SYSCALL_FSTAT: usize = 80;
+pub const SYSCALL_TIMERFD_CREATE: usize = 85;
+pub const SYSCALL_TIMERFD_SETTIME: usize = 86;
+pub const SYSCALL_TIMERFD_GETTIME: usize = 87;
 pub const SYSCALL_EXIT: usize = 93;
+pub const SYSCALL_EXIT_GROUP: usize = 94;
+pub const SYSCALL_FUTEX: usize = 98;
 pub const SYSCALL_YIELD: usize = 124;
+pub const SYSCALL_RT_SIGPROCMASK: usize = 135;
+pub const SYSCALL_GETTID: usize = 178;
+pub const SYSCALL_MQ_OPEN: usize = 180;
+pub const SYSCALL_MQ_UNLINK: usize = 181;
+pub const SYSCALL_MQ_TIMEDSEND: usize = 182;
+pub const SYSCALL_MQ_TIMEDRECEIVE: usize = 183;
+pub const SYSCALL_MQ_GETSETATTR: usize = 185;
+pub const SYSCALL_SHMGET: usize = 194;
+pub const SYSCALL_SHMCTL: usize = 195;
+pub const SYSCALL_SHMAT: usize = 196;
+pub const SYSCALL_SHMDT: usize = 197;
+pub const SYSCALL_SOCKET: usize = 198;
+pub const SYSCALL_SOCKETPAIR: usize = 199;
+pub const SYSCALL_BIND: usize = 200;
+pub const SYSCALL_LISTEN: usize = 201;
+pub const SYSCALL_ACCEPT: usize = 202;
+pub const SYSCALL_CONNECT: usize = 203;
+pub const SYSCALL_SENDTO: usize = 206;
+pub const SYSCALL_RECVFROM: usize = 207;
 pub const SYSCALL_FORK: usize = 220;
 pub const SYSCALL_EXEC: usize = 221;
+pub const SYSCALL_ACCEPT4: usize = 242;
 pub const SYSCALL_WAITPID: usize = 260;
+pub const SYSCALL_ENABLE_DEADLOCK_DETECT: usize = 469;
+pub const SYSCALL_THREAD_CREATE: usize = 1000;
+pub const SYSCALL_WAITTID: usize = 1002;
+pub const SYSCALL_MUTEX_CREATE: usize = 1010;
+pub const SYSCALL_MUTEX_LOCK: usize = 1011;
+pub const SYSCALL_MUTEX_UNLOCK: usize = 1012;
+pub const SYSCALL_SEMAPHORE_CREATE: usize = 1020;
+pub const SYSCALL_SEMAPHORE_UP: usize = 1021;
+pub const SYSCALL_SEMAPHORE_DOWN: usize = 1022;
+pub const SYSCALL_CONDVAR_CREATE: usize = 1030;
+pub const SYSCALL_CONDVAR_SIGNAL: usize = 1031;
+pub const SYSCALL_CONDVAR_WAIT: usize = 1032;
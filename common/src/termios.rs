@@ -0,0 +1,131 @@
+//! Terminal attributes and ioctl requests of TTYs, laid out as on Linux
+
+use bitflags::bitflags;
+
+pub const TCGETS: usize = 0x5401;
+pub const TCSETS: usize = 0x5402;
+/// Set the attributes after the output is drained, which is always the case.
+pub const TCSETSW: usize = 0x5403;
+/// Set the attributes and discard the pending input.
+pub const TCSETSF: usize = 0x5404;
+pub const TIOCGPGRP: usize = 0x540f;
+pub const TIOCSPGRP: usize = 0x5410;
+pub const TIOCGWINSZ: usize = 0x5413;
+pub const TIOCSWINSZ: usize = 0x5414;
+
+pub const NCCS: usize = 19;
+
+// indices of the control characters in `Termios::cc`
+pub const VINTR: usize = 0;
+pub const VQUIT: usize = 1;
+pub const VERASE: usize = 2;
+pub const VKILL: usize = 3;
+pub const VEOF: usize = 4;
+pub const VTIME: usize = 5;
+pub const VMIN: usize = 6;
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct InputModes: u32 {
+        /// Translate carriage return to newline on input.
+        const ICRNL = 0o400;
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct OutputModes: u32 {
+        const OPOST = 0o1;
+        const ONLCR = 0o4;
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct LocalModes: u32 {
+        /// Send signals for the INTR and QUIT characters.
+        const ISIG = 0o1;
+        /// Canonical mode, the input is edited and made available line by line.
+        const ICANON = 0o2;
+        const ECHO = 0o10;
+        /// Erase the last character on the screen for the ERASE character.
+        const ECHOE = 0o20;
+        /// Erase the line on the screen for the KILL character.
+        const ECHOK = 0o40;
+        /// Echo newlines even if `ECHO` is not set.
+        const ECHONL = 0o100;
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Termios {
+    pub iflag: InputModes,
+    pub oflag: OutputModes,
+    pub cflag: u32,
+    pub lflag: LocalModes,
+    pub line: u8,
+    pub cc: [u8; NCCS],
+}
+
+impl Default for Termios {
+    /// Canonical mode with echo.
+    fn default() -> Self {
+        let mut cc = [0; NCCS];
+        cc[VINTR] = 0x03; // ^C
+        cc[VQUIT] = 0x1c; // ^\
+        cc[VERASE] = 0x7f;
+        cc[VKILL] = 0x15; // ^U
+        cc[VEOF] = 0x04; // ^D
+        cc[VMIN] = 1;
+        Self {
+            iflag: InputModes::ICRNL,
+            oflag: OutputModes::empty(),
+            cflag: 0,
+            lflag: LocalModes::ISIG
+                | LocalModes::ICANON
+                | LocalModes::ECHO
+                | LocalModes::ECHOE
+                | LocalModes::ECHOK,
+            line: 0,
+            cc,
+        }
+    }
+}
+
+impl Termios {
+    /// Switch to raw mode like `cfmakeraw`, every byte is read as is without echo.
+    pub fn make_raw(&mut self) {
+        self.iflag.remove(InputModes::ICRNL);
+        self.lflag.remove(
+            LocalModes::ISIG
+                | LocalModes::ICANON
+                | LocalModes::ECHO
+                | LocalModes::ECHOE
+                | LocalModes::ECHOK
+                | LocalModes::ECHONL,
+        );
+        self.cc[VMIN] = 1;
+        self.cc[VTIME] = 0;
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WinSize {
+    pub row: u16,
+    pub col: u16,
+    pub xpixel: u16,
+    pub ypixel: u16,
+}
+
+#[cfg(all(unix, test))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_termios_layout() {
+        // same as `struct termios` of the Linux kernel
+        assert_eq!(core::mem::size_of::<Termios>(), 36);
+        let mut termios = Termios::default();
+        termios.make_raw();
+        assert!(!termios.lflag.contains(LocalModes::ICANON));
+        assert_eq!(termios.cc[VINTR], 0x03);
+    }
+}
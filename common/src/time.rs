@@ -0,0 +1,67 @@
+//! Time representation shared by the kernel and user programs
+
+pub const NSEC_PER_SEC: usize = 1_000_000_000;
+pub const NSEC_PER_MSEC: usize = 1_000_000;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+impl TimeSpec {
+    pub fn from_ms(ms: usize) -> Self {
+        Self {
+            sec: ms / 1000,
+            nsec: ms % 1000 * NSEC_PER_MSEC,
+        }
+    }
+
+    /// Round up to milliseconds, saturating at `usize::MAX`.
+    pub fn as_ms(&self) -> usize {
+        self.sec
+            .saturating_mul(1000)
+            .saturating_add(self.nsec.div_ceil(NSEC_PER_MSEC))
+    }
+}
+
+/// Clocks of `timerfd_create`, both count from boot as there is no real-time clock.
+pub const CLOCK_REALTIME: usize = 0;
+pub const CLOCK_MONOTONIC: usize = 1;
+
+/// With `timerfd_settime`, the expiration is an absolute time of the clock.
+pub const TFD_TIMER_ABSTIME: u32 = 1;
+
+/// The setting of a timer, which is disarmed if `value` is zero.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ItimerSpec {
+    /// The period of a periodic timer, zero for a one-shot timer.
+    pub interval: TimeSpec,
+    /// The time until the next expiration.
+    pub value: TimeSpec,
+}
+
+#[cfg(all(unix, test))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_timespec_ms() {
+        assert_eq!(
+            TimeSpec::from_ms(1500),
+            TimeSpec {
+                sec: 1,
+                nsec: 500 * NSEC_PER_MSEC
+            }
+        );
+        assert_eq!(TimeSpec::from_ms(1500).as_ms(), 1500);
+        assert_eq!(TimeSpec { sec: 0, nsec: 1 }.as_ms(), 1);
+        let max = TimeSpec {
+            sec: usize::MAX,
+            nsec: usize::MAX,
+        };
+        assert_eq!(max.as_ms(), usize::MAX);
+    }
+}
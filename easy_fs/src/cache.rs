@@ -1,7 +1,7 @@
 use crate::BLOCK_CACHE_SIZE;
 
 use super::{BLOCK_SIZE, BlockDevice};
-use alloc::{boxed::Box, collections::VecDeque, sync::Arc};
+use alloc::{boxed::Box, collections::VecDeque, sync::Arc, vec::Vec};
 use core::{
     alloc::Layout,
     mem::ManuallyDrop,
@@ -9,7 +9,7 @@ use core::{
     slice,
 };
 use lazy_static::*;
-use spin::Mutex;
+use spin::{Mutex, MutexGuard};
 
 /// Use `ManuallyDrop` to ensure data is deallocated with an alignment of `BLOCK_SIZE`
 struct CacheData(ManuallyDrop<Box<[u8; BLOCK_SIZE]>>);
@@ -67,6 +67,11 @@ impl BlockCache {
         // for alignment and move effciency
         let mut cache = CacheData::new();
         block_device.read_block(block_id, cache.as_mut());
+        Self::with_data(block_id, block_device, cache)
+    }
+
+    /// Wrap data which has been loaded from disk.
+    fn with_data(block_id: usize, block_device: Arc<dyn BlockDevice>, cache: CacheData) -> Self {
         Self {
             cache,
             block_id,
@@ -125,8 +130,15 @@ impl Drop for BlockCache {
     }
 }
 
+/// Identify a block by the address of its device and its id
+type BlockKey = (usize, usize);
+
+fn block_key(block_id: usize, block_device: &Arc<dyn BlockDevice>) -> BlockKey {
+    (Arc::as_ptr(block_device) as *const () as usize, block_id)
+}
+
 pub struct BlockCacheManager {
-    queue: VecDeque<(usize, Arc<Mutex<BlockCache>>)>,
+    queue: VecDeque<(BlockKey, Arc<Mutex<BlockCache>>)>,
 }
 
 impl BlockCacheManager {
@@ -141,32 +153,75 @@ impl BlockCacheManager {
         block_id: usize,
         block_device: Arc<dyn BlockDevice>,
     ) -> Arc<Mutex<BlockCache>> {
-        if let Some(pair) = self.queue.iter().find(|pair| pair.0 == block_id) {
+        let key = block_key(block_id, &block_device);
+        if let Some(pair) = self.queue.iter().find(|pair| pair.0 == key) {
             Arc::clone(&pair.1)
         } else {
-            // substitute
-            if self.queue.len() == BLOCK_CACHE_SIZE {
-                // from front to tail
-                if let Some((idx, _)) = self
-                    .queue
-                    .iter()
-                    .enumerate()
-                    .find(|(_, pair)| Arc::strong_count(&pair.1) == 1)
-                {
-                    self.queue.drain(idx..=idx);
-                } else {
-                    panic!("Run out of BlockCache!");
-                }
-            }
+            self.reserve();
             // load block into mem and push back
             let block_cache = Arc::new(Mutex::new(BlockCache::new(
                 block_id,
                 Arc::clone(&block_device),
             )));
-            self.queue.push_back((block_id, Arc::clone(&block_cache)));
+            self.queue.push_back((key, Arc::clone(&block_cache)));
             block_cache
         }
     }
+
+    /// Make room for one more block, evicting the oldest unused one if the cache is full.
+    fn reserve(&mut self) {
+        if self.queue.len() == BLOCK_CACHE_SIZE {
+            // from front to tail
+            if let Some((idx, _)) = self
+                .queue
+                .iter()
+                .enumerate()
+                .find(|(_, pair)| Arc::strong_count(&pair.1) == 1)
+            {
+                self.queue.drain(idx..=idx);
+            } else {
+                panic!("Run out of BlockCache!");
+            }
+        }
+    }
+
+    /// Load the blocks which are not cached yet with one batched read. No more blocks are
+    /// loaded than there are free or unused slots, so that readahead cannot run the cache out.
+    pub fn prefetch(&mut self, block_ids: &[usize], block_device: &Arc<dyn BlockDevice>) {
+        let available = BLOCK_CACHE_SIZE - self.queue.len()
+            + self
+                .queue
+                .iter()
+                .filter(|pair| Arc::strong_count(&pair.1) == 1)
+                .count();
+        let mut missing: Vec<usize> = Vec::new();
+        for &block_id in block_ids {
+            let key = block_key(block_id, block_device);
+            if !self.queue.iter().any(|pair| pair.0 == key) && !missing.contains(&block_id) {
+                missing.push(block_id);
+            }
+        }
+        missing.truncate(available);
+        if missing.is_empty() {
+            return;
+        }
+        let mut data: Vec<CacheData> = missing.iter().map(|_| CacheData::new()).collect();
+        let mut blocks: Vec<(usize, &mut [u8])> = missing
+            .iter()
+            .copied()
+            .zip(data.iter_mut().map(|cache| cache.as_mut()))
+            .collect();
+        block_device.read_blocks(&mut blocks);
+        for (block_id, cache) in missing.into_iter().zip(data) {
+            // the blocks loaded here are behind the evicted ones in the queue
+            self.reserve();
+            let block_cache = BlockCache::with_data(block_id, Arc::clone(block_device), cache);
+            self.queue.push_back((
+                block_key(block_id, block_device),
+                Arc::new(Mutex::new(block_cache)),
+            ));
+        }
+    }
 }
 
 lazy_static! {
@@ -181,10 +236,32 @@ pub fn get_block(block_id: usize, block_device: &Arc<dyn BlockDevice>) -> Arc<Mu
         .lock()
         .get_block_cache(block_id, Arc::clone(block_device))
 }
-/// Sync all block cache to block device
+/// Load the given blocks into the block cache together, as far as there is room for them
+pub fn prefetch_blocks(block_ids: &[usize], block_device: &Arc<dyn BlockDevice>) {
+    BLOCK_CACHE_MANAGER.lock().prefetch(block_ids, block_device);
+}
+/// Sync all block cache to block device, the dirty blocks of a device are written together
 pub fn block_cache_sync_all() {
     let manager = BLOCK_CACHE_MANAGER.lock();
-    for (_, cache) in manager.queue.iter() {
-        cache.lock().sync();
+    let mut dirty: Vec<MutexGuard<BlockCache>> = manager
+        .queue
+        .iter()
+        .map(|(_, cache)| cache.lock())
+        .filter(|cache| cache.modified)
+        .collect();
+    while let Some(first) = dirty.first() {
+        let block_device = Arc::clone(&first.block_device);
+        let (mut same_device, rest): (Vec<_>, Vec<_>) = dirty
+            .into_iter()
+            .partition(|cache| Arc::ptr_eq(&cache.block_device, &block_device));
+        let blocks: Vec<(usize, &[u8])> = same_device
+            .iter()
+            .map(|cache| (cache.block_id, cache.cache.as_ref()))
+            .collect();
+        block_device.write_blocks(&blocks);
+        for cache in same_device.iter_mut() {
+            cache.modified = false;
+        }
+        dirty = rest;
     }
 }
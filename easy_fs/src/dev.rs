@@ -6,4 +6,17 @@ pub trait BlockDevice: Send + Sync + Any {
     fn read_block(&self, block_id: usize, buf: &mut [u8]);
     ///Write data from buffer to block
     fn write_block(&self, block_id: usize, buf: &[u8]);
+    /// Read several blocks, each into its own buffer. Devices which can have multiple requests
+    /// in flight should override it to submit them together.
+    fn read_blocks(&self, blocks: &mut [(usize, &mut [u8])]) {
+        for (block_id, buf) in blocks.iter_mut() {
+            self.read_block(*block_id, buf);
+        }
+    }
+    /// Write several blocks, each from its own buffer.
+    fn write_blocks(&self, blocks: &[(usize, &[u8])]) {
+        for &(block_id, buf) in blocks {
+            self.write_block(block_id, buf);
+        }
+    }
 }
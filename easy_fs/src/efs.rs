@@ -84,11 +84,18 @@ impl EasyFileSystem {
 
     /// Open a block device as a filesystem
     pub fn open(block_device: Arc<dyn BlockDevice>) -> Arc<Mutex<Self>> {
+        Self::try_open(block_device).expect("Error loading EFS!")
+    }
+
+    /// Open the filesystem on `block_device`, return `None` if it does not hold one
+    pub fn try_open(block_device: Arc<dyn BlockDevice>) -> Option<Arc<Mutex<Self>>> {
         // read SuperBlock
         get_block(0, &block_device)
             .lock()
             .read(0, |super_block: &SuperBlock| {
-                assert!(super_block.is_valid(), "Error loading EFS!");
+                if !super_block.is_valid() {
+                    return None;
+                }
                 let inode_total_blocks =
                     super_block.inode_bitmap_blocks + super_block.inode_area_blocks;
                 let efs = Self {
@@ -101,7 +108,7 @@ impl EasyFileSystem {
                     inode_area_start_block: 1 + super_block.inode_bitmap_blocks,
                     data_area_start_block: 1 + inode_total_blocks + super_block.data_bitmap_blocks,
                 };
-                Arc::new(Mutex::new(efs))
+                Some(Arc::new(Mutex::new(efs)))
             })
     }
 
@@ -111,7 +118,7 @@ impl EasyFileSystem {
         // acquire efs lock temporarily
         let (block_id, block_offset) = efs.lock().get_disk_inode_pos(0);
         // release efs lock
-        Inode::new(block_id, block_offset, Arc::clone(efs), block_device)
+        Inode::new(0, block_id, block_offset, Arc::clone(efs), block_device)
     }
 
     /// Get inode by id
@@ -135,6 +142,12 @@ impl EasyFileSystem {
         self.inode_bitmap.alloc(&self.block_device).unwrap() as u32
     }
 
+    /// Deallocate an inode
+    pub fn dealloc_inode(&mut self, inode_id: u32) {
+        self.inode_bitmap
+            .dealloc(&self.block_device, inode_id as usize);
+    }
+
     /// Allocate a data block
     pub fn alloc_data(&mut self) -> u32 {
         self.data_bitmap.alloc(&self.block_device).unwrap() as u32 + self.data_area_start_block
@@ -1,6 +1,9 @@
 use alloc::{sync::Arc, vec::Vec};
 
-use crate::{BLOCK_SIZE, BlockDevice, cache::get_block};
+use crate::{
+    BLOCK_SIZE, BlockDevice, NAME_MAX, READAHEAD_BLOCKS,
+    cache::{get_block, prefetch_blocks},
+};
 
 pub(crate) type DataBlock = [u8; BLOCK_SIZE];
 pub(crate) type IndirectBlock = [u32; BLOCK_SIZE / 4];
@@ -17,7 +20,7 @@ const DIRECT_BOUND: usize = INODE_DIRECT_COUNT;
 /// The upper bound of indirect1 inode index
 const INDIRECT1_BOUND: usize = DIRECT_BOUND + INODE_INDIRECT1_COUNT;
 /// The max length of inode name (including null terminator)
-const NAME_LENGTH_LIMIT: usize = 28;
+const NAME_LENGTH_LIMIT: usize = NAME_MAX + 1;
 
 #[repr(C)]
 pub struct SuperBlock {
@@ -62,6 +65,10 @@ impl SuperBlock {
 pub enum DiskInodeType {
     File,
     Directory,
+    /// A named pipe, whose data is never stored.
+    Fifo,
+    /// The name of a local socket, whose data is never stored.
+    Socket,
 }
 
 #[repr(C)]
@@ -92,6 +99,14 @@ impl DiskInode {
         self.type_ == DiskInodeType::Directory
     }
 
+    pub fn is_fifo(&self) -> bool {
+        self.type_ == DiskInodeType::Fifo
+    }
+
+    pub fn is_socket(&self) -> bool {
+        self.type_ == DiskInodeType::Socket
+    }
+
     fn _data_blocks(size: u32) -> u32 {
         size.div_ceil(BLOCK_SIZE as u32)
     }
@@ -315,9 +330,13 @@ impl DiskInode {
         if start >= end {
             return 0;
         }
-        let mut start_block = start / BLOCK_SIZE;
+        let first_block = start / BLOCK_SIZE;
+        let mut start_block = first_block;
         let mut read_size = 0usize;
         loop {
+            if (start_block - first_block) % READAHEAD_BLOCKS == 0 {
+                self.readahead(start_block, block_device);
+            }
             // calculate end of current block
             let mut end_current_block = (start / BLOCK_SIZE + 1) * BLOCK_SIZE;
             end_current_block = end_current_block.min(end);
@@ -342,6 +361,17 @@ impl DiskInode {
         read_size
     }
 
+    /// Load the data blocks from `start_block` on into the block cache together
+    fn readahead(&self, start_block: usize, block_device: &Arc<dyn BlockDevice>) {
+        let end_block = (self.size as usize)
+            .div_ceil(BLOCK_SIZE)
+            .min(start_block + READAHEAD_BLOCKS);
+        let block_ids: Vec<usize> = (start_block..end_block)
+            .map(|inner_id| self.get_block_id(inner_id as u32, block_device) as usize)
+            .collect();
+        prefetch_blocks(&block_ids, block_device);
+    }
+
     /// Write data into current disk inode
     /// size must be adjusted properly beforehand
     pub fn write_at(
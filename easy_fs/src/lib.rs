@@ -17,8 +17,12 @@ pub use vfs::Inode;
 
 /// Size of a block in bytes
 pub const BLOCK_SIZE: usize = 512;
+/// Max length of a file name in bytes
+pub const NAME_MAX: usize = 27;
 /// Use a block cache of 16 blocks
 const BLOCK_CACHE_SIZE: usize = 16;
+/// Number of file blocks read ahead together
+const READAHEAD_BLOCKS: usize = 8;
 
 #[cfg(all(not(unix), test))]
 fn test_runner(_tests: &[&dyn Fn()]) {
@@ -72,10 +76,30 @@ mod test {
         let root_inode = EasyFileSystem::root_inode(&efs);
         root_inode.create("file_a");
         root_inode.create("file_b");
+        let dir = root_inode.create_dir("dir").unwrap();
+        assert!(dir.is_dir() && !dir.is_fifo());
+        let fifo = dir.create_fifo("fifo").unwrap();
+        assert!(fifo.is_fifo() && !fifo.is_dir());
+        assert!(dir.find("fifo").unwrap().is_fifo());
+        assert!(dir.create("fifo").is_none());
+        assert!(dir.unlink("fifo"));
+        assert!(dir.create_socket("socket").unwrap().is_socket());
+        assert!(!dir.find("socket").unwrap().is_fifo());
+        assert!(dir.unlink("socket"));
+        dir.create("file_c").unwrap();
+        assert!(dir.find("file_c").is_some());
+        assert!(root_inode.find("file_c").is_none());
+        assert!(dir.unlink("file_c"));
+        assert!(dir.find("file_c").is_none() && dir.ls().is_empty());
+        assert!(!dir.unlink("file_c"));
+        // the removed entry is reused
+        dir.create("file_d").unwrap();
+        assert_eq!(dir.size() as usize, size_of::<layout::DirEntry>());
         for name in root_inode.ls() {
             println!("{}", name);
         }
         let file_a = root_inode.find("file_a").unwrap();
+        assert!(!file_a.is_dir());
         let greet_str = "Hello, world!";
         file_a.write_at(0, greet_str.as_bytes());
         //let mut buffer = [0u8; 512];
@@ -10,6 +10,7 @@ use crate::{
 
 /// Virtual filesystem layer over easy-fs
 pub struct Inode {
+    inode_id: u32,
     block_id: usize,
     block_offset: usize,
     fs: Arc<Mutex<EasyFileSystem>>,
@@ -18,12 +19,14 @@ pub struct Inode {
 
 impl Inode {
     pub fn new(
+        inode_id: u32,
         block_id: u32,
         block_offset: usize,
         fs: Arc<Mutex<EasyFileSystem>>,
         block_device: Arc<dyn BlockDevice>,
     ) -> Self {
         Self {
+            inode_id,
             block_id: block_id as usize,
             block_offset,
             fs,
@@ -44,6 +47,16 @@ impl Inode {
     }
 
     fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Option<u32> {
+        if name.is_empty() {
+            return None;
+        }
+        self.find_dirent(name, disk_inode)
+            .map(|(_, dirent)| dirent.inode_number())
+    }
+
+    /// Find the entry `name`, return its index and itself. Removed entries are empty, so an
+    /// empty `name` finds the first of them.
+    fn find_dirent(&self, name: &str, disk_inode: &DiskInode) -> Option<(usize, DirEntry)> {
         // assert it is a directory
         assert!(disk_inode.is_dir());
         let file_count = (disk_inode.size as usize) / size_of::<DirEntry>();
@@ -58,7 +71,7 @@ impl Inode {
                 size_of::<DirEntry>(),
             );
             if dirent.name() == name {
-                return Some(dirent.inode_number());
+                return Some((i, dirent));
             }
         }
         None
@@ -70,6 +83,7 @@ impl Inode {
             self.find_inode_id(name, disk_inode).map(|inode_id| {
                 let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
                 Arc::new(Self::new(
+                    inode_id,
                     block_id,
                     block_offset,
                     self.fs.clone(),
@@ -94,7 +108,9 @@ impl Inode {
                     ),
                     size_of::<DirEntry>(),
                 );
-                v.push(String::from(dirent.name()));
+                if !dirent.name().is_empty() {
+                    v.push(String::from(dirent.name()));
+                }
             }
             v
         })
@@ -118,7 +134,42 @@ impl Inode {
         disk_inode.increase_size(new_size, v, &self.block_device);
     }
 
+    pub fn is_dir(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.is_dir())
+    }
+
+    pub fn is_fifo(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.is_fifo())
+    }
+
+    pub fn is_socket(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.is_socket())
+    }
+
+    /// Create a file, return `None` if the name exists
     pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::File)
+    }
+
+    /// Create an empty directory, return `None` if the name exists
+    pub fn create_dir(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::Directory)
+    }
+
+    /// Create a named pipe, return `None` if the name exists
+    pub fn create_fifo(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::Fifo)
+    }
+
+    /// Create the name of a local socket, return `None` if the name exists
+    pub fn create_socket(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::Socket)
+    }
+
+    fn create_inode(&self, name: &str, type_: DiskInodeType) -> Option<Arc<Inode>> {
         let mut fs = self.fs.lock();
         // check if the file already exists
         if self
@@ -140,18 +191,23 @@ impl Inode {
         get_block(new_inode_block_id as usize, &self.block_device)
             .lock()
             .modify(new_inode_block_offset, |new_inode: &mut DiskInode| {
-                new_inode.initialize(DiskInodeType::File);
+                new_inode.initialize(type_);
             });
         self.modify_disk_inode(|root_inode| {
-            // append file in the dirent
+            // reuse the entry of a removed file, or append one
             let file_count = (root_inode.size as usize) / size_of::<DirEntry>();
-            let new_size = (file_count + 1) * size_of::<DirEntry>();
-            // increase size
-            self.increase_size(new_size as u32, root_inode, &mut fs);
+            let index = self
+                .find_dirent("", root_inode)
+                .map_or(file_count, |(i, _)| i);
+            if index == file_count {
+                let new_size = (file_count + 1) * size_of::<DirEntry>();
+                // increase size
+                self.increase_size(new_size as u32, root_inode, &mut fs);
+            }
             // write dirent
             let dirent = DirEntry::new(name, new_inode_id);
             root_inode.write_at(
-                file_count * size_of::<DirEntry>(),
+                index * size_of::<DirEntry>(),
                 dirent.as_bytes(),
                 &self.block_device,
             );
@@ -160,6 +216,7 @@ impl Inode {
         let (block_id, block_offset) = fs.get_disk_inode_pos(new_inode_id);
         // return inode
         Some(Arc::new(Self::new(
+            new_inode_id,
             block_id,
             block_offset,
             self.fs.clone(),
@@ -168,6 +225,51 @@ impl Inode {
         // release efs lock automatically by compiler
     }
 
+    /// Remove the entry `name` and free its inode and data, return `false` if it does not exist.
+    /// A directory is removed along with its entries, which the caller should make sure
+    /// to be empty.
+    pub fn unlink(&self, name: &str) -> bool {
+        if name.is_empty() {
+            return false;
+        }
+        let mut fs = self.fs.lock();
+        let Some((index, dirent)) = self.read_disk_inode(|dir| self.find_dirent(name, dir)) else {
+            return false;
+        };
+        let inode_id = dirent.inode_number();
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        let data_blocks_dealloc = get_block(block_id as usize, &self.block_device)
+            .lock()
+            .modify(block_offset, |disk_inode: &mut DiskInode| {
+                disk_inode.clear_size(&self.block_device)
+            });
+        for data_block in data_blocks_dealloc.into_iter() {
+            fs.dealloc_data(data_block);
+        }
+        fs.dealloc_inode(inode_id);
+        // leave an empty entry, so that the other entries stay in place
+        self.modify_disk_inode(|dir| {
+            dir.write_at(
+                index * size_of::<DirEntry>(),
+                DirEntry::empty().as_bytes(),
+                &self.block_device,
+            );
+        });
+        block_cache_sync_all();
+        true
+    }
+
+    /// Get the number of this inode
+    pub fn inode_id(&self) -> u32 {
+        self.inode_id
+    }
+
+    /// Get the size of the data in bytes
+    pub fn size(&self) -> u32 {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.size)
+    }
+
     pub fn clear(&self) {
         let mut fs = self.fs.lock();
         self.modify_disk_inode(|disk_inode| {
@@ -13,6 +13,7 @@ fn main() {
     println!("{}", ld.display());
     fs::write(&ld, LINKER).unwrap();
     easy_fs_pack().unwrap();
+    scratch_pack().unwrap();
     println!("cargo:rerun-if-changed=../user_lib");
     println!("cargo:rerun-if-changed=build.rs");
     println!("cargo:rerun-if-env-changed=LOG");
@@ -43,23 +44,39 @@ impl BlockDevice for BlockFile {
 }
 
 const FS_SIZE: usize = 64 * 1024 * 2 * BLOCK_SIZE; // 64 MiB
+const SCRATCH_SIZE: usize = 16 * 1024 * 2 * BLOCK_SIZE; // 16 MiB
+
+/// Create an empty disk image of `size` bytes at `path`.
+fn create_image(path: &str, size: usize) -> Result<Arc<BlockFile>> {
+    let f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?;
+    f.set_len(size as u64)?;
+    Ok(Arc::new(BlockFile(Mutex::new(f))))
+}
+
+/// Create an empty easy_fs image for the tests to mount.
+fn scratch_pack() -> Result<()> {
+    let block_file = create_image("scratch.img", SCRATCH_SIZE)?;
+    EasyFileSystem::create(block_file, (SCRATCH_SIZE / BLOCK_SIZE) as u32, 1);
+    Ok(())
+}
 
 fn easy_fs_pack() -> Result<()> {
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
     let profile = env::var("PROFILE").unwrap();
     let target_path = out_dir.ancestors().nth(4).unwrap();
-    let block_file = Arc::new(BlockFile(Mutex::new({
-        let f = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open("fs.img")?;
-        f.set_len(FS_SIZE as u64)?;
-        f
-    })));
+    let block_file = create_image("fs.img", FS_SIZE)?;
     let efs = EasyFileSystem::create(block_file, (FS_SIZE / BLOCK_SIZE) as u32, 1);
     let root_inode = Arc::new(EasyFileSystem::root_inode(&efs));
+    // mount points of the scratch disk, the devfs, the procfs and the tmpfs
+    root_inode.create_dir("mnt").unwrap();
+    root_inode.create_dir("dev").unwrap();
+    root_inode.create_dir("proc").unwrap();
+    root_inode.create_dir("tmp").unwrap();
     let apps: Vec<_> = read_dir("../user_lib/src/bin")
         .unwrap()
         .map(|dir_entry| {
@@ -5,6 +5,21 @@ pub const USER_STACK_SIZE: usize = PAGE_SIZE * 2;
 pub const KERNEL_STACK_SIZE: usize = PAGE_SIZE * 2;
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 pub const TRAP_FRAME: usize = TRAMPOLINE - PAGE_SIZE;
+/// Shared memory segments are attached from here, far above the program and its stacks.
+pub const USER_SHM_BASE: usize = 0x10_0000_0000; // 64 GiB
+/// End of the segments, the end of the lower half of the address space, far below the trap
+/// frames and the trampoline.
+pub const USER_SHM_END: usize = 0x40_0000_0000; // 256 GiB
+/// Limit of the fds asked for by `dup3` and `F_DUPFD`, like `RLIMIT_NOFILE`.
+pub const MAX_FDS: usize = 1024;
 pub const CLOCK_FREQ: usize = 12500000;
+pub const MAX_HART_NUM: usize = 4;
+/// Size limit of a tmpfs in pages, so that it cannot take all the frames.
+pub const TMPFS_MAX_PAGES: usize = 1024; // 4 MiB
 
-pub const MMIO: &[(usize, usize)] = &[(0x10001000, 0x1000)]; // TODO: get this from device tree
+// TODO: get this from device tree
+pub const MMIO: &[(usize, usize)] = &[
+    (0x0c00_0000, 0x21_0000), // PLIC
+    (0x1000_0000, 0x1000),    // UART
+    (0x1000_1000, 0x8000),    // VirtIO
+];
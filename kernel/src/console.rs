@@ -1,13 +1,29 @@
 use core::fmt::Write;
 use sbi_rt::console_write_byte;
 
+use crate::{drivers::UART, sync::SpinLock};
+
 struct Console;
 
+/// Keep the output of different harts from interleaving.
+static CONSOLE: SpinLock<Console> = SpinLock::new(Console);
+
+impl Console {
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        if UART.is_ready() {
+            UART.write_bytes(bytes);
+        } else {
+            // SBI talks to the UART before we take it over
+            for &byte in bytes {
+                console_write_byte(byte);
+            }
+        }
+    }
+}
+
 impl Write for Console {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        for c in s.bytes() {
-            console_write_byte(c);
-        }
+        self.write_bytes(s.as_bytes());
         Ok(())
     }
 }
@@ -15,7 +31,12 @@ impl Write for Console {
 #[doc(hidden)]
 #[inline]
 pub fn _print(args: core::fmt::Arguments) {
-    Console.write_fmt(args).unwrap();
+    CONSOLE.lock().write_fmt(args).unwrap();
+}
+
+/// Write raw bytes, which may not be UTF-8, to the console.
+pub fn write_bytes(bytes: &[u8]) {
+    CONSOLE.lock().write_bytes(bytes);
 }
 
 #[macro_export]
@@ -28,10 +49,9 @@ macro_rules! print {
 #[macro_export]
 macro_rules! println {
     () => ($crate::print!("\n"));
-    ($($arg:tt)*) => {{
-        $crate::console::_print(core::format_args!($($arg)*));
-        $crate::println!();
-    }}
+    ($($arg:tt)*) => {
+        $crate::console::_print(core::format_args!("{}\n", core::format_args!($($arg)*)));
+    }
 }
 
 #[cfg(test)]
@@ -1,20 +1,79 @@
+mod plic;
+mod uart;
 mod virtio_blk;
+mod virtio_net;
 
+pub use plic::{handle_external_irq, init_hart, register_irq};
+pub use uart::Uart;
 pub use virtio_blk::VirtIOBlock;
+pub use virtio_net::{MAX_FRAME_SIZE, VirtIONetwork};
 
-use alloc::sync::Arc;
+use alloc::{format, string::String, sync::Arc, vec::Vec};
 use easy_fs::BlockDevice;
 use lazy_static::*;
 
-type BlockDeviceImpl = VirtIOBlock;
+use uart::{UART0, UART0_IRQ};
 
 lazy_static! {
-    pub static ref BLOCK_DEVICE: Arc<dyn BlockDevice> = Arc::new(BlockDeviceImpl::new());
+    /// The virtio-blk devices named `vda`, `vdb`, ... in the order of their slots, with their
+    /// interrupts.
+    static ref BLOCK_DEVICES: Vec<(String, Arc<VirtIOBlock>, usize)> = virtio_blk::probe()
+        .into_iter()
+        .zip(b'a'..=b'z')
+        .map(|((device, irq), letter)| (format!("vd{}", letter as char), device, irq))
+        .collect();
+    /// The first virtio-net device with its interrupt.
+    static ref NET_DEVICE: Option<(Arc<VirtIONetwork>, usize)> = virtio_net::probe();
+}
+
+/// Return the block device called `name`.
+pub fn block_device(name: &str) -> Option<Arc<dyn BlockDevice>> {
+    BLOCK_DEVICES
+        .iter()
+        .find(|(device_name, _, _)| device_name == name)
+        .map(|(_, device, _)| Arc::clone(device) as Arc<dyn BlockDevice>)
+}
+
+/// Return the names of the block devices with the devices and their numbers of blocks.
+pub fn block_devices() -> Vec<(String, Arc<dyn BlockDevice>, usize)> {
+    BLOCK_DEVICES
+        .iter()
+        .map(|(name, device, _)| {
+            let capacity = device.capacity();
+            (
+                name.clone(),
+                device.clone() as Arc<dyn BlockDevice>,
+                capacity,
+            )
+        })
+        .collect()
+}
+
+/// Return the network device, if any.
+pub fn net_device() -> Option<Arc<VirtIONetwork>> {
+    NET_DEVICE.as_ref().map(|(device, _)| Arc::clone(device))
+}
+
+/// The console, usable before the heap is initialized.
+pub static UART: Uart = Uart::new(UART0);
+
+/// Initialize the devices and route their interrupts to the boot hart, other harts join
+/// with `init_hart` once started.
+pub fn init() {
+    UART.init();
+    register_irq(UART0_IRQ, Arc::new(&UART));
+    for (_, device, irq) in BLOCK_DEVICES.iter() {
+        register_irq(*irq, device.clone());
+    }
+    if let Some((device, irq)) = NET_DEVICE.as_ref() {
+        register_irq(*irq, device.clone());
+    }
+    init_hart();
 }
 
 #[test_case]
 pub fn block_device_test() {
-    let block_device = BLOCK_DEVICE.clone();
+    let block_device = block_device("vda").unwrap();
     let mut write_buffer = [0u8; 512];
     let mut read_buffer = [0u8; 512];
     for i in 0..512 {
@@ -26,3 +85,27 @@ pub fn block_device_test() {
         assert_eq!(write_buffer, read_buffer);
     }
 }
+
+#[test_case]
+pub fn block_device_batch_test() {
+    let block_device = block_device("vda").unwrap();
+    // more requests than fit into the queue at once
+    let mut write_buffers = [[0u8; 512]; 32];
+    for (i, buf) in write_buffers.iter_mut().enumerate() {
+        buf.fill(i as u8 ^ 0x5a);
+    }
+    let writes: alloc::vec::Vec<(usize, &[u8])> = write_buffers
+        .iter()
+        .enumerate()
+        .map(|(i, buf)| (i, buf.as_slice()))
+        .collect();
+    block_device.write_blocks(&writes);
+    let mut read_buffers = [[0u8; 512]; 32];
+    let mut reads: alloc::vec::Vec<(usize, &mut [u8])> = read_buffers
+        .iter_mut()
+        .enumerate()
+        .map(|(i, buf)| (i, buf.as_mut_slice()))
+        .collect();
+    block_device.read_blocks(&mut reads);
+    assert_eq!(write_buffers, read_buffers);
+}
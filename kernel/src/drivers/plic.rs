@@ -0,0 +1,132 @@
+//! Platform-Level Interrupt Controller of QEMU virt
+//!
+//! Every hart has an interrupt context for M-mode and one for S-mode, the kernel routes all
+//! device interrupts to the S-mode contexts of the online harts. Whichever hart claims an
+//! interrupt first handles it.
+
+use alloc::{collections::BTreeMap, sync::Arc};
+use lazy_static::lazy_static;
+use log::warn;
+use riscv::register::sie;
+
+use crate::{smp::hart_id, sync::SpinLock};
+
+const PLIC_BASE: usize = 0x0c00_0000;
+const PRIORITY_OFFSET: usize = 0x0;
+const ENABLE_OFFSET: usize = 0x2000;
+const ENABLE_STRIDE: usize = 0x80;
+const CONTEXT_OFFSET: usize = 0x20_0000;
+const CONTEXT_STRIDE: usize = 0x1000;
+const THRESHOLD: usize = 0x0;
+const CLAIM_COMPLETE: usize = 0x4;
+
+/// A device driver which handles the interrupts of its device.
+pub trait IrqHandler: Send + Sync {
+    fn handle_irq(&self);
+}
+
+/// Statically allocated drivers are registered by reference.
+impl<T: IrqHandler + ?Sized> IrqHandler for &'static T {
+    fn handle_irq(&self) {
+        (**self).handle_irq();
+    }
+}
+
+struct IrqTable {
+    handlers: BTreeMap<usize, Arc<dyn IrqHandler>>,
+    /// Bit mask of the harts whose contexts have been set up.
+    harts: usize,
+}
+
+lazy_static! {
+    static ref IRQ_TABLE: SpinLock<IrqTable> = SpinLock::new(IrqTable {
+        handlers: BTreeMap::new(),
+        harts: 0,
+    });
+}
+
+fn reg(offset: usize) -> *mut u32 {
+    (PLIC_BASE + offset) as *mut u32
+}
+
+/// Return the S-mode context of `hart_id`.
+fn s_context(hart_id: usize) -> usize {
+    2 * hart_id + 1
+}
+
+fn set_priority(irq: usize, priority: u32) {
+    unsafe {
+        reg(PRIORITY_OFFSET + irq * 4).write_volatile(priority);
+    }
+}
+
+fn enable(context: usize, irq: usize) {
+    let reg = reg(ENABLE_OFFSET + context * ENABLE_STRIDE + irq / 32 * 4);
+    unsafe {
+        reg.write_volatile(reg.read_volatile() | 1 << (irq % 32));
+    }
+}
+
+fn set_threshold(context: usize, threshold: u32) {
+    unsafe {
+        reg(CONTEXT_OFFSET + context * CONTEXT_STRIDE + THRESHOLD).write_volatile(threshold);
+    }
+}
+
+/// Return the highest-priority pending interrupt of `context`, or 0 if there is none.
+fn claim(context: usize) -> usize {
+    unsafe {
+        reg(CONTEXT_OFFSET + context * CONTEXT_STRIDE + CLAIM_COMPLETE).read_volatile() as usize
+    }
+}
+
+fn complete(context: usize, irq: usize) {
+    unsafe {
+        reg(CONTEXT_OFFSET + context * CONTEXT_STRIDE + CLAIM_COMPLETE).write_volatile(irq as u32);
+    }
+}
+
+/// Set up the S-mode context of the current hart and enable external interrupts on it.
+pub fn init_hart() {
+    let hart_id = hart_id();
+    let context = s_context(hart_id);
+    let mut table = IRQ_TABLE.lock();
+    for &irq in table.handlers.keys() {
+        enable(context, irq);
+    }
+    table.harts |= 1 << hart_id;
+    set_threshold(context, 0);
+    unsafe {
+        sie::set_sext();
+    }
+}
+
+/// Route interrupt `irq` to `handler` on all harts.
+pub fn register_irq(irq: usize, handler: Arc<dyn IrqHandler>) {
+    let mut table = IRQ_TABLE.lock();
+    assert!(
+        table.handlers.insert(irq, handler).is_none(),
+        "IRQ {irq} has been registered"
+    );
+    set_priority(irq, 1);
+    for hart_id in (0..usize::BITS as usize).filter(|id| table.harts & 1 << id != 0) {
+        enable(s_context(hart_id), irq);
+    }
+}
+
+/// Handle a supervisor external interrupt on the current hart.
+pub fn handle_external_irq() {
+    let context = s_context(hart_id());
+    let irq = claim(context);
+    if irq == 0 {
+        // claimed by another hart
+        return;
+    }
+    // the handler may take a while, so do not keep the table locked
+    let handler = IRQ_TABLE.lock().handlers.get(&irq).cloned();
+    match handler {
+        Some(handler) => handler.handle_irq(),
+        None => warn!("Unhandled IRQ {irq}"),
+    }
+    complete(context, irq);
+}
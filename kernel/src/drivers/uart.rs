@@ -0,0 +1,205 @@
+//! NS16550a UART of QEMU virt
+//!
+//! Received bytes are passed to a receiver, the TTY, from the RX interrupt. Output is queued
+//! in a transmit buffer, which is drained while the transmitter is idle and by the TX
+//! interrupt afterwards.
+
+use core::{
+    hint::spin_loop,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::sync::SpinLock;
+
+use super::plic::IrqHandler;
+
+pub const UART0: usize = 0x1000_0000;
+pub const UART0_IRQ: usize = 10;
+
+/// Size of the receive FIFO.
+const RX_FIFO_SIZE: usize = 16;
+const TX_BUFFER_SIZE: usize = 1024;
+
+/// Receive buffer register (read) and transmit holding register (write).
+const RBR_THR: usize = 0;
+/// Interrupt enable register.
+const IER: usize = 1;
+/// FIFO control register, write only.
+const FCR: usize = 2;
+/// Line control register.
+const LCR: usize = 3;
+/// Line status register.
+const LSR: usize = 5;
+/// Low byte of the divisor latch with `LCR_DLAB` set.
+const DLL: usize = 0;
+/// High byte of the divisor latch with `LCR_DLAB` set.
+const DLM: usize = 1;
+
+const IER_RX_AVAILABLE: u8 = 1 << 0;
+const IER_TX_EMPTY: u8 = 1 << 1;
+const FCR_FIFO_ENABLE: u8 = 1 << 0;
+const FCR_FIFO_CLEAR: u8 = 3 << 1;
+const LCR_EIGHT_BITS: u8 = 3;
+const LCR_DLAB: u8 = 1 << 7;
+const LSR_DATA_READY: u8 = 1 << 0;
+const LSR_TX_IDLE: u8 = 1 << 5;
+
+/// A fixed-size byte queue.
+struct RingBuffer<const N: usize> {
+    buf: [u8; N],
+    head: usize,
+    len: usize,
+}
+
+impl<const N: usize> RingBuffer<N> {
+    const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn push(&mut self, byte: u8) -> Result<(), u8> {
+        if self.len == N {
+            return Err(byte);
+        }
+        self.buf[(self.head + self.len) % N] = byte;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.is_empty() {
+            return None;
+        }
+        let byte = self.buf[self.head];
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(byte)
+    }
+}
+
+pub struct Uart {
+    base: usize,
+    /// Whether the UART has been initialized, SBI is used for output before.
+    ready: AtomicBool,
+    inner: SpinLock<UartInner>,
+}
+
+struct UartInner {
+    tx_buffer: RingBuffer<TX_BUFFER_SIZE>,
+    /// Called with every received byte, which is dropped if there is no receiver.
+    receiver: Option<fn(u8)>,
+}
+
+impl Uart {
+    pub const fn new(base: usize) -> Self {
+        Self {
+            base,
+            ready: AtomicBool::new(false),
+            inner: SpinLock::new(UartInner {
+                tx_buffer: RingBuffer::new(),
+                receiver: None,
+            }),
+        }
+    }
+
+    fn read_reg(&self, reg: usize) -> u8 {
+        unsafe { ((self.base + reg) as *const u8).read_volatile() }
+    }
+
+    fn write_reg(&self, reg: usize, val: u8) {
+        unsafe { ((self.base + reg) as *mut u8).write_volatile(val) }
+    }
+
+    /// Set the UART to 38400 baud 8N1 with FIFOs, and enable the RX interrupt.
+    pub fn init(&self) {
+        let _inner = self.inner.lock();
+        self.write_reg(IER, 0);
+        self.write_reg(LCR, LCR_DLAB);
+        self.write_reg(DLL, 0x03);
+        self.write_reg(DLM, 0x00);
+        self.write_reg(LCR, LCR_EIGHT_BITS);
+        self.write_reg(FCR, FCR_FIFO_ENABLE | FCR_FIFO_CLEAR);
+        self.write_reg(IER, IER_RX_AVAILABLE);
+        self.ready.store(true, Ordering::Release);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    pub fn set_receiver(&self, receiver: fn(u8)) {
+        self.inner.lock().receiver = Some(receiver);
+    }
+
+    /// Queue `bytes` for transmission.
+    pub fn write_bytes(&self, bytes: &[u8]) {
+        let mut inner = self.inner.lock();
+        for &byte in bytes {
+            while inner.tx_buffer.push(byte).is_err() {
+                // interrupts are rarely taken in the kernel, so wait for the transmitter here
+                self.wait_tx_idle();
+                self.transmit(&mut inner);
+            }
+        }
+        self.transmit(&mut inner);
+    }
+
+    /// Block the hart until all queued bytes have been transmitted.
+    pub fn flush(&self) {
+        let mut inner = self.inner.lock();
+        while !inner.tx_buffer.is_empty() {
+            self.wait_tx_idle();
+            self.transmit(&mut inner);
+        }
+    }
+
+    fn wait_tx_idle(&self) {
+        while self.read_reg(LSR) & LSR_TX_IDLE == 0 {
+            spin_loop();
+        }
+    }
+
+    /// Move queued bytes to the transmitter while it is idle, the TX interrupt is enabled if
+    /// some bytes are left.
+    fn transmit(&self, inner: &mut UartInner) {
+        while self.read_reg(LSR) & LSR_TX_IDLE != 0 {
+            let Some(byte) = inner.tx_buffer.pop() else {
+                break;
+            };
+            self.write_reg(RBR_THR, byte);
+        }
+        if inner.tx_buffer.is_empty() {
+            self.write_reg(IER, IER_RX_AVAILABLE);
+        } else {
+            self.write_reg(IER, IER_RX_AVAILABLE | IER_TX_EMPTY);
+        }
+    }
+}
+
+impl IrqHandler for Uart {
+    fn handle_irq(&self) {
+        let mut inner = self.inner.lock();
+        // reading the received bytes clears the RX interrupt
+        let mut received = [0; RX_FIFO_SIZE];
+        let mut len = 0;
+        while len < RX_FIFO_SIZE && self.read_reg(LSR) & LSR_DATA_READY != 0 {
+            received[len] = self.read_reg(RBR_THR);
+            len += 1;
+        }
+        // refilling the transmitter or disabling the TX interrupt clears it
+        self.transmit(&mut inner);
+        let receiver = inner.receiver;
+        // the receiver may write back to the UART
+        drop(inner);
+        if let Some(receiver) = receiver {
+            received[..len].iter().for_each(|&byte| receiver(byte));
+        }
+    }
+}
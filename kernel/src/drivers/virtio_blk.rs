@@ -1,43 +1,194 @@
-use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+    vec::Vec,
+};
 use easy_fs::BlockDevice;
 use lazy_static::*;
-use virtio_drivers::{Hal, VirtIOBlk, VirtIOHeader};
+use virtio_drivers::{
+    BlkResp, DeviceType, Error, Hal, RespStatus, Result, VirtIOBlk, VirtIOHeader,
+};
 
 use crate::memory::{
     FRAME_ALLOCATOR, FrameAllocator, FrameTracker, KERNEL_SPACE, PageTable, PhysAddr, PhysPageNum,
     VirtAddr,
 };
-use crate::sync::UPSafeCell;
+use crate::proc::{
+    ThreadControlBlock, Uninterruptible, block_current_and_run_next, current_thread,
+    try_current_thread, wakeup_thread,
+};
+use crate::sync::SpinLock;
+
+use super::plic::IrqHandler;
 
-const VIRTIO0: usize = 0x10001000;
-pub struct VirtIOBlock(UPSafeCell<VirtIOBlk<'static, VirtioHal>>);
+/// The first of the virtio-mmio slots of QEMU virt, slot `i` is at `VIRTIO0 + i * VIRTIO_STRIDE`
+/// with interrupt `VIRTIO0_IRQ + i`.
+pub(super) const VIRTIO0: usize = 0x10001000;
+pub(super) const VIRTIO0_IRQ: usize = 1;
+pub(super) const VIRTIO_STRIDE: usize = 0x1000;
+pub(super) const VIRTIO_SLOTS: usize = 8;
+/// Offset of the device configuration from the header, virtio-blk starts it with the capacity
+/// in 512-byte sectors and virtio-net with the MAC address.
+pub(super) const VIRTIO_CONFIG: usize = 0x100;
+
+/// The requests of a thread in flight, the thread is woken up once all of them complete.
+struct Batch {
+    thread: Arc<ThreadControlBlock>,
+    pending: AtomicUsize,
+}
+
+type Submit<'a> =
+    dyn FnMut(&mut VirtIOBlk<'static, VirtioHal>, usize, &mut BlkResp) -> Result<u16> + 'a;
+
+pub struct VirtIOBlock {
+    blk: SpinLock<VirtIOBlk<'static, VirtioHal>>,
+    /// Number of blocks.
+    capacity: usize,
+    /// Batches waiting for their requests to complete, indexed by the token of the request.
+    waiters: SpinLock<BTreeMap<u16, Arc<Batch>>>,
+    /// Threads waiting for room in the queue.
+    queue_waiters: SpinLock<VecDeque<Arc<ThreadControlBlock>>>,
+}
 
 lazy_static! {
-    static ref QUEUE_FRAMES: UPSafeCell<Vec<FrameTracker>> = unsafe { UPSafeCell::new(Vec::new()) };
+    static ref QUEUE_FRAMES: SpinLock<Vec<FrameTracker>> = SpinLock::new(Vec::new());
 }
 
 impl BlockDevice for VirtIOBlock {
     fn read_block(&self, block_id: usize, buf: &mut [u8]) {
-        self.0
-            .borrow_mut()
-            .read_block(block_id, buf)
-            .expect("Error when reading VirtIOBlk");
+        self.read_blocks(&mut [(block_id, buf)]);
     }
     fn write_block(&self, block_id: usize, buf: &[u8]) {
-        self.0
-            .borrow_mut()
-            .write_block(block_id, buf)
-            .expect("Error when writing VirtIOBlk");
+        self.write_blocks(&[(block_id, buf)]);
+    }
+    fn read_blocks(&self, blocks: &mut [(usize, &mut [u8])]) {
+        if try_current_thread().is_none() {
+            // nobody can be blocked before the processes start, poll the device instead
+            let mut blk = self.blk.lock();
+            for (block_id, buf) in blocks.iter_mut() {
+                blk.read_block(*block_id, buf)
+                    .expect("Error when reading VirtIOBlk");
+            }
+            return;
+        }
+        self.transfer(blocks.len(), &mut |blk, i, resp| {
+            let (block_id, buf) = &mut blocks[i];
+            unsafe { blk.read_block_nb(*block_id, buf, resp) }
+        });
+    }
+    fn write_blocks(&self, blocks: &[(usize, &[u8])]) {
+        if try_current_thread().is_none() {
+            let mut blk = self.blk.lock();
+            for &(block_id, buf) in blocks {
+                blk.write_block(block_id, buf)
+                    .expect("Error when writing VirtIOBlk");
+            }
+            return;
+        }
+        self.transfer(blocks.len(), &mut |blk, i, resp| {
+            let (block_id, buf) = blocks[i];
+            unsafe { blk.write_block_nb(block_id, buf, resp) }
+        });
     }
 }
 
+impl IrqHandler for VirtIOBlock {
+    fn handle_irq(&self) {
+        let mut blk = self.blk.lock();
+        blk.ack_interrupt();
+        let mut completed = false;
+        while let Ok(token) = blk.pop_used() {
+            completed = true;
+            let Some(batch) = self.waiters.lock().remove(&token) else {
+                continue;
+            };
+            if batch.pending.fetch_sub(1, Ordering::AcqRel) == 1 {
+                wakeup_thread(Arc::clone(&batch.thread));
+            }
+        }
+        if completed {
+            for thread in self.queue_waiters.lock().drain(..) {
+                wakeup_thread(thread);
+            }
+        }
+    }
+}
+
+/// Find the virtio-blk devices on the MMIO bus, return them with their interrupts in the
+/// order of their slots.
+pub fn probe() -> Vec<(Arc<VirtIOBlock>, usize)> {
+    (0..VIRTIO_SLOTS)
+        .filter_map(|slot| {
+            let header = unsafe { &mut *((VIRTIO0 + slot * VIRTIO_STRIDE) as *mut VirtIOHeader) };
+            (header.verify() && header.device_type() == DeviceType::Block)
+                .then(|| (Arc::new(VirtIOBlock::new(header)), VIRTIO0_IRQ + slot))
+        })
+        .collect()
+}
+
 impl VirtIOBlock {
-    #[allow(unused)]
-    pub fn new() -> Self {
-        unsafe {
-            Self(UPSafeCell::new(
-                VirtIOBlk::<VirtioHal>::new(&mut *(VIRTIO0 as *mut VirtIOHeader)).unwrap(),
-            ))
+    fn new(header: &'static mut VirtIOHeader) -> Self {
+        let config = header as *mut VirtIOHeader as usize + VIRTIO_CONFIG;
+        let capacity = unsafe { (config as *const u64).read_volatile() } as usize;
+        Self {
+            blk: SpinLock::new(VirtIOBlk::<VirtioHal>::new(header).unwrap()),
+            capacity,
+            waiters: SpinLock::new(BTreeMap::new()),
+            queue_waiters: SpinLock::new(VecDeque::new()),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Submit `count` requests with `submit`, as many at a time as fit into the queue, and
+    /// block the current thread until all of them complete.
+    fn transfer(&self, count: usize, submit: &mut Submit<'_>) {
+        // the buffers of the requests live on our kernel stack until they complete
+        let _uninterruptible = Uninterruptible::new();
+        let thread = current_thread();
+        let batch = Arc::new(Batch {
+            thread: Arc::clone(&thread),
+            pending: AtomicUsize::new(0),
+        });
+        let mut resps: Vec<BlkResp> = (0..count).map(|_| BlkResp::default()).collect();
+        let mut submitted = 0;
+        while submitted < count {
+            let mut blk = self.blk.lock();
+            let mut waiters = self.waiters.lock();
+            let first = submitted;
+            while submitted < count {
+                match submit(&mut blk, submitted, &mut resps[submitted]) {
+                    Ok(token) => {
+                        // registered with the device locked, so that the interrupt cannot
+                        // come before
+                        waiters.insert(token, Arc::clone(&batch));
+                        submitted += 1;
+                    }
+                    // the queue is full
+                    Err(Error::BufferTooSmall) => break,
+                    Err(err) => panic!("Error when submitting to VirtIOBlk: {err:?}"),
+                }
+            }
+            drop(waiters);
+            if submitted == first {
+                // wait for the requests of other threads to make room
+                self.queue_waiters.lock().push_back(Arc::clone(&thread));
+            } else {
+                batch.pending.store(submitted - first, Ordering::Release);
+            }
+            drop(blk);
+            block_current_and_run_next();
+        }
+        for resp in resps {
+            assert_eq!(
+                resp.status(),
+                RespStatus::Ok,
+                "Error when accessing VirtIOBlk"
+            );
         }
     }
 }
@@ -48,12 +199,12 @@ impl Hal for VirtioHal {
     fn dma_alloc(pages: usize) -> usize {
         let mut ppn_base = PhysPageNum::new(0);
         for i in 0..pages {
-            let frame = FRAME_ALLOCATOR.borrow_mut().frame_alloc().unwrap();
+            let frame = FRAME_ALLOCATOR.lock().frame_alloc().unwrap();
             if i == 0 {
                 ppn_base = frame.ppn;
             }
             assert_eq!(frame.ppn, ppn_base + i);
-            QUEUE_FRAMES.borrow_mut().push(frame);
+            QUEUE_FRAMES.lock().push(frame);
         }
         let pa = PhysAddr::from(ppn_base);
         pa.into()
@@ -62,7 +213,7 @@ impl Hal for VirtioHal {
     fn dma_dealloc(pa: usize, pages: usize) -> i32 {
         let ppn_base = PhysAddr::from(pa).page_number();
         for ppn_base in ppn_base..ppn_base + pages {
-            FRAME_ALLOCATOR.borrow_mut().frame_dealloc(ppn_base);
+            FRAME_ALLOCATOR.lock().frame_dealloc(ppn_base);
         }
         0
     }
@@ -72,7 +223,7 @@ impl Hal for VirtioHal {
     }
 
     fn virt_to_phys(addr: usize) -> usize {
-        PageTable::from_token(KERNEL_SPACE.borrow_mut().token())
+        PageTable::from_token(KERNEL_SPACE.lock().token())
             .translate_va(VirtAddr::from(addr))
             .unwrap()
             .into()
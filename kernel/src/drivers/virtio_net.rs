@@ -0,0 +1,298 @@
+//! Driver of virtio-net devices on the legacy virtio-mmio transport
+//!
+//! The `VirtIONet` of virtio-drivers waits for each packet it receives, so this driver keeps
+//! the receive queue filled with buffers instead and takes the packets which have arrived.
+//! The buffers are pages, and descriptor `i` of a queue always points to buffer `i`.
+
+use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::{Ordering, fence};
+
+use virtio_drivers::{DeviceType, Hal, VirtIOHeader};
+
+use crate::{
+    config::PAGE_SIZE,
+    memory::{FRAME_ALLOCATOR, FrameAllocator, FrameTracker, PhysAddr},
+    sync::SpinLock,
+};
+
+use super::{
+    plic::IrqHandler,
+    virtio_blk::{VIRTIO_CONFIG, VIRTIO_SLOTS, VIRTIO_STRIDE, VIRTIO0, VIRTIO0_IRQ, VirtioHal},
+};
+
+// registers of the legacy interface
+const HOST_FEATURES: usize = 0x010;
+const HOST_FEATURES_SEL: usize = 0x014;
+const GUEST_FEATURES: usize = 0x020;
+const GUEST_FEATURES_SEL: usize = 0x024;
+const GUEST_PAGE_SIZE: usize = 0x028;
+const QUEUE_SEL: usize = 0x030;
+const QUEUE_NUM_MAX: usize = 0x034;
+const QUEUE_NUM: usize = 0x038;
+const QUEUE_ALIGN: usize = 0x03c;
+const QUEUE_PFN: usize = 0x040;
+const QUEUE_NOTIFY: usize = 0x050;
+const INTERRUPT_STATUS: usize = 0x060;
+const INTERRUPT_ACK: usize = 0x064;
+const STATUS: usize = 0x070;
+
+const STATUS_ACKNOWLEDGE: u32 = 1;
+const STATUS_DRIVER: u32 = 2;
+const STATUS_DRIVER_OK: u32 = 4;
+
+/// The device has a MAC address in its configuration.
+const VIRTIO_NET_F_MAC: u32 = 1 << 5;
+/// The address QEMU gives to a device by default, used if the device has none.
+const DEFAULT_MAC: [u8; 6] = [0x52, 0x54, 0x00, 0x12, 0x34, 0x56];
+
+const QUEUE_RECEIVE: u32 = 0;
+const QUEUE_TRANSMIT: u32 = 1;
+const QUEUE_SIZE: usize = 16;
+/// The buffer is written by the device.
+const DESC_F_WRITE: u16 = 2;
+
+/// Size of `virtio_net_hdr` before each packet, without mergeable receive buffers.
+const NET_HDR_SIZE: usize = 10;
+/// The longest Ethernet frame sent, without its checksum.
+pub const MAX_FRAME_SIZE: usize = 1514;
+
+#[repr(C)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+#[repr(C)]
+struct UsedElem {
+    id: u32,
+    len: u32,
+}
+
+/// A virtqueue in the legacy layout: the descriptors and the available ring in the first page,
+/// the used ring in the second one.
+struct VirtQueue {
+    /// The address of the descriptors, the memory is identity-mapped.
+    base: usize,
+    buffers: Vec<FrameTracker>,
+    /// The next entry of the available ring to fill.
+    avail_idx: u16,
+    /// The next entry of the used ring to take.
+    last_used: u16,
+}
+
+impl VirtQueue {
+    /// Set up queue `index` of the device at `regs`, whose buffers are written by the device
+    /// if `writable`.
+    fn new(regs: usize, index: u32, writable: bool) -> Self {
+        write_reg(regs, QUEUE_SEL, index);
+        assert!(
+            read_reg(regs, QUEUE_NUM_MAX) as usize >= QUEUE_SIZE,
+            "virtio-net queue too small"
+        );
+        write_reg(regs, QUEUE_NUM, QUEUE_SIZE as u32);
+        write_reg(regs, QUEUE_ALIGN, PAGE_SIZE as u32);
+        let base = VirtioHal::dma_alloc(2);
+        write_reg(regs, QUEUE_PFN, (base / PAGE_SIZE) as u32);
+        let queue = Self {
+            base: VirtioHal::phys_to_virt(base),
+            buffers: (0..QUEUE_SIZE)
+                .map(|_| FRAME_ALLOCATOR.lock().frame_alloc().unwrap())
+                .collect(),
+            avail_idx: 0,
+            last_used: 0,
+        };
+        for (i, buffer) in queue.buffers.iter().enumerate() {
+            let desc = unsafe { &mut *queue.desc(i) };
+            desc.addr = usize::from(PhysAddr::from(buffer.ppn)) as u64;
+            desc.len = PAGE_SIZE as u32;
+            desc.flags = if writable { DESC_F_WRITE } else { 0 };
+        }
+        queue
+    }
+
+    fn desc(&self, i: usize) -> *mut Descriptor {
+        (self.base + i * size_of::<Descriptor>()) as *mut Descriptor
+    }
+
+    fn avail(&self) -> usize {
+        self.base + QUEUE_SIZE * size_of::<Descriptor>()
+    }
+
+    fn used(&self) -> usize {
+        self.base + PAGE_SIZE
+    }
+
+    fn buffer(&self, id: usize) -> &'static mut [u8] {
+        self.buffers[id].ppn.get_bytes_array()
+    }
+
+    /// Make descriptor `id` available to the device, with `len` bytes of its buffer.
+    fn push(&mut self, id: usize, len: usize) {
+        unsafe {
+            (*self.desc(id)).len = len as u32;
+            let ring = (self.avail() + 4) as *mut u16;
+            ring.add(self.avail_idx as usize % QUEUE_SIZE)
+                .write_volatile(id as u16);
+            // the entry is written before the device sees the index
+            fence(Ordering::SeqCst);
+            self.avail_idx = self.avail_idx.wrapping_add(1);
+            ((self.avail() + 2) as *mut u16).write_volatile(self.avail_idx);
+        }
+    }
+
+    /// Take the next descriptor used by the device with the number of bytes written.
+    fn pop_used(&mut self) -> Option<(usize, usize)> {
+        let used_idx = unsafe { ((self.used() + 2) as *const u16).read_volatile() };
+        if used_idx == self.last_used {
+            return None;
+        }
+        // the entry is read after the index
+        fence(Ordering::SeqCst);
+        let ring = (self.used() + 4) as *const UsedElem;
+        let elem = unsafe {
+            ring.add(self.last_used as usize % QUEUE_SIZE)
+                .read_volatile()
+        };
+        self.last_used = self.last_used.wrapping_add(1);
+        Some((elem.id as usize, elem.len as usize))
+    }
+}
+
+struct Queues {
+    receive: VirtQueue,
+    transmit: VirtQueue,
+    /// The transmit descriptors not in use by the device.
+    transmit_free: Vec<usize>,
+}
+
+impl Queues {
+    /// Take back the transmit buffers the device is done with.
+    fn reclaim(&mut self) {
+        while let Some((id, _)) = self.transmit.pop_used() {
+            self.transmit_free.push(id);
+        }
+    }
+}
+
+pub struct VirtIONetwork {
+    regs: usize,
+    mac: [u8; 6],
+    queues: SpinLock<Queues>,
+    /// Called on every interrupt, once the device is unlocked.
+    handler: SpinLock<Option<fn()>>,
+}
+
+fn read_reg(regs: usize, offset: usize) -> u32 {
+    unsafe { ((regs + offset) as *const u32).read_volatile() }
+}
+
+fn write_reg(regs: usize, offset: usize, value: u32) {
+    unsafe { ((regs + offset) as *mut u32).write_volatile(value) }
+}
+
+impl VirtIONetwork {
+    fn new(regs: usize) -> Self {
+        write_reg(regs, STATUS, 0);
+        write_reg(regs, STATUS, STATUS_ACKNOWLEDGE);
+        write_reg(regs, STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+        write_reg(regs, HOST_FEATURES_SEL, 0);
+        let features = read_reg(regs, HOST_FEATURES) & VIRTIO_NET_F_MAC;
+        write_reg(regs, GUEST_FEATURES_SEL, 0);
+        write_reg(regs, GUEST_FEATURES, features);
+        write_reg(regs, GUEST_PAGE_SIZE, PAGE_SIZE as u32);
+        let mut receive = VirtQueue::new(regs, QUEUE_RECEIVE, true);
+        let transmit = VirtQueue::new(regs, QUEUE_TRANSMIT, false);
+        for id in 0..QUEUE_SIZE {
+            receive.push(id, PAGE_SIZE);
+        }
+        write_reg(
+            regs,
+            STATUS,
+            STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_DRIVER_OK,
+        );
+        write_reg(regs, QUEUE_NOTIFY, QUEUE_RECEIVE);
+        let mac = if features & VIRTIO_NET_F_MAC != 0 {
+            core::array::from_fn(|i| unsafe {
+                ((regs + VIRTIO_CONFIG + i) as *const u8).read_volatile()
+            })
+        } else {
+            DEFAULT_MAC
+        };
+        Self {
+            regs,
+            mac,
+            queues: SpinLock::new(Queues {
+                receive,
+                transmit,
+                transmit_free: (0..QUEUE_SIZE).collect(),
+            }),
+            handler: SpinLock::new(None),
+        }
+    }
+
+    pub fn mac(&self) -> [u8; 6] {
+        self.mac
+    }
+
+    /// Call `handler` on every interrupt, when packets have been received or sent.
+    pub fn set_handler(&self, handler: fn()) {
+        *self.handler.lock() = Some(handler);
+    }
+
+    /// Return the next frame received, if any.
+    pub fn receive(&self) -> Option<Vec<u8>> {
+        let mut queues = self.queues.lock();
+        let (id, len) = queues.receive.pop_used()?;
+        let frame = queues.receive.buffer(id)[NET_HDR_SIZE..len.max(NET_HDR_SIZE)].to_vec();
+        queues.receive.push(id, PAGE_SIZE);
+        write_reg(self.regs, QUEUE_NOTIFY, QUEUE_RECEIVE);
+        Some(frame)
+    }
+
+    /// Whether a frame can be sent without waiting.
+    pub fn can_send(&self) -> bool {
+        let mut queues = self.queues.lock();
+        queues.reclaim();
+        !queues.transmit_free.is_empty()
+    }
+
+    /// Send `frame`, return false if the transmit queue is full.
+    pub fn send(&self, frame: &[u8]) -> bool {
+        assert!(frame.len() <= MAX_FRAME_SIZE, "frame too long");
+        let mut queues = self.queues.lock();
+        queues.reclaim();
+        let Some(id) = queues.transmit_free.pop() else {
+            return false;
+        };
+        let buffer = queues.transmit.buffer(id);
+        // no checksum offload nor segmentation
+        buffer[..NET_HDR_SIZE].fill(0);
+        buffer[NET_HDR_SIZE..NET_HDR_SIZE + frame.len()].copy_from_slice(frame);
+        queues.transmit.push(id, NET_HDR_SIZE + frame.len());
+        write_reg(self.regs, QUEUE_NOTIFY, QUEUE_TRANSMIT);
+        true
+    }
+}
+
+impl IrqHandler for VirtIONetwork {
+    fn handle_irq(&self) {
+        let status = read_reg(self.regs, INTERRUPT_STATUS);
+        write_reg(self.regs, INTERRUPT_ACK, status);
+        let handler = *self.handler.lock();
+        if let Some(handler) = handler {
+            handler();
+        }
+    }
+}
+
+/// Find the first virtio-net device on the MMIO bus, return it with its interrupt.
+pub fn probe() -> Option<(Arc<VirtIONetwork>, usize)> {
+    (0..VIRTIO_SLOTS).find_map(|slot| {
+        let regs = VIRTIO0 + slot * VIRTIO_STRIDE;
+        let header = unsafe { &*(regs as *const VirtIOHeader) };
+        (header.verify() && header.device_type() == DeviceType::Network)
+            .then(|| (Arc::new(VirtIONetwork::new(regs)), VIRTIO0_IRQ + slot))
+    })
+}
@@ -0,0 +1,323 @@
+//! The device filesystem mounted at `/dev`
+//!
+//! It lists the character devices and the block devices, which are read and written by their
+//! own `File` rather than through the data operations of the inodes. Its entries are fixed.
+
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use common::{
+    errno::{ENOENT, ENOTTY, EPERM},
+    fs::{FileType, Stat},
+    poll::PollEvents,
+};
+use core::ops::Range;
+use easy_fs::{BLOCK_SIZE, BlockDevice};
+
+use crate::{
+    console::write_bytes,
+    drivers::block_devices,
+    memory::UserBuffer,
+    sync::{SleepLock, SpinLock, Waiter},
+    timer::get_time,
+};
+
+use super::{
+    File, FileStatus, OpenFlags,
+    tty::TTY,
+    vfs::{DirEntry, Inode, SuperBlock, alloc_dev},
+};
+
+pub struct DevfsSuperBlock {
+    root: Arc<DevfsRoot>,
+}
+
+struct DevfsRoot {
+    dev: u64,
+    devices: Vec<Arc<DeviceInode>>,
+}
+
+#[derive(Clone)]
+enum Device {
+    Null,
+    Zero,
+    Random,
+    Console,
+    /// A block device of the given number of blocks.
+    Block(Arc<dyn BlockDevice>, usize),
+}
+
+pub struct DeviceInode {
+    name: String,
+    stat: Stat,
+    device: Device,
+}
+
+/// An open device.
+pub struct DeviceFile {
+    readable: bool,
+    writable: bool,
+    status: FileStatus,
+    stat: Stat,
+    device: Device,
+    /// In bytes for block devices, unused otherwise.
+    offset: SleepLock<usize>,
+}
+
+/// State of the xorshift generator of `/dev/random`.
+///
+/// It is stirred with the timer on every read, whose low bits vary with the interrupts and the
+/// scheduling. This is not cryptographically secure.
+static RANDOM_STATE: SpinLock<u64> = SpinLock::new(0x9e37_79b9_7f4a_7c15);
+
+impl DevfsSuperBlock {
+    pub fn new() -> Arc<Self> {
+        let dev = alloc_dev();
+        let mut devices = Vec::from(
+            [
+                ("null", Device::Null),
+                ("zero", Device::Zero),
+                ("random", Device::Random),
+                ("console", Device::Console),
+            ]
+            .map(|(name, device)| (name.to_string(), device)),
+        );
+        for (name, block_device, blocks) in block_devices() {
+            devices.push((name, Device::Block(block_device, blocks)));
+        }
+        let devices = devices
+            .into_iter()
+            .zip(2..)
+            .map(|((name, device), ino)| {
+                let (file_type, permission, size) = match device {
+                    Device::Block(_, blocks) => (FileType::BlockDevice, 0o660, blocks * BLOCK_SIZE),
+                    Device::Console => (FileType::CharDevice, 0o620, 0),
+                    _ => (FileType::CharDevice, 0o666, 0),
+                };
+                Arc::new(DeviceInode {
+                    name,
+                    stat: Stat {
+                        dev,
+                        ino,
+                        mode: file_type.mode() | permission,
+                        nlink: 1,
+                        size: size as u64,
+                    },
+                    device,
+                })
+            })
+            .collect();
+        Arc::new(Self {
+            root: Arc::new(DevfsRoot { dev, devices }),
+        })
+    }
+}
+
+impl SuperBlock for DevfsSuperBlock {
+    fn root(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+}
+
+impl Inode for DevfsRoot {
+    fn stat(&self) -> Stat {
+        Stat {
+            dev: self.dev,
+            ino: 1,
+            mode: FileType::Directory.mode() | 0o755,
+            nlink: 1,
+            size: self.devices.len() as u64,
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, isize> {
+        let device = self.devices.iter().find(|device| device.name == name);
+        Ok(device.ok_or(ENOENT)?.clone())
+    }
+
+    fn create(&self, _name: &str, _file_type: FileType) -> Result<Arc<dyn Inode>, isize> {
+        Err(EPERM)
+    }
+
+    fn unlink(&self, _name: &str) -> Result<(), isize> {
+        Err(EPERM)
+    }
+
+    fn readdir(&self) -> Result<Vec<DirEntry>, isize> {
+        Ok(self
+            .devices
+            .iter()
+            .map(|device| DirEntry {
+                name: device.name.clone(),
+                ino: device.stat.ino,
+                file_type: device.stat.file_type().unwrap(),
+            })
+            .collect())
+    }
+}
+
+impl Inode for DeviceInode {
+    fn stat(&self) -> Stat {
+        self.stat
+    }
+
+    /// Devices are not truncated when opened with `O_TRUNC`.
+    fn truncate(&self) -> Result<(), isize> {
+        Ok(())
+    }
+
+    fn open(&self, flags: OpenFlags) -> Option<Arc<dyn File>> {
+        let (readable, writable) = flags.read_write();
+        Some(Arc::new(DeviceFile {
+            readable,
+            writable,
+            status: FileStatus::new(flags),
+            stat: self.stat,
+            device: self.device.clone(),
+            offset: SleepLock::new(0),
+        }))
+    }
+}
+
+/// Fill `buf` with pseudo-random bytes.
+fn random_fill(buf: &mut [u8]) {
+    let mut state = RANDOM_STATE.lock();
+    *state ^= (get_time() as u64).wrapping_mul(0x2545_f491_4f6c_dd1d);
+    if *state == 0 {
+        // xorshift is stuck at 0
+        *state = 1;
+    }
+    for chunk in buf.chunks_mut(8) {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        chunk.copy_from_slice(&state.to_le_bytes()[..chunk.len()]);
+    }
+}
+
+/// Split an access of `len` bytes at `offset` of a block device of `blocks` blocks by block.
+/// `access` is called with the block id, the range of the block and the position in the
+/// accessed bytes. Return the number of bytes accessed, which stops at the end of the device.
+fn access_blocks(
+    blocks: usize,
+    offset: usize,
+    len: usize,
+    mut access: impl FnMut(usize, Range<usize>, usize),
+) -> usize {
+    let end = (blocks * BLOCK_SIZE).min(offset + len);
+    let mut pos = offset;
+    while pos < end {
+        let block_offset = pos % BLOCK_SIZE;
+        let len = (BLOCK_SIZE - block_offset).min(end - pos);
+        access(
+            pos / BLOCK_SIZE,
+            block_offset..block_offset + len,
+            pos - offset,
+        );
+        pos += len;
+    }
+    end.saturating_sub(offset)
+}
+
+impl File for DeviceFile {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    fn status(&self) -> &FileStatus {
+        &self.status
+    }
+
+    fn read(&self, mut buf: UserBuffer) -> Result<usize, isize> {
+        Ok(match &self.device {
+            Device::Null => 0,
+            Device::Zero => {
+                for slice in buf.iter_mut() {
+                    slice.fill(0);
+                }
+                buf.len()
+            }
+            Device::Random => {
+                for slice in buf.iter_mut() {
+                    random_fill(slice);
+                }
+                buf.len()
+            }
+            Device::Console => {
+                return TTY.read(buf, self.status.get().contains(OpenFlags::NONBLOCK));
+            }
+            Device::Block(device, blocks) => {
+                let mut offset = self.offset.lock();
+                let mut total_read_size = 0;
+                for slice in buf.iter_mut() {
+                    let mut block = [0u8; BLOCK_SIZE];
+                    let read_size =
+                        access_blocks(*blocks, *offset, slice.len(), |block_id, range, pos| {
+                            device.read_block(block_id, &mut block);
+                            slice[pos..pos + range.len()].copy_from_slice(&block[range]);
+                        });
+                    *offset += read_size;
+                    total_read_size += read_size;
+                }
+                total_read_size
+            }
+        })
+    }
+
+    /// Writes to a block device bypass the block cache, so they should not be mixed with a
+    /// filesystem mounted from it.
+    fn write(&self, buf: UserBuffer) -> Result<usize, isize> {
+        Ok(match &self.device {
+            Device::Null | Device::Zero | Device::Random => buf.len(),
+            Device::Console => {
+                for slice in buf.iter() {
+                    write_bytes(slice);
+                }
+                buf.len()
+            }
+            Device::Block(device, blocks) => {
+                let mut offset = self.offset.lock();
+                let mut total_write_size = 0;
+                for slice in buf.iter() {
+                    let mut block = [0u8; BLOCK_SIZE];
+                    let write_size =
+                        access_blocks(*blocks, *offset, slice.len(), |block_id, range, pos| {
+                            if range.len() < BLOCK_SIZE {
+                                device.read_block(block_id, &mut block);
+                            }
+                            let len = range.len();
+                            block[range].copy_from_slice(&slice[pos..pos + len]);
+                            device.write_block(block_id, &block);
+                        });
+                    *offset += write_size;
+                    total_write_size += write_size;
+                }
+                total_write_size
+            }
+        })
+    }
+
+    fn stat(&self) -> Stat {
+        self.stat
+    }
+
+    fn poll(&self, waiter: Option<&Arc<Waiter>>) -> PollEvents {
+        match self.device {
+            Device::Console => TTY.poll(waiter),
+            _ => PollEvents::IN | PollEvents::OUT,
+        }
+    }
+
+    fn ioctl(&self, request: usize, arg: usize) -> isize {
+        match self.device {
+            Device::Console => TTY.ioctl(request, arg),
+            _ => -ENOTTY,
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! easy_fs behind the VFS
+
+use alloc::{
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use common::{
+    errno::{EBUSY, EEXIST, EINVAL, EISDIR, ENAMETOOLONG, ENOENT, ENOTDIR, ENOTEMPTY},
+    fs::{FileType, Stat},
+};
+use easy_fs::{BlockDevice, EasyFileSystem, NAME_MAX};
+
+use crate::sync::{SleepLock, SpinLock};
+
+use super::vfs::{DirEntry, Inode, SuperBlock, alloc_dev};
+
+/// Serializes the accesses to easy_fs.
+///
+/// easy_fs guards its state with spin locks, which are held while the disk is read or written,
+/// so other threads must sleep here rather than spinning on them with interrupts disabled.
+static EFS_LOCK: SleepLock<()> = SleepLock::new(());
+
+pub struct EfsSuperBlock {
+    root: Arc<EfsInode>,
+}
+
+/// The inodes in use, by number.
+struct InodeCache {
+    dev: u64,
+    inodes: SpinLock<BTreeMap<u32, Weak<EfsInode>>>,
+}
+
+pub struct EfsInode {
+    inode: Arc<easy_fs::Inode>,
+    cache: Arc<InodeCache>,
+}
+
+impl EfsSuperBlock {
+    /// Open the easy_fs on `block_device`, return `EINVAL` if it does not hold one.
+    pub fn open(block_device: Arc<dyn BlockDevice>) -> Result<Arc<Self>, isize> {
+        let _fs = EFS_LOCK.lock();
+        let efs = EasyFileSystem::try_open(block_device).ok_or(EINVAL)?;
+        let cache = Arc::new(InodeCache {
+            dev: alloc_dev(),
+            inodes: SpinLock::new(BTreeMap::new()),
+        });
+        let root = cache.get(Arc::new(EasyFileSystem::root_inode(&efs)));
+        Ok(Arc::new(Self { root }))
+    }
+}
+
+impl SuperBlock for EfsSuperBlock {
+    fn root(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+}
+
+impl InodeCache {
+    /// Return the `EfsInode` of `inode`, the one in use if any.
+    fn get(self: &Arc<Self>, inode: Arc<easy_fs::Inode>) -> Arc<EfsInode> {
+        let mut inodes = self.inodes.lock();
+        if let Some(cached) = inodes.get(&inode.inode_id()).and_then(Weak::upgrade) {
+            return cached;
+        }
+        inodes.retain(|_, cached| cached.strong_count() > 0);
+        let id = inode.inode_id();
+        let new = Arc::new(EfsInode {
+            inode,
+            cache: Arc::clone(self),
+        });
+        inodes.insert(id, Arc::downgrade(&new));
+        new
+    }
+
+    fn is_in_use(&self, inode_id: u32) -> bool {
+        self.inodes
+            .lock()
+            .get(&inode_id)
+            .is_some_and(|cached| cached.strong_count() > 0)
+    }
+}
+
+impl EfsInode {
+    /// Must be called with `EFS_LOCK` held.
+    fn file_type_of(inode: &easy_fs::Inode) -> FileType {
+        if inode.is_dir() {
+            FileType::Directory
+        } else if inode.is_fifo() {
+            FileType::Fifo
+        } else if inode.is_socket() {
+            FileType::Socket
+        } else {
+            FileType::Regular
+        }
+    }
+}
+
+impl Inode for EfsInode {
+    fn stat(&self) -> Stat {
+        let _fs = EFS_LOCK.lock();
+        let file_type = Self::file_type_of(&self.inode);
+        let permission = if file_type == FileType::Directory {
+            0o755
+        } else {
+            0o644
+        };
+        Stat {
+            dev: self.cache.dev,
+            ino: self.inode.inode_id() as u64,
+            mode: file_type.mode() | permission,
+            nlink: 1,
+            size: self.inode.size() as u64,
+        }
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, isize> {
+        let _fs = EFS_LOCK.lock();
+        if self.inode.is_dir() {
+            return Err(EISDIR);
+        }
+        Ok(self.inode.read_at(offset, buf))
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, isize> {
+        let _fs = EFS_LOCK.lock();
+        if self.inode.is_dir() {
+            return Err(EISDIR);
+        }
+        Ok(self.inode.write_at(offset, buf))
+    }
+
+    fn truncate(&self) -> Result<(), isize> {
+        let _fs = EFS_LOCK.lock();
+        if self.inode.is_dir() {
+            return Err(EISDIR);
+        }
+        self.inode.clear();
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, isize> {
+        let _fs = EFS_LOCK.lock();
+        if !self.inode.is_dir() {
+            return Err(ENOTDIR);
+        }
+        let inode = self.inode.find(name).ok_or(ENOENT)?;
+        Ok(self.cache.get(inode))
+    }
+
+    fn create(&self, name: &str, file_type: FileType) -> Result<Arc<dyn Inode>, isize> {
+        if name.len() > NAME_MAX {
+            return Err(ENAMETOOLONG);
+        }
+        let _fs = EFS_LOCK.lock();
+        if !self.inode.is_dir() {
+            return Err(ENOTDIR);
+        }
+        let inode = match file_type {
+            FileType::Regular => self.inode.create(name),
+            FileType::Directory => self.inode.create_dir(name),
+            FileType::Fifo => self.inode.create_fifo(name),
+            FileType::Socket => self.inode.create_socket(name),
+            _ => return Err(EINVAL),
+        }
+        .ok_or(EEXIST)?;
+        Ok(self.cache.get(inode))
+    }
+
+    fn unlink(&self, name: &str) -> Result<(), isize> {
+        let _fs = EFS_LOCK.lock();
+        if !self.inode.is_dir() {
+            return Err(ENOTDIR);
+        }
+        let inode = self.inode.find(name).ok_or(ENOENT)?;
+        // the data is freed right away, so the inode must not be open or a mount point
+        if self.cache.is_in_use(inode.inode_id()) {
+            return Err(EBUSY);
+        }
+        if inode.is_dir() && !inode.ls().is_empty() {
+            return Err(ENOTEMPTY);
+        }
+        if !self.inode.unlink(name) {
+            return Err(ENOENT);
+        }
+        Ok(())
+    }
+
+    fn readdir(&self) -> Result<Vec<DirEntry>, isize> {
+        let _fs = EFS_LOCK.lock();
+        if !self.inode.is_dir() {
+            return Err(ENOTDIR);
+        }
+        Ok(self
+            .inode
+            .ls()
+            .into_iter()
+            .filter_map(|name| {
+                let inode = self.inode.find(&name)?;
+                Some(DirEntry {
+                    ino: inode.inode_id() as u64,
+                    file_type: Self::file_type_of(&inode),
+                    name,
+                })
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,154 @@
+//! Event counters read and written as 8-byte integers
+
+use alloc::sync::Arc;
+use common::{
+    errno::{EAGAIN, EINTR, EINVAL},
+    fs::{FileType, Stat},
+    poll::PollEvents,
+};
+
+use crate::{
+    fs::{File, FileStatus, OpenFlags},
+    memory::UserBuffer,
+    proc::current_proc,
+    sync::{SpinLock, WaitQueue, Waiter},
+};
+
+/// The counter never exceeds this, a write which would is blocked.
+const MAX_COUNTER: u64 = u64::MAX - 1;
+
+pub struct EventFd {
+    status: FileStatus,
+    /// Reads decrement the counter by one instead of resetting it.
+    semaphore: bool,
+    inner: SpinLock<EventFdInner>,
+}
+
+struct EventFdInner {
+    counter: u64,
+    readers: WaitQueue,
+    writers: WaitQueue,
+}
+
+impl EventFd {
+    pub fn new(initval: u64, semaphore: bool, flags: OpenFlags) -> Arc<Self> {
+        Arc::new(Self {
+            status: FileStatus::new(flags),
+            semaphore,
+            inner: SpinLock::new(EventFdInner {
+                counter: initval,
+                readers: WaitQueue::new(),
+                writers: WaitQueue::new(),
+            }),
+        })
+    }
+
+    /// Run `f` on the counter until it succeeds or fails other than with `EAGAIN`, waiting on
+    /// the queue chosen by `queue` in between unless nonblocking.
+    fn wait_until(
+        &self,
+        queue: fn(&mut EventFdInner) -> &mut WaitQueue,
+        mut f: impl FnMut(&mut EventFdInner) -> Result<u64, isize>,
+    ) -> Result<u64, isize> {
+        let nonblock = self.status.get().contains(OpenFlags::NONBLOCK);
+        loop {
+            if !current_proc()
+                .borrow_inner_mut()
+                .unblocked_signals()
+                .is_empty()
+            {
+                return Err(EINTR);
+            }
+            let mut inner = self.inner.lock();
+            match f(&mut inner) {
+                Err(EAGAIN) if nonblock => return Err(EAGAIN),
+                Err(EAGAIN) => {}
+                result => return result,
+            }
+            let waiter = Waiter::new(None);
+            queue(&mut inner).push(&waiter);
+            drop(inner);
+            waiter.wait();
+        }
+    }
+}
+
+impl File for EventFd {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn status(&self) -> &FileStatus {
+        &self.status
+    }
+
+    /// Read the counter and reset it, or decrement it by one in semaphore mode, waiting while
+    /// it is zero.
+    fn read(&self, mut buf: UserBuffer) -> Result<usize, isize> {
+        if buf.len() < size_of::<u64>() {
+            return Err(EINVAL);
+        }
+        let value = self.wait_until(
+            |inner| &mut inner.readers,
+            |inner| {
+                if inner.counter == 0 {
+                    return Err(EAGAIN);
+                }
+                let value = if self.semaphore { 1 } else { inner.counter };
+                inner.counter -= value;
+                inner.writers.wake_all();
+                Ok(value)
+            },
+        )?;
+        Ok(buf.write(&value.to_ne_bytes()))
+    }
+
+    /// Add the value written to the counter, waiting while it would exceed `MAX_COUNTER`.
+    fn write(&self, buf: UserBuffer) -> Result<usize, isize> {
+        if buf.len() < size_of::<u64>() {
+            return Err(EINVAL);
+        }
+        let value = u64::from_ne_bytes(buf.to_vec()[..size_of::<u64>()].try_into().unwrap());
+        if value == u64::MAX {
+            return Err(EINVAL);
+        }
+        self.wait_until(
+            |inner| &mut inner.writers,
+            |inner| {
+                if inner.counter > MAX_COUNTER - value {
+                    return Err(EAGAIN);
+                }
+                inner.counter += value;
+                if value > 0 {
+                    inner.readers.wake_all();
+                }
+                Ok(value)
+            },
+        )?;
+        Ok(size_of::<u64>())
+    }
+
+    fn stat(&self) -> Stat {
+        Stat {
+            mode: FileType::Regular.mode() | 0o600,
+            nlink: 1,
+            ..Default::default()
+        }
+    }
+
+    fn poll(&self, waiter: Option<&Arc<Waiter>>) -> PollEvents {
+        let mut inner = self.inner.lock();
+        if let Some(waiter) = waiter {
+            inner.readers.push(waiter);
+            inner.writers.push(waiter);
+        }
+        let mut events = PollEvents::empty();
+        events.set(PollEvents::IN, inner.counter > 0);
+        events.set(PollEvents::OUT, inner.counter < MAX_COUNTER);
+        events
+    }
+}
@@ -1,43 +1,58 @@
 use alloc::{sync::Arc, vec::Vec};
 use bitflags::bitflags;
-use easy_fs::{EasyFileSystem, Inode};
-use lazy_static::lazy_static;
+use common::{
+    errno::{EEXIST, EINVAL, EISDIR, ENXIO},
+    fs::{DIRENT64_NAME_OFFSET, Dirent64, FileType, Stat},
+};
 
-use crate::{drivers::BLOCK_DEVICE, fs::File, memory::UserBuffer, sync::UPSafeCell};
+use crate::{
+    fs::{File, FileStatus, Pipe},
+    memory::UserBuffer,
+    sync::SleepLock,
+};
 
-lazy_static! {
-    pub static ref ROOT_INODE: Arc<Inode> = {
-        let efs = EasyFileSystem::open(BLOCK_DEVICE.clone());
-        Arc::new(EasyFileSystem::root_inode(&efs))
-    };
-}
+use super::{
+    mount::{self, Mount},
+    vfs::{Dentry, Inode},
+};
 
+/// An open file or directory of a mounted filesystem.
 pub struct OSInode {
     readable: bool,
     writable: bool,
-    inner: UPSafeCell<OSInodeInner>,
+    status: FileStatus,
+    inner: SleepLock<OSInodeInner>,
+    /// Keeps the filesystem mounted.
+    _mount: Arc<Mount>,
 }
 
 pub struct OSInodeInner {
+    /// In bytes for files, in entries for directories.
     offset: usize,
-    inode: Arc<Inode>,
+    inode: Arc<dyn Inode>,
 }
 
 impl OSInode {
-    pub fn new(readable: bool, writable: bool, inode: Arc<Inode>) -> Self {
+    pub fn new(flags: OpenFlags, dentry: Dentry) -> Self {
+        let (readable, writable) = flags.read_write();
         Self {
             readable,
             writable,
-            inner: unsafe { UPSafeCell::new(OSInodeInner { offset: 0, inode }) },
+            status: FileStatus::new(flags),
+            inner: SleepLock::new(OSInodeInner {
+                offset: 0,
+                inode: dentry.inode,
+            }),
+            _mount: dentry.mount,
         }
     }
 
     pub fn read_all(&self) -> Vec<u8> {
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.inner.lock();
         let mut buffer = [0u8; 512];
         let mut v: Vec<u8> = Vec::new();
         loop {
-            let len = inner.inode.read_at(inner.offset, &mut buffer);
+            let len = inner.inode.read_at(inner.offset, &mut buffer).unwrap_or(0);
             if len == 0 {
                 break;
             }
@@ -57,40 +72,102 @@ impl File for OSInode {
         self.writable
     }
 
-    fn read(&self, mut buf: UserBuffer) -> usize {
-        let mut inner = self.inner.borrow_mut();
+    fn status(&self) -> &FileStatus {
+        &self.status
+    }
+
+    /// Nothing can be read from directories.
+    fn read(&self, mut buf: UserBuffer) -> Result<usize, isize> {
+        let mut inner = self.inner.lock();
         let mut total_read_size = 0usize;
         for slice in buf.iter_mut() {
-            let read_size = inner.inode.read_at(inner.offset, slice);
+            let read_size = inner.inode.read_at(inner.offset, slice).unwrap_or(0);
             if read_size == 0 {
                 break;
             }
             inner.offset += read_size;
             total_read_size += read_size;
         }
-        total_read_size
+        Ok(total_read_size)
     }
 
-    fn write(&self, buf: UserBuffer) -> usize {
-        let mut inner = self.inner.borrow_mut();
+    /// The write ends short if the filesystem is full. With `O_APPEND` it starts at the end of
+    /// the file.
+    fn write(&self, buf: UserBuffer) -> Result<usize, isize> {
+        let mut inner = self.inner.lock();
+        if self.status.get().contains(OpenFlags::APPEND) {
+            inner.offset = inner.inode.stat().size as usize;
+        }
         let mut total_write_size = 0usize;
         for slice in buf.iter() {
-            let write_size = inner.inode.write_at(inner.offset, slice);
+            let Ok(write_size) = inner.inode.write_at(inner.offset, slice) else {
+                break;
+            };
             assert_eq!(write_size, slice.len());
             inner.offset += write_size;
             total_write_size += write_size;
         }
-        total_write_size
+        Ok(total_write_size)
+    }
+
+    fn stat(&self) -> Stat {
+        self.inner.lock().inode.stat()
+    }
+
+    fn getdents(&self, mut buf: UserBuffer) -> isize {
+        let mut inner = self.inner.lock();
+        let entries = match inner.inode.readdir() {
+            Ok(entries) => entries,
+            Err(errno) => return -errno,
+        };
+        let mut records = Vec::new();
+        let mut count = 0;
+        for entry in entries.iter().skip(inner.offset) {
+            let reclen = (DIRENT64_NAME_OFFSET + entry.name.len() + 1).next_multiple_of(8);
+            if records.len() + reclen > buf.len() {
+                break;
+            }
+            count += 1;
+            let header = Dirent64 {
+                ino: entry.ino,
+                off: (inner.offset + count) as i64,
+                reclen: reclen as u16,
+                dtype: entry.file_type as u8,
+            };
+            let header = unsafe {
+                core::slice::from_raw_parts(&header as *const _ as *const u8, DIRENT64_NAME_OFFSET)
+            };
+            records.extend_from_slice(header);
+            records.extend_from_slice(entry.name.as_bytes());
+            records.resize(
+                records.len() + reclen - DIRENT64_NAME_OFFSET - entry.name.len(),
+                0,
+            );
+        }
+        if count == 0 && inner.offset < entries.len() {
+            // the buffer is too small for the next entry
+            return -EINVAL;
+        }
+        inner.offset += count;
+        buf.write(&records) as isize
     }
 }
 
 bitflags! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
     pub struct OpenFlags: u32 {
         const RDONLY = 0;
         const WRONLY = 1 << 0;
         const RDWR = 1 << 1;
+        /// With `CREATE`, fail if the file exists.
+        const EXCL = 1 << 7;
         const CREATE = 1 << 9;
         const TRUNC = 1 << 10;
+        const NONBLOCK = 1 << 11;
+        const APPEND = 1 << 12;
+        const CLOEXEC = 1 << 19;
+        /// The status flags, which belong to the open file.
+        const STATUS = Self::NONBLOCK.bits() | Self::APPEND.bits();
     }
 }
 
@@ -98,44 +175,74 @@ impl OpenFlags {
     /// Do not check validity for simplicity
     /// Return (readable, writable)
     pub fn read_write(&self) -> (bool, bool) {
-        if self.is_empty() {
-            (true, false)
-        } else if self.contains(Self::WRONLY) {
+        if self.contains(Self::WRONLY) {
             (false, true)
-        } else {
+        } else if self.contains(Self::RDWR) {
             (true, true)
+        } else {
+            (true, false)
         }
     }
-}
 
-pub fn open_file(name: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
-    let (readable, writable) = flags.read_write();
-    if flags.contains(OpenFlags::CREATE) {
-        if let Some(inode) = ROOT_INODE.find(name) {
-            // clear size
-            inode.clear();
-            Some(Arc::new(OSInode::new(readable, writable, inode)))
-        } else {
-            // create file
-            ROOT_INODE
-                .create(name)
-                .map(|inode| Arc::new(OSInode::new(readable, writable, inode)))
+    /// Return the access mode of a file opened with these flags.
+    pub fn access_mode(readable: bool, writable: bool) -> Self {
+        match (readable, writable) {
+            (true, true) => Self::RDWR,
+            (false, true) => Self::WRONLY,
+            _ => Self::RDONLY,
         }
-    } else {
-        ROOT_INODE.find(name).map(|inode| {
-            if flags.contains(OpenFlags::TRUNC) {
-                inode.clear();
-            }
-            Arc::new(OSInode::new(readable, writable, inode))
-        })
     }
 }
 
+/// Open the file at `path`, directories can only be opened for reading.
+pub fn open_file(path: &str, flags: OpenFlags) -> Result<Arc<OSInode>, isize> {
+    Ok(Arc::new(OSInode::new(flags, resolve(path, flags)?)))
+}
+
+/// Open the file at `path` like `open_file`, or the device or the FIFO at `path`.
+pub fn open(path: &str, flags: OpenFlags) -> Result<Arc<dyn File>, isize> {
+    let dentry = resolve(path, flags)?;
+    if let Some(device) = dentry.inode.open(flags) {
+        return Ok(device);
+    }
+    match dentry.inode.file_type() {
+        FileType::Fifo => return Ok(Pipe::open_fifo(dentry, flags)?),
+        // sockets are connected to instead
+        FileType::Socket => return Err(ENXIO),
+        _ => {}
+    }
+    Ok(Arc::new(OSInode::new(flags, dentry)))
+}
+
+/// Resolve `path` to be opened with `flags`, creating or truncating it.
+fn resolve(path: &str, flags: OpenFlags) -> Result<Dentry, isize> {
+    let (_, writable) = flags.read_write();
+    let dentry = match mount::lookup(path) {
+        Ok(_) if flags.contains(OpenFlags::CREATE | OpenFlags::EXCL) => return Err(EEXIST),
+        Ok(dentry) => {
+            if writable && dentry.inode.file_type() == FileType::Directory {
+                return Err(EISDIR);
+            }
+            // only regular files opened for writing are truncated
+            if flags.contains(OpenFlags::TRUNC)
+                && writable
+                && dentry.inode.file_type() == FileType::Regular
+            {
+                dentry.inode.truncate()?;
+            }
+            dentry
+        }
+        Err(_) if flags.contains(OpenFlags::CREATE) => mount::create(path, FileType::Regular)?,
+        Err(errno) => return Err(errno),
+    };
+    Ok(dentry)
+}
+
 pub fn list_apps() {
-    let apps = ROOT_INODE.ls();
+    let apps = mount::lookup("/").unwrap().inode.readdir().unwrap();
     println!("/**** APPS ****");
     for app in apps {
-        println!("{}", app);
+        println!("{}", app.name);
     }
     println!("**************/");
 }
@@ -1,16 +1,118 @@
-use crate::memory::UserBuffer;
+use alloc::sync::Arc;
+use common::{
+    errno::{EINVAL, ENOTDIR, ENOTTY},
+    fs::Stat,
+    poll::PollEvents,
+};
+use core::sync::atomic::{AtomicU32, Ordering};
 
+use crate::{ipc::mqueue::MqFile, memory::UserBuffer, net::Socket, sync::Waiter};
+
+mod devfs;
+mod efs;
+mod eventfd;
 mod inode;
+mod mount;
 mod pipe;
-mod stdio;
+mod poll;
+mod procfs;
+mod signalfd;
+mod timerfd;
+mod tmpfs;
+mod tty;
+mod vfs;
 
-pub use self::inode::{OpenFlags, list_apps, open_file};
+pub use self::eventfd::EventFd;
+pub use self::inode::{OpenFlags, list_apps, open, open_file};
+pub use self::mount::{create, lookup, mount, umount, unlink};
 pub use self::pipe::Pipe;
-pub use self::stdio::{Stdin, Stdout};
+pub use self::poll::poll;
+pub use self::signalfd::SignalFd;
+pub use self::timerfd::TimerFd;
+
+/// Mount the root filesystem and connect the console TTY.
+pub fn init() {
+    mount::init();
+    tty::init();
+}
 
+/// An open file, shared by the file descriptors duplicated from one another.
 pub trait File: Send + Sync {
     fn readable(&self) -> bool;
     fn writable(&self) -> bool;
-    fn read(&self, buf: UserBuffer) -> usize;
-    fn write(&self, buf: UserBuffer) -> usize;
+    /// The status flags set by `open` and `F_SETFL`.
+    fn status(&self) -> &FileStatus;
+    /// Return the number of bytes read, or `EAGAIN` if it would block with `O_NONBLOCK`.
+    fn read(&self, buf: UserBuffer) -> Result<usize, isize>;
+    /// Return the number of bytes written, or `EAGAIN` if it would block with `O_NONBLOCK`.
+    fn write(&self, buf: UserBuffer) -> Result<usize, isize>;
+    fn stat(&self) -> Stat;
+    /// Return the events which are ready. If `waiter` is given, it is woken up when they may
+    /// have changed, files which never block ignore it.
+    fn poll(&self, _waiter: Option<&Arc<Waiter>>) -> PollEvents {
+        let mut events = PollEvents::empty();
+        events.set(PollEvents::IN, self.readable());
+        events.set(PollEvents::OUT, self.writable());
+        events
+    }
+    /// Device-specific control operations, `arg` is usually a pointer in user space.
+    fn ioctl(&self, _request: usize, _arg: usize) -> isize {
+        -ENOTTY
+    }
+    /// File-specific `fcntl` commands, such as the pipe capacity ones.
+    fn fcntl(&self, _cmd: usize, _arg: usize) -> isize {
+        -EINVAL
+    }
+    /// Return the socket operations of a socket.
+    fn as_socket(&self) -> Option<&dyn Socket> {
+        None
+    }
+    /// Return the message queue of a message queue descriptor.
+    fn as_mqueue(&self) -> Option<&MqFile> {
+        None
+    }
+    /// Return the timer of a timerfd.
+    fn as_timerfd(&self) -> Option<&TimerFd> {
+        None
+    }
+    /// Return the signalfd whose mask `signalfd4` changes.
+    fn as_signalfd(&self) -> Option<&SignalFd> {
+        None
+    }
+    /// Read the entries of a directory as `Dirent64` records, return the number of bytes read.
+    fn getdents(&self, _buf: UserBuffer) -> isize {
+        -ENOTDIR
+    }
+}
+
+/// The status flags of an open file, the `OpenFlags` which can be changed by `F_SETFL`.
+pub struct FileStatus(AtomicU32);
+
+impl FileStatus {
+    pub fn new(flags: OpenFlags) -> Self {
+        Self(AtomicU32::new((flags & OpenFlags::STATUS).bits()))
+    }
+
+    pub fn get(&self) -> OpenFlags {
+        OpenFlags::from_bits_truncate(self.0.load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, flags: OpenFlags) {
+        self.0
+            .store((flags & OpenFlags::STATUS).bits(), Ordering::Relaxed);
+    }
+}
+
+/// An entry of the fd table of a process.
+#[derive(Clone)]
+pub struct FileDescriptor {
+    pub file: Arc<dyn File>,
+    /// Closed by `exec`.
+    pub cloexec: bool,
+}
+
+impl FileDescriptor {
+    pub fn new(file: Arc<dyn File>, cloexec: bool) -> Self {
+        Self { file, cloexec }
+    }
 }
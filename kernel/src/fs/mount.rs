@@ -0,0 +1,207 @@
+//! The mount table and path lookup
+//!
+//! The root filesystem is the easy_fs on `/dev/vda` with the devfs on `/dev`, the procfs on
+//! `/proc` and a tmpfs on `/tmp`, others are mounted on directories of the mounted ones. There
+//! are no working directories, so all paths are resolved from the root, and a mount point
+//! resolves to the root directory of the filesystem mounted there.
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use common::{
+    errno::{EBUSY, EEXIST, EINVAL, ENODEV, ENOENT, ENOTDIR},
+    fs::FileType,
+};
+use lazy_static::lazy_static;
+
+use crate::{drivers::block_device, sync::SpinLock};
+
+use super::{
+    devfs::DevfsSuperBlock,
+    efs::EfsSuperBlock,
+    procfs::ProcfsSuperBlock,
+    tmpfs::TmpfsSuperBlock,
+    vfs::{Dentry, Inode, SuperBlock},
+};
+
+/// Block device holding the root filesystem.
+const ROOT_DEVICE: &str = "vda";
+
+/// A mounted filesystem. The open files on it and the filesystems mounted on it hold
+/// references, so that it is not unmounted under them.
+pub struct Mount {
+    sb: Arc<dyn SuperBlock>,
+    /// The directory it is mounted on and the filesystem of it, `None` for the root.
+    mountpoint: Option<(Arc<dyn Inode>, Arc<Mount>)>,
+    /// The device or the name of the filesystem.
+    source: String,
+    /// The path of the mount point.
+    target: String,
+    fstype: String,
+}
+
+lazy_static! {
+    /// The mounted filesystems in mount order, the root filesystem comes first.
+    static ref MOUNT_TABLE: SpinLock<Vec<Arc<Mount>>> = SpinLock::new(Vec::new());
+}
+
+/// Split `path` into its components, `..` is resolved by dropping the previous one.
+fn components(path: &str) -> Vec<&str> {
+    let mut components = Vec::new();
+    for name in path.split('/') {
+        match name {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            _ => components.push(name),
+        }
+    }
+    components
+}
+
+/// Mount the root filesystem, the devfs, the procfs and the tmpfs.
+pub fn init() {
+    let block_device = block_device(ROOT_DEVICE).expect("No root block device");
+    let sb = EfsSuperBlock::open(block_device).expect("Error loading EFS!");
+    MOUNT_TABLE.lock().push(Arc::new(Mount {
+        sb,
+        mountpoint: None,
+        source: format!("/dev/{ROOT_DEVICE}"),
+        target: String::from("/"),
+        fstype: String::from("easy_fs"),
+    }));
+    mount("devfs", "/dev", "devfs").expect("Error mounting devfs!");
+    mount("proc", "/proc", "procfs").expect("Error mounting procfs!");
+    mount("tmpfs", "/tmp", "tmpfs").expect("Error mounting tmpfs!");
+}
+
+/// Return the filesystem mounted on `inode` last.
+fn mounted_on(inode: &Arc<dyn Inode>) -> Option<Arc<Mount>> {
+    MOUNT_TABLE
+        .lock()
+        .iter()
+        .rev()
+        .find(|mount| {
+            mount
+                .mountpoint
+                .as_ref()
+                .is_some_and(|(mountpoint, _)| Arc::ptr_eq(mountpoint, inode))
+        })
+        .cloned()
+}
+
+fn walk(path: &[&str]) -> Result<Dentry, isize> {
+    let mut mount = Arc::clone(&MOUNT_TABLE.lock()[0]);
+    let mut inode = mount.sb.root();
+    for name in path {
+        inode = inode.lookup(name)?;
+        while let Some(mounted) = mounted_on(&inode) {
+            inode = mounted.sb.root();
+            mount = mounted;
+        }
+    }
+    Ok(Dentry { inode, mount })
+}
+
+/// Resolve `path`, crossing mount points.
+pub fn lookup(path: &str) -> Result<Dentry, isize> {
+    walk(&components(path))
+}
+
+/// Create a file or directory at `path`.
+pub fn create(path: &str, file_type: FileType) -> Result<Dentry, isize> {
+    let path = components(path);
+    // the root always exists
+    let (name, parent) = path.split_last().ok_or(EEXIST)?;
+    let parent = walk(parent)?;
+    let inode = parent.inode.create(name, file_type)?;
+    Ok(Dentry {
+        inode,
+        mount: parent.mount,
+    })
+}
+
+/// Remove the file or the empty directory at `path`.
+pub fn unlink(path: &str) -> Result<(), isize> {
+    let path = components(path);
+    let (name, parent) = path.split_last().ok_or(EBUSY)?;
+    let parent = walk(parent)?.inode;
+    if mounted_on(&parent.lookup(name)?).is_some() {
+        return Err(EBUSY);
+    }
+    parent.unlink(name)
+}
+
+/// Mount the filesystem of type `fstype` from `source`, like `/dev/vdb`, on directory `target`.
+pub fn mount(source: &str, target: &str, fstype: &str) -> Result<(), isize> {
+    let path = components(target);
+    let target = walk(&path)?;
+    if target.inode.file_type() != FileType::Directory {
+        return Err(ENOTDIR);
+    }
+    let source_mounted = |mounts: &[Arc<Mount>]| mounts.iter().any(|mount| mount.source == source);
+    // a device is mounted once, as the superblocks do not share their state
+    let mut exclusive = false;
+    let sb: Arc<dyn SuperBlock> = match fstype {
+        "easy_fs" => {
+            // devices are only known by name for now
+            let device = source.strip_prefix("/dev/").ok_or(ENOENT)?;
+            let block_device = block_device(device).ok_or(ENOENT)?;
+            if source_mounted(&MOUNT_TABLE.lock()) {
+                return Err(EBUSY);
+            }
+            exclusive = true;
+            EfsSuperBlock::open(block_device)?
+        }
+        "devfs" => DevfsSuperBlock::new(),
+        "procfs" => ProcfsSuperBlock::new(),
+        "tmpfs" => TmpfsSuperBlock::new(),
+        _ => return Err(ENODEV),
+    };
+    let mut mount_table = MOUNT_TABLE.lock();
+    // checked again with the table locked, as it may have been mounted while opening it
+    if exclusive && source_mounted(&mount_table) {
+        return Err(EBUSY);
+    }
+    mount_table.push(Arc::new(Mount {
+        sb,
+        mountpoint: Some((target.inode, target.mount)),
+        source: source.to_string(),
+        target: format!("/{}", path.join("/")),
+        fstype: fstype.to_string(),
+    }));
+    Ok(())
+}
+
+/// Return the lines of `/proc/mounts`, the source, mount point and type of each filesystem.
+pub fn mounts() -> String {
+    MOUNT_TABLE
+        .lock()
+        .iter()
+        .map(|mount| format!("{} {} {}\n", mount.source, mount.target, mount.fstype))
+        .collect()
+}
+
+/// Unmount the filesystem mounted on `target`.
+pub fn umount(target: &str) -> Result<(), isize> {
+    let target = lookup(target)?;
+    if !Arc::ptr_eq(&target.inode, &target.mount.sb.root()) {
+        return Err(EINVAL);
+    }
+    drop(target.inode);
+    let mount = target.mount;
+    if mount.mountpoint.is_none() {
+        return Err(EBUSY);
+    }
+    let mut table = MOUNT_TABLE.lock();
+    // referenced by the table and `mount` only
+    if Arc::strong_count(&mount) > 2 {
+        return Err(EBUSY);
+    }
+    table.retain(|mounted| !Arc::ptr_eq(mounted, &mount));
+    Ok(())
+}
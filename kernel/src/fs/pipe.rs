@@ -1,89 +1,248 @@
-use alloc::sync::{Arc, Weak};
+use alloc::{
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use common::{
+    errno::{EAGAIN, EBUSY, EINTR, EINVAL, ENOMEM, ENXIO, EPERM, EPIPE},
+    fs::{F_GETPIPE_SZ, F_SETPIPE_SZ, FileType, PIPE_BUF, Stat},
+    poll::PollEvents,
+    sig::SignalFlags,
+};
 
-use crate::{fs::File, memory::UserBuffer, proc::suspend_current_and_run_next, sync::UPSafeCell};
+use lazy_static::lazy_static;
 
-const RING_BUFFER_SIZE: usize = 32;
+use crate::{
+    config::PAGE_SIZE,
+    fs::{File, FileStatus, OpenFlags},
+    memory::{FRAME_ALLOCATOR, FrameAllocator, FrameTracker, UserBuffer},
+    proc::current_proc,
+    sync::{SpinLock, WaitQueue, Waiter},
+};
+
+use super::vfs::Dentry;
+
+/// The capacity of a new pipe.
+const DEFAULT_PIPE_SIZE: usize = 16 * PAGE_SIZE;
+/// The largest capacity `F_SETPIPE_SZ` accepts.
+const MAX_PIPE_SIZE: usize = 256 * PAGE_SIZE;
 
 pub struct Pipe {
     readable: bool,
     writable: bool,
-    buffer: Arc<UPSafeCell<PipeRingBuffer>>,
+    status: FileStatus,
+    buffer: Arc<SharedBuffer>,
+    /// The FIFO opened, `None` for an anonymous pipe.
+    fifo: Option<Dentry>,
 }
 
+/// A ring of bytes stored in frames, so its capacity is a multiple of `PAGE_SIZE`.
 pub struct PipeRingBuffer {
-    arr: [u8; RING_BUFFER_SIZE],
-    head: usize, // point to the next write position
-    tail: usize, // point to the next read position
-    write_end: Option<Weak<Pipe>>,
+    pages: Vec<FrameTracker>,
+    /// The position of the next byte to read.
+    tail: usize,
+    /// Number of bytes stored.
+    len: usize,
+    /// Number of open `Pipe`s reading and writing the buffer, each shared by the fds
+    /// duplicated by `dup` and `fork`.
+    read_ends: usize,
+    write_ends: usize,
+    /// Threads waiting for data, for the write ends to be closed, or for a write end to be
+    /// opened.
+    readers: WaitQueue,
+    /// Threads waiting for space, or for a read end to be opened.
+    writers: WaitQueue,
+}
+
+/// The buffer shared by the ends of a pipe.
+type SharedBuffer = SpinLock<PipeRingBuffer>;
+
+lazy_static! {
+    /// The buffers of the open FIFOs by device and inode number.
+    static ref FIFOS: SpinLock<BTreeMap<(u64, u64), Weak<SharedBuffer>>> =
+        SpinLock::new(BTreeMap::new());
+}
+
+/// Whether unblocked signals are pending, checked without the buffer locked as the process is
+/// locked before the buffer elsewhere.
+fn interrupted() -> bool {
+    !current_proc()
+        .borrow_inner_mut()
+        .unblocked_signals()
+        .is_empty()
+}
+
+/// Allocate the frames of a buffer of `size` bytes rounded up to pages.
+fn alloc_pages(size: usize) -> Result<Vec<FrameTracker>, isize> {
+    (0..size.div_ceil(PAGE_SIZE).max(1))
+        .map(|_| FRAME_ALLOCATOR.lock().frame_alloc().ok_or(ENOMEM))
+        .collect()
 }
 
 impl Pipe {
-    pub fn read_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
-        Self {
-            readable: true,
-            writable: false,
-            buffer,
+    /// Open an end of `buffer`, the threads waiting for it to be opened are woken up.
+    fn with_buffer(buffer: Arc<SharedBuffer>, flags: OpenFlags, fifo: Option<Dentry>) -> Arc<Self> {
+        let (readable, writable) = flags.read_write();
+        let mut ring_buffer = buffer.lock();
+        if readable {
+            ring_buffer.read_ends += 1;
+            ring_buffer.writers.wake_all();
         }
-    }
-    pub fn write_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
-        Self {
-            readable: false,
-            writable: true,
-            buffer,
+        if writable {
+            ring_buffer.write_ends += 1;
+            ring_buffer.readers.wake_all();
         }
+        drop(ring_buffer);
+        Arc::new(Self {
+            readable,
+            writable,
+            status: FileStatus::new(flags),
+            buffer,
+            fifo,
+        })
+    }
+
+    /// Return the read end and the write end, whose status flags are those of `flags`.
+    pub fn new(flags: OpenFlags) -> Result<(Arc<Pipe>, Arc<Pipe>), isize> {
+        let buffer = Arc::new(SpinLock::new(PipeRingBuffer::new()?));
+        let flags = flags & OpenFlags::STATUS;
+        let read_end = Pipe::with_buffer(buffer.clone(), flags | OpenFlags::RDONLY, None);
+        let write_end = Pipe::with_buffer(buffer, flags | OpenFlags::WRONLY, None);
+        Ok((read_end, write_end))
     }
 
-    pub fn new() -> (Arc<Pipe>, Arc<Pipe>) {
-        let buffer = Arc::new(unsafe { UPSafeCell::new(PipeRingBuffer::new()) });
-        let read_end = Arc::new(Pipe::read_end_with_buffer(buffer.clone()));
-        let write_end = Arc::new(Pipe::write_end_with_buffer(buffer.clone()));
-        buffer.borrow_mut().set_write_end(&write_end);
-        (read_end, write_end)
+    /// Open the FIFO `fifo`, whose data is shared by all its open files. Without `O_NONBLOCK`
+    /// a read end waits for a write end to be opened and the other way around, otherwise
+    /// opening a write end fails with `ENXIO` if there is no read end.
+    pub fn open_fifo(fifo: Dentry, flags: OpenFlags) -> Result<Arc<Self>, isize> {
+        let (readable, writable) = flags.read_write();
+        let stat = fifo.inode.stat();
+        let buffer = {
+            let mut fifos = FIFOS.lock();
+            fifos.retain(|_, buffer| buffer.strong_count() > 0);
+            match fifos.get(&(stat.dev, stat.ino)).and_then(Weak::upgrade) {
+                Some(buffer) => buffer,
+                None => {
+                    let buffer = Arc::new(SpinLock::new(PipeRingBuffer::new()?));
+                    fifos.insert((stat.dev, stat.ino), Arc::downgrade(&buffer));
+                    buffer
+                }
+            }
+        };
+        let nonblock = flags.contains(OpenFlags::NONBLOCK);
+        if nonblock && !readable && buffer.lock().read_ends == 0 {
+            return Err(ENXIO);
+        }
+        let pipe = Pipe::with_buffer(Arc::clone(&buffer), flags, Some(fifo));
+        if nonblock || (readable && writable) {
+            return Ok(pipe);
+        }
+        loop {
+            if interrupted() {
+                return Err(EINTR);
+            }
+            let mut ring_buffer = buffer.lock();
+            let other_end_opened = if readable {
+                ring_buffer.write_ends > 0
+            } else {
+                ring_buffer.read_ends > 0
+            };
+            if other_end_opened {
+                return Ok(pipe);
+            }
+            let waiter = Waiter::new(None);
+            if readable {
+                ring_buffer.readers.push(&waiter);
+            } else {
+                ring_buffer.writers.push(&waiter);
+            }
+            drop(ring_buffer);
+            waiter.wait();
+        }
     }
 }
 
 impl PipeRingBuffer {
-    pub fn new() -> Self {
-        Self {
-            arr: [0; RING_BUFFER_SIZE],
-            head: 0,
+    pub fn new() -> Result<Self, isize> {
+        Ok(Self {
+            pages: alloc_pages(DEFAULT_PIPE_SIZE)?,
             tail: 0,
-            write_end: None,
-        }
+            len: 0,
+            read_ends: 0,
+            write_ends: 0,
+            readers: WaitQueue::new(),
+            writers: WaitQueue::new(),
+        })
     }
 
-    fn set_write_end(&mut self, write_end: &Arc<Pipe>) {
-        self.write_end = Some(Arc::downgrade(write_end));
+    fn capacity(&self) -> usize {
+        self.pages.len() * PAGE_SIZE
     }
 
-    fn is_full(&self) -> bool {
-        (self.head + 1) % RING_BUFFER_SIZE == self.tail
+    fn free(&self) -> usize {
+        self.capacity() - self.len
     }
 
     fn is_empty(&self) -> bool {
-        self.head == self.tail
+        self.len == 0
     }
 
-    fn push(&mut self, byte: u8) -> Result<(), u8> {
-        if self.is_full() {
-            return Err(byte);
+    /// Move the bytes of `buf` which fit in the ring, return their number.
+    fn push_slice(&mut self, buf: &[u8]) -> usize {
+        let mut pushed = 0;
+        while pushed < buf.len() && self.free() > 0 {
+            let head = (self.tail + self.len) % self.capacity();
+            let offset = head % PAGE_SIZE;
+            // the capacity is a multiple of pages, so the ring wraps at a page boundary
+            let len = (PAGE_SIZE - offset)
+                .min(self.free())
+                .min(buf.len() - pushed);
+            let page = self.pages[head / PAGE_SIZE].ppn.get_bytes_array();
+            page[offset..offset + len].copy_from_slice(&buf[pushed..pushed + len]);
+            self.len += len;
+            pushed += len;
         }
-        self.arr[self.head] = byte;
-        self.head = (self.head + 1) % RING_BUFFER_SIZE;
-        Ok(())
+        pushed
     }
 
-    fn pop(&mut self) -> Option<u8> {
-        if self.is_empty() {
-            return None;
+    /// Move the bytes of the ring to `buf` until it is full, return their number.
+    fn pop_slice(&mut self, buf: &mut [u8]) -> usize {
+        let mut popped = 0;
+        while popped < buf.len() && !self.is_empty() {
+            let offset = self.tail % PAGE_SIZE;
+            let len = (PAGE_SIZE - offset).min(self.len).min(buf.len() - popped);
+            let page = self.pages[self.tail / PAGE_SIZE].ppn.get_bytes_array();
+            buf[popped..popped + len].copy_from_slice(&page[offset..offset + len]);
+            self.tail = (self.tail + len) % self.capacity();
+            self.len -= len;
+            popped += len;
         }
-        let byte = self.arr[self.tail];
-        self.tail = (self.tail + 1) % RING_BUFFER_SIZE;
-        Some(byte)
+        popped
+    }
+
+    /// Move the data to new frames holding `size` bytes rounded up to pages, fail with
+    /// `EBUSY` if the data does not fit.
+    fn resize(&mut self, size: usize) -> Result<(), isize> {
+        let mut pages = alloc_pages(size)?;
+        if self.len > pages.len() * PAGE_SIZE {
+            return Err(EBUSY);
+        }
+        let mut copied = 0;
+        for page in pages.iter_mut() {
+            copied += self.pop_slice(page.ppn.get_bytes_array());
+        }
+        self.pages = pages;
+        self.tail = 0;
+        self.len = copied;
+        Ok(())
+    }
+
+    fn all_read_ends_closed(&self) -> bool {
+        self.read_ends == 0
     }
 
     fn all_write_ends_closed(&self) -> bool {
-        self.write_end.as_ref().unwrap().upgrade().is_none()
+        self.write_ends == 0
     }
 }
 
@@ -96,46 +255,199 @@ impl File for Pipe {
         self.writable
     }
 
-    /// Reads data until the buffer is full or no more data is available.
-    fn read(&self, buf: UserBuffer) -> usize {
+    fn status(&self) -> &FileStatus {
+        &self.status
+    }
+
+    fn stat(&self) -> Stat {
+        match &self.fifo {
+            Some(fifo) => fifo.inode.stat(),
+            None => Stat {
+                mode: FileType::Fifo.mode() | 0o600,
+                nlink: 1,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Waits until some data is available or all the write ends are closed, then reads the
+    /// data available until the buffer is full. With `O_NONBLOCK`, it never waits, and a
+    /// signal interrupts the wait with `EINTR`.
+    fn read(&self, buf: UserBuffer) -> Result<usize, isize> {
         assert!(self.readable());
-        let mut buf_iter = buf.into_iter();
+        let nonblock = self.status.get().contains(OpenFlags::NONBLOCK);
+        let mut segments = buf.buffer.into_iter();
+        let mut segment: &mut [u8] = &mut [];
         let mut read_cnt = 0;
         loop {
-            let mut ring_buffer = self.buffer.borrow_mut();
+            if segment.is_empty() {
+                match segments.next() {
+                    Some(next) => segment = next,
+                    None => return Ok(read_cnt), // Buffer is full
+                }
+                continue;
+            }
+            let mut ring_buffer = self.buffer.lock();
             if ring_buffer.is_empty() {
-                if ring_buffer.all_write_ends_closed() {
-                    return read_cnt; // No more data to read
+                // no more data to read now, or ever once the write ends are closed
+                if read_cnt > 0 || ring_buffer.all_write_ends_closed() {
+                    return Ok(read_cnt);
+                }
+                if nonblock {
+                    return Err(EAGAIN);
                 }
+                let waiter = Waiter::new(None);
+                ring_buffer.readers.push(&waiter);
                 drop(ring_buffer);
-                suspend_current_and_run_next();
-                continue; // Retry reading after suspension
+                waiter.wait();
+                // nothing has been read yet
+                if interrupted() {
+                    return Err(EINTR);
+                }
+                continue; // Retry reading after being woken up
             }
-            let Some(byte_ptr) = buf_iter.next() else {
-                return read_cnt; // Buffer is full or no more data to read
-            };
-            unsafe { *byte_ptr = ring_buffer.pop().unwrap() };
-            read_cnt += 1;
+            let len = ring_buffer.pop_slice(segment);
+            ring_buffer.writers.wake_all();
+            segment = &mut core::mem::take(&mut segment)[len..];
+            read_cnt += len;
         }
     }
 
-    /// Writes data until the buffer is full or no more data needs to be written.
-    fn write(&self, buf: UserBuffer) -> usize {
+    /// Writes data until no more data needs to be written, with `O_NONBLOCK` only what fits
+    /// now. A write of at most `PIPE_BUF` bytes waits until it fits as a whole, so it is not
+    /// interleaved with other writes. Once all the read ends are closed, the writer gets
+    /// `SIGPIPE` and `EPIPE` if nothing was written. A signal interrupts the wait, with `EINTR`
+    /// if nothing was written.
+    fn write(&self, buf: UserBuffer) -> Result<usize, isize> {
         assert!(self.writable());
-        let mut buf_iter = buf.into_iter();
+        let nonblock = self.status.get().contains(OpenFlags::NONBLOCK);
+        let total = buf.len();
+        let atomic = total <= PIPE_BUF;
+        let mut segments = buf.buffer.into_iter();
+        let mut segment: &[u8] = &[];
         let mut write_cnt = 0;
         loop {
-            let mut ring_buffer = self.buffer.borrow_mut();
-            if ring_buffer.is_full() {
+            if write_cnt == total {
+                return Ok(write_cnt);
+            }
+            let mut ring_buffer = self.buffer.lock();
+            if ring_buffer.all_read_ends_closed() {
                 drop(ring_buffer);
-                suspend_current_and_run_next();
+                // the process is locked after the buffer is unlocked
+                current_proc().send_signal(SignalFlags::SIGPIPE);
+                return if write_cnt == 0 {
+                    Err(EPIPE)
+                } else {
+                    Ok(write_cnt)
+                };
+            }
+            let free = ring_buffer.free();
+            if free == 0 || (atomic && free < total) {
+                if nonblock {
+                    return if write_cnt == 0 {
+                        Err(EAGAIN)
+                    } else {
+                        Ok(write_cnt)
+                    };
+                }
+                let waiter = Waiter::new(None);
+                ring_buffer.writers.push(&waiter);
+                drop(ring_buffer);
+                waiter.wait();
+                if interrupted() {
+                    return if write_cnt == 0 {
+                        Err(EINTR)
+                    } else {
+                        Ok(write_cnt)
+                    };
+                }
                 continue;
             }
-            let Some(byte_ptr) = buf_iter.next() else {
-                return write_cnt;
-            };
-            ring_buffer.push(unsafe { *byte_ptr }).unwrap();
-            write_cnt += 1;
+            while ring_buffer.free() > 0 {
+                if segment.is_empty() {
+                    match segments.next() {
+                        Some(next) => segment = next,
+                        None => break,
+                    }
+                    continue;
+                }
+                let len = ring_buffer.push_slice(segment);
+                segment = &segment[len..];
+                write_cnt += len;
+            }
+            ring_buffer.readers.wake_all();
+        }
+    }
+
+    /// The read end is readable at EOF as well, the write end is writable when `PIPE_BUF`
+    /// bytes fit and reports an error without read ends.
+    fn poll(&self, waiter: Option<&Arc<Waiter>>) -> PollEvents {
+        let mut ring_buffer = self.buffer.lock();
+        let mut events = PollEvents::empty();
+        if self.readable {
+            if let Some(waiter) = waiter {
+                ring_buffer.readers.push(waiter);
+            }
+            if !ring_buffer.is_empty() {
+                events |= PollEvents::IN;
+            }
+            if ring_buffer.all_write_ends_closed() {
+                events |= PollEvents::HUP;
+            }
+        }
+        if self.writable {
+            if let Some(waiter) = waiter {
+                ring_buffer.writers.push(waiter);
+            }
+            if ring_buffer.free() >= PIPE_BUF {
+                events |= PollEvents::OUT;
+            }
+            if ring_buffer.all_read_ends_closed() {
+                events |= PollEvents::ERR;
+            }
+        }
+        events
+    }
+
+    /// Get or set the capacity shared by all the ends of the pipe.
+    fn fcntl(&self, cmd: usize, arg: usize) -> isize {
+        let mut ring_buffer = self.buffer.lock();
+        match cmd {
+            F_GETPIPE_SZ => ring_buffer.capacity() as isize,
+            F_SETPIPE_SZ => {
+                if arg > MAX_PIPE_SIZE {
+                    return -EPERM;
+                }
+                match ring_buffer.resize(arg) {
+                    Ok(()) => {
+                        // more space may be available
+                        ring_buffer.writers.wake_all();
+                        ring_buffer.capacity() as isize
+                    }
+                    Err(errno) => -errno,
+                }
+            }
+            _ => -EINVAL,
+        }
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let mut ring_buffer = self.buffer.lock();
+        if self.readable {
+            ring_buffer.read_ends -= 1;
+            if ring_buffer.all_read_ends_closed() {
+                // the blocked writers get EPIPE
+                ring_buffer.writers.wake_all();
+            }
+        }
+        if self.writable {
+            ring_buffer.write_ends -= 1;
+            if ring_buffer.all_write_ends_closed() {
+                // the readers see the EOF
+                ring_buffer.readers.wake_all();
+            }
         }
     }
 }
@@ -0,0 +1,44 @@
+//! Waiting for events on several files
+
+use alloc::{sync::Arc, vec::Vec};
+use common::{errno::EINTR, poll::PollEvents};
+
+use crate::{proc::current_proc, sync::Waiter, timer::get_time_ms};
+
+use super::File;
+
+/// Wait until one of `files` is ready for its events, or until `expire_ms`. Return the ready
+/// events of each file, which may be `ERR` and `HUP` as well, or `EINTR` if a signal is
+/// pending.
+pub fn poll(
+    files: &[(Arc<dyn File>, PollEvents)],
+    expire_ms: Option<usize>,
+) -> Result<Vec<PollEvents>, isize> {
+    loop {
+        let timed_out = expire_ms.is_some_and(|expire_ms| get_time_ms() >= expire_ms);
+        // registered before checking, so that a change after the check is not missed
+        let waiter = (!timed_out).then(|| Waiter::new(expire_ms));
+        let ready: Vec<_> = files
+            .iter()
+            .map(|(file, events)| {
+                file.poll(waiter.as_ref()) & (*events | PollEvents::ERR | PollEvents::HUP)
+            })
+            .collect();
+        let Some(waiter) = waiter else {
+            return Ok(ready);
+        };
+        if ready.iter().any(|events| !events.is_empty()) {
+            waiter.cancel();
+            return Ok(ready);
+        }
+        if !current_proc()
+            .borrow_inner_mut()
+            .unblocked_signals()
+            .is_empty()
+        {
+            waiter.cancel();
+            return Err(EINTR);
+        }
+        waiter.wait();
+    }
+}
@@ -0,0 +1,377 @@
+//! The process filesystem mounted at `/proc`
+//!
+//! Its files are generated from the state of the kernel every time they are read. Each process
+//! has a directory named by its pid, whose `fd` directory lists the open files, and opening an
+//! entry of it opens the same file.
+
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use common::{
+    errno::{ENOENT, EPERM},
+    fs::{FileType, Stat},
+};
+use core::fmt::Write;
+
+use crate::{
+    config::PAGE_SIZE,
+    memory::{FRAME_ALLOCATOR, MapPermission, VirtAddr, heap_usage},
+    proc::{ProcControlBlock, ThreadStatus, processes},
+    timer::get_time_ms,
+};
+
+use super::{
+    File, OpenFlags, mount,
+    vfs::{DirEntry, Inode, SuperBlock, alloc_dev},
+};
+
+/// Generates the data of a file of `/proc`.
+type Generate = fn() -> String;
+/// Generates the data of a file of the directory of a process.
+type GenerateProc = fn(&ProcControlBlock) -> String;
+
+/// The files of `/proc`, inode numbers 2 and up.
+const ROOT_FILES: [(&str, Generate); 3] = [
+    ("meminfo", meminfo),
+    ("uptime", uptime),
+    ("mounts", mount::mounts),
+];
+/// The files of the directory of a process, the inode numbers are those of the directory plus
+/// 1 and up.
+const PROC_FILES: [(&str, GenerateProc); 3] =
+    [("status", status), ("cmdline", cmdline), ("maps", maps)];
+/// The `fd` directory of a process, after `PROC_FILES`.
+const FD_DIR: &str = "fd";
+
+pub struct ProcfsSuperBlock {
+    root: Arc<ProcRoot>,
+}
+
+struct ProcRoot {
+    dev: u64,
+}
+
+/// The directory of a process, or its `fd` directory.
+struct ProcDir {
+    dev: u64,
+    proc: Weak<ProcControlBlock>,
+    pid: usize,
+    is_fd: bool,
+}
+
+/// A file whose data is generated when read.
+struct ProcFile {
+    stat: Stat,
+    generate: Box<dyn Fn() -> String + Send + Sync>,
+}
+
+/// An open file of a process.
+struct FdEntry {
+    file: Arc<dyn File>,
+}
+
+/// Return the inode number of the directory of process `pid`, the inodes in it follow.
+fn proc_ino(pid: usize) -> u64 {
+    ((pid as u64) + 1) << 16
+}
+
+fn dir_stat(dev: u64, ino: u64, size: usize) -> Stat {
+    Stat {
+        dev,
+        ino,
+        mode: FileType::Directory.mode() | 0o555,
+        nlink: 1,
+        size: size as u64,
+    }
+}
+
+fn file_stat(dev: u64, ino: u64) -> Stat {
+    Stat {
+        dev,
+        ino,
+        mode: FileType::Regular.mode() | 0o444,
+        nlink: 1,
+        size: 0,
+    }
+}
+
+fn meminfo() -> String {
+    let (total, free) = {
+        let frames = FRAME_ALLOCATOR.lock();
+        (frames.total(), frames.free())
+    };
+    let (heap_total, heap_used) = heap_usage();
+    format!(
+        "MemTotal: {} kB\nMemFree: {} kB\nHeapTotal: {} kB\nHeapUsed: {} kB\n",
+        total * PAGE_SIZE / 1024,
+        free * PAGE_SIZE / 1024,
+        heap_total / 1024,
+        heap_used / 1024,
+    )
+}
+
+/// The seconds since boot.
+fn uptime() -> String {
+    let ms = get_time_ms();
+    format!("{}.{:02}\n", ms / 1000, ms % 1000 / 10)
+}
+
+fn status(proc: &ProcControlBlock) -> String {
+    let inner = proc.borrow_inner_mut();
+    let name = inner.cmdline.first().cloned().unwrap_or_default();
+    let is_zombie = inner.is_zombie;
+    let ppid = inner
+        .parent
+        .as_ref()
+        .and_then(Weak::upgrade)
+        .map_or(0, |parent| parent.pid());
+    let children: Vec<_> = inner.children.iter().map(|child| child.pid()).collect();
+    let threads: Vec<_> = inner.threads.iter().flatten().cloned().collect();
+    // threads are locked before their process elsewhere
+    drop(inner);
+    let state = if is_zombie {
+        "Z (zombie)"
+    } else if threads
+        .iter()
+        .any(|thread| !matches!(thread.borrow_inner_mut().status, ThreadStatus::Blocked))
+    {
+        "R (running)"
+    } else {
+        "S (sleeping)"
+    };
+    let mut status = format!(
+        "Name:\t{name}\nState:\t{state}\nPid:\t{}\nPPid:\t{ppid}\nThreads:\t{}\nChildren:\t",
+        proc.pid(),
+        threads.len(),
+    );
+    for (i, pid) in children.iter().enumerate() {
+        let separator = if i == 0 { "" } else { " " };
+        write!(status, "{separator}{pid}").unwrap();
+    }
+    status.push('\n');
+    status
+}
+
+/// The arguments separated by NUL.
+fn cmdline(proc: &ProcControlBlock) -> String {
+    proc.borrow_inner_mut()
+        .cmdline
+        .iter()
+        .flat_map(|arg| [arg.as_str(), "\0"])
+        .collect()
+}
+
+/// The mapped areas of the memory space with their permissions.
+fn maps(proc: &ProcControlBlock) -> String {
+    let inner = proc.borrow_inner_mut();
+    let mut maps = String::new();
+    for area in inner.memory_space.areas() {
+        let perm = |flag, c| if area.map_perm.contains(flag) { c } else { '-' };
+        writeln!(
+            maps,
+            "{:016x}-{:016x} {}{}{}{}",
+            usize::from(VirtAddr::from(area.start_vpn)),
+            usize::from(VirtAddr::from(area.end_vpn)),
+            perm(MapPermission::R, 'r'),
+            perm(MapPermission::W, 'w'),
+            perm(MapPermission::X, 'x'),
+            perm(MapPermission::U, 'u'),
+        )
+        .unwrap();
+    }
+    maps
+}
+
+impl ProcfsSuperBlock {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            root: Arc::new(ProcRoot { dev: alloc_dev() }),
+        })
+    }
+}
+
+impl SuperBlock for ProcfsSuperBlock {
+    fn root(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+}
+
+impl Inode for ProcRoot {
+    fn stat(&self) -> Stat {
+        dir_stat(self.dev, 1, ROOT_FILES.len())
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, isize> {
+        if let Some((ino, (_, generate))) = (2..).zip(ROOT_FILES).find(|(_, (n, _))| *n == name) {
+            return Ok(Arc::new(ProcFile {
+                stat: file_stat(self.dev, ino),
+                generate: Box::new(generate),
+            }));
+        }
+        let pid = name.parse::<usize>().map_err(|_| ENOENT)?;
+        let proc = processes()
+            .into_iter()
+            .find(|proc| proc.pid() == pid)
+            .ok_or(ENOENT)?;
+        Ok(Arc::new(ProcDir {
+            dev: self.dev,
+            proc: Arc::downgrade(&proc),
+            pid,
+            is_fd: false,
+        }))
+    }
+
+    fn create(&self, _name: &str, _file_type: FileType) -> Result<Arc<dyn Inode>, isize> {
+        Err(EPERM)
+    }
+
+    fn unlink(&self, _name: &str) -> Result<(), isize> {
+        Err(EPERM)
+    }
+
+    fn readdir(&self) -> Result<Vec<DirEntry>, isize> {
+        let files = (2..).zip(ROOT_FILES).map(|(ino, (name, _))| DirEntry {
+            name: name.to_string(),
+            ino,
+            file_type: FileType::Regular,
+        });
+        let procs = processes().into_iter().map(|proc| DirEntry {
+            name: proc.pid().to_string(),
+            ino: proc_ino(proc.pid()),
+            file_type: FileType::Directory,
+        });
+        Ok(files.chain(procs).collect())
+    }
+}
+
+impl ProcDir {
+    fn ino(&self) -> u64 {
+        if self.is_fd {
+            proc_ino(self.pid) + PROC_FILES.len() as u64 + 1
+        } else {
+            proc_ino(self.pid)
+        }
+    }
+
+    /// The inode number of open file `fd`, after the `fd` directory.
+    fn fd_ino(&self, fd: usize) -> u64 {
+        proc_ino(self.pid) + PROC_FILES.len() as u64 + 2 + fd as u64
+    }
+
+    fn proc(&self) -> Result<Arc<ProcControlBlock>, isize> {
+        self.proc.upgrade().ok_or(ENOENT)
+    }
+}
+
+impl Inode for ProcDir {
+    fn stat(&self) -> Stat {
+        dir_stat(self.dev, self.ino(), 0)
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, isize> {
+        let proc = self.proc()?;
+        if self.is_fd {
+            let fd = name.parse::<usize>().map_err(|_| ENOENT)?;
+            let inner = proc.borrow_inner_mut();
+            let fd = inner.fd_table.get(fd).cloned().flatten().ok_or(ENOENT)?;
+            return Ok(Arc::new(FdEntry { file: fd.file }));
+        }
+        if name == FD_DIR {
+            return Ok(Arc::new(ProcDir {
+                dev: self.dev,
+                proc: self.proc.clone(),
+                pid: self.pid,
+                is_fd: true,
+            }));
+        }
+        let (ino, (_, generate)) = (self.ino() + 1..)
+            .zip(PROC_FILES)
+            .find(|(_, (n, _))| *n == name)
+            .ok_or(ENOENT)?;
+        // the file is empty once the process has been waited for
+        let proc = Arc::downgrade(&proc);
+        Ok(Arc::new(ProcFile {
+            stat: file_stat(self.dev, ino),
+            generate: Box::new(move || {
+                proc.upgrade().map_or(String::new(), |proc| generate(&proc))
+            }),
+        }))
+    }
+
+    fn create(&self, _name: &str, _file_type: FileType) -> Result<Arc<dyn Inode>, isize> {
+        Err(EPERM)
+    }
+
+    fn unlink(&self, _name: &str) -> Result<(), isize> {
+        Err(EPERM)
+    }
+
+    fn readdir(&self) -> Result<Vec<DirEntry>, isize> {
+        let proc = self.proc()?;
+        if self.is_fd {
+            let inner = proc.borrow_inner_mut();
+            let files: Vec<_> = inner
+                .fd_table
+                .iter()
+                .enumerate()
+                .filter_map(|(fd, entry)| Some((fd, entry.as_ref()?.file.clone())))
+                .collect();
+            drop(inner);
+            return Ok(files
+                .into_iter()
+                .map(|(fd, file)| DirEntry {
+                    name: fd.to_string(),
+                    ino: self.fd_ino(fd),
+                    file_type: file.stat().file_type().unwrap_or(FileType::Regular),
+                })
+                .collect());
+        }
+        let mut entries: Vec<_> = (self.ino() + 1..)
+            .zip(PROC_FILES)
+            .map(|(ino, (name, _))| DirEntry {
+                name: name.to_string(),
+                ino,
+                file_type: FileType::Regular,
+            })
+            .collect();
+        entries.push(DirEntry {
+            name: FD_DIR.to_string(),
+            ino: self.ino() + PROC_FILES.len() as u64 + 1,
+            file_type: FileType::Directory,
+        });
+        Ok(entries)
+    }
+}
+
+impl Inode for ProcFile {
+    fn stat(&self) -> Stat {
+        self.stat
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, isize> {
+        let data = (self.generate)();
+        let data = data.as_bytes().get(offset..).unwrap_or_default();
+        let len = data.len().min(buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        Ok(len)
+    }
+}
+
+impl Inode for FdEntry {
+    fn stat(&self) -> Stat {
+        self.file.stat()
+    }
+
+    fn truncate(&self) -> Result<(), isize> {
+        Err(EPERM)
+    }
+
+    /// The open file is shared, like a duplicated file descriptor.
+    fn open(&self, _flags: OpenFlags) -> Option<Arc<dyn File>> {
+        Some(self.file.clone())
+    }
+}
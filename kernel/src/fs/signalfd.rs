@@ -0,0 +1,120 @@
+//! Pending signals read as `SignalfdSiginfo` records
+//!
+//! The signals read are those of the reading process, which should block them so that they
+//! do not terminate it first.
+
+use alloc::{sync::Arc, vec::Vec};
+use common::{
+    errno::{EAGAIN, EINTR, EINVAL},
+    fs::{FileType, Stat},
+    poll::PollEvents,
+    sig::{SignalFlags, SignalfdSiginfo},
+};
+
+use crate::{
+    fs::{File, FileStatus, OpenFlags},
+    memory::UserBuffer,
+    proc::current_proc,
+    sync::{SpinLock, Waiter},
+};
+
+pub struct SignalFd {
+    status: FileStatus,
+    /// The signals read, changed by `signalfd4` on the fd.
+    mask: SpinLock<SignalFlags>,
+}
+
+impl SignalFd {
+    pub fn new(mask: SignalFlags, flags: OpenFlags) -> Arc<Self> {
+        Arc::new(Self {
+            status: FileStatus::new(flags),
+            mask: SpinLock::new(mask),
+        })
+    }
+
+    pub fn set_mask(&self, mask: SignalFlags) {
+        *self.mask.lock() = mask;
+    }
+}
+
+impl File for SignalFd {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    fn status(&self) -> &FileStatus {
+        &self.status
+    }
+
+    /// Take as many pending signals of the mask as records fit in `buf`, waiting for one.
+    fn read(&self, mut buf: UserBuffer) -> Result<usize, isize> {
+        let count = buf.len() / size_of::<SignalfdSiginfo>();
+        if count == 0 {
+            return Err(EINVAL);
+        }
+        let nonblock = self.status.get().contains(OpenFlags::NONBLOCK);
+        let proc = current_proc();
+        loop {
+            let mask = *self.mask.lock();
+            let mut inner = proc.borrow_inner_mut();
+            let signals: Vec<_> = (inner.signals & mask).iter().take(count).collect();
+            if !signals.is_empty() {
+                let mut records = Vec::new();
+                for &signal in signals.iter() {
+                    inner.signals.remove(signal);
+                    let info = SignalfdSiginfo::new(signal);
+                    records.extend_from_slice(unsafe {
+                        core::slice::from_raw_parts(
+                            &info as *const _ as *const u8,
+                            size_of::<SignalfdSiginfo>(),
+                        )
+                    });
+                }
+                return Ok(buf.write(&records));
+            }
+            if !inner.unblocked_signals().is_empty() {
+                return Err(EINTR);
+            }
+            if nonblock {
+                return Err(EAGAIN);
+            }
+            let waiter = Waiter::new(None);
+            inner.signal_waiters.push(&waiter);
+            drop(inner);
+            waiter.wait();
+        }
+    }
+
+    fn write(&self, _buf: UserBuffer) -> Result<usize, isize> {
+        Err(EINVAL)
+    }
+
+    fn stat(&self) -> Stat {
+        Stat {
+            mode: FileType::Regular.mode() | 0o600,
+            nlink: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Readable while a signal of the mask is pending for the polling process.
+    fn poll(&self, waiter: Option<&Arc<Waiter>>) -> PollEvents {
+        let mask = *self.mask.lock();
+        let proc = current_proc();
+        let mut inner = proc.borrow_inner_mut();
+        if let Some(waiter) = waiter {
+            inner.signal_waiters.push(waiter);
+        }
+        let mut events = PollEvents::empty();
+        events.set(PollEvents::IN, inner.signals.intersects(mask));
+        events
+    }
+
+    fn as_signalfd(&self) -> Option<&SignalFd> {
+        Some(self)
+    }
+}
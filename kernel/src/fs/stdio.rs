@@ -1,49 +0,0 @@
-//!Stdin & Stdout
-use crate::{memory::UserBuffer, sbi::get_char_blocking};
-
-use super::File;
-
-///Standard input
-pub struct Stdin;
-///Standard output
-pub struct Stdout;
-
-impl File for Stdin {
-    fn readable(&self) -> bool {
-        true
-    }
-    fn writable(&self) -> bool {
-        false
-    }
-
-    fn read(&self, mut user_buf: UserBuffer) -> usize {
-        assert_eq!(user_buf.len(), 1);
-        // busy loop
-        let ch = get_char_blocking();
-        unsafe {
-            user_buf.buffer[0].as_mut_ptr().write_volatile(ch);
-        }
-        1
-    }
-    fn write(&self, _user_buf: UserBuffer) -> usize {
-        panic!("Cannot write to stdin!");
-    }
-}
-
-impl File for Stdout {
-    fn readable(&self) -> bool {
-        false
-    }
-    fn writable(&self) -> bool {
-        true
-    }
-    fn read(&self, _user_buf: UserBuffer) -> usize {
-        panic!("Cannot read from stdout!");
-    }
-    fn write(&self, user_buf: UserBuffer) -> usize {
-        for buffer in user_buf.iter() {
-            print!("{}", core::str::from_utf8(buffer).unwrap());
-        }
-        user_buf.len()
-    }
-}
@@ -0,0 +1,214 @@
+//! Timers read as the number of expirations
+//!
+//! Expirations are counted from the time when they are read, the callback timer at the next
+//! expiration only wakes up the waiters.
+
+use alloc::sync::{Arc, Weak};
+use common::{
+    errno::{EAGAIN, EINTR, EINVAL},
+    fs::{FileType, Stat},
+    poll::PollEvents,
+    time::{ItimerSpec, NSEC_PER_SEC, TimeSpec},
+};
+
+use crate::{
+    fs::{File, FileStatus, OpenFlags},
+    memory::UserBuffer,
+    proc::current_proc,
+    sync::{SpinLock, WaitQueue, Waiter},
+    timer::{TimerId, add_callback_timer, get_time_ms, remove_callback_timer},
+};
+
+pub struct TimerFd {
+    this: Weak<TimerFd>,
+    status: FileStatus,
+    inner: SpinLock<TimerFdInner>,
+}
+
+#[derive(Default)]
+struct TimerFdInner {
+    /// The next expiration in milliseconds since boot, none if disarmed.
+    expire_ms: Option<usize>,
+    /// The period of a periodic timer, 0 for a one-shot timer.
+    interval_ms: usize,
+    /// The expirations which have not been read.
+    expirations: u64,
+    /// The callback timer at `expire_ms`.
+    timer: Option<TimerId>,
+    readers: WaitQueue,
+}
+
+impl TimerFdInner {
+    /// Count the expirations until `current_ms`.
+    fn update(&mut self, current_ms: usize) {
+        let Some(expire_ms) = self.expire_ms.filter(|&expire_ms| expire_ms <= current_ms) else {
+            return;
+        };
+        if self.interval_ms == 0 {
+            self.expirations += 1;
+            self.expire_ms = None;
+        } else {
+            let count = (current_ms - expire_ms) / self.interval_ms + 1;
+            self.expirations += count as u64;
+            self.expire_ms = Some(expire_ms.saturating_add(count.saturating_mul(self.interval_ms)));
+        }
+    }
+
+    /// The setting of the timer, relative to `current_ms`.
+    fn get(&self, current_ms: usize) -> ItimerSpec {
+        ItimerSpec {
+            interval: TimeSpec::from_ms(self.interval_ms),
+            value: self
+                .expire_ms
+                .map(|expire_ms| TimeSpec::from_ms(expire_ms.saturating_sub(current_ms)))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl TimerFd {
+    /// Create a disarmed timer.
+    pub fn new(flags: OpenFlags) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            this: Weak::clone(this),
+            status: FileStatus::new(flags),
+            inner: SpinLock::new(TimerFdInner::default()),
+        })
+    }
+
+    /// Replace the callback timer by one at the next expiration, if any.
+    fn arm(&self, inner: &mut TimerFdInner) {
+        if let Some(id) = inner.timer.take() {
+            remove_callback_timer(id);
+        }
+        if let Some(expire_ms) = inner.expire_ms {
+            let this = Weak::clone(&self.this);
+            inner.timer = Some(add_callback_timer(expire_ms, move |id| {
+                if let Some(timerfd) = this.upgrade() {
+                    timerfd.expire(id);
+                }
+            }));
+        }
+    }
+
+    /// Called by the callback timer `id` at the next expiration.
+    fn expire(&self, id: TimerId) {
+        let mut inner = self.inner.lock();
+        // the timer has been set again since
+        if inner.timer != Some(id) {
+            return;
+        }
+        inner.timer = None;
+        inner.update(get_time_ms());
+        inner.readers.wake_all();
+        self.arm(&mut inner);
+    }
+
+    /// Arm the timer with `new`, or disarm it if its value is zero, and return the previous
+    /// setting. The value is the time since boot with `abs`.
+    pub fn set(&self, new: ItimerSpec, abs: bool) -> Result<ItimerSpec, isize> {
+        if new.value.nsec >= NSEC_PER_SEC || new.interval.nsec >= NSEC_PER_SEC {
+            return Err(EINVAL);
+        }
+        let current_ms = get_time_ms();
+        let mut inner = self.inner.lock();
+        inner.update(current_ms);
+        let old = inner.get(current_ms);
+        inner.expirations = 0;
+        inner.interval_ms = new.interval.as_ms();
+        inner.expire_ms = match new.value.as_ms() {
+            0 => None,
+            value_ms if abs => Some(value_ms),
+            value_ms => Some(current_ms.saturating_add(value_ms)),
+        };
+        self.arm(&mut inner);
+        Ok(old)
+    }
+
+    /// Return the time until the next expiration, zero if disarmed, and the interval.
+    pub fn get(&self) -> ItimerSpec {
+        let current_ms = get_time_ms();
+        let mut inner = self.inner.lock();
+        inner.update(current_ms);
+        inner.get(current_ms)
+    }
+}
+
+impl File for TimerFd {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    fn status(&self) -> &FileStatus {
+        &self.status
+    }
+
+    /// Read the number of expirations since the last read as a `u64`, waiting for one.
+    fn read(&self, mut buf: UserBuffer) -> Result<usize, isize> {
+        if buf.len() < size_of::<u64>() {
+            return Err(EINVAL);
+        }
+        let nonblock = self.status.get().contains(OpenFlags::NONBLOCK);
+        loop {
+            if !current_proc()
+                .borrow_inner_mut()
+                .unblocked_signals()
+                .is_empty()
+            {
+                return Err(EINTR);
+            }
+            let mut inner = self.inner.lock();
+            inner.update(get_time_ms());
+            if inner.expirations > 0 {
+                let expirations = core::mem::take(&mut inner.expirations);
+                return Ok(buf.write(&expirations.to_ne_bytes()));
+            }
+            if nonblock {
+                return Err(EAGAIN);
+            }
+            let waiter = Waiter::new(None);
+            inner.readers.push(&waiter);
+            drop(inner);
+            waiter.wait();
+        }
+    }
+
+    fn write(&self, _buf: UserBuffer) -> Result<usize, isize> {
+        Err(EINVAL)
+    }
+
+    fn stat(&self) -> Stat {
+        Stat {
+            mode: FileType::Regular.mode() | 0o600,
+            nlink: 1,
+            ..Default::default()
+        }
+    }
+
+    fn poll(&self, waiter: Option<&Arc<Waiter>>) -> PollEvents {
+        let mut inner = self.inner.lock();
+        if let Some(waiter) = waiter {
+            inner.readers.push(waiter);
+        }
+        inner.update(get_time_ms());
+        let mut events = PollEvents::empty();
+        events.set(PollEvents::IN, inner.expirations > 0);
+        events
+    }
+
+    fn as_timerfd(&self) -> Option<&TimerFd> {
+        Some(self)
+    }
+}
+
+impl Drop for TimerFd {
+    fn drop(&mut self) {
+        if let Some(id) = self.inner.lock().timer.take() {
+            remove_callback_timer(id);
+        }
+    }
+}
@@ -0,0 +1,243 @@
+//! A filesystem in memory, the data of files is kept in frames
+//!
+//! The inodes are reference counted, so a removed file lives on until it is closed.
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use common::{
+    errno::{EEXIST, EINVAL, EISDIR, ENAMETOOLONG, ENOENT, ENOMEM, ENOSPC, ENOTDIR, ENOTEMPTY},
+    fs::{FileType, Stat},
+};
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use crate::{
+    config::{PAGE_SIZE, TMPFS_MAX_PAGES},
+    memory::{FRAME_ALLOCATOR, FrameAllocator, FrameTracker},
+    sync::SpinLock,
+};
+
+use super::vfs::{DirEntry, Inode, SuperBlock, alloc_dev};
+
+/// Longest name of an entry.
+const NAME_MAX: usize = 255;
+
+pub struct TmpfsSuperBlock {
+    root: Arc<TmpfsInode>,
+}
+
+/// The state shared by the inodes of a filesystem.
+struct Tmpfs {
+    dev: u64,
+    next_ino: AtomicU64,
+    /// Frames holding the data of files, at most `TMPFS_MAX_PAGES`.
+    pages: AtomicUsize,
+}
+
+pub struct TmpfsInode {
+    ino: u64,
+    fs: Arc<Tmpfs>,
+    content: SpinLock<Content>,
+}
+
+enum Content {
+    File {
+        size: usize,
+        frames: Vec<FrameTracker>,
+    },
+    Directory(BTreeMap<String, Arc<TmpfsInode>>),
+}
+
+impl TmpfsSuperBlock {
+    pub fn new() -> Arc<Self> {
+        let fs = Arc::new(Tmpfs {
+            dev: alloc_dev(),
+            next_ino: AtomicU64::new(1),
+            pages: AtomicUsize::new(0),
+        });
+        let root = TmpfsInode::new(&fs, FileType::Directory);
+        Arc::new(Self { root })
+    }
+}
+
+impl SuperBlock for TmpfsSuperBlock {
+    fn root(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+}
+
+impl Tmpfs {
+    /// Reserve `count` pages, return `false` if the filesystem would be full.
+    fn reserve_pages(&self, count: usize) -> bool {
+        self.pages
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                (used + count <= TMPFS_MAX_PAGES).then_some(used + count)
+            })
+            .is_ok()
+    }
+
+    fn release_pages(&self, count: usize) {
+        self.pages.fetch_sub(count, Ordering::Relaxed);
+    }
+}
+
+impl TmpfsInode {
+    fn new(fs: &Arc<Tmpfs>, file_type: FileType) -> Arc<Self> {
+        let content = match file_type {
+            FileType::Directory => Content::Directory(BTreeMap::new()),
+            _ => Content::File {
+                size: 0,
+                frames: Vec::new(),
+            },
+        };
+        Arc::new(Self {
+            ino: fs.next_ino.fetch_add(1, Ordering::Relaxed),
+            fs: Arc::clone(fs),
+            content: SpinLock::new(content),
+        })
+    }
+}
+
+impl Drop for TmpfsInode {
+    fn drop(&mut self) {
+        if let Content::File { frames, .. } = &*self.content.lock() {
+            self.fs.release_pages(frames.len());
+        }
+    }
+}
+
+impl Inode for TmpfsInode {
+    fn stat(&self) -> Stat {
+        let (file_type, permission, size) = match &*self.content.lock() {
+            Content::File { size, .. } => (FileType::Regular, 0o644, *size),
+            Content::Directory(entries) => (FileType::Directory, 0o755, entries.len()),
+        };
+        Stat {
+            dev: self.fs.dev,
+            ino: self.ino,
+            mode: file_type.mode() | permission,
+            nlink: 1,
+            size: size as u64,
+        }
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, isize> {
+        let content = self.content.lock();
+        let Content::File { size, frames } = &*content else {
+            return Err(EISDIR);
+        };
+        let end = (*size).min(offset + buf.len());
+        let mut pos = offset;
+        while pos < end {
+            let page_offset = pos % PAGE_SIZE;
+            let len = (PAGE_SIZE - page_offset).min(end - pos);
+            let page = frames[pos / PAGE_SIZE].ppn.get_bytes_array();
+            buf[pos - offset..pos - offset + len]
+                .copy_from_slice(&page[page_offset..page_offset + len]);
+            pos += len;
+        }
+        Ok(end.saturating_sub(offset))
+    }
+
+    /// Fail with `ENOSPC` rather than writing a part of `buf` if the filesystem is full.
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, isize> {
+        let mut content = self.content.lock();
+        let Content::File { size, frames } = &mut *content else {
+            return Err(EISDIR);
+        };
+        let end = offset + buf.len();
+        let new_pages = end.div_ceil(PAGE_SIZE).saturating_sub(frames.len());
+        if !self.fs.reserve_pages(new_pages) {
+            return Err(ENOSPC);
+        }
+        for allocated in 0..new_pages {
+            let Some(frame) = FRAME_ALLOCATOR.lock().frame_alloc() else {
+                self.fs.release_pages(new_pages - allocated);
+                return Err(ENOMEM);
+            };
+            frames.push(frame);
+        }
+        let mut pos = offset;
+        while pos < end {
+            let page_offset = pos % PAGE_SIZE;
+            let len = (PAGE_SIZE - page_offset).min(end - pos);
+            let page = frames[pos / PAGE_SIZE].ppn.get_bytes_array();
+            page[page_offset..page_offset + len]
+                .copy_from_slice(&buf[pos - offset..pos - offset + len]);
+            pos += len;
+        }
+        *size = (*size).max(end);
+        Ok(buf.len())
+    }
+
+    fn truncate(&self) -> Result<(), isize> {
+        let mut content = self.content.lock();
+        let Content::File { size, frames } = &mut *content else {
+            return Err(EISDIR);
+        };
+        self.fs.release_pages(frames.len());
+        // the frames are zeroed when allocated again
+        frames.clear();
+        *size = 0;
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, isize> {
+        let Content::Directory(entries) = &*self.content.lock() else {
+            return Err(ENOTDIR);
+        };
+        let inode = entries.get(name).ok_or(ENOENT)?;
+        Ok(inode.clone())
+    }
+
+    fn create(&self, name: &str, file_type: FileType) -> Result<Arc<dyn Inode>, isize> {
+        if name.len() > NAME_MAX {
+            return Err(ENAMETOOLONG);
+        }
+        if !matches!(file_type, FileType::Regular | FileType::Directory) {
+            return Err(EINVAL);
+        }
+        let Content::Directory(entries) = &mut *self.content.lock() else {
+            return Err(ENOTDIR);
+        };
+        if entries.contains_key(name) {
+            return Err(EEXIST);
+        }
+        let inode = TmpfsInode::new(&self.fs, file_type);
+        entries.insert(name.to_string(), inode.clone());
+        Ok(inode)
+    }
+
+    fn unlink(&self, name: &str) -> Result<(), isize> {
+        let Content::Directory(entries) = &mut *self.content.lock() else {
+            return Err(ENOTDIR);
+        };
+        let inode = entries.get(name).ok_or(ENOENT)?;
+        // the directory and the inode are never locked the other way around
+        if matches!(&*inode.content.lock(), Content::Directory(children) if !children.is_empty()) {
+            return Err(ENOTEMPTY);
+        }
+        entries.remove(name);
+        Ok(())
+    }
+
+    fn readdir(&self) -> Result<Vec<DirEntry>, isize> {
+        let Content::Directory(entries) = &*self.content.lock() else {
+            return Err(ENOTDIR);
+        };
+        Ok(entries
+            .iter()
+            .map(|(name, inode)| DirEntry {
+                name: name.clone(),
+                ino: inode.ino,
+                file_type: match &*inode.content.lock() {
+                    Content::File { .. } => FileType::Regular,
+                    Content::Directory(_) => FileType::Directory,
+                },
+            })
+            .collect())
+    }
+}
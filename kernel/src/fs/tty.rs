@@ -0,0 +1,289 @@
+//! The console TTY and its line discipline
+//!
+//! In canonical mode the input is edited line by line and a read returns at most one line,
+//! otherwise the bytes are made available as they are received. Received bytes are processed
+//! right away by the UART interrupt, so that echo and `^C` work while nobody is reading.
+
+use alloc::{
+    collections::VecDeque,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use common::{
+    errno::{EAGAIN, EINVAL, EPERM},
+    poll::PollEvents,
+    sig::SignalFlags,
+    termios::{
+        InputModes, LocalModes, TCGETS, TCSETS, TCSETSF, TCSETSW, TIOCGPGRP, TIOCGWINSZ, TIOCSPGRP,
+        TIOCSWINSZ, Termios, VEOF, VERASE, VINTR, VKILL, VMIN, VQUIT, WinSize,
+    },
+};
+use lazy_static::lazy_static;
+
+use crate::{
+    console::write_bytes,
+    drivers::UART,
+    memory::UserBuffer,
+    proc::{ProcControlBlock, current_proc},
+    sync::{SpinLock, WaitQueue, Waiter},
+};
+
+const BS: u8 = 0x08;
+
+pub struct Tty {
+    inner: SpinLock<TtyInner>,
+}
+
+struct TtyInner {
+    termios: Termios,
+    winsize: WinSize,
+    /// Bytes which can be read.
+    input: VecDeque<u8>,
+    /// Number of bytes ever read.
+    consumed: usize,
+    /// In canonical mode, where the lines in `input` end counted like `consumed`, a read never
+    /// goes past the first one. A line ended by EOF may be empty.
+    line_ends: VecDeque<usize>,
+    /// The line being edited in canonical mode.
+    line: Vec<u8>,
+    /// Threads waiting for input.
+    readers: WaitQueue,
+    /// The process receiving the signals of the TTY.
+    foreground: Weak<ProcControlBlock>,
+}
+
+lazy_static! {
+    pub static ref TTY: Tty = Tty {
+        inner: SpinLock::new(TtyInner {
+            termios: Termios::default(),
+            winsize: WinSize {
+                row: 24,
+                col: 80,
+                ..Default::default()
+            },
+            input: VecDeque::new(),
+            consumed: 0,
+            line_ends: VecDeque::new(),
+            line: Vec::new(),
+            readers: WaitQueue::new(),
+            foreground: Weak::new(),
+        }),
+    };
+}
+
+/// Connect the TTY to the UART.
+pub fn init() {
+    UART.set_receiver(|byte| TTY.receive(byte));
+}
+
+impl TtyInner {
+    fn canonical(&self) -> bool {
+        self.termios.lflag.contains(LocalModes::ICANON)
+    }
+
+    fn echo(&self, bytes: &[u8]) {
+        if self.termios.lflag.contains(LocalModes::ECHO) {
+            write_bytes(bytes);
+        }
+    }
+
+    /// Make the edited line readable.
+    fn commit_line(&mut self) {
+        self.input.extend(self.line.drain(..));
+        if self.canonical() {
+            let end = self.consumed + self.input.len();
+            self.line_ends.push_back(end);
+        }
+    }
+
+    fn readable(&self) -> bool {
+        if self.canonical() {
+            !self.line_ends.is_empty()
+        } else {
+            !self.input.is_empty() || self.termios.cc[VMIN] == 0
+        }
+    }
+
+    fn wake_readers(&mut self) {
+        self.readers.wake_all();
+    }
+
+    /// Copy the readable bytes to `buf`, return the number of bytes copied.
+    fn read_to(&mut self, buf: &mut UserBuffer) -> usize {
+        let mut len = buf.len().min(self.input.len());
+        if let Some(&end) = self.line_ends.front() {
+            len = len.min(end - self.consumed);
+        }
+        for byte in buf.iter_mut().flat_map(|slice| slice.iter_mut()).take(len) {
+            *byte = self.input.pop_front().unwrap();
+        }
+        self.consumed += len;
+        if self.line_ends.front() == Some(&self.consumed) {
+            self.line_ends.pop_front();
+        }
+        len
+    }
+
+    fn set_termios(&mut self, termios: Termios) {
+        let was_canonical = self.canonical();
+        self.termios = termios;
+        if was_canonical && !self.canonical() {
+            // the lines are not delimited in raw mode
+            self.commit_line();
+            self.line_ends.clear();
+        }
+        self.wake_readers();
+    }
+
+    fn flush_input(&mut self) {
+        self.consumed += self.input.len();
+        self.input.clear();
+        self.line_ends.clear();
+        self.line.clear();
+    }
+}
+
+impl Tty {
+    /// Process a byte received by the UART.
+    fn receive(&self, byte: u8) {
+        let mut inner = self.inner.lock();
+        let termios = inner.termios;
+        let byte = if termios.iflag.contains(InputModes::ICRNL) && byte == b'\r' {
+            b'\n'
+        } else {
+            byte
+        };
+        if termios.lflag.contains(LocalModes::ISIG)
+            && (byte == termios.cc[VINTR] || byte == termios.cc[VQUIT])
+        {
+            inner.line.clear();
+            inner.echo(if byte == termios.cc[VINTR] {
+                b"^C\n"
+            } else {
+                b"^\\\n"
+            });
+            if let Some(proc) = inner.foreground.upgrade() {
+                proc.send_signal(if byte == termios.cc[VINTR] {
+                    SignalFlags::SIGINT
+                } else {
+                    SignalFlags::SIGQUIT
+                });
+            }
+            // an interrupted reader has to notice the signal
+            inner.wake_readers();
+            return;
+        }
+        if !inner.canonical() {
+            inner.input.push_back(byte);
+            inner.echo(&[byte]);
+            inner.wake_readers();
+            return;
+        }
+        if byte == termios.cc[VERASE] || byte == BS {
+            if inner.line.pop().is_some() && termios.lflag.contains(LocalModes::ECHOE) {
+                inner.echo(b"\x08 \x08");
+            }
+        } else if byte == termios.cc[VKILL] {
+            while inner.line.pop().is_some() {
+                if termios.lflag.contains(LocalModes::ECHOK) {
+                    inner.echo(b"\x08 \x08");
+                }
+            }
+        } else if byte == termios.cc[VEOF] {
+            inner.commit_line();
+            inner.wake_readers();
+        } else {
+            inner.line.push(byte);
+            if byte == b'\n' {
+                if termios.lflag.contains(LocalModes::ECHONL) {
+                    write_bytes(b"\n");
+                } else {
+                    inner.echo(b"\n");
+                }
+                inner.commit_line();
+                inner.wake_readers();
+            } else {
+                inner.echo(&[byte]);
+            }
+        }
+    }
+
+    /// Read the input, blocking until some is readable unless `nonblock`. Return 0 for EOF, or
+    /// when interrupted by a signal which terminates the process.
+    pub fn read(&self, mut buf: UserBuffer, nonblock: bool) -> Result<usize, isize> {
+        loop {
+            let mut inner = self.inner.lock();
+            if !current_proc()
+                .borrow_inner_mut()
+                .unblocked_signals()
+                .is_empty()
+            {
+                return Ok(0);
+            }
+            if inner.readable() {
+                return Ok(inner.read_to(&mut buf));
+            }
+            if nonblock {
+                return Err(EAGAIN);
+            }
+            let waiter = Waiter::new(None);
+            inner.readers.push(&waiter);
+            drop(inner);
+            waiter.wait();
+        }
+    }
+
+    /// The console can always be written.
+    pub fn poll(&self, waiter: Option<&Arc<Waiter>>) -> PollEvents {
+        let mut inner = self.inner.lock();
+        if let Some(waiter) = waiter {
+            inner.readers.push(waiter);
+        }
+        if inner.readable() {
+            PollEvents::IN | PollEvents::OUT
+        } else {
+            PollEvents::OUT
+        }
+    }
+
+    pub fn ioctl(&self, request: usize, arg: usize) -> isize {
+        let proc = current_proc();
+        let page_table = proc.page_table();
+        let mut inner = self.inner.lock();
+        match request {
+            TCGETS => *page_table.translate_mut_ptr(arg as *mut Termios) = inner.termios,
+            TCSETS | TCSETSW | TCSETSF => {
+                if request == TCSETSF {
+                    inner.flush_input();
+                }
+                inner.set_termios(*page_table.translate_ptr(arg as *const Termios));
+            }
+            TIOCGWINSZ => *page_table.translate_mut_ptr(arg as *mut WinSize) = inner.winsize,
+            TIOCSWINSZ => inner.winsize = *page_table.translate_ptr(arg as *const WinSize),
+            TIOCGPGRP => {
+                let pid = inner.foreground.upgrade().map_or(0, |proc| proc.pid());
+                *page_table.translate_mut_ptr(arg as *mut i32) = pid as i32;
+            }
+            TIOCSPGRP => {
+                // there are no process groups, the foreground is the caller or one of its children
+                let pid = *page_table.translate_ptr(arg as *const i32) as usize;
+                let foreground = if proc.pid() == pid {
+                    Some(Arc::clone(&proc))
+                } else {
+                    let proc_inner = proc.borrow_inner_mut();
+                    proc_inner
+                        .children
+                        .iter()
+                        .find(|child| child.pid() == pid)
+                        .cloned()
+                };
+                let Some(foreground) = foreground else {
+                    return -EPERM;
+                };
+                inner.foreground = Arc::downgrade(&foreground);
+            }
+            _ => return -EINVAL,
+        }
+        0
+    }
+}
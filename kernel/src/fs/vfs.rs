@@ -0,0 +1,92 @@
+//! The interface between the kernel and the filesystems
+//!
+//! A filesystem is mounted as a `SuperBlock`, and its files and directories are `Inode`s. An
+//! inode is represented by a single object while in use, so that holding a reference to it
+//! tells the filesystem that it is in use. Paths are resolved into `Dentry`s by the mount
+//! table.
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use common::{
+    errno::{EINVAL, ENOTDIR},
+    fs::{FileType, Stat},
+};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use super::{File, OpenFlags, mount::Mount};
+
+/// A mounted filesystem.
+pub trait SuperBlock: Send + Sync {
+    fn root(&self) -> Arc<dyn Inode>;
+}
+
+/// A file or directory of a filesystem. Errors are returned as errno.
+///
+/// The data operations fail with `EINVAL` and the directory operations with `ENOTDIR` unless
+/// implemented.
+pub trait Inode: Send + Sync {
+    fn stat(&self) -> Stat;
+
+    fn file_type(&self) -> FileType {
+        self.stat().file_type().unwrap()
+    }
+
+    /// Read the data at `offset`, return the number of bytes read, 0 at the end.
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> Result<usize, isize> {
+        Err(EINVAL)
+    }
+
+    /// Write the data at `offset`, growing the file if needed.
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize, isize> {
+        Err(EINVAL)
+    }
+
+    /// Truncate the file to zero length.
+    fn truncate(&self) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    /// Find the entry `name` of the directory.
+    fn lookup(&self, _name: &str) -> Result<Arc<dyn Inode>, isize> {
+        Err(ENOTDIR)
+    }
+
+    /// Create the entry `name` of the directory, it must not exist.
+    fn create(&self, _name: &str, _file_type: FileType) -> Result<Arc<dyn Inode>, isize> {
+        Err(ENOTDIR)
+    }
+
+    /// Remove the entry `name` of the directory. Directories must be empty, and inodes in use
+    /// may not be removable.
+    fn unlink(&self, _name: &str) -> Result<(), isize> {
+        Err(ENOTDIR)
+    }
+
+    /// List the entries of the directory.
+    fn readdir(&self) -> Result<Vec<DirEntry>, isize> {
+        Err(ENOTDIR)
+    }
+
+    /// Open a device, which is read and written by its own `File` rather than by the data
+    /// operations. Return `None` for the other files.
+    fn open(&self, _flags: OpenFlags) -> Option<Arc<dyn File>> {
+        None
+    }
+}
+
+pub struct DirEntry {
+    pub name: String,
+    pub ino: u64,
+    pub file_type: FileType,
+}
+
+/// A resolved path, the inode and the mounted filesystem it is on.
+pub struct Dentry {
+    pub inode: Arc<dyn Inode>,
+    pub mount: Arc<Mount>,
+}
+
+/// Return a new device number identifying a filesystem in `Stat`.
+pub fn alloc_dev() -> u64 {
+    static NEXT_DEV: AtomicU64 = AtomicU64::new(1);
+    NEXT_DEV.fetch_add(1, Ordering::Relaxed)
+}
@@ -0,0 +1,4 @@
+//! Inter-process communication beyond pipes and sockets
+
+pub mod mqueue;
+pub mod shm;
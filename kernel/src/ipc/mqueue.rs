@@ -0,0 +1,277 @@
+//! POSIX message queues
+//!
+//! Queues are named in their own namespace and opened as files, so that they can be polled
+//! with other files. A queue stays until it is unlinked and no file has it open.
+
+use alloc::{
+    collections::btree_map::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::cmp::Reverse;
+
+use common::{
+    errno::{EAGAIN, EEXIST, EINTR, EINVAL, EMSGSIZE, ENOENT, ENOSPC, ETIMEDOUT},
+    fs::{FileType, Stat},
+    mqueue::{
+        MQ_DEFAULT_MAXMSG, MQ_DEFAULT_MSGSIZE, MQ_MAXMSG_MAX, MQ_MSGSIZE_MAX, MQ_PRIO_MAX,
+        MQ_QUEUES_MAX, MqAttr,
+    },
+    poll::PollEvents,
+};
+use lazy_static::lazy_static;
+
+use crate::{
+    fs::{File, FileStatus, OpenFlags},
+    memory::UserBuffer,
+    proc::current_proc,
+    sync::{SpinLock, WaitQueue, Waiter},
+    timer::get_time_ms,
+};
+
+pub struct MessageQueue {
+    maxmsg: usize,
+    msgsize: usize,
+    inner: SpinLock<QueueInner>,
+}
+
+struct QueueInner {
+    /// Ordered by decreasing priority, then by the order they were sent.
+    messages: BTreeMap<(Reverse<u32>, u64), Vec<u8>>,
+    next_seq: u64,
+    readers: WaitQueue,
+    writers: WaitQueue,
+}
+
+lazy_static! {
+    static ref QUEUES: SpinLock<BTreeMap<String, Arc<MessageQueue>>> =
+        SpinLock::new(BTreeMap::new());
+}
+
+/// A message queue opened as a file.
+pub struct MqFile {
+    readable: bool,
+    writable: bool,
+    status: FileStatus,
+    queue: Arc<MessageQueue>,
+}
+
+/// Check that `name` has no slash, as the leading one is stripped in user space.
+fn check_name(name: &str) -> Result<(), isize> {
+    if name.is_empty() || name.contains('/') {
+        return Err(EINVAL);
+    }
+    Ok(())
+}
+
+/// Open the queue `name`, created with `attr` if given or the default capacity otherwise if
+/// there is none and `flags` has `CREATE`.
+pub fn open(name: &str, flags: OpenFlags, attr: Option<MqAttr>) -> Result<Arc<MqFile>, isize> {
+    check_name(name)?;
+    let mut queues = QUEUES.lock();
+    let queue = match queues.get(name) {
+        Some(_) if flags.contains(OpenFlags::CREATE | OpenFlags::EXCL) => return Err(EEXIST),
+        Some(queue) => Arc::clone(queue),
+        None if flags.contains(OpenFlags::CREATE) => {
+            if queues.len() == MQ_QUEUES_MAX {
+                return Err(ENOSPC);
+            }
+            let queue = Arc::new(MessageQueue::new(attr)?);
+            queues.insert(name.to_string(), Arc::clone(&queue));
+            queue
+        }
+        None => return Err(ENOENT),
+    };
+    let (readable, writable) = flags.read_write();
+    Ok(Arc::new(MqFile {
+        readable,
+        writable,
+        status: FileStatus::new(flags),
+        queue,
+    }))
+}
+
+/// Remove `name`, the queue is freed once no file has it open.
+pub fn unlink(name: &str) -> Result<(), isize> {
+    check_name(name)?;
+    QUEUES.lock().remove(name).ok_or(ENOENT)?;
+    Ok(())
+}
+
+impl MessageQueue {
+    fn new(attr: Option<MqAttr>) -> Result<Self, isize> {
+        let (maxmsg, msgsize) = match attr {
+            Some(attr) => (attr.maxmsg, attr.msgsize),
+            None => (MQ_DEFAULT_MAXMSG as i64, MQ_DEFAULT_MSGSIZE as i64),
+        };
+        if !(1..=MQ_MAXMSG_MAX as i64).contains(&maxmsg)
+            || !(1..=MQ_MSGSIZE_MAX as i64).contains(&msgsize)
+        {
+            return Err(EINVAL);
+        }
+        Ok(Self {
+            maxmsg: maxmsg as usize,
+            msgsize: msgsize as usize,
+            inner: SpinLock::new(QueueInner {
+                messages: BTreeMap::new(),
+                next_seq: 0,
+                readers: WaitQueue::new(),
+                writers: WaitQueue::new(),
+            }),
+        })
+    }
+
+    /// Run `f` on the queue until it succeeds or fails other than with `EAGAIN`, waiting on the
+    /// queue chosen by `queue` in between unless `nonblock`, until `expire_ms` if any.
+    fn wait_until<T>(
+        &self,
+        nonblock: bool,
+        expire_ms: Option<usize>,
+        queue: fn(&mut QueueInner) -> &mut WaitQueue,
+        mut f: impl FnMut(&mut QueueInner) -> Result<T, isize>,
+    ) -> Result<T, isize> {
+        loop {
+            if !current_proc()
+                .borrow_inner_mut()
+                .unblocked_signals()
+                .is_empty()
+            {
+                return Err(EINTR);
+            }
+            let mut inner = self.inner.lock();
+            match f(&mut inner) {
+                Err(EAGAIN) if nonblock => return Err(EAGAIN),
+                Err(EAGAIN) if expire_ms.is_some_and(|expire_ms| get_time_ms() >= expire_ms) => {
+                    return Err(ETIMEDOUT);
+                }
+                Err(EAGAIN) => {}
+                result => return result,
+            }
+            let waiter = Waiter::new(expire_ms);
+            queue(&mut inner).push(&waiter);
+            drop(inner);
+            waiter.wait();
+        }
+    }
+}
+
+impl MqFile {
+    fn nonblock(&self) -> bool {
+        self.status.get().contains(OpenFlags::NONBLOCK)
+    }
+
+    /// Queue `message` with `priority`, waiting for room until `expire_ms` if any.
+    pub fn send(
+        &self,
+        message: Vec<u8>,
+        priority: u32,
+        expire_ms: Option<usize>,
+    ) -> Result<(), isize> {
+        if message.len() > self.queue.msgsize {
+            return Err(EMSGSIZE);
+        }
+        if priority >= MQ_PRIO_MAX {
+            return Err(EINVAL);
+        }
+        let mut message = Some(message);
+        self.queue.wait_until(
+            self.nonblock(),
+            expire_ms,
+            |inner| &mut inner.writers,
+            |inner| {
+                if inner.messages.len() == self.queue.maxmsg {
+                    return Err(EAGAIN);
+                }
+                let seq = inner.next_seq;
+                inner.next_seq += 1;
+                inner
+                    .messages
+                    .insert((Reverse(priority), seq), message.take().unwrap());
+                inner.readers.wake_all();
+                Ok(())
+            },
+        )
+    }
+
+    /// Take the oldest message of the highest priority into `buf`, waiting for one until
+    /// `expire_ms` if any. Return its length and priority.
+    pub fn receive(
+        &self,
+        mut buf: UserBuffer,
+        expire_ms: Option<usize>,
+    ) -> Result<(usize, u32), isize> {
+        // any message must fit
+        if buf.len() < self.queue.msgsize {
+            return Err(EMSGSIZE);
+        }
+        self.queue.wait_until(
+            self.nonblock(),
+            expire_ms,
+            |inner| &mut inner.readers,
+            |inner| {
+                let ((Reverse(priority), _), message) = inner.messages.pop_first().ok_or(EAGAIN)?;
+                inner.writers.wake_all();
+                Ok((buf.write(&message), priority))
+            },
+        )
+    }
+
+    pub fn attr(&self) -> MqAttr {
+        MqAttr {
+            flags: (self.status.get() & OpenFlags::NONBLOCK).bits() as i64,
+            maxmsg: self.queue.maxmsg as i64,
+            msgsize: self.queue.msgsize as i64,
+            curmsgs: self.queue.inner.lock().messages.len() as i64,
+            reserved: [0; 4],
+        }
+    }
+}
+
+impl File for MqFile {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    fn status(&self) -> &FileStatus {
+        &self.status
+    }
+
+    /// Messages are only received by `mq_timedreceive`.
+    fn read(&self, _buf: UserBuffer) -> Result<usize, isize> {
+        Err(EINVAL)
+    }
+
+    /// Messages are only sent by `mq_timedsend`.
+    fn write(&self, _buf: UserBuffer) -> Result<usize, isize> {
+        Err(EINVAL)
+    }
+
+    fn stat(&self) -> Stat {
+        Stat {
+            mode: FileType::Regular.mode() | 0o600,
+            nlink: 1,
+            ..Default::default()
+        }
+    }
+
+    fn poll(&self, waiter: Option<&Arc<Waiter>>) -> PollEvents {
+        let mut inner = self.queue.inner.lock();
+        if let Some(waiter) = waiter {
+            inner.readers.push(waiter);
+            inner.writers.push(waiter);
+        }
+        let mut events = PollEvents::empty();
+        events.set(PollEvents::IN, !inner.messages.is_empty());
+        events.set(PollEvents::OUT, inner.messages.len() < self.queue.maxmsg);
+        events
+    }
+
+    fn as_mqueue(&self) -> Option<&MqFile> {
+        Some(self)
+    }
+}
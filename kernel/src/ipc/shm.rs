@@ -0,0 +1,117 @@
+//! System V shared memory segments
+//!
+//! A segment is a set of frames mapped by a shared area into each memory space attaching it.
+//! The table holds a reference to the frames until the segment is removed, and every attached
+//! area holds another, so the frames are freed once the segment is removed and detached
+//! everywhere, whether by `shmdt`, exec or exit.
+
+use alloc::{collections::btree_map::BTreeMap, sync::Arc};
+
+use common::{
+    errno::{EEXIST, EINVAL, ENOENT, ENOMEM},
+    ipc::{IPC_CREAT, IPC_EXCL, IPC_PRIVATE, ShmIdDs},
+};
+use lazy_static::lazy_static;
+
+use crate::{
+    config::{PAGE_SIZE, USER_SHM_BASE, USER_SHM_END},
+    memory::SharedFrames,
+    sync::SpinLock,
+};
+
+/// The size limit of a segment, which must fit in the region where segments are attached.
+const SHMMAX: usize = USER_SHM_END - USER_SHM_BASE;
+
+struct Segment {
+    key: i32,
+    mode: u32,
+    size: usize,
+    cpid: usize,
+    frames: Arc<SharedFrames>,
+}
+
+struct ShmTable {
+    segments: BTreeMap<usize, Segment>,
+    next_id: usize,
+}
+
+lazy_static! {
+    static ref SHM_TABLE: SpinLock<ShmTable> = SpinLock::new(ShmTable {
+        segments: BTreeMap::new(),
+        next_id: 0,
+    });
+}
+
+/// Return the id of the segment of `key`, created with `size` bytes by process `pid` if there
+/// is none and `IPC_CREAT` is set, or a new one for `IPC_PRIVATE`.
+pub fn get(key: i32, size: usize, flags: u32, pid: usize) -> Result<usize, isize> {
+    let mut table = SHM_TABLE.lock();
+    if key != IPC_PRIVATE
+        && let Some((&id, segment)) = table
+            .segments
+            .iter()
+            .find(|(_, segment)| segment.key == key)
+    {
+        if flags & IPC_CREAT != 0 && flags & IPC_EXCL != 0 {
+            return Err(EEXIST);
+        }
+        if size > segment.size {
+            return Err(EINVAL);
+        }
+        return Ok(id);
+    }
+    if key != IPC_PRIVATE && flags & IPC_CREAT == 0 {
+        return Err(ENOENT);
+    }
+    if size == 0 || size > SHMMAX {
+        return Err(EINVAL);
+    }
+    let frames = SharedFrames::new(size.div_ceil(PAGE_SIZE)).ok_or(ENOMEM)?;
+    let id = table.next_id;
+    table.next_id += 1;
+    table.segments.insert(
+        id,
+        Segment {
+            key,
+            mode: flags & 0o777,
+            size,
+            cpid: pid,
+            frames,
+        },
+    );
+    Ok(id)
+}
+
+/// Return the frames of segment `id` to attach them.
+pub fn frames(id: usize) -> Result<Arc<SharedFrames>, isize> {
+    let table = SHM_TABLE.lock();
+    let segment = table.segments.get(&id).ok_or(EINVAL)?;
+    Ok(segment.frames.clone())
+}
+
+pub fn stat(id: usize) -> Result<ShmIdDs, isize> {
+    let table = SHM_TABLE.lock();
+    let segment = table.segments.get(&id).ok_or(EINVAL)?;
+    Ok(ShmIdDs {
+        key: segment.key,
+        mode: segment.mode,
+        size: segment.size as u64,
+        cpid: segment.cpid as u32,
+        // every other reference is an attached area
+        nattch: (Arc::strong_count(&segment.frames) - 1) as u32,
+    })
+}
+
+/// Set the permission bits of segment `id`.
+pub fn set_mode(id: usize, mode: u32) -> Result<(), isize> {
+    let mut table = SHM_TABLE.lock();
+    let segment = table.segments.get_mut(&id).ok_or(EINVAL)?;
+    segment.mode = mode & 0o777;
+    Ok(())
+}
+
+/// Remove segment `id`, its frames are freed once it is detached everywhere.
+pub fn remove(id: usize) -> Result<(), isize> {
+    SHM_TABLE.lock().segments.remove(&id).ok_or(EINVAL)?;
+    Ok(())
+}
@@ -9,6 +9,7 @@
 
 extern crate alloc;
 
+use crate::config::MAX_HART_NUM;
 #[cfg(test)]
 use crate::sbi::shutdown;
 use core::arch::naked_asm;
@@ -20,10 +21,13 @@ mod common;
 mod config;
 mod drivers;
 mod fs;
+mod ipc;
 mod logger;
 mod memory;
+mod net;
 mod proc;
 mod sbi;
+mod smp;
 mod sync;
 mod syscall;
 #[cfg(test)]
@@ -31,30 +35,57 @@ mod test_utils;
 mod timer;
 mod trap;
 
+/// Size of the boot stack of each hart.
+const BOOT_STACK_SIZE: usize = 16 * 1024; // 16 KiB
+
+#[unsafe(link_section = ".bss.uninit")]
+static mut BOOT_STACK: [u8; BOOT_STACK_SIZE * MAX_HART_NUM] = [0u8; BOOT_STACK_SIZE * MAX_HART_NUM];
+
+/// Entry of the boot hart, `a0` is the hart id and `a1` is the address of the device tree.
 #[unsafe(naked)]
 #[unsafe(no_mangle)]
 #[unsafe(link_section = ".text.entry")]
 unsafe extern "C" fn _start() -> ! {
-    const STACK_SIZE: usize = 16 * 1024; // 16 KiB
-
-    #[unsafe(link_section = ".bss.uninit")]
-    static mut STACK: [u8; STACK_SIZE] = [0u8; STACK_SIZE];
-
     naked_asm!(
-    "la sp, {stack} + {stack_size}",
+    "mv tp, a0",
+    "addi t0, a0, 1",
+    "li t1, {stack_size}",
+    "mul t0, t0, t1",
+    "la sp, {stack}",
+    "add sp, sp, t0",
     "j  {main}",
-    stack_size = const STACK_SIZE,
-    stack      =   sym STACK,
+    stack_size = const BOOT_STACK_SIZE,
+    stack      =   sym BOOT_STACK,
     main       =   sym kernel_main,
     )
 }
 
+/// Entry of other harts started by the boot hart, `a0` is the hart id.
+#[unsafe(naked)]
+unsafe extern "C" fn _start_other_hart() -> ! {
+    naked_asm!(
+    "mv tp, a0",
+    "addi t0, a0, 1",
+    "li t1, {stack_size}",
+    "mul t0, t0, t1",
+    "la sp, {stack}",
+    "add sp, sp, t0",
+    "j  {main}",
+    stack_size = const BOOT_STACK_SIZE,
+    stack      =   sym BOOT_STACK,
+    main       =   sym other_hart_main,
+    )
+}
+
 pub fn kernel_main(hart_id: usize, dtb_pa: usize) -> ! {
     crate::common::clear_bss();
     logger::init();
     memory::init();
     trap::init();
     timer::init();
+    drivers::init();
+    fs::init();
+    net::init();
     #[cfg(not(test))]
     {
         info!(r" _____         _     _  __                    _ ");
@@ -68,6 +99,8 @@ pub fn kernel_main(hart_id: usize, dtb_pa: usize) -> ! {
         info!(r"------------------------------------------------");
         info!("");
         proc::init();
+        smp::set_online();
+        smp::start_other_harts(_start_other_hart as usize);
         proc::run();
     }
     #[cfg(test)]
@@ -79,3 +112,13 @@ pub fn kernel_main(hart_id: usize, dtb_pa: usize) -> ! {
         shutdown(false);
     }
 }
+
+fn other_hart_main(hart_id: usize) -> ! {
+    memory::activate();
+    trap::init();
+    timer::init();
+    drivers::init_hart();
+    smp::set_online();
+    info!("Hart {hart_id} started");
+    proc::run();
+}
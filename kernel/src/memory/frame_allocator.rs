@@ -1,13 +1,12 @@
 use crate::{config::MEMORY_END, memory::address::PhysAddr};
 use alloc::vec::Vec;
 
-use crate::sync::UPSafeCell;
+use crate::sync::SpinLock;
 
 use super::address::PhysPageNum;
 
 type FrameAllocatorImpl = StackFrameAllocator;
-pub static FRAME_ALLOCATOR: UPSafeCell<FrameAllocatorImpl> =
-    unsafe { UPSafeCell::new(FrameAllocatorImpl::new()) };
+pub static FRAME_ALLOCATOR: SpinLock<FrameAllocatorImpl> = SpinLock::new(FrameAllocatorImpl::new());
 
 #[derive(Debug)]
 pub struct FrameTracker {
@@ -26,7 +25,7 @@ impl FrameTracker {
 
 impl Drop for FrameTracker {
     fn drop(&mut self) {
-        FRAME_ALLOCATOR.borrow_mut().dealloc(self.ppn);
+        FRAME_ALLOCATOR.lock().dealloc(self.ppn);
     }
 }
 
@@ -36,6 +35,7 @@ pub trait FrameAllocator {
 }
 
 pub struct StackFrameAllocator {
+    start: PhysPageNum,
     current: PhysPageNum,
     end: PhysPageNum,
     recycled: Vec<PhysPageNum>,
@@ -44,6 +44,7 @@ pub struct StackFrameAllocator {
 impl StackFrameAllocator {
     pub const fn new() -> Self {
         Self {
+            start: PhysPageNum::zero(),
             current: PhysPageNum::zero(),
             end: PhysPageNum::zero(),
             recycled: Vec::new(),
@@ -51,10 +52,21 @@ impl StackFrameAllocator {
     }
 
     pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum) {
+        self.start = l;
         self.current = l;
         self.end = r;
     }
 
+    /// Number of frames managed.
+    pub fn total(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Number of frames not allocated.
+    pub fn free(&self) -> usize {
+        self.end - self.current + self.recycled.len()
+    }
+
     fn alloc(&mut self) -> Option<PhysPageNum> {
         if let Some(ppn) = self.recycled.pop() {
             Some(ppn)
@@ -90,7 +102,7 @@ pub fn init_frame_allocator() {
     unsafe extern "C" {
         safe fn ekernel();
     }
-    FRAME_ALLOCATOR.borrow_mut().init(
+    FRAME_ALLOCATOR.lock().init(
         // 使用 ekernel 之后的一个 Page 作为可用 FRAME 的起始地址，避免覆盖内核代码
         PhysAddr::new(ekernel as usize).next_page_number(),
         PhysAddr::new(MEMORY_END).page_number(),
@@ -105,13 +117,13 @@ mod tests {
     pub fn frame_allocator_test() {
         let mut v: Vec<FrameTracker> = Vec::new();
         for i in 0..5 {
-            let frame = FRAME_ALLOCATOR.borrow_mut().frame_alloc().unwrap();
+            let frame = FRAME_ALLOCATOR.lock().frame_alloc().unwrap();
             // println!("{:?}", frame);
             v.push(frame);
         }
         v.clear();
         for i in 0..5 {
-            let frame = FRAME_ALLOCATOR.borrow_mut().frame_alloc().unwrap();
+            let frame = FRAME_ALLOCATOR.lock().frame_alloc().unwrap();
             // println!("{:?}", frame);
             v.push(frame);
         }
@@ -26,6 +26,12 @@ pub fn init_heap() {
     }
 }
 
+/// Return the size of the heap and the bytes allocated from it.
+pub fn heap_usage() -> (usize, usize) {
+    let heap = HEAP_ALLOCATOR.lock();
+    (heap.stats_total_bytes(), heap.stats_alloc_actual())
+}
+
 #[cfg(test)]
 mod tests {
     use alloc::vec::Vec;
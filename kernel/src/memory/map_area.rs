@@ -1,18 +1,44 @@
 use core::ops::Range;
 
-use alloc::collections::btree_map::BTreeMap;
+use alloc::{collections::btree_map::BTreeMap, sync::Arc, vec::Vec};
 
 use bitflags::bitflags;
 
-use super::{VirtPageNum, frame_allocator::FrameTracker};
+use super::{
+    PhysPageNum, VirtPageNum,
+    frame_allocator::{FRAME_ALLOCATOR, FrameAllocator, FrameTracker},
+};
+
+/// Frames mapped by the areas of several memory spaces, freed once no area maps them.
+pub struct SharedFrames(Vec<FrameTracker>);
+
+impl SharedFrames {
+    /// Allocate `pages` zeroed frames, return `None` if out of memory.
+    pub fn new(pages: usize) -> Option<Arc<Self>> {
+        // the allocator is locked per frame as the frames are freed on failure
+        let frames = (0..pages)
+            .map(|_| FRAME_ALLOCATOR.lock().frame_alloc())
+            .collect::<Option<_>>()?;
+        Some(Arc::new(Self(frames)))
+    }
+
+    pub fn pages(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn ppn(&self, page: usize) -> PhysPageNum {
+        self.0[page].ppn
+    }
+}
 
 pub struct MapArea {
     pub start_vpn: VirtPageNum,
     pub end_vpn: VirtPageNum,
     pub map_type: MapType,
-    #[allow(unused)]
     pub map_perm: MapPermission,
     data_frames: BTreeMap<VirtPageNum, FrameTracker>,
+    /// The frames of a shared area.
+    shared_frames: Option<Arc<SharedFrames>>,
 }
 
 impl MapArea {
@@ -26,11 +52,32 @@ impl MapArea {
             start_vpn,
             end_vpn,
             data_frames: BTreeMap::new(),
+            shared_frames: None,
             map_type,
             map_perm,
         }
     }
 
+    /// An area mapping all of `frames` from `start_vpn`.
+    pub fn new_shared(
+        start_vpn: VirtPageNum,
+        frames: Arc<SharedFrames>,
+        map_perm: MapPermission,
+    ) -> Self {
+        Self {
+            start_vpn,
+            end_vpn: start_vpn + frames.pages(),
+            data_frames: BTreeMap::new(),
+            shared_frames: Some(frames),
+            map_type: MapType::Shared,
+            map_perm,
+        }
+    }
+
+    pub fn shared_frames(&self) -> Option<&Arc<SharedFrames>> {
+        self.shared_frames.as_ref()
+    }
+
     pub fn range(&self) -> Range<VirtPageNum> {
         self.start_vpn..self.end_vpn
     }
@@ -62,6 +109,8 @@ impl Clone for MapArea {
             start_vpn: self.start_vpn,
             end_vpn: self.end_vpn,
             data_frames: BTreeMap::new(),
+            // the copy maps the same frames
+            shared_frames: self.shared_frames.clone(),
             map_type: self.map_type,
             map_perm: self.map_perm,
         }
@@ -69,10 +118,12 @@ impl Clone for MapArea {
 }
 
 #[derive(Copy, Clone, PartialEq, Debug)]
-/// map type for memory set: identical or framed
+/// map type for memory set: identical, framed or shared
 pub enum MapType {
     Identical,
     Framed,
+    /// Mapping frames which the area does not own, kept across fork.
+    Shared,
 }
 
 bitflags! {
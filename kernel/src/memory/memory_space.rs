@@ -1,4 +1,4 @@
-use core::arch::asm;
+use core::{arch::asm, ops::Range};
 
 use alloc::{sync::Arc, vec::Vec};
 use lazy_static::lazy_static;
@@ -6,21 +6,22 @@ use log::trace;
 use riscv::register::satp;
 
 use crate::{
-    config::{MEMORY_END, MMIO, PAGE_SIZE, TRAMPOLINE, TRAP_FRAME, USER_STACK_SIZE},
-    sync::UPSafeCell,
+    config::{MEMORY_END, MMIO, PAGE_SIZE, TRAMPOLINE},
+    smp::tlb_shootdown,
+    sync::SpinLock,
 };
 
 use super::{
     PhysAddr, PhysPageNum, VirtAddr, VirtPageNum,
     frame_allocator::{FRAME_ALLOCATOR, FrameAllocator},
-    map_area::{MapArea, MapPermission, MapType},
+    map_area::{MapArea, MapPermission, MapType, SharedFrames},
     page_table::{PTEFlags, PageTable, PageTableEntry},
 };
 
 lazy_static! {
     /// a memory set instance through lazy_static! managing kernel space
-    pub static ref KERNEL_SPACE: Arc<UPSafeCell<MemorySpace>> =
-        Arc::new(unsafe { UPSafeCell::new(MemorySpace::new_kernel()) });
+    pub static ref KERNEL_SPACE: Arc<SpinLock<MemorySpace>> =
+        Arc::new(SpinLock::new(MemorySpace::new_kernel()));
 }
 
 unsafe extern "C" {
@@ -129,6 +130,9 @@ impl MemorySpace {
         );
     }
 
+    /// Return (memory space, user stack base, entry point).
+    ///
+    /// User stacks and trap frames are not mapped here, they are allocated per thread.
     pub fn from_elf(elf_data: impl AsRef<[u8]>) -> (Self, usize, usize) {
         let elf_data = elf_data.as_ref();
         let mut space = Self::new_bare();
@@ -166,36 +170,14 @@ impl MemorySpace {
             let data = &elf_data[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize];
             space.map_range_with_data(start_vpn, end_vpn, MapType::Framed, map_perm, data);
         }
-        // map user stack with U flags
-        let mut user_stack_bottom =
+        // user stacks of threads are allocated above the last program segment
+        let mut user_stack_base =
             VirtAddr::from(max_end_vpn.expect("No loadable program header found in ELF file"));
         // guard page
-        user_stack_bottom += PAGE_SIZE;
-        let user_stack_top = user_stack_bottom + USER_STACK_SIZE;
-        space.map_range(
-            user_stack_bottom.page_number(),
-            user_stack_top.page_number(),
-            MapType::Framed,
-            MapPermission::R | MapPermission::W | MapPermission::U,
-        );
-        // used in sbrk
-        // FIXME: 这个有什么用？
-        space.map_range(
-            user_stack_top.page_number(),
-            user_stack_top.page_number(),
-            MapType::Framed,
-            MapPermission::R | MapPermission::W | MapPermission::U,
-        );
-        // map TrapFrame
-        space.map_range(
-            VirtAddr::new(TRAP_FRAME).page_number(),
-            VirtAddr::new(TRAMPOLINE).page_number(),
-            MapType::Framed,
-            MapPermission::R | MapPermission::W,
-        );
+        user_stack_base += PAGE_SIZE;
         (
             space,
-            user_stack_top.into(),
+            user_stack_base.into(),
             elf.header.pt2.entry_point() as usize,
         )
     }
@@ -212,6 +194,10 @@ impl MemorySpace {
         self.page_table.write_c_str(ptr, s);
     }
 
+    pub fn areas(&self) -> &[MapArea] {
+        &self.areas
+    }
+
     pub fn token(&self) -> usize {
         self.page_table.token()
     }
@@ -229,22 +215,62 @@ impl MemorySpace {
     }
 
     pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) {
-        if let Some((idx, area)) = self
+        if let Some(idx) = self
             .areas
-            .iter_mut()
-            .enumerate()
-            .find(|(_, area)| area.start_vpn() == start_vpn)
+            .iter()
+            .position(|area| area.start_vpn() == start_vpn)
         {
+            let mut area = self.areas.remove(idx);
+            let mut frames = Vec::new();
             for vpn in area.range() {
                 if area.map_type == MapType::Framed {
-                    area.remove(vpn);
+                    frames.extend(area.remove(vpn));
                 }
                 self.page_table.unmap(vpn);
             }
-            self.areas.remove(idx);
+            let start = VirtAddr::from(area.start_vpn);
+            let end = VirtAddr::from(area.end_vpn);
+            // other harts running this memory space may still cache the translations
+            tlb_shootdown(start.into(), usize::from(end) - usize::from(start));
+            drop(frames);
+            // shared frames are freed with the last area mapping them
+            drop(area);
         }
     }
 
+    /// Map `frames` at `start_vpn`, which must not overlap any area.
+    pub fn insert_shared_area(
+        &mut self,
+        start_vpn: VirtPageNum,
+        frames: Arc<SharedFrames>,
+        permission: MapPermission,
+    ) {
+        let area = MapArea::new_shared(start_vpn, frames, permission);
+        self.map_range_with_data_inner(area, &[]);
+    }
+
+    /// Whether no area overlaps `range`.
+    pub fn is_free(&self, range: Range<VirtPageNum>) -> bool {
+        self.areas
+            .iter()
+            .all(|area| area.end_vpn <= range.start || range.end <= area.start_vpn)
+    }
+
+    /// Return the lowest page from `start_vpn` followed by `pages` pages without any area.
+    pub fn find_free_range(&self, mut start_vpn: VirtPageNum, pages: usize) -> VirtPageNum {
+        let mut areas: Vec<_> = self.areas.iter().map(MapArea::range).collect();
+        areas.sort_by_key(|range| range.start);
+        for range in areas {
+            if start_vpn + pages <= range.start {
+                break;
+            }
+            if range.end > start_vpn {
+                start_vpn = range.end;
+            }
+        }
+        start_vpn
+    }
+
     fn map_trampoline(&mut self) {
         let vpn = VirtAddr::new(TRAMPOLINE).page_number();
         let ppn = PhysAddr::new(strampoline as usize).page_number();
@@ -278,11 +304,12 @@ impl MemorySpace {
             let ppn = match area.map_type {
                 MapType::Identical => PhysPageNum::new(usize::from(vpn)),
                 MapType::Framed => {
-                    let frame = FRAME_ALLOCATOR.borrow_mut().frame_alloc().unwrap();
+                    let frame = FRAME_ALLOCATOR.lock().frame_alloc().unwrap();
                     let ppn = frame.ppn;
                     area.insert(vpn, frame);
                     ppn
                 }
+                MapType::Shared => area.shared_frames().unwrap().ppn(vpn - area.start_vpn),
             };
             self.page_table
                 .map(vpn, ppn, PTEFlags::from_bits(area.map_perm.bits()).unwrap());
@@ -303,6 +330,9 @@ impl Clone for MemorySpace {
         for area in self.areas.iter() {
             let new_area = area.clone();
             new_space.map_range_with_data_inner(new_area, &[]);
+            if area.map_type == MapType::Shared {
+                continue;
+            }
             // Copy data
             for vpn in area.range() {
                 let src_ppn = self.page_table.translate(vpn).unwrap().ppn();
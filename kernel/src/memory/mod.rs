@@ -7,12 +7,18 @@ mod page_table;
 
 pub use self::address::*;
 pub use self::frame_allocator::{FRAME_ALLOCATOR, FrameAllocator, FrameTracker};
-pub use self::map_area::MapPermission;
+pub use self::heap_allocator::heap_usage;
+pub use self::map_area::{MapPermission, MapType, SharedFrames};
 pub use self::memory_space::{KERNEL_SPACE, MemorySpace};
 pub use self::page_table::{PageTable, UserBuffer};
 
 pub fn init() {
     heap_allocator::init_heap();
     frame_allocator::init_frame_allocator();
-    KERNEL_SPACE.borrow_mut().activate();
+    activate();
+}
+
+/// Switch the current hart to the kernel memory space.
+pub fn activate() {
+    KERNEL_SPACE.lock().activate();
 }
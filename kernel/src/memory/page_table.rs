@@ -74,7 +74,7 @@ pub struct PageTable {
 
 impl PageTable {
     pub fn new() -> Self {
-        let frame = FRAME_ALLOCATOR.borrow_mut().frame_alloc().unwrap();
+        let frame = FRAME_ALLOCATOR.lock().frame_alloc().unwrap();
         Self {
             root_ppn: frame.ppn,
             frames: vec![frame],
@@ -114,7 +114,7 @@ impl PageTable {
                 return Some(pte);
             }
             if !pte.is_valid() {
-                let frame = FRAME_ALLOCATOR.borrow_mut().frame_alloc().unwrap();
+                let frame = FRAME_ALLOCATOR.lock().frame_alloc().unwrap();
                 *pte = PageTableEntry::new(frame.ppn, PTEFlags::V);
                 self.frames.push(frame);
             }
@@ -142,6 +142,7 @@ impl PageTable {
 
     pub fn translate_va(&self, va: VirtAddr) -> Option<PhysAddr> {
         self.translate(va.page_number())
+            .filter(|pte| pte.is_valid())
             .map(|pte| PhysAddr::from(pte.ppn()) + va.page_offset())
     }
 
@@ -252,6 +253,22 @@ impl UserBuffer {
     pub fn iter(&self) -> impl Iterator<Item = &&'static mut [u8]> {
         self.buffer.iter()
     }
+
+    /// Copy the bytes of the buffer.
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.buffer.concat()
+    }
+
+    /// Copy `data` to the start of the buffer, return the number of bytes copied.
+    pub fn write(&mut self, data: &[u8]) -> usize {
+        let mut copied = 0;
+        for slice in self.buffer.iter_mut() {
+            let len = slice.len().min(data.len() - copied);
+            slice[..len].copy_from_slice(&data[copied..copied + len]);
+            copied += len;
+        }
+        copied
+    }
 }
 
 impl IntoIterator for UserBuffer {
@@ -0,0 +1,405 @@
+//! The network interfaces and the sockets of the TCP/IP stack
+//!
+//! The loopback interface always exists, the Ethernet interface exists if there is a virtio-net
+//! device and is set up for QEMU user networking. Each interface has its own smoltcp sockets,
+//! so a socket bound to any address has a smoltcp socket on each interface.
+//!
+//! The interfaces are polled by the device interrupt and by every socket operation, and threads
+//! wait for the sockets to change on a single wait queue, with a timer for the TCP timers.
+
+use alloc::{collections::BTreeSet, sync::Arc, vec, vec::Vec};
+use core::net::{Ipv4Addr, SocketAddrV4};
+
+use common::errno::{EADDRINUSE, EADDRNOTAVAIL, EAGAIN, ENETUNREACH};
+use lazy_static::lazy_static;
+use smoltcp::{
+    iface::{Config, Context, Interface, PollResult, SocketHandle, SocketSet},
+    phy::{self, Device, DeviceCapabilities, Loopback, Medium},
+    socket::{AnySocket, tcp},
+    time::Instant,
+    wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr, IpEndpoint, IpListenEndpoint},
+};
+
+use crate::{
+    drivers::{MAX_FRAME_SIZE, VirtIONetwork, net_device},
+    sync::{SpinLock, WaitQueue, Waiter},
+    timer::{get_time, get_time_ms},
+};
+
+use super::check_signals;
+
+/// The address QEMU user networking gives to the guest, with its gateway.
+const ETHERNET_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 2, 15);
+const ETHERNET_PREFIX_LEN: u8 = 24;
+const GATEWAY: Ipv4Addr = Ipv4Addr::new(10, 0, 2, 2);
+
+/// The ports given to sockets which are not bound to one.
+const EPHEMERAL_PORTS: core::ops::RangeInclusive<u16> = 49152..=65535;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IfaceId {
+    Loopback,
+    Ethernet,
+}
+
+/// The virtio-net device as seen by smoltcp.
+struct NetDevice(Arc<VirtIONetwork>);
+
+struct NetRxToken(Vec<u8>);
+
+struct NetTxToken<'a>(&'a VirtIONetwork);
+
+impl phy::RxToken for NetRxToken {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(&self.0)
+    }
+}
+
+impl phy::TxToken for NetTxToken<'_> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut frame = vec![0; len];
+        let result = f(&mut frame);
+        // dropped if the queue has filled up since, as if lost on the wire
+        self.0.send(&frame);
+        result
+    }
+}
+
+impl Device for NetDevice {
+    type RxToken<'a> = NetRxToken;
+    type TxToken<'a> = NetTxToken<'a>;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(NetRxToken, NetTxToken<'_>)> {
+        let frame = self.0.receive()?;
+        Some((NetRxToken(frame), NetTxToken(&self.0)))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<NetTxToken<'_>> {
+        self.0.can_send().then_some(NetTxToken(&self.0))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.medium = Medium::Ethernet;
+        caps.max_transmission_unit = MAX_FRAME_SIZE;
+        caps
+    }
+}
+
+/// An interface with its device and its sockets.
+struct NetIface<D: Device> {
+    iface: Interface,
+    device: D,
+    sockets: SocketSet<'static>,
+}
+
+impl<D: Device> NetIface<D> {
+    fn new(mut device: D, hardware_addr: HardwareAddress, addrs: &[IpCidr]) -> Self {
+        let mut config = Config::new(hardware_addr);
+        config.random_seed = get_time() as u64;
+        let mut iface = Interface::new(config, &mut device, now());
+        iface.update_ip_addrs(|ip_addrs| {
+            for &addr in addrs {
+                ip_addrs.push(addr).unwrap();
+            }
+        });
+        Self {
+            iface,
+            device,
+            sockets: SocketSet::new(Vec::new()),
+        }
+    }
+
+    /// Process packets until the sockets stop changing, return whether any has changed.
+    fn poll(&mut self, now: Instant) -> bool {
+        let mut changed = false;
+        while self.iface.poll(now, &mut self.device, &mut self.sockets)
+            == PollResult::SocketStateChanged
+        {
+            changed = true;
+        }
+        changed
+    }
+}
+
+pub struct Stack {
+    loopback: NetIface<Loopback>,
+    ethernet: Option<NetIface<NetDevice>>,
+    /// The ports bound by sockets.
+    ports: BTreeSet<(Protocol, u16)>,
+    next_port: u16,
+    /// TCP sockets whose files are closed, removed once their connections are closed.
+    closing: Vec<(IfaceId, SocketHandle)>,
+    /// Threads waiting for any socket to change.
+    waiters: WaitQueue,
+}
+
+lazy_static! {
+    static ref STACK: SpinLock<Stack> = SpinLock::new(Stack::new());
+}
+
+fn now() -> Instant {
+    Instant::from_millis(get_time_ms() as i64)
+}
+
+pub fn endpoint(addr: SocketAddrV4) -> IpEndpoint {
+    IpEndpoint::new(IpAddress::Ipv4(*addr.ip()), addr.port())
+}
+
+/// The endpoint to listen on or bind to, on any address if `addr` is unspecified.
+pub fn listen_endpoint(addr: SocketAddrV4) -> IpListenEndpoint {
+    IpListenEndpoint {
+        addr: (!addr.ip().is_unspecified()).then_some(IpAddress::Ipv4(*addr.ip())),
+        port: addr.port(),
+    }
+}
+
+pub fn socket_addr(endpoint: IpEndpoint) -> SocketAddrV4 {
+    let IpAddress::Ipv4(addr) = endpoint.addr;
+    SocketAddrV4::new(addr, endpoint.port)
+}
+
+impl Stack {
+    fn new() -> Self {
+        let loopback_addrs = [
+            IpCidr::new(IpAddress::Ipv4(Ipv4Addr::LOCALHOST), 8),
+            // the host reaches itself at its own address without any device
+            IpCidr::new(IpAddress::Ipv4(ETHERNET_ADDR), 32),
+        ];
+        let loopback = NetIface::new(
+            Loopback::new(Medium::Ip),
+            HardwareAddress::Ip,
+            &loopback_addrs,
+        );
+        let ethernet = net_device().map(|device| {
+            let mac = EthernetAddress(device.mac());
+            let mut ethernet = NetIface::new(
+                NetDevice(device),
+                HardwareAddress::Ethernet(mac),
+                &[IpCidr::new(
+                    IpAddress::Ipv4(ETHERNET_ADDR),
+                    ETHERNET_PREFIX_LEN,
+                )],
+            );
+            ethernet
+                .iface
+                .routes_mut()
+                .add_default_ipv4_route(GATEWAY)
+                .unwrap();
+            ethernet
+        });
+        Self {
+            loopback,
+            ethernet,
+            ports: BTreeSet::new(),
+            next_port: *EPHEMERAL_PORTS.start(),
+            closing: Vec::new(),
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    pub fn sockets(&mut self, id: IfaceId) -> &mut SocketSet<'static> {
+        match id {
+            IfaceId::Loopback => &mut self.loopback.sockets,
+            IfaceId::Ethernet => &mut self.ethernet.as_mut().unwrap().sockets,
+        }
+    }
+
+    /// Return the context of interface `id` with its sockets, to connect a socket.
+    pub fn context(&mut self, id: IfaceId) -> (&mut Context, &mut SocketSet<'static>) {
+        match id {
+            IfaceId::Loopback => (self.loopback.iface.context(), &mut self.loopback.sockets),
+            IfaceId::Ethernet => {
+                let ethernet = self.ethernet.as_mut().unwrap();
+                (ethernet.iface.context(), &mut ethernet.sockets)
+            }
+        }
+    }
+
+    pub fn get<T: AnySocket<'static>>(&mut self, (id, handle): (IfaceId, SocketHandle)) -> &mut T {
+        self.sockets(id).get_mut(handle)
+    }
+
+    pub fn add<T: AnySocket<'static>>(&mut self, id: IfaceId, socket: T) -> SocketHandle {
+        self.sockets(id).add(socket)
+    }
+
+    pub fn remove(&mut self, (id, handle): (IfaceId, SocketHandle)) {
+        self.sockets(id).remove(handle);
+    }
+
+    /// Close the connection of a TCP socket whose file is closed, the socket is removed once
+    /// the peer has acknowledged.
+    pub fn close_tcp(&mut self, socket: (IfaceId, SocketHandle)) {
+        self.get::<tcp::Socket>(socket).close();
+        self.closing.push(socket);
+    }
+
+    /// Return the interfaces having the local address `addr`, all of them if it is unspecified.
+    pub fn local_ifaces(&self, addr: Ipv4Addr) -> Result<Vec<IfaceId>, isize> {
+        let mut ifaces = Vec::new();
+        let ip = IpAddress::Ipv4(addr);
+        if addr.is_unspecified() || self.loopback.iface.has_ip_addr(ip) {
+            ifaces.push(IfaceId::Loopback);
+        }
+        if let Some(ethernet) = &self.ethernet
+            && (addr.is_unspecified() || ethernet.iface.has_ip_addr(ip))
+        {
+            ifaces.push(IfaceId::Ethernet);
+        }
+        if ifaces.is_empty() {
+            return Err(EADDRNOTAVAIL);
+        }
+        Ok(ifaces)
+    }
+
+    /// Return the interface reaching `addr`, the unspecified address is this host.
+    pub fn route(&self, addr: Ipv4Addr) -> Result<IfaceId, isize> {
+        if addr.is_unspecified() || self.loopback.iface.has_ip_addr(IpAddress::Ipv4(addr)) {
+            Ok(IfaceId::Loopback)
+        } else if self.ethernet.is_some() {
+            Ok(IfaceId::Ethernet)
+        } else {
+            Err(ENETUNREACH)
+        }
+    }
+
+    /// Return the longest IP packet interface `id` sends, as it does not fragment them.
+    pub fn ip_mtu(&self, id: IfaceId) -> usize {
+        match id {
+            IfaceId::Loopback => self.loopback.device.capabilities().ip_mtu(),
+            IfaceId::Ethernet => self
+                .ethernet
+                .as_ref()
+                .unwrap()
+                .device
+                .capabilities()
+                .ip_mtu(),
+        }
+    }
+
+    /// Reserve `port` of `protocol`, or a free ephemeral port if 0, and return it.
+    pub fn bind_port(&mut self, protocol: Protocol, port: u16) -> Result<u16, isize> {
+        if port != 0 {
+            return if self.ports.insert((protocol, port)) {
+                Ok(port)
+            } else {
+                Err(EADDRINUSE)
+            };
+        }
+        for _ in EPHEMERAL_PORTS {
+            let port = self.next_port;
+            self.next_port = if port == *EPHEMERAL_PORTS.end() {
+                *EPHEMERAL_PORTS.start()
+            } else {
+                port + 1
+            };
+            if self.ports.insert((protocol, port)) {
+                return Ok(port);
+            }
+        }
+        Err(EADDRINUSE)
+    }
+
+    pub fn release_port(&mut self, protocol: Protocol, port: u16) {
+        self.ports.remove(&(protocol, port));
+    }
+
+    /// Wait for the sockets to change along with the other waiters.
+    pub fn register(&mut self, waiter: &Arc<Waiter>) {
+        self.waiters.push(waiter);
+    }
+
+    /// Poll every interface, return whether any socket has changed.
+    fn poll(&mut self) -> bool {
+        let now = now();
+        let mut changed = self.loopback.poll(now);
+        if let Some(ethernet) = &mut self.ethernet {
+            changed |= ethernet.poll(now);
+        }
+        let closing = core::mem::take(&mut self.closing);
+        for socket in closing {
+            match self.get::<tcp::Socket>(socket).state() {
+                tcp::State::Closed | tcp::State::TimeWait => self.remove(socket),
+                _ => self.closing.push(socket),
+            }
+        }
+        if changed {
+            self.waiters.wake_all();
+        }
+        changed
+    }
+
+    /// Return the time in milliseconds when the interfaces have to be polled again, if any.
+    fn poll_at(&mut self) -> Option<usize> {
+        let now = now();
+        let loopback = self.loopback.iface.poll_delay(now, &self.loopback.sockets);
+        let ethernet = self
+            .ethernet
+            .as_mut()
+            .and_then(|ethernet| ethernet.iface.poll_delay(now, &ethernet.sockets));
+        let delay = match (loopback, ethernet) {
+            (Some(loopback), Some(ethernet)) => Some(loopback.min(ethernet)),
+            (delay, None) | (None, delay) => delay,
+        }?;
+        Some(get_time_ms() + delay.total_millis() as usize)
+    }
+}
+
+/// Call `f` on the stack, polled before and after.
+pub fn with_stack<T>(f: impl FnOnce(&mut Stack) -> T) -> T {
+    let mut stack = STACK.lock();
+    stack.poll();
+    let result = f(&mut stack);
+    stack.poll();
+    result
+}
+
+/// Call `f` on the stack until it returns other than `EAGAIN`, waiting for the sockets to change
+/// in between unless `nonblock`.
+pub fn block_on<T>(
+    nonblock: bool,
+    mut f: impl FnMut(&mut Stack) -> Result<T, isize>,
+) -> Result<T, isize> {
+    loop {
+        check_signals()?;
+        let mut stack = STACK.lock();
+        stack.poll();
+        let result = f(&mut stack);
+        // send what `f` has queued, which may change the sockets again
+        let changed = stack.poll();
+        match result {
+            Err(EAGAIN) if !nonblock => {}
+            result => return result,
+        }
+        if changed {
+            continue;
+        }
+        let waiter = Waiter::new(stack.poll_at());
+        stack.register(&waiter);
+        drop(stack);
+        waiter.wait();
+    }
+}
+
+/// Poll the interfaces whenever the network device interrupts.
+pub fn init() {
+    lazy_static::initialize(&STACK);
+    if let Some(device) = net_device() {
+        device.set_handler(|| {
+            STACK.lock().poll();
+        });
+    }
+}
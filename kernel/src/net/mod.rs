@@ -0,0 +1,167 @@
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::net::{Ipv4Addr, SocketAddrV4};
+
+use common::{
+    errno::{EAFNOSUPPORT, EINTR, EINVAL, EPROTONOSUPPORT},
+    socket::{
+        AF_INET, AF_UNIX, IPPROTO_TCP, IPPROTO_UDP, MsgFlags, SOCK_DGRAM, SOCK_STREAM, SockAddrIn,
+        UNIX_PATH_MAX,
+    },
+};
+
+use crate::{
+    fs::{File, OpenFlags},
+    memory::UserBuffer,
+    proc::current_proc,
+};
+
+mod iface;
+mod tcp;
+mod udp;
+mod unix;
+
+pub use self::{iface::init, tcp::TcpSocket, udp::UdpSocket, unix::UnixSocket};
+
+/// The operations of a socket beyond reading and writing it as a file.
+pub trait Socket: File {
+    fn bind(&self, addr: SocketAddr) -> Result<(), isize>;
+    /// Accept connections, at most `backlog` of them waiting to be accepted.
+    fn listen(&self, backlog: usize) -> Result<(), isize>;
+    /// Return a socket connected to the next peer and the address of the peer.
+    fn accept(&self) -> Result<(Arc<dyn File>, SocketAddr), isize>;
+    /// Connect a stream to the listener at `addr`, or set the peer of a datagram socket.
+    fn connect(&self, addr: SocketAddr) -> Result<(), isize>;
+    /// Send to the connected peer, or to `addr` for a datagram socket.
+    fn send_to(
+        &self,
+        buf: UserBuffer,
+        flags: MsgFlags,
+        addr: Option<SocketAddr>,
+    ) -> Result<usize, isize>;
+    /// Return the number of bytes received and the address of the sender.
+    fn recv_from(&self, buf: UserBuffer, flags: MsgFlags) -> Result<(usize, SocketAddr), isize>;
+}
+
+/// The kind of a socket, given by the type argument of `socket`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketType {
+    Stream,
+    Datagram,
+}
+
+impl SocketType {
+    pub fn from_raw(type_: u32) -> Option<Self> {
+        match type_ {
+            SOCK_STREAM => Some(Self::Stream),
+            SOCK_DGRAM => Some(Self::Datagram),
+            _ => None,
+        }
+    }
+}
+
+/// An address parsed from user space, of one of the supported families.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SocketAddr {
+    Unix(UnixAddr),
+    Inet(SocketAddrV4),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnixAddr {
+    /// The address of a socket which is not bound.
+    Unnamed,
+    /// The path of a socket file.
+    Path(String),
+    /// A name in the abstract namespace, without the leading NUL.
+    Abstract(Vec<u8>),
+}
+
+impl SocketAddr {
+    /// Parse the address in `bytes`, laid out as on Linux.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, isize> {
+        let Some((family, data)) = bytes.split_first_chunk::<2>() else {
+            return Err(EINVAL);
+        };
+        match u16::from_ne_bytes(*family) {
+            AF_UNIX => {
+                let data = &data[..data.len().min(UNIX_PATH_MAX)];
+                let addr = match data.split_first() {
+                    None => UnixAddr::Unnamed,
+                    Some((0, name)) => UnixAddr::Abstract(name.to_vec()),
+                    Some(_) => {
+                        let len = data.iter().position(|&byte| byte == 0);
+                        let path = &data[..len.unwrap_or(data.len())];
+                        UnixAddr::Path(String::from_utf8(path.to_vec()).map_err(|_| EINVAL)?)
+                    }
+                };
+                Ok(Self::Unix(addr))
+            }
+            AF_INET => {
+                if bytes.len() < size_of::<SockAddrIn>() {
+                    return Err(EINVAL);
+                }
+                let port = u16::from_be_bytes([data[0], data[1]]);
+                let addr = Ipv4Addr::new(data[2], data[3], data[4], data[5]);
+                Ok(Self::Inet(SocketAddrV4::new(addr, port)))
+            }
+            _ => Err(EAFNOSUPPORT),
+        }
+    }
+
+    /// Lay out the address as on Linux.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Unix(addr) => {
+                let mut bytes = AF_UNIX.to_ne_bytes().to_vec();
+                match addr {
+                    UnixAddr::Unnamed => {}
+                    UnixAddr::Path(path) => {
+                        bytes.extend_from_slice(path.as_bytes());
+                        bytes.push(0);
+                    }
+                    UnixAddr::Abstract(name) => {
+                        bytes.push(0);
+                        bytes.extend_from_slice(name);
+                    }
+                }
+                bytes
+            }
+            Self::Inet(addr) => {
+                let mut bytes = AF_INET.to_ne_bytes().to_vec();
+                bytes.extend_from_slice(&addr.port().to_be_bytes());
+                bytes.extend_from_slice(&addr.ip().octets());
+                bytes.resize(size_of::<SockAddrIn>(), 0);
+                bytes
+            }
+        }
+    }
+}
+
+/// Create a socket of `domain` and `type_`, whose status flags are those of `flags`.
+pub fn socket(
+    domain: u16,
+    type_: SocketType,
+    protocol: usize,
+    flags: OpenFlags,
+) -> Result<Arc<dyn File>, isize> {
+    match (domain, type_, protocol) {
+        (AF_UNIX, _, 0) => Ok(UnixSocket::new(type_, flags)),
+        (AF_INET, SocketType::Stream, 0 | IPPROTO_TCP) => Ok(TcpSocket::new(flags)),
+        (AF_INET, SocketType::Datagram, 0 | IPPROTO_UDP) => Ok(UdpSocket::new(flags)),
+        (AF_UNIX | AF_INET, _, _) => Err(EPROTONOSUPPORT),
+        _ => Err(EAFNOSUPPORT),
+    }
+}
+
+/// Fail with `EINTR` if unblocked signals are pending, checked before blocking again.
+fn check_signals() -> Result<(), isize> {
+    if current_proc()
+        .borrow_inner_mut()
+        .unblocked_signals()
+        .is_empty()
+    {
+        Ok(())
+    } else {
+        Err(EINTR)
+    }
+}
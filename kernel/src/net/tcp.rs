@@ -0,0 +1,469 @@
+//! TCP sockets
+//!
+//! A listening socket has `backlog` smoltcp sockets listening on each of its interfaces, a
+//! connection is accepted by taking one of them once it is established and listening with a new
+//! one in its place.
+
+use alloc::{sync::Arc, vec, vec::Vec};
+use core::net::{Ipv4Addr, SocketAddrV4};
+
+use common::{
+    errno::{
+        EAGAIN, EALREADY, ECONNREFUSED, ECONNRESET, EINPROGRESS, EINVAL, EISCONN, ENOTCONN, EPIPE,
+    },
+    fs::{FileType, Stat},
+    poll::PollEvents,
+    sig::SignalFlags,
+    socket::MsgFlags,
+};
+use smoltcp::{iface::SocketHandle, socket::tcp};
+
+use crate::{
+    fs::{File, FileStatus, OpenFlags},
+    memory::UserBuffer,
+    proc::current_proc,
+    sync::{SpinLock, Waiter},
+};
+
+use super::{
+    Socket, SocketAddr,
+    iface::{
+        IfaceId, Protocol, Stack, block_on, endpoint, listen_endpoint, socket_addr, with_stack,
+    },
+};
+
+const BUFFER_SIZE: usize = 16 * 1024;
+/// The connections waiting to be accepted on each interface at most.
+const MAX_BACKLOG: usize = 8;
+
+pub struct TcpSocket {
+    status: FileStatus,
+    /// Locked after the stack.
+    inner: SpinLock<TcpInner>,
+}
+
+struct TcpInner {
+    state: State,
+    /// The port reserved by this socket, which an accepted connection shares with its listener.
+    port: Option<u16>,
+}
+
+enum State {
+    /// Neither listening nor connected, bound to `local` if any.
+    Closed { local: Option<SocketAddrV4> },
+    Listening {
+        local: SocketAddrV4,
+        sockets: Vec<(IfaceId, SocketHandle)>,
+    },
+    /// Connecting or connected to `remote`.
+    Connected {
+        socket: (IfaceId, SocketHandle),
+        local: SocketAddrV4,
+        remote: SocketAddrV4,
+    },
+}
+
+fn new_socket() -> tcp::Socket<'static> {
+    tcp::Socket::new(
+        tcp::SocketBuffer::new(vec![0; BUFFER_SIZE]),
+        tcp::SocketBuffer::new(vec![0; BUFFER_SIZE]),
+    )
+}
+
+/// Add a socket listening on `local` to interface `id`.
+fn listen_on(stack: &mut Stack, id: IfaceId, local: SocketAddrV4) -> (IfaceId, SocketHandle) {
+    let mut socket = new_socket();
+    socket.listen(listen_endpoint(local)).unwrap();
+    (id, stack.add(id, socket))
+}
+
+impl TcpSocket {
+    /// Create a socket which is neither bound nor connected, whose status flags are those of
+    /// `flags`.
+    pub fn new(flags: OpenFlags) -> Arc<Self> {
+        Self::with_state(
+            flags,
+            TcpInner {
+                state: State::Closed { local: None },
+                port: None,
+            },
+        )
+    }
+
+    fn with_state(flags: OpenFlags, inner: TcpInner) -> Arc<Self> {
+        Arc::new(Self {
+            status: FileStatus::new(flags),
+            inner: SpinLock::new(inner),
+        })
+    }
+
+    fn nonblock(&self, flags: MsgFlags) -> bool {
+        flags.contains(MsgFlags::DONTWAIT) || self.status.get().contains(OpenFlags::NONBLOCK)
+    }
+
+    /// Reserve the port of `addr` on the interfaces having its address.
+    fn bind_locked(
+        stack: &mut Stack,
+        inner: &mut TcpInner,
+        addr: SocketAddrV4,
+    ) -> Result<(), isize> {
+        let State::Closed { local: None } = inner.state else {
+            return Err(EINVAL);
+        };
+        stack.local_ifaces(*addr.ip())?;
+        let port = stack.bind_port(Protocol::Tcp, addr.port())?;
+        inner.port = Some(port);
+        inner.state = State::Closed {
+            local: Some(SocketAddrV4::new(*addr.ip(), port)),
+        };
+        Ok(())
+    }
+
+    /// Take a connection established on one of the listening `sockets`, which listens again.
+    fn take_established(
+        stack: &mut Stack,
+        local: SocketAddrV4,
+        sockets: &mut [(IfaceId, SocketHandle)],
+    ) -> Option<(IfaceId, SocketHandle)> {
+        for listening in sockets.iter_mut() {
+            match stack.get::<tcp::Socket>(*listening).state() {
+                tcp::State::Listen | tcp::State::SynReceived => continue,
+                // reset before being accepted
+                tcp::State::Closed => {
+                    stack.remove(*listening);
+                    *listening = listen_on(stack, listening.0, local);
+                }
+                _ => {
+                    let established = *listening;
+                    *listening = listen_on(stack, established.0, local);
+                    return Some(established);
+                }
+            }
+        }
+        None
+    }
+
+    /// Wait until the connection is established, unless `nonblock`.
+    fn wait_connected(&self, nonblock: bool) -> Result<(), isize> {
+        let result = block_on(nonblock, |stack| {
+            let mut inner = self.inner.lock();
+            let State::Connected { socket, local, .. } = inner.state else {
+                return Ok(());
+            };
+            match stack.get::<tcp::Socket>(socket).state() {
+                tcp::State::SynSent => Err(EAGAIN),
+                tcp::State::Closed => {
+                    // refused, the socket stays bound to its port
+                    stack.remove(socket);
+                    inner.state = State::Closed { local: Some(local) };
+                    Err(ECONNREFUSED)
+                }
+                _ => Ok(()),
+            }
+        });
+        match result {
+            Err(EAGAIN) => Err(EINPROGRESS),
+            result => result,
+        }
+    }
+}
+
+impl File for TcpSocket {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn status(&self) -> &FileStatus {
+        &self.status
+    }
+
+    fn read(&self, buf: UserBuffer) -> Result<usize, isize> {
+        self.recv_from(buf, MsgFlags::empty()).map(|(len, _)| len)
+    }
+
+    fn write(&self, buf: UserBuffer) -> Result<usize, isize> {
+        self.send_to(buf, MsgFlags::empty(), None)
+    }
+
+    fn stat(&self) -> Stat {
+        Stat {
+            mode: FileType::Socket.mode() | 0o777,
+            nlink: 1,
+            ..Default::default()
+        }
+    }
+
+    /// A listening socket is readable when a connection is established, a connection reports a
+    /// hangup once the peer has closed it and it is fully sent.
+    fn poll(&self, waiter: Option<&Arc<Waiter>>) -> PollEvents {
+        with_stack(|stack| {
+            if let Some(waiter) = waiter {
+                stack.register(waiter);
+            }
+            let inner = self.inner.lock();
+            let mut events = PollEvents::empty();
+            match &inner.state {
+                State::Closed { .. } => events |= PollEvents::OUT | PollEvents::HUP,
+                State::Listening { sockets, .. } => {
+                    let pending = sockets.iter().any(|&socket| {
+                        !matches!(
+                            stack.get::<tcp::Socket>(socket).state(),
+                            tcp::State::Listen | tcp::State::SynReceived
+                        )
+                    });
+                    events.set(PollEvents::IN, pending);
+                }
+                State::Connected { socket, .. } => {
+                    let socket = stack.get::<tcp::Socket>(*socket);
+                    if socket.state() == tcp::State::SynSent {
+                        return events;
+                    }
+                    events.set(PollEvents::IN, socket.can_recv() || !socket.may_recv());
+                    events.set(PollEvents::OUT, socket.can_send());
+                    if !socket.may_send() {
+                        events |= PollEvents::OUT | PollEvents::ERR;
+                    }
+                    if !socket.may_recv() && !socket.may_send() {
+                        events |= PollEvents::HUP;
+                    }
+                }
+            }
+            events
+        })
+    }
+
+    fn as_socket(&self) -> Option<&dyn Socket> {
+        Some(self)
+    }
+}
+
+impl Socket for TcpSocket {
+    fn bind(&self, addr: SocketAddr) -> Result<(), isize> {
+        let SocketAddr::Inet(addr) = addr else {
+            return Err(EINVAL);
+        };
+        with_stack(|stack| Self::bind_locked(stack, &mut self.inner.lock(), addr))
+    }
+
+    fn listen(&self, backlog: usize) -> Result<(), isize> {
+        with_stack(|stack| {
+            let mut inner = self.inner.lock();
+            let local = match inner.state {
+                State::Closed { local: Some(local) } => local,
+                State::Closed { local: None } => {
+                    let any = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
+                    Self::bind_locked(stack, &mut inner, any)?;
+                    let State::Closed { local: Some(local) } = inner.state else {
+                        unreachable!();
+                    };
+                    local
+                }
+                // the backlog is not changed
+                State::Listening { .. } => return Ok(()),
+                State::Connected { .. } => return Err(EINVAL),
+            };
+            let backlog = backlog.clamp(1, MAX_BACKLOG);
+            let mut sockets = Vec::new();
+            for id in stack.local_ifaces(*local.ip())? {
+                for _ in 0..backlog {
+                    sockets.push(listen_on(stack, id, local));
+                }
+            }
+            inner.state = State::Listening { local, sockets };
+            Ok(())
+        })
+    }
+
+    fn accept(&self) -> Result<(Arc<dyn File>, SocketAddr), isize> {
+        block_on(self.nonblock(MsgFlags::empty()), |stack| {
+            let mut inner = self.inner.lock();
+            let State::Listening { local, sockets } = &mut inner.state else {
+                return Err(EINVAL);
+            };
+            let socket = Self::take_established(stack, *local, sockets).ok_or(EAGAIN)?;
+            let connection = stack.get::<tcp::Socket>(socket);
+            let local = socket_addr(connection.local_endpoint().unwrap());
+            let remote = socket_addr(connection.remote_endpoint().unwrap());
+            let accepted = Self::with_state(
+                OpenFlags::empty(),
+                TcpInner {
+                    state: State::Connected {
+                        socket,
+                        local,
+                        remote,
+                    },
+                    port: None,
+                },
+            );
+            Ok((accepted as Arc<dyn File>, SocketAddr::Inet(remote)))
+        })
+    }
+
+    fn connect(&self, addr: SocketAddr) -> Result<(), isize> {
+        let SocketAddr::Inet(mut remote) = addr else {
+            return Err(EINVAL);
+        };
+        if remote.ip().is_unspecified() {
+            // this host
+            remote.set_ip(Ipv4Addr::LOCALHOST);
+        }
+        with_stack(|stack| {
+            let mut inner = self.inner.lock();
+            let local = match inner.state {
+                State::Closed { local } => local,
+                State::Listening { .. } => return Err(EINVAL),
+                State::Connected { socket, .. } => {
+                    return match stack.get::<tcp::Socket>(socket).state() {
+                        tcp::State::SynSent => Err(EALREADY),
+                        _ => Err(EISCONN),
+                    };
+                }
+            };
+            let id = stack.route(*remote.ip())?;
+            let local = match local {
+                Some(local) => local,
+                None => {
+                    let any = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
+                    Self::bind_locked(stack, &mut inner, any)?;
+                    let State::Closed { local: Some(local) } = inner.state else {
+                        unreachable!();
+                    };
+                    local
+                }
+            };
+            let mut socket = new_socket();
+            let (cx, sockets) = stack.context(id);
+            socket
+                .connect(cx, endpoint(remote), listen_endpoint(local))
+                .map_err(|_| EINVAL)?;
+            inner.state = State::Connected {
+                socket: (id, sockets.add(socket)),
+                local,
+                remote,
+            };
+            Ok(())
+        })?;
+        self.wait_connected(self.nonblock(MsgFlags::empty()))
+    }
+
+    fn send_to(
+        &self,
+        buf: UserBuffer,
+        flags: MsgFlags,
+        addr: Option<SocketAddr>,
+    ) -> Result<usize, isize> {
+        if addr.is_some() {
+            return Err(EISCONN);
+        }
+        let nonblock = self.nonblock(flags);
+        let total = buf.len();
+        let mut sent = 0;
+        let result = block_on(nonblock, |stack| {
+            let inner = self.inner.lock();
+            let State::Connected { socket, .. } = inner.state else {
+                return Err(ENOTCONN);
+            };
+            let socket = stack.get::<tcp::Socket>(socket);
+            if socket.state() == tcp::State::SynSent {
+                return Err(EAGAIN);
+            }
+            if !socket.may_send() {
+                return Err(match socket.state() {
+                    tcp::State::Closed if sent == 0 => ECONNRESET,
+                    _ => EPIPE,
+                });
+            }
+            let mut skip = sent;
+            for segment in buf.buffer.iter() {
+                if skip >= segment.len() {
+                    skip -= segment.len();
+                    continue;
+                }
+                let rest = &segment[skip..];
+                let len = socket.send_slice(rest).unwrap();
+                skip = 0;
+                sent += len;
+                if len < rest.len() {
+                    break;
+                }
+            }
+            if sent == total || (nonblock && sent > 0) {
+                Ok(sent)
+            } else {
+                Err(EAGAIN)
+            }
+        });
+        match result {
+            Err(EPIPE) => {
+                if !flags.contains(MsgFlags::NOSIGNAL) {
+                    current_proc().send_signal(SignalFlags::SIGPIPE);
+                }
+                if sent == 0 { Err(EPIPE) } else { Ok(sent) }
+            }
+            Err(_) if sent > 0 => Ok(sent),
+            result => result,
+        }
+    }
+
+    /// Receive the bytes available, waiting for some, 0 at the end of the stream.
+    fn recv_from(
+        &self,
+        mut buf: UserBuffer,
+        flags: MsgFlags,
+    ) -> Result<(usize, SocketAddr), isize> {
+        block_on(self.nonblock(flags), |stack| {
+            let inner = self.inner.lock();
+            let State::Connected { socket, remote, .. } = inner.state else {
+                return Err(ENOTCONN);
+            };
+            let socket = stack.get::<tcp::Socket>(socket);
+            if socket.can_recv() {
+                let mut len = 0;
+                for segment in buf.iter_mut() {
+                    let received = socket.recv_slice(segment).unwrap_or(0);
+                    len += received;
+                    if received < segment.len() {
+                        break;
+                    }
+                }
+                return Ok((len, SocketAddr::Inet(remote)));
+            }
+            match socket.state() {
+                tcp::State::SynSent => Err(EAGAIN),
+                // reset by the peer
+                tcp::State::Closed if socket.remote_endpoint().is_none() => Err(ECONNRESET),
+                _ if !socket.may_recv() => Ok((0, SocketAddr::Inet(remote))),
+                _ => Err(EAGAIN),
+            }
+        })
+    }
+}
+
+impl Drop for TcpSocket {
+    fn drop(&mut self) {
+        let mut inner = self.inner.lock();
+        let state = core::mem::replace(&mut inner.state, State::Closed { local: None });
+        let port = inner.port;
+        drop(inner);
+        with_stack(|stack| {
+            match state {
+                State::Closed { .. } => {}
+                State::Listening { sockets, .. } => {
+                    // the connections not accepted are reset
+                    for socket in sockets {
+                        stack.get::<tcp::Socket>(socket).abort();
+                        stack.close_tcp(socket);
+                    }
+                }
+                State::Connected { socket, .. } => stack.close_tcp(socket),
+            }
+            if let Some(port) = port {
+                stack.release_port(Protocol::Tcp, port);
+            }
+        });
+    }
+}
@@ -0,0 +1,266 @@
+//! UDP sockets
+
+use alloc::{sync::Arc, vec, vec::Vec};
+use core::net::{Ipv4Addr, SocketAddrV4};
+
+use common::{
+    errno::{EAGAIN, EDESTADDRREQ, EINVAL, EMSGSIZE, ENETUNREACH, EOPNOTSUPP},
+    fs::{FileType, Stat},
+    poll::PollEvents,
+    socket::MsgFlags,
+};
+use smoltcp::{iface::SocketHandle, socket::udp};
+
+use crate::{
+    fs::{File, FileStatus, OpenFlags},
+    memory::UserBuffer,
+    sync::{SpinLock, Waiter},
+};
+
+use super::{
+    Socket, SocketAddr,
+    iface::{
+        IfaceId, Protocol, Stack, block_on, endpoint, listen_endpoint, socket_addr, with_stack,
+    },
+};
+
+/// Size of the IPv4 and UDP headers before the payload.
+const HEADER_SIZE: usize = 28;
+const BUFFER_SIZE: usize = 16 * 1024;
+/// The datagrams queued to each direction at most.
+const MAX_DATAGRAMS: usize = 16;
+
+pub struct UdpSocket {
+    status: FileStatus,
+    /// Locked after the stack.
+    inner: SpinLock<UdpInner>,
+}
+
+#[derive(Default)]
+struct UdpInner {
+    local: Option<SocketAddrV4>,
+    /// The sockets bound on the interfaces of `local`.
+    sockets: Vec<(IfaceId, SocketHandle)>,
+    /// The default destination, and the only source received from.
+    peer: Option<SocketAddrV4>,
+}
+
+fn new_socket() -> udp::Socket<'static> {
+    let buffer = || {
+        udp::PacketBuffer::new(
+            vec![udp::PacketMetadata::EMPTY; MAX_DATAGRAMS],
+            vec![0; BUFFER_SIZE],
+        )
+    };
+    udp::Socket::new(buffer(), buffer())
+}
+
+/// Return `addr`, or this host if it is unspecified.
+fn destination(addr: SocketAddrV4) -> SocketAddrV4 {
+    if addr.ip().is_unspecified() {
+        SocketAddrV4::new(Ipv4Addr::LOCALHOST, addr.port())
+    } else {
+        addr
+    }
+}
+
+impl UdpInner {
+    /// Bind to `addr` on the interfaces having its address.
+    fn bind(&mut self, stack: &mut Stack, addr: SocketAddrV4) -> Result<(), isize> {
+        if self.local.is_some() {
+            return Err(EINVAL);
+        }
+        let ifaces = stack.local_ifaces(*addr.ip())?;
+        let port = stack.bind_port(Protocol::Udp, addr.port())?;
+        let local = SocketAddrV4::new(*addr.ip(), port);
+        for id in ifaces {
+            let mut socket = new_socket();
+            socket.bind(listen_endpoint(local)).unwrap();
+            self.sockets.push((id, stack.add(id, socket)));
+        }
+        self.local = Some(local);
+        Ok(())
+    }
+
+    /// Bind to an ephemeral port of any address unless bound.
+    fn bind_any(&mut self, stack: &mut Stack) -> Result<(), isize> {
+        if self.local.is_none() {
+            self.bind(stack, SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0))?;
+        }
+        Ok(())
+    }
+}
+
+impl UdpSocket {
+    /// Create a socket which is not bound, whose status flags are those of `flags`.
+    pub fn new(flags: OpenFlags) -> Arc<Self> {
+        Arc::new(Self {
+            status: FileStatus::new(flags),
+            inner: SpinLock::new(UdpInner::default()),
+        })
+    }
+
+    fn nonblock(&self, flags: MsgFlags) -> bool {
+        flags.contains(MsgFlags::DONTWAIT) || self.status.get().contains(OpenFlags::NONBLOCK)
+    }
+}
+
+impl File for UdpSocket {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn status(&self) -> &FileStatus {
+        &self.status
+    }
+
+    fn read(&self, buf: UserBuffer) -> Result<usize, isize> {
+        self.recv_from(buf, MsgFlags::empty()).map(|(len, _)| len)
+    }
+
+    fn write(&self, buf: UserBuffer) -> Result<usize, isize> {
+        self.send_to(buf, MsgFlags::empty(), None)
+    }
+
+    fn stat(&self) -> Stat {
+        Stat {
+            mode: FileType::Socket.mode() | 0o777,
+            nlink: 1,
+            ..Default::default()
+        }
+    }
+
+    fn poll(&self, waiter: Option<&Arc<Waiter>>) -> PollEvents {
+        with_stack(|stack| {
+            if let Some(waiter) = waiter {
+                stack.register(waiter);
+            }
+            let inner = self.inner.lock();
+            let mut events = PollEvents::empty();
+            // an unbound socket is bound by sending
+            let (mut readable, mut writable) = (false, inner.sockets.is_empty());
+            for &socket in inner.sockets.iter() {
+                let socket = stack.get::<udp::Socket>(socket);
+                readable |= socket.can_recv();
+                writable |= socket.can_send();
+            }
+            events.set(PollEvents::IN, readable);
+            events.set(PollEvents::OUT, writable);
+            events
+        })
+    }
+
+    fn as_socket(&self) -> Option<&dyn Socket> {
+        Some(self)
+    }
+}
+
+impl Socket for UdpSocket {
+    fn bind(&self, addr: SocketAddr) -> Result<(), isize> {
+        let SocketAddr::Inet(addr) = addr else {
+            return Err(EINVAL);
+        };
+        with_stack(|stack| self.inner.lock().bind(stack, addr))
+    }
+
+    fn listen(&self, _backlog: usize) -> Result<(), isize> {
+        Err(EOPNOTSUPP)
+    }
+
+    fn accept(&self) -> Result<(Arc<dyn File>, SocketAddr), isize> {
+        Err(EOPNOTSUPP)
+    }
+
+    /// Set the default destination, and only receive from it.
+    fn connect(&self, addr: SocketAddr) -> Result<(), isize> {
+        let SocketAddr::Inet(addr) = addr else {
+            return Err(EINVAL);
+        };
+        let peer = destination(addr);
+        with_stack(|stack| {
+            stack.route(*peer.ip())?;
+            let mut inner = self.inner.lock();
+            inner.bind_any(stack)?;
+            inner.peer = Some(peer);
+            Ok(())
+        })
+    }
+
+    fn send_to(
+        &self,
+        buf: UserBuffer,
+        flags: MsgFlags,
+        addr: Option<SocketAddr>,
+    ) -> Result<usize, isize> {
+        let peer = match addr {
+            Some(SocketAddr::Inet(addr)) => Some(destination(addr)),
+            Some(_) => return Err(EINVAL),
+            None => None,
+        };
+        let datagram = buf.to_vec();
+        block_on(self.nonblock(flags), |stack| {
+            let mut inner = self.inner.lock();
+            let peer = peer.or(inner.peer).ok_or(EDESTADDRREQ)?;
+            inner.bind_any(stack)?;
+            let id = stack.route(*peer.ip())?;
+            if datagram.len() + HEADER_SIZE > stack.ip_mtu(id) {
+                return Err(EMSGSIZE);
+            }
+            // none if bound to an address of another interface
+            let &socket = inner
+                .sockets
+                .iter()
+                .find(|(socket_id, _)| *socket_id == id)
+                .ok_or(ENETUNREACH)?;
+            let socket = stack.get::<udp::Socket>(socket);
+            if datagram.len() > socket.payload_send_capacity() {
+                return Err(EMSGSIZE);
+            }
+            match socket.send_slice(&datagram, endpoint(peer)) {
+                Ok(()) => Ok(datagram.len()),
+                Err(udp::SendError::BufferFull) => Err(EAGAIN),
+                Err(udp::SendError::Unaddressable) => Err(ENETUNREACH),
+            }
+        })
+    }
+
+    /// Receive a datagram, truncated to `buf`, with the address of its sender.
+    fn recv_from(
+        &self,
+        mut buf: UserBuffer,
+        flags: MsgFlags,
+    ) -> Result<(usize, SocketAddr), isize> {
+        block_on(self.nonblock(flags), |stack| {
+            let inner = self.inner.lock();
+            for &socket in inner.sockets.iter() {
+                let socket = stack.get::<udp::Socket>(socket);
+                while let Ok((datagram, meta)) = socket.recv() {
+                    let sender = socket_addr(meta.endpoint);
+                    if inner.peer.is_some_and(|peer| peer != sender) {
+                        continue;
+                    }
+                    return Ok((buf.write(datagram), SocketAddr::Inet(sender)));
+                }
+            }
+            Err(EAGAIN)
+        })
+    }
+}
+
+impl Drop for UdpSocket {
+    fn drop(&mut self) {
+        let inner = core::mem::take(&mut *self.inner.lock());
+        with_stack(|stack| {
+            for socket in inner.sockets {
+                stack.remove(socket);
+            }
+            if let Some(local) = inner.local {
+                stack.release_port(Protocol::Udp, local.port());
+            }
+        });
+    }
+}
@@ -0,0 +1,649 @@
+//! Local sockets, bound to a socket file or to a name in the abstract namespace
+
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use common::{
+    errno::{
+        EADDRINUSE, EAGAIN, ECONNREFUSED, EDESTADDRREQ, EEXIST, EINVAL, EISCONN, EMSGSIZE,
+        ENOTCONN, EOPNOTSUPP, EPIPE, EPROTOTYPE,
+    },
+    fs::{FileType, Stat},
+    poll::PollEvents,
+    sig::SignalFlags,
+    socket::MsgFlags,
+};
+use lazy_static::lazy_static;
+
+use crate::{
+    fs::{File, FileStatus, OpenFlags, create, lookup},
+    memory::UserBuffer,
+    proc::current_proc,
+    sync::{SpinLock, WaitQueue, Waiter},
+};
+
+use super::{Socket, SocketAddr, SocketType, UnixAddr, check_signals};
+
+/// The bytes queued to a socket at most.
+const BUFFER_SIZE: usize = 64 * 1024;
+/// The connections waiting to be accepted at most, whatever the backlog asked for.
+const MAX_BACKLOG: usize = 128;
+
+pub struct UnixSocket {
+    type_: SocketType,
+    status: FileStatus,
+    /// Registered in `BOUND` once bound.
+    this: Weak<UnixSocket>,
+    inner: SpinLock<SocketInner>,
+    /// The data received, locked after `inner` and never with the queue of another socket.
+    queue: Arc<SharedQueue>,
+}
+
+struct SocketInner {
+    name: UnixAddr,
+    state: State,
+}
+
+enum State {
+    Unconnected,
+    /// The sockets connected by peers waiting to be accepted, with the names of the peers.
+    Listening {
+        pending: VecDeque<(Arc<UnixSocket>, UnixAddr)>,
+        backlog: usize,
+    },
+    /// A stream connected to the peer receiving to `peer`, or a datagram socket sending to
+    /// `peer` by default.
+    Connected {
+        peer: Arc<SharedQueue>,
+        peer_name: UnixAddr,
+    },
+}
+
+/// The data received by a socket.
+struct Queue {
+    data: Data,
+    /// Number of bytes queued.
+    len: usize,
+    /// The peer of a stream is closed, so no more data comes.
+    peer_closed: bool,
+    /// The socket is closed, so the data is never read.
+    closed: bool,
+    /// Threads waiting for data, or for a connection to accept.
+    readers: WaitQueue,
+    /// Threads waiting for space, or for room in the backlog.
+    writers: WaitQueue,
+}
+
+enum Data {
+    Stream(VecDeque<u8>),
+    /// The datagrams with the names of their senders.
+    Datagram(VecDeque<(Vec<u8>, UnixAddr)>),
+}
+
+type SharedQueue = SpinLock<Queue>;
+
+/// The key of a bound name, the socket file or the abstract name.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum BoundKey {
+    File(u64, u64),
+    Abstract(Vec<u8>),
+}
+
+lazy_static! {
+    /// The bound sockets by name, locked before any socket.
+    static ref BOUND: SpinLock<BTreeMap<BoundKey, Weak<UnixSocket>>> =
+        SpinLock::new(BTreeMap::new());
+}
+
+/// Return the key of `addr`, creating its socket file if `bind`. The filesystem is accessed
+/// without any lock held.
+fn key_of(addr: &UnixAddr, bind: bool) -> Result<BoundKey, isize> {
+    match addr {
+        UnixAddr::Unnamed => Err(EINVAL),
+        UnixAddr::Path(path) => {
+            let dentry = if bind {
+                create(path, FileType::Socket).map_err(|errno| match errno {
+                    EEXIST => EADDRINUSE,
+                    errno => errno,
+                })?
+            } else {
+                lookup(path)?
+            };
+            if dentry.inode.file_type() != FileType::Socket {
+                return Err(ECONNREFUSED);
+            }
+            let stat = dentry.inode.stat();
+            Ok(BoundKey::File(stat.dev, stat.ino))
+        }
+        UnixAddr::Abstract(name) => Ok(BoundKey::Abstract(name.clone())),
+    }
+}
+
+/// Return the socket bound to `addr`.
+fn find(addr: &UnixAddr) -> Result<Arc<UnixSocket>, isize> {
+    let key = key_of(addr, false)?;
+    let bound = BOUND.lock();
+    bound.get(&key).and_then(Weak::upgrade).ok_or(ECONNREFUSED)
+}
+
+impl Queue {
+    fn new(type_: SocketType) -> Self {
+        Self {
+            data: match type_ {
+                SocketType::Stream => Data::Stream(VecDeque::new()),
+                SocketType::Datagram => Data::Datagram(VecDeque::new()),
+            },
+            len: 0,
+            peer_closed: false,
+            closed: false,
+            readers: WaitQueue::new(),
+            writers: WaitQueue::new(),
+        }
+    }
+
+    fn free(&self) -> usize {
+        BUFFER_SIZE - self.len
+    }
+
+    /// Queue the bytes of `buf` which fit, return their number.
+    fn push_bytes(&mut self, buf: &[u8]) -> usize {
+        let len = buf.len().min(self.free());
+        let Data::Stream(bytes) = &mut self.data else {
+            unreachable!("bytes sent to a datagram socket");
+        };
+        bytes.extend(&buf[..len]);
+        self.len += len;
+        len
+    }
+
+    /// Move the queued bytes to `buf` until it is full, return their number.
+    fn pop_bytes(&mut self, buf: &mut [u8]) -> usize {
+        let Data::Stream(bytes) = &mut self.data else {
+            unreachable!("bytes received by a datagram socket");
+        };
+        let len = buf.len().min(bytes.len());
+        for (dst, src) in buf.iter_mut().zip(bytes.drain(..len)) {
+            *dst = src;
+        }
+        self.len -= len;
+        len
+    }
+
+    fn push_datagram(&mut self, datagram: Vec<u8>, sender: UnixAddr) {
+        let Data::Datagram(datagrams) = &mut self.data else {
+            unreachable!("datagram sent to a stream");
+        };
+        self.len += datagram.len();
+        datagrams.push_back((datagram, sender));
+    }
+
+    fn pop_datagram(&mut self) -> Option<(Vec<u8>, UnixAddr)> {
+        let Data::Datagram(datagrams) = &mut self.data else {
+            unreachable!("datagram received by a stream");
+        };
+        let (datagram, sender) = datagrams.pop_front()?;
+        self.len -= datagram.len();
+        Some((datagram, sender))
+    }
+}
+
+impl UnixSocket {
+    fn with_state(type_: SocketType, flags: OpenFlags, name: UnixAddr, state: State) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            type_,
+            status: FileStatus::new(flags),
+            this: this.clone(),
+            inner: SpinLock::new(SocketInner { name, state }),
+            queue: Arc::new(SpinLock::new(Queue::new(type_))),
+        })
+    }
+
+    /// Create a socket which is neither bound nor connected, whose status flags are those of
+    /// `flags`.
+    pub fn new(type_: SocketType, flags: OpenFlags) -> Arc<Self> {
+        Self::with_state(type_, flags, UnixAddr::Unnamed, State::Unconnected)
+    }
+
+    /// Create two sockets connected to each other.
+    pub fn pair(type_: SocketType, flags: OpenFlags) -> (Arc<Self>, Arc<Self>) {
+        let first = Self::new(type_, flags);
+        let second = Self::with_state(
+            type_,
+            flags,
+            UnixAddr::Unnamed,
+            State::Connected {
+                peer: first.queue.clone(),
+                peer_name: UnixAddr::Unnamed,
+            },
+        );
+        first.inner.lock().state = State::Connected {
+            peer: second.queue.clone(),
+            peer_name: UnixAddr::Unnamed,
+        };
+        (first, second)
+    }
+
+    /// Queue a connection to `listener`, whose name is `addr`.
+    fn connect_stream(&self, listener: &UnixSocket, addr: UnixAddr) -> Result<(), isize> {
+        let nonblock = self.status.get().contains(OpenFlags::NONBLOCK);
+        loop {
+            check_signals()?;
+            // a connecting socket is locked before the listener
+            let mut inner = self.inner.lock();
+            match inner.state {
+                State::Unconnected => {}
+                State::Listening { .. } => return Err(EINVAL),
+                State::Connected { .. } => return Err(EISCONN),
+            }
+            let mut listener_inner = listener.inner.lock();
+            let SocketInner {
+                name: listener_name,
+                state,
+            } = &mut *listener_inner;
+            let State::Listening { pending, backlog } = state else {
+                return Err(ECONNREFUSED);
+            };
+            if pending.len() >= *backlog {
+                if nonblock {
+                    return Err(EAGAIN);
+                }
+                let waiter = Waiter::new(None);
+                listener.queue.lock().writers.push(&waiter);
+                drop(listener_inner);
+                drop(inner);
+                waiter.wait();
+                continue;
+            }
+            let server = Self::with_state(
+                SocketType::Stream,
+                OpenFlags::empty(),
+                listener_name.clone(),
+                State::Connected {
+                    peer: self.queue.clone(),
+                    peer_name: inner.name.clone(),
+                },
+            );
+            inner.state = State::Connected {
+                peer: server.queue.clone(),
+                peer_name: addr,
+            };
+            pending.push_back((server, inner.name.clone()));
+            listener.queue.lock().readers.wake_all();
+            return Ok(());
+        }
+    }
+
+    /// Send the bytes of `buf` to the queue `peer` of the peer.
+    fn send_stream(
+        &self,
+        peer: &SharedQueue,
+        buf: UserBuffer,
+        flags: MsgFlags,
+    ) -> Result<usize, isize> {
+        let nonblock =
+            flags.contains(MsgFlags::DONTWAIT) || self.status.get().contains(OpenFlags::NONBLOCK);
+        let total = buf.len();
+        let mut segments = buf.buffer.into_iter();
+        let mut segment: &[u8] = &[];
+        let mut sent = 0;
+        loop {
+            if sent == total {
+                return Ok(sent);
+            }
+            if let Err(errno) = check_signals() {
+                return if sent == 0 { Err(errno) } else { Ok(sent) };
+            }
+            let mut queue = peer.lock();
+            if queue.closed {
+                drop(queue);
+                if !flags.contains(MsgFlags::NOSIGNAL) {
+                    current_proc().send_signal(SignalFlags::SIGPIPE);
+                }
+                return if sent == 0 { Err(EPIPE) } else { Ok(sent) };
+            }
+            if queue.free() == 0 {
+                if nonblock {
+                    return if sent == 0 { Err(EAGAIN) } else { Ok(sent) };
+                }
+                let waiter = Waiter::new(None);
+                queue.writers.push(&waiter);
+                drop(queue);
+                waiter.wait();
+                continue;
+            }
+            while queue.free() > 0 {
+                if segment.is_empty() {
+                    match segments.next() {
+                        Some(next) => segment = next,
+                        None => break,
+                    }
+                    continue;
+                }
+                let len = queue.push_bytes(segment);
+                segment = &segment[len..];
+                sent += len;
+            }
+            queue.readers.wake_all();
+        }
+    }
+
+    /// Send `datagram` as a whole to the queue `peer` of the receiver.
+    fn send_datagram(
+        &self,
+        peer: &SharedQueue,
+        datagram: Vec<u8>,
+        flags: MsgFlags,
+    ) -> Result<usize, isize> {
+        if datagram.len() > BUFFER_SIZE {
+            return Err(EMSGSIZE);
+        }
+        let nonblock =
+            flags.contains(MsgFlags::DONTWAIT) || self.status.get().contains(OpenFlags::NONBLOCK);
+        let sender = self.inner.lock().name.clone();
+        loop {
+            check_signals()?;
+            let mut queue = peer.lock();
+            if queue.closed {
+                return Err(ECONNREFUSED);
+            }
+            if queue.free() >= datagram.len() {
+                let len = datagram.len();
+                queue.push_datagram(datagram, sender);
+                queue.readers.wake_all();
+                return Ok(len);
+            }
+            if nonblock {
+                return Err(EAGAIN);
+            }
+            let waiter = Waiter::new(None);
+            queue.writers.push(&waiter);
+            drop(queue);
+            waiter.wait();
+        }
+    }
+}
+
+impl File for UnixSocket {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn status(&self) -> &FileStatus {
+        &self.status
+    }
+
+    fn read(&self, buf: UserBuffer) -> Result<usize, isize> {
+        self.recv_from(buf, MsgFlags::empty()).map(|(len, _)| len)
+    }
+
+    fn write(&self, buf: UserBuffer) -> Result<usize, isize> {
+        self.send_to(buf, MsgFlags::empty(), None)
+    }
+
+    fn stat(&self) -> Stat {
+        Stat {
+            mode: FileType::Socket.mode() | 0o777,
+            nlink: 1,
+            ..Default::default()
+        }
+    }
+
+    /// A listening socket is readable when a connection is pending, a stream reports a hangup
+    /// once its peer is closed.
+    fn poll(&self, waiter: Option<&Arc<Waiter>>) -> PollEvents {
+        let inner = self.inner.lock();
+        let mut events = PollEvents::empty();
+        let mut queue = self.queue.lock();
+        if let Some(waiter) = waiter {
+            queue.readers.push(waiter);
+        }
+        let peer = match &inner.state {
+            State::Listening { pending, .. } => {
+                events.set(PollEvents::IN, !pending.is_empty());
+                return events;
+            }
+            State::Unconnected if self.type_ == SocketType::Stream => return events,
+            State::Unconnected => None,
+            State::Connected { peer, .. } => Some(peer),
+        };
+        events.set(PollEvents::IN, queue.len > 0);
+        if self.type_ == SocketType::Stream && queue.peer_closed {
+            events |= PollEvents::IN | PollEvents::HUP;
+        }
+        drop(queue);
+        let Some(peer) = peer else {
+            // an unconnected datagram socket sends to any address
+            events |= PollEvents::OUT;
+            return events;
+        };
+        let mut queue = peer.lock();
+        if let Some(waiter) = waiter {
+            queue.writers.push(waiter);
+        }
+        if queue.closed {
+            // writing fails right away
+            events |= PollEvents::OUT | PollEvents::ERR;
+        } else if queue.free() > 0 {
+            events |= PollEvents::OUT;
+        }
+        events
+    }
+
+    fn as_socket(&self) -> Option<&dyn Socket> {
+        Some(self)
+    }
+}
+
+impl Socket for UnixSocket {
+    fn bind(&self, addr: SocketAddr) -> Result<(), isize> {
+        let SocketAddr::Unix(addr) = addr else {
+            return Err(EINVAL);
+        };
+        if self.inner.lock().name != UnixAddr::Unnamed {
+            return Err(EINVAL);
+        }
+        let key = key_of(&addr, true)?;
+        let mut bound = BOUND.lock();
+        bound.retain(|_, socket| socket.strong_count() > 0);
+        if bound.contains_key(&key) {
+            return Err(EADDRINUSE);
+        }
+        let mut inner = self.inner.lock();
+        if inner.name != UnixAddr::Unnamed {
+            return Err(EINVAL);
+        }
+        inner.name = addr;
+        bound.insert(key, self.this.clone());
+        Ok(())
+    }
+
+    fn listen(&self, backlog: usize) -> Result<(), isize> {
+        if self.type_ != SocketType::Stream {
+            return Err(EOPNOTSUPP);
+        }
+        let mut inner = self.inner.lock();
+        if inner.name == UnixAddr::Unnamed {
+            return Err(EINVAL);
+        }
+        let backlog = backlog.clamp(1, MAX_BACKLOG);
+        match &mut inner.state {
+            State::Unconnected => {
+                inner.state = State::Listening {
+                    pending: VecDeque::new(),
+                    backlog,
+                };
+            }
+            State::Listening {
+                backlog: current, ..
+            } => *current = backlog,
+            State::Connected { .. } => return Err(EINVAL),
+        }
+        // more connections may fit
+        self.queue.lock().writers.wake_all();
+        Ok(())
+    }
+
+    fn accept(&self) -> Result<(Arc<dyn File>, SocketAddr), isize> {
+        if self.type_ != SocketType::Stream {
+            return Err(EOPNOTSUPP);
+        }
+        let nonblock = self.status.get().contains(OpenFlags::NONBLOCK);
+        loop {
+            check_signals()?;
+            let mut inner = self.inner.lock();
+            let State::Listening { pending, .. } = &mut inner.state else {
+                return Err(EINVAL);
+            };
+            let mut queue = self.queue.lock();
+            if let Some((socket, peer_name)) = pending.pop_front() {
+                queue.writers.wake_all();
+                return Ok((socket, SocketAddr::Unix(peer_name)));
+            }
+            if nonblock {
+                return Err(EAGAIN);
+            }
+            let waiter = Waiter::new(None);
+            queue.readers.push(&waiter);
+            drop(queue);
+            drop(inner);
+            waiter.wait();
+        }
+    }
+
+    fn connect(&self, addr: SocketAddr) -> Result<(), isize> {
+        let SocketAddr::Unix(addr) = addr else {
+            return Err(EINVAL);
+        };
+        let target = find(&addr)?;
+        if target.type_ != self.type_ {
+            return Err(EPROTOTYPE);
+        }
+        match self.type_ {
+            SocketType::Stream => self.connect_stream(&target, addr),
+            SocketType::Datagram => {
+                self.inner.lock().state = State::Connected {
+                    peer: target.queue.clone(),
+                    peer_name: addr,
+                };
+                Ok(())
+            }
+        }
+    }
+
+    fn send_to(
+        &self,
+        buf: UserBuffer,
+        flags: MsgFlags,
+        addr: Option<SocketAddr>,
+    ) -> Result<usize, isize> {
+        let connected_peer = match &self.inner.lock().state {
+            State::Connected { peer, .. } => Some(peer.clone()),
+            _ => None,
+        };
+        match self.type_ {
+            SocketType::Stream => {
+                let peer = match (connected_peer, addr) {
+                    (Some(_), Some(_)) => return Err(EISCONN),
+                    (Some(peer), None) => peer,
+                    (None, _) => return Err(ENOTCONN),
+                };
+                self.send_stream(&peer, buf, flags)
+            }
+            SocketType::Datagram => {
+                let peer = match addr {
+                    Some(SocketAddr::Unix(addr)) => {
+                        let target = find(&addr)?;
+                        if target.type_ != SocketType::Datagram {
+                            return Err(EPROTOTYPE);
+                        }
+                        target.queue.clone()
+                    }
+                    Some(_) => return Err(EINVAL),
+                    None => connected_peer.ok_or(EDESTADDRREQ)?,
+                };
+                self.send_datagram(&peer, buf.to_vec(), flags)
+            }
+        }
+    }
+
+    /// A stream receives the bytes available, waiting for some, a datagram socket receives a
+    /// datagram, truncated to `buf`.
+    fn recv_from(
+        &self,
+        mut buf: UserBuffer,
+        flags: MsgFlags,
+    ) -> Result<(usize, SocketAddr), isize> {
+        let nonblock =
+            flags.contains(MsgFlags::DONTWAIT) || self.status.get().contains(OpenFlags::NONBLOCK);
+        let stream_peer_name = match &self.inner.lock().state {
+            State::Connected { peer_name, .. } => Some(peer_name.clone()),
+            _ => None,
+        };
+        if self.type_ == SocketType::Stream && stream_peer_name.is_none() {
+            return Err(ENOTCONN);
+        }
+        loop {
+            check_signals()?;
+            let mut queue = self.queue.lock();
+            match self.type_ {
+                SocketType::Stream if queue.len > 0 => {
+                    let mut len = 0;
+                    for segment in buf.iter_mut() {
+                        let popped = queue.pop_bytes(segment);
+                        len += popped;
+                        if popped < segment.len() {
+                            break;
+                        }
+                    }
+                    queue.writers.wake_all();
+                    let peer_name = stream_peer_name.unwrap();
+                    return Ok((len, SocketAddr::Unix(peer_name)));
+                }
+                SocketType::Stream if queue.peer_closed => {
+                    // EOF
+                    let peer_name = stream_peer_name.unwrap();
+                    return Ok((0, SocketAddr::Unix(peer_name)));
+                }
+                SocketType::Datagram => {
+                    if let Some((datagram, sender)) = queue.pop_datagram() {
+                        queue.writers.wake_all();
+                        return Ok((buf.write(&datagram), SocketAddr::Unix(sender)));
+                    }
+                }
+                SocketType::Stream => {}
+            }
+            if nonblock {
+                return Err(EAGAIN);
+            }
+            let waiter = Waiter::new(None);
+            queue.readers.push(&waiter);
+            drop(queue);
+            waiter.wait();
+        }
+    }
+}
+
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        let mut queue = self.queue.lock();
+        queue.closed = true;
+        // the writers fail
+        queue.writers.wake_all();
+        drop(queue);
+        if self.type_ != SocketType::Stream {
+            return;
+        }
+        if let State::Connected { peer, .. } = &self.inner.lock().state {
+            let mut queue = peer.lock();
+            queue.peer_closed = true;
+            // the peer sees the EOF
+            queue.readers.wake_all();
+        }
+    }
+}
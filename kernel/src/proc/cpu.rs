@@ -1,87 +1,183 @@
-use alloc::sync::Arc;
-use lazy_static::lazy_static;
+use core::cell::UnsafeCell;
 
-use crate::sync::UPSafeCell;
+use alloc::sync::Arc;
+use riscv::{asm::wfi, register::sstatus};
 
-use super::{PROC_MANAGER, ProcContext, ProcControlBlock, ProcStatus, switch};
+use crate::{config::MAX_HART_NUM, smp::hart_id};
 
-lazy_static! {
-    /// A global instance of the CPU.
-    static ref CPU: UPSafeCell<Cpu> = unsafe { UPSafeCell::new(Cpu::new()) };
-}
+use super::{
+    PROC_MANAGER, ProcControlBlock, ThreadContext, ThreadControlBlock, ThreadStatus, switch,
+};
 
+/// Per-hart state.
 struct Cpu {
-    current: Option<Arc<ProcControlBlock>>,
-    scheduler_ctx: ProcContext,
+    current: Option<Arc<ThreadControlBlock>>,
+    scheduler_ctx: ThreadContext,
+    /// Depth of nested `push_off`.
+    off_depth: usize,
+    /// Whether interrupts were enabled before the outermost `push_off`.
+    interrupt_enabled: bool,
 }
 
 impl Cpu {
-    fn new() -> Self {
+    const fn new() -> Self {
         Self {
             current: None,
-            scheduler_ctx: ProcContext::zero_init(),
+            scheduler_ctx: ThreadContext::zero_init(),
+            off_depth: 0,
+            interrupt_enabled: false,
         }
     }
+}
 
-    fn current(&self) -> Option<Arc<ProcControlBlock>> {
-        self.current.clone()
-    }
+struct Cpus([UnsafeCell<Cpu>; MAX_HART_NUM]);
 
-    fn take_current(&mut self) -> Option<Arc<ProcControlBlock>> {
-        self.current.take()
-    }
+// Each hart only accesses its own `Cpu`.
+unsafe impl Sync for Cpus {}
+
+static CPUS: Cpus = Cpus([const { UnsafeCell::new(Cpu::new()) }; MAX_HART_NUM]);
+
+/// Run `f` with the `Cpu` of the current hart, `f` must not call `with_cpu` again.
+fn with_cpu<R>(f: impl FnOnce(&mut Cpu) -> R) -> R {
+    // nothing else on this hart can access the `Cpu` during `f`
+    f(unsafe { &mut *CPUS.0[hart_id()].get() })
+}
 
-    fn set_current(&mut self, proc: Arc<ProcControlBlock>) {
-        self.current = Some(proc);
+/// Disable interrupts of the current hart, calls can be nested and each must be matched by
+/// a `pop_off`.
+pub fn push_off() {
+    let enabled = sstatus::read().sie();
+    unsafe {
+        sstatus::clear_sie();
     }
+    with_cpu(|cpu| {
+        if cpu.off_depth == 0 {
+            cpu.interrupt_enabled = enabled;
+        }
+        cpu.off_depth += 1;
+    });
+}
 
-    fn get_scheduler_ctx_ptr(&mut self) -> *mut ProcContext {
-        &mut self.scheduler_ctx as *mut _
+/// Undo a `push_off`, interrupts are enabled again by the outermost one if they were enabled.
+pub fn pop_off() {
+    let enable = with_cpu(|cpu| {
+        assert!(cpu.off_depth > 0, "pop_off without push_off");
+        cpu.off_depth -= 1;
+        cpu.off_depth == 0 && cpu.interrupt_enabled
+    });
+    if enable {
+        unsafe {
+            sstatus::set_sie();
+        }
     }
 }
 
-pub fn current_proc() -> Arc<ProcControlBlock> {
-    CPU.borrow_mut().current().expect("No current process")
+pub fn current_thread() -> Arc<ThreadControlBlock> {
+    try_current_thread().expect("No current thread")
+}
+
+/// Return the current thread, or `None` if the hart is running the scheduler.
+pub fn try_current_thread() -> Option<Arc<ThreadControlBlock>> {
+    with_cpu(|cpu| cpu.current.clone())
+}
+
+pub fn take_current_thread() -> Arc<ThreadControlBlock> {
+    with_cpu(|cpu| cpu.current.take()).expect("No current thread")
 }
 
-pub fn take_current_proc() -> Arc<ProcControlBlock> {
-    CPU.borrow_mut().take_current().expect("No current process")
+pub fn current_proc() -> Arc<ProcControlBlock> {
+    current_thread().proc()
 }
 
 pub fn current_token() -> usize {
-    current_proc().borrow_inner_mut().get_token()
+    current_thread().get_user_token()
 }
+
 pub fn current_trap_frame_mut() -> &'static mut crate::trap::TrapFrame {
-    current_proc().borrow_inner_mut().get_trap_frame_mut()
+    current_thread().borrow_inner_mut().get_trap_frame_mut()
+}
+
+pub fn current_trap_frame_user_va() -> usize {
+    current_thread().borrow_inner_mut().trap_frame_user_va()
+}
+
+/// Wait until an interrupt arrives, which may have made some threads ready to run.
+fn idle() {
+    debug_assert_eq!(
+        with_cpu(|cpu| cpu.off_depth),
+        0,
+        "Idle with a spin lock held"
+    );
+    // an interrupt taken before `wfi` only delays the idle hart until the next tick
+    unsafe {
+        sstatus::set_sie();
+        wfi();
+        sstatus::clear_sie();
+    }
 }
 
 pub fn run() -> ! {
     loop {
-        let mut processor = CPU.borrow_mut();
-        let proc_opt = PROC_MANAGER.borrow_mut().pop();
-        if let Some(proc) = proc_opt {
-            let scheduler_ctx = processor.get_scheduler_ctx_ptr();
-            let mut proc_inner = proc.borrow_inner_mut();
-            let proc_ctx = &proc_inner.ctx as *const _;
-            proc_inner.status = ProcStatus::Running;
-            drop(proc_inner);
-            // release coming task TCB manually
-            processor.set_current(proc);
-            // release processor manually
-            drop(processor);
-            unsafe {
-                switch(scheduler_ctx, proc_ctx);
-            }
+        let Some(thread) = PROC_MANAGER.lock().pop() else {
+            idle();
+            continue;
+        };
+        // the thread may be still switching out on the hart where it ran before
+        while thread.is_on_cpu() {
+            core::hint::spin_loop();
         }
+        let mut thread_inner = thread.borrow_inner_mut();
+        if thread_inner.res.is_none() {
+            // the process of this thread has exited
+            continue;
+        }
+        let thread_ctx = &thread_inner.ctx as *const _;
+        thread_inner.status = ThreadStatus::Running;
+        thread.set_on_cpu(true);
+        drop(thread_inner);
+        let scheduler_ctx = with_cpu(|cpu| {
+            debug_assert_eq!(cpu.off_depth, 0, "Switch with a spin lock held");
+            cpu.current = Some(Arc::clone(&thread));
+            &mut cpu.scheduler_ctx as *mut _
+        });
+        unsafe {
+            switch(scheduler_ctx, thread_ctx);
+        }
+        // the context of the thread has been saved, now it can run on other harts
+        thread.set_on_cpu(false);
     }
 }
 
-/// Switch the current process to the scheduler process.
-pub fn schedule(switched_proc_ctx_ptr: *mut ProcContext) {
-    // switched_proc_ctx_ptr is passed from outside, because generally,
-    // proc data has been retrieved from outside, avoiding additional operations
-    let scheduler_ctx_ptr = CPU.borrow_mut().get_scheduler_ctx_ptr();
+/// Switch the current thread to the scheduler.
+pub fn schedule(switched_thread_ctx_ptr: *mut ThreadContext) {
+    // switched_thread_ctx_ptr is passed from outside, because generally,
+    // thread data has been retrieved from outside, avoiding additional operations
+    let scheduler_ctx_ptr = with_cpu(|cpu| &mut cpu.scheduler_ctx as *mut _);
     unsafe {
-        switch(switched_proc_ctx_ptr, scheduler_ctx_ptr);
+        switch(switched_thread_ctx_ptr, scheduler_ctx_ptr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::timer::{MSEC_PER_SEC, TICKS_PER_SEC, get_time_ms};
+
+    use super::idle;
+
+    #[test_case]
+    fn test_idle() {
+        const IDLE_MS: usize = 3000;
+        let start = get_time_ms();
+        let mut wakeups = 0;
+        while get_time_ms() - start < IDLE_MS {
+            idle();
+            wakeups += 1;
+        }
+        // a busy-spinning hart would come back millions of times
+        let ticks = IDLE_MS * TICKS_PER_SEC / MSEC_PER_SEC;
+        assert!(
+            wakeups <= 2 * ticks,
+            "{wakeups} wakeups in {IDLE_MS} ms, expected about {ticks}"
+        );
     }
 }
@@ -1,9 +1,9 @@
-//! Implementation of [`ProcContext`]
+//! Implementation of [`ThreadContext`]
 use crate::trap::trap_return;
 
 #[repr(C)]
-/// task context structure containing some registers
-pub struct ProcContext {
+/// thread context structure containing some registers
+pub struct ThreadContext {
     /// return address ( e.g. __restore ) of __switch ASM function
     ra: usize,
     /// kernel stack pointer of app
@@ -12,9 +12,9 @@ pub struct ProcContext {
     s: [usize; 12],
 }
 
-impl ProcContext {
+impl ThreadContext {
     /// init task context
-    pub fn zero_init() -> Self {
+    pub const fn zero_init() -> Self {
         Self {
             ra: 0,
             sp: 0,
@@ -1,49 +1,52 @@
+use lazy_static::lazy_static;
+
 use crate::{
     config::{KERNEL_STACK_SIZE, PAGE_SIZE, TRAMPOLINE},
     memory::{KERNEL_SPACE, MapPermission, VirtAddr},
+    sync::SpinLock,
 };
 
-use super::pid::PidTracker;
+use super::pid::RecycleAllocator;
+
+lazy_static! {
+    static ref KSTACK_ALLOCATOR: SpinLock<RecycleAllocator> =
+        SpinLock::new(RecycleAllocator::new());
+}
 
 /// Return (bottom, top) of a kernel stack in kernel space.
-pub fn kernel_stack_position(app_id: usize) -> (usize, usize) {
-    let top = TRAMPOLINE - app_id * (KERNEL_STACK_SIZE + PAGE_SIZE);
+pub fn kernel_stack_position(kstack_id: usize) -> (usize, usize) {
+    let top = TRAMPOLINE - kstack_id * (KERNEL_STACK_SIZE + PAGE_SIZE);
     let bottom = top - KERNEL_STACK_SIZE;
     (bottom, top)
 }
 
-pub struct KernelStack {
-    // pid: usize,
-    kernel_stack_top: usize,
-    kernel_stack_bottom: usize,
-}
+/// Kernel stack of a thread, identified by an id independent of pid and tid.
+pub struct KernelStack(usize);
 
 impl KernelStack {
-    pub fn new(pid: &PidTracker) -> Self {
-        let pid = pid.0;
-        let (kernel_stack_bottom, kernel_stack_top) = kernel_stack_position(pid);
-        KERNEL_SPACE.borrow_mut().insert_framed_area(
+    pub fn new() -> Self {
+        let kstack_id = KSTACK_ALLOCATOR.lock().alloc();
+        let (kernel_stack_bottom, kernel_stack_top) = kernel_stack_position(kstack_id);
+        KERNEL_SPACE.lock().insert_framed_area(
             VirtAddr::new(kernel_stack_bottom),
             VirtAddr::new(kernel_stack_top),
             MapPermission::R | MapPermission::W,
         );
-        Self {
-            // pid,
-            kernel_stack_top,
-            kernel_stack_bottom,
-        }
+        Self(kstack_id)
     }
 
     pub fn get_top(&self) -> usize {
-        self.kernel_stack_top
+        let (_, kernel_stack_top) = kernel_stack_position(self.0);
+        kernel_stack_top
     }
 }
 
 impl Drop for KernelStack {
     fn drop(&mut self) {
-        let kernel_stack_bottom_va = VirtAddr::new(self.kernel_stack_bottom);
+        let (kernel_stack_bottom, _) = kernel_stack_position(self.0);
         KERNEL_SPACE
-            .borrow_mut()
-            .remove_area_with_start_vpn(kernel_stack_bottom_va.page_number());
+            .lock()
+            .remove_area_with_start_vpn(VirtAddr::new(kernel_stack_bottom).page_number());
+        KSTACK_ALLOCATOR.lock().dealloc(self.0);
     }
 }
@@ -1,60 +1,163 @@
-use alloc::{collections::vec_deque::VecDeque, sync::Arc};
+use core::hint::spin_loop;
+
+use alloc::{
+    collections::vec_deque::VecDeque,
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
 use lazy_static::lazy_static;
 use log::{info, trace};
 
 use crate::{
     fs::{OpenFlags, open_file},
     sbi::shutdown,
-    sync::UPSafeCell,
+    smp::send_ipi_to_others,
+    sync::SpinLock,
 };
 
-use super::{ProcControlBlock, ProcStatus, schedule, take_current_proc};
+use super::{ProcControlBlock, ThreadControlBlock, ThreadStatus, schedule, take_current_thread};
 
 lazy_static! {
     /// A global instance of the process manager.
-    pub static ref PROC_MANAGER: UPSafeCell<ProcManager> = unsafe { UPSafeCell::new(ProcManager::new()) };
+    pub static ref PROC_MANAGER: SpinLock<ProcManager> = SpinLock::new(ProcManager::new());
     /// A global instance of the init process control block.``
-    pub static ref INIT_PROC: Arc<ProcControlBlock> = Arc::new({
+    pub static ref INIT_PROC: Arc<ProcControlBlock> = {
         let inode = open_file("init", OpenFlags::RDONLY).unwrap();
-        ProcControlBlock::new(inode.read_all())
-    });
+        ProcControlBlock::new(inode.read_all(), Vec::from([String::from("init")]))
+    };
 
 }
 
 const INIT_PROC_PID: usize = 0;
 
+/// Holds the threads that are ready to run.
 pub struct ProcManager {
-    procs: VecDeque<Arc<ProcControlBlock>>,
+    threads: VecDeque<Arc<ThreadControlBlock>>,
 }
 
 impl ProcManager {
     fn new() -> Self {
         Self {
-            procs: VecDeque::from([INIT_PROC.clone()]),
+            threads: VecDeque::from([INIT_PROC.borrow_inner_mut().get_thread(0)]),
         }
     }
 
-    pub fn push(&mut self, proc: Arc<ProcControlBlock>) {
-        self.procs.push_back(proc);
+    pub fn push(&mut self, thread: Arc<ThreadControlBlock>) {
+        self.threads.push_back(thread);
+    }
+
+    pub fn pop(&mut self) -> Option<Arc<ThreadControlBlock>> {
+        self.threads.pop_front()
     }
 
-    pub fn pop(&mut self) -> Option<Arc<ProcControlBlock>> {
-        self.procs.pop_front()
+    /// Remove all threads of `proc` from the ready queue, except the uninterruptible ones.
+    fn remove_proc(&mut self, proc: &Arc<ProcControlBlock>) {
+        self.threads
+            .retain(|thread| !thread.belongs_to(proc) || thread.is_uninterruptible());
+    }
+}
+
+/// Return the processes which have not been waited for, by pid.
+pub fn processes() -> Vec<Arc<ProcControlBlock>> {
+    let mut procs = Vec::from([Arc::clone(&INIT_PROC)]);
+    let mut i = 0;
+    while i < procs.len() {
+        let children = procs[i].borrow_inner_mut().children.clone();
+        procs.extend(children);
+        i += 1;
     }
+    // a process may be seen twice if its parent exits meanwhile
+    procs.sort_by_key(|proc| proc.pid());
+    procs.dedup_by_key(|proc| proc.pid());
+    procs
 }
 
 pub fn suspend_current_and_run_next() {
-    let proc = take_current_proc();
-    let mut inner = proc.borrow_inner_mut();
-    inner.status = ProcStatus::Ready;
+    let thread = take_current_thread();
+    let mut inner = thread.borrow_inner_mut();
+    inner.status = ThreadStatus::Ready;
+    let ctx = &mut inner.ctx as *mut _;
+    drop(inner);
+    PROC_MANAGER.lock().push(thread);
+    schedule(ctx);
+}
+
+/// Let other threads run before going on with `thread`, which has been taken from the
+/// current hart.
+fn yield_thread(thread: &Arc<ThreadControlBlock>) {
+    let mut inner = thread.borrow_inner_mut();
+    inner.status = ThreadStatus::Ready;
     let ctx = &mut inner.ctx as *mut _;
     drop(inner);
-    PROC_MANAGER.borrow_mut().push(proc);
+    PROC_MANAGER.lock().push(Arc::clone(thread));
     schedule(ctx);
+    // `run` has made it the current thread again
+    take_current_thread();
 }
 
+/// Block the current thread until it is woken up by `wakeup_thread`.
+///
+/// The caller must have saved the current thread in some wait queue.
+pub fn block_current_and_run_next() {
+    let thread = take_current_thread();
+    let mut inner = thread.borrow_inner_mut();
+    inner.status = ThreadStatus::Blocked;
+    let ctx = &mut inner.ctx as *mut _;
+    drop(inner);
+    drop(thread);
+    schedule(ctx);
+}
+
+/// Make a blocked thread ready to run again.
+pub fn wakeup_thread(thread: Arc<ThreadControlBlock>) {
+    thread.borrow_inner_mut().status = ThreadStatus::Ready;
+    PROC_MANAGER.lock().push(thread);
+}
+
+/// Exit the current thread. The process exits as well if it is the main thread.
 pub fn exit_current_and_run_next(exit_code: i32) {
-    let proc = take_current_proc();
+    let thread = take_current_thread();
+    let tid = thread.tid();
+    if tid == 0 {
+        exit_proc_and_run_next(thread, exit_code);
+        return;
+    }
+    trace!(
+        "Thread {tid} of process {} exits with exit code {exit_code}",
+        thread.proc().pid()
+    );
+    // the thread keeps its tid and user stack until it is joined by `sys_waittid`
+    let mut inner = thread.borrow_inner_mut();
+    inner.exit_code = Some(exit_code);
+    let ctx = &mut inner.ctx as *mut _;
+    drop(inner);
+    drop(thread);
+    schedule(ctx);
+}
+
+/// Stop the current thread as its process has been exited by a thread on another hart, which
+/// releases the resources of the thread.
+pub fn stop_current_and_run_next() {
+    stop_thread_and_run_next(take_current_thread());
+}
+
+fn stop_thread_and_run_next(thread: Arc<ThreadControlBlock>) {
+    let mut thread_inner = thread.borrow_inner_mut();
+    let ctx = &mut thread_inner.ctx as *mut _;
+    drop(thread_inner);
+    drop(thread);
+    schedule(ctx);
+}
+
+/// Exit all threads of the current process.
+pub fn exit_group_and_run_next(exit_code: i32) {
+    let thread = take_current_thread();
+    exit_proc_and_run_next(thread, exit_code);
+}
+
+fn exit_proc_and_run_next(thread: Arc<ThreadControlBlock>, exit_code: i32) {
+    let proc = thread.proc();
     let pid = proc.pid();
     trace!("Process {pid} exits with exit code {exit_code}");
 
@@ -69,18 +172,92 @@ pub fn exit_current_and_run_next(exit_code: i32) {
 
     // update process data
     let mut inner = proc.borrow_inner_mut();
-    inner.status = ProcStatus::Zombie;
+    if inner.is_zombie {
+        // another thread of the process is exiting it on another hart
+        drop(inner);
+        drop(proc);
+        stop_thread_and_run_next(thread);
+        return;
+    }
+    inner.is_zombie = true;
     inner.exit_code = exit_code;
+    let parent = inner.parent.as_ref().and_then(Weak::upgrade);
+    let children = core::mem::take(&mut inner.children);
+    let mut threads: Vec<_> = inner
+        .threads
+        .iter()
+        .flatten()
+        .filter(|t| !Arc::ptr_eq(t, &thread))
+        .cloned()
+        .collect();
+    drop(inner);
+
+    // other threads of the process will never be scheduled again
+    PROC_MANAGER.lock().remove_proc(&proc);
 
     // Move child processes to init process
-    INIT_PROC.extend_children(inner.children.drain(..));
-    debug_assert!(inner.children.is_empty());
+    INIT_PROC.extend_children(children.into_iter());
+
+    // Take the user resources of all threads, they must be released before the memory space.
+    // Threads running on other harts are interrupted and stop once they see the process exited,
+    // and uninterruptible ones are left to finish their work in the kernel first.
+    let mut user_res = Vec::new();
+    let mut ipi_sent = false;
+    loop {
+        threads.retain(|t| {
+            let mut t_inner = t.borrow_inner_mut();
+            if t.is_on_cpu() || t.is_uninterruptible() {
+                return true;
+            }
+            t_inner.exit_code.get_or_insert(exit_code);
+            user_res.extend(t_inner.res.take());
+            false
+        });
+        if threads.is_empty() {
+            break;
+        }
+        if !ipi_sent {
+            send_ipi_to_others();
+            ipi_sent = true;
+        }
+        if threads.iter().any(|t| !t.is_on_cpu()) {
+            // the uninterruptible threads may need this hart to run
+            yield_thread(&thread);
+        } else {
+            spin_loop();
+        }
+    }
+    let mut thread_inner = thread.borrow_inner_mut();
+    thread_inner.exit_code.get_or_insert(exit_code);
+    user_res.extend(thread_inner.res.take());
+    drop(thread_inner);
+    // Keep the exiting thread only, we are still running on its kernel stack
+    let mut inner = proc.borrow_inner_mut();
+    for slot in inner.threads.iter_mut() {
+        if slot.as_ref().is_some_and(|t| !Arc::ptr_eq(t, &thread)) {
+            slot.take();
+        }
+    }
+    drop(inner);
+    drop(user_res);
 
     // Clear the memory space of the process, excluding the page table
     // TODO: should we free the page table?
+    let mut inner = proc.borrow_inner_mut();
     inner.memory_space.clear();
-    let ctx = &mut inner.ctx as *mut _;
+    inner.fd_table.clear();
     drop(inner);
     drop(proc);
+
+    // the parent waiting for us, and init as some of the moved children may be zombies already
+    if let Some(parent) = parent {
+        parent.notify_child_exit();
+    }
+    INIT_PROC.notify_child_exit();
+
+    let mut thread_inner = thread.borrow_inner_mut();
+    let ctx = &mut thread_inner.ctx as *mut _;
+    drop(thread_inner);
+    drop(thread);
     schedule(ctx);
 }
@@ -5,18 +5,24 @@ mod manager;
 mod pcb;
 mod pid;
 mod switch;
+mod tcb;
 
 use crate::fs::list_apps;
 
 pub use self::cpu::{
-    current_proc, current_token, current_trap_frame_mut, run, schedule, take_current_proc,
+    current_proc, current_thread, current_token, current_trap_frame_mut,
+    current_trap_frame_user_va, pop_off, push_off, run, schedule, take_current_thread,
+    try_current_thread,
 };
-pub use self::ctx::ProcContext;
+pub use self::ctx::ThreadContext;
 pub use self::manager::{
-    INIT_PROC, PROC_MANAGER, exit_current_and_run_next, suspend_current_and_run_next,
+    INIT_PROC, PROC_MANAGER, block_current_and_run_next, exit_current_and_run_next,
+    exit_group_and_run_next, processes, stop_current_and_run_next, suspend_current_and_run_next,
+    wakeup_thread,
 };
-pub use self::pcb::{ProcControlBlock, ProcStatus};
+pub use self::pcb::ProcControlBlock;
 pub use self::switch::switch;
+pub use self::tcb::{ThreadControlBlock, ThreadStatus, Uninterruptible};
 
 pub fn init() {
     list_apps();
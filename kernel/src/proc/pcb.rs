@@ -1,107 +1,141 @@
-use core::cell::RefMut;
-
 use alloc::{
     string::String,
     sync::{Arc, Weak},
-    vec,
     vec::Vec,
 };
 
+use common::{errno::EINVAL, sig::SignalFlags};
+
 use crate::{
-    config::TRAP_FRAME,
-    fs::{File, Stdin, Stdout},
-    memory::{KERNEL_SPACE, MemorySpace, PageTable, PhysPageNum, VirtAddr},
+    fs::{File, FileDescriptor},
+    memory::{KERNEL_SPACE, MemorySpace, PageTable},
     proc::INIT_PROC,
-    sync::UPSafeCell,
+    sync::{Condvar, DeadlockDetector, Mutex, Semaphore, SpinLock, SpinLockGuard, WaitQueue},
     trap::{TrapFrame, trap_handler},
 };
 
 use super::{
-    ProcContext,
-    kernel_stack::KernelStack,
-    pid::{PID_ALLOCATOR, PidTracker},
+    ThreadControlBlock,
+    pid::{PidTracker, RecycleAllocator},
+    wakeup_thread,
 };
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum ProcStatus {
-    Ready,
-    Running,
-    // Waiting,
-    Zombie,
-    // Terminated,
-}
-
 pub struct ProcControlBlock {
     pid: PidTracker,
-    kernel_stack: KernelStack,
-    inner: UPSafeCell<ProcControlBlockInner>,
+    inner: SpinLock<ProcControlBlockInner>,
 }
 
 pub struct ProcControlBlockInner {
-    pub ctx: ProcContext,
-    pub status: ProcStatus,
+    pub is_zombie: bool,
     pub memory_space: MemorySpace,
-    pub trap_frame_ppn: PhysPageNum,
-    #[allow(dead_code)]
-    pub base_size: usize,
     pub exit_code: i32,
+    /// The arguments of the program run by the process.
+    pub cmdline: Vec<String>,
 
     pub parent: Option<Weak<ProcControlBlock>>, // TODO: remove Option?
     pub children: Vec<Arc<ProcControlBlock>>,
-    pub fd_table: Vec<Option<Arc<dyn File + Send + Sync>>>,
+    /// Threads blocked in `sys_waitpid` until a child process exits.
+    pub child_exit_waiters: Vec<Arc<ThreadControlBlock>>,
+    pub fd_table: Vec<Option<FileDescriptor>>,
+    /// Threads of the process indexed by tid, the main thread has tid 0.
+    pub threads: Vec<Option<Arc<ThreadControlBlock>>>,
+    tid_allocator: RecycleAllocator,
+    pub mutex_list: Vec<Option<Arc<dyn Mutex>>>,
+    pub semaphore_list: Vec<Option<Arc<Semaphore>>>,
+    pub condvar_list: Vec<Option<Arc<Condvar>>>,
+    pub deadlock_detector: DeadlockDetector,
+    /// Pending signals, which terminate the process when it is about to return to user space
+    /// unless blocked.
+    pub signals: SignalFlags,
+    /// Signals which stay pending until read from a signalfd, set by `rt_sigprocmask`.
+    pub blocked: SignalFlags,
+    /// Woken up when a signal is sent, which are the blocking waits of the threads and the
+    /// readers of signalfds.
+    pub signal_waiters: WaitQueue,
 }
 
 impl ProcControlBlock {
-    pub fn new(elf_data: impl AsRef<[u8]>) -> Self {
-        let (memory_space, user_sp, entry_point) = MemorySpace::from_elf(elf_data);
-        let trap_frame_ppn = memory_space
-            .translate(VirtAddr::new(TRAP_FRAME).page_number())
-            .unwrap()
-            .ppn();
-        let status = ProcStatus::Ready;
-        let pid = PID_ALLOCATOR.borrow_mut().alloc();
-        let kernel_stack = KernelStack::new(&pid);
-        let kernel_stack_top = kernel_stack.get_top();
-        let pcb = Self {
-            pid,
-            kernel_stack,
-            inner: unsafe {
-                UPSafeCell::new(ProcControlBlockInner {
-                    status,
-                    ctx: ProcContext::goto_trap_return(kernel_stack_top),
-                    memory_space,
-                    trap_frame_ppn,
-                    base_size: user_sp,
-                    exit_code: 0,
-                    parent: None,
-                    children: Vec::new(),
-                    fd_table: vec![
-                        Some(Arc::new(Stdin)),
-                        Some(Arc::new(Stdout)),
-                        Some(Arc::new(Stdout)),
-                    ],
-                })
-            },
-        };
-        let trap_frame = pcb.inner.borrow_mut().get_trap_frame_mut();
-        *trap_frame = TrapFrame::new(
+    pub fn new(elf_data: impl AsRef<[u8]>, args: Vec<String>) -> Arc<Self> {
+        let (memory_space, ustack_base, entry_point) = MemorySpace::from_elf(elf_data);
+        let pcb = Arc::new(Self {
+            pid: PidTracker::new(),
+            inner: SpinLock::new(ProcControlBlockInner {
+                is_zombie: false,
+                memory_space,
+                exit_code: 0,
+                cmdline: args,
+                parent: None,
+                children: Vec::new(),
+                child_exit_waiters: Vec::new(),
+                // opened by the process
+                fd_table: Vec::new(),
+                threads: Vec::new(),
+                tid_allocator: RecycleAllocator::new(),
+                mutex_list: Vec::new(),
+                semaphore_list: Vec::new(),
+                condvar_list: Vec::new(),
+                deadlock_detector: DeadlockDetector::new(),
+                signals: SignalFlags::empty(),
+                blocked: SignalFlags::empty(),
+                signal_waiters: WaitQueue::new(),
+            }),
+        });
+        // create the main thread
+        let thread = Arc::new(ThreadControlBlock::new(&pcb, ustack_base, true));
+        let thread_inner = thread.borrow_inner_mut();
+        let ustack_top = thread_inner.res.as_ref().unwrap().ustack_top();
+        *thread_inner.get_trap_frame_mut() = TrapFrame::new(
             entry_point,
-            user_sp,
-            KERNEL_SPACE.borrow_mut().token(),
-            kernel_stack_top,
+            ustack_top,
+            KERNEL_SPACE.lock().token(),
+            thread.kernel_stack_top(),
             trap_handler as usize, // physical address of trap handler
         );
+        drop(thread_inner);
+        pcb.borrow_inner_mut().threads.push(Some(thread));
         pcb
     }
 
+    /// Replace the program of the process, which fails with `EINVAL` while threads other than
+    /// the calling one are running.
     pub fn exec(&self, elf_data: impl AsRef<[u8]>, args: Vec<String>) -> isize {
-        let (memory_space, mut user_sp, entry_point) = MemorySpace::from_elf(elf_data);
-        let trap_frame_ppn = memory_space
-            .translate(VirtAddr::new(TRAP_FRAME).page_number())
-            .unwrap()
-            .ppn();
+        let mut inner = self.borrow_inner_mut();
+        if inner.running_thread_count() > 1 {
+            return -EINVAL;
+        }
+        // the exited threads which have not been joined are released with the old memory space
+        let exited: Vec<_> = inner
+            .threads
+            .iter_mut()
+            .skip(1)
+            .filter_map(Option::take)
+            .collect();
+        drop(inner);
+        drop(exited);
+        let (memory_space, ustack_base, entry_point) = MemorySpace::from_elf(elf_data);
+        let mut inner = self.borrow_inner_mut();
+        inner.memory_space = memory_space;
+        inner.cmdline = args.clone();
+        for fd in inner.fd_table.iter_mut() {
+            if fd.as_ref().is_some_and(|fd| fd.cloexec) {
+                fd.take();
+            }
+        }
+        drop(inner);
+
+        // the user resources of the main thread now live in the new memory space
+        let thread = self.borrow_inner_mut().get_thread(0);
+        let mut thread_inner = thread.borrow_inner_mut();
+        let res = thread_inner.res.as_mut().unwrap();
+        res.ustack_base = ustack_base;
+        res.alloc_user_res();
+        let mut user_sp = res.ustack_top();
+        let trap_frame_ppn = res.trap_frame_ppn();
+        thread_inner.trap_frame_ppn = trap_frame_ppn;
 
         // Push arguments onto the user stack [arg0_ptr, arg1_ptr, ..., argN_ptr, 0]
+        let inner = self.borrow_inner_mut();
+        let memory_space = &inner.memory_space;
         user_sp -= (args.len() + 1) * core::mem::size_of::<usize>();
         let argv_base = user_sp;
         let mut argv = (0..=args.len())
@@ -121,25 +155,21 @@ impl ProcControlBlock {
         // align user stack pointer to 8 bytes
         user_sp -= user_sp % core::mem::size_of::<usize>();
 
-        let mut inner = self.inner.borrow_mut();
-        inner.memory_space = memory_space;
-        inner.trap_frame_ppn = trap_frame_ppn;
-        inner.base_size = user_sp;
         let mut tf = TrapFrame::new(
             entry_point,
             user_sp,
-            KERNEL_SPACE.borrow_mut().token(),
-            self.kernel_stack.get_top(),
+            KERNEL_SPACE.lock().token(),
+            thread.kernel_stack_top(),
             trap_handler as usize, // physical address of trap handler
         );
         // only set a1, a0 will be set by syscall return value
         tf.x[11] = argv_base; // a1 = argv
-        *inner.get_trap_frame_mut() = tf;
+        *thread_inner.get_trap_frame_mut() = tf;
         args.len() as isize
     }
 
-    pub fn borrow_inner_mut(&self) -> RefMut<'_, ProcControlBlockInner> {
-        self.inner.borrow_mut()
+    pub fn borrow_inner_mut(&self) -> SpinLockGuard<'_, ProcControlBlockInner> {
+        self.inner.lock()
     }
 
     pub fn pid(&self) -> usize {
@@ -148,73 +178,140 @@ impl ProcControlBlock {
 
     pub fn extend_children(&self, children: impl Iterator<Item = Arc<ProcControlBlock>>) {
         assert_eq!(self.pid.0, 0, "Only init process can extend children");
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.inner.lock();
         for child in children {
             child.borrow_inner_mut().parent = Some(Arc::downgrade(&INIT_PROC));
             inner.children.push(child);
         }
     }
 
-    pub fn fork(self: &Arc<Self>) -> Arc<Self> {
+    pub fn send_signal(&self, signal: SignalFlags) {
+        let mut inner = self.inner.lock();
+        inner.signals.insert(signal);
+        inner.signal_waiters.wake_all();
+    }
+
+    /// Wake up the threads waiting for a child process to exit.
+    pub fn notify_child_exit(&self) {
+        let waiters = core::mem::take(&mut self.inner.lock().child_exit_waiters);
+        for thread in waiters {
+            wakeup_thread(thread);
+        }
+    }
+
+    /// Fork the process, only the calling thread is copied. It fails with `EINVAL` while
+    /// threads other than the calling one are running.
+    pub fn fork(self: &Arc<Self>) -> Result<Arc<Self>, isize> {
         let mut parent_inner = self.borrow_inner_mut();
-        let child_space = parent_inner.memory_space.clone();
-        let trap_frame_ppn = child_space
-            .translate(VirtAddr::new(TRAP_FRAME).page_number())
-            .unwrap()
-            .ppn();
-        let child_pid = PID_ALLOCATOR.borrow_mut().alloc();
-        let kernel_stack = KernelStack::new(&child_pid);
-        let kernel_stack_top = kernel_stack.get_top();
+        if parent_inner.running_thread_count() > 1 {
+            return Err(EINVAL);
+        }
+        let mut child_space = parent_inner.memory_space.clone();
+        // the exited threads which have not been joined stay with the parent
+        for thread in parent_inner.threads.iter().skip(1).flatten() {
+            if let Some(res) = thread.borrow_inner_mut().res.as_ref() {
+                res.unmap_user_res(&mut child_space);
+            }
+        }
         let child_pcb = Arc::new(Self {
-            pid: child_pid,
-            kernel_stack,
-            inner: unsafe {
-                UPSafeCell::new(ProcControlBlockInner {
-                    status: ProcStatus::Ready,
-                    ctx: ProcContext::goto_trap_return(kernel_stack_top),
-                    memory_space: child_space,
-                    trap_frame_ppn,
-                    base_size: parent_inner.base_size,
-                    exit_code: 0,
-                    parent: Some(Arc::downgrade(self)),
-                    children: Vec::new(),
-                    fd_table: parent_inner.fd_table.clone(),
-                })
-            },
+            pid: PidTracker::new(),
+            inner: SpinLock::new(ProcControlBlockInner {
+                is_zombie: false,
+                memory_space: child_space,
+                exit_code: 0,
+                cmdline: parent_inner.cmdline.clone(),
+                parent: Some(Arc::downgrade(self)),
+                children: Vec::new(),
+                child_exit_waiters: Vec::new(),
+                fd_table: parent_inner.fd_table.clone(),
+                threads: Vec::new(),
+                tid_allocator: RecycleAllocator::new(),
+                mutex_list: Vec::new(),
+                semaphore_list: Vec::new(),
+                condvar_list: Vec::new(),
+                deadlock_detector: DeadlockDetector::new(),
+                signals: SignalFlags::empty(),
+                blocked: parent_inner.blocked,
+                signal_waiters: WaitQueue::new(),
+            }),
         });
         parent_inner.children.push(Arc::clone(&child_pcb));
+        let ustack_base = parent_inner
+            .get_thread(0)
+            .borrow_inner_mut()
+            .res
+            .as_ref()
+            .unwrap()
+            .ustack_base;
+        drop(parent_inner);
 
-        let trap_frame = child_pcb.borrow_inner_mut().get_trap_frame_mut();
-        trap_frame.kernel_sp = kernel_stack_top;
+        // the user stack and trap frame have been copied with the memory space
+        let thread = Arc::new(ThreadControlBlock::new(&child_pcb, ustack_base, false));
+        let trap_frame = thread.borrow_inner_mut().get_trap_frame_mut();
+        trap_frame.kernel_sp = thread.kernel_stack_top();
+        child_pcb.borrow_inner_mut().threads.push(Some(thread));
 
-        child_pcb
+        Ok(child_pcb)
     }
 
     // get the readonly page table of this process
     pub fn page_table(&self) -> PageTable {
-        PageTable::from_token(self.inner.borrow_mut().get_token())
+        PageTable::from_token(self.inner.lock().get_token())
     }
 }
 
 impl ProcControlBlockInner {
-    pub fn get_token(&self) -> usize {
-        self.memory_space.token()
+    /// The pending signals which are not blocked, which interrupt blocking syscalls.
+    pub fn unblocked_signals(&self) -> SignalFlags {
+        self.signals - self.blocked
     }
 
-    pub fn get_trap_frame_mut(&self) -> &'static mut TrapFrame {
-        self.trap_frame_ppn.get_mut()
+    pub fn get_token(&self) -> usize {
+        self.memory_space.token()
     }
 
     pub fn is_zombie(&self) -> bool {
-        self.status == ProcStatus::Zombie
+        self.is_zombie
     }
 
     pub fn alloc_fd(&mut self) -> usize {
-        if let Some(fd) = (0..self.fd_table.len()).find(|fd| self.fd_table[*fd].is_none()) {
+        self.alloc_fd_from(0)
+    }
+
+    /// Return the lowest free fd not less than `min`.
+    pub fn alloc_fd_from(&mut self, min: usize) -> usize {
+        if let Some(fd) = (min..self.fd_table.len()).find(|fd| self.fd_table[*fd].is_none()) {
             fd
         } else {
-            self.fd_table.push(None);
-            self.fd_table.len() - 1
+            let fd = self.fd_table.len().max(min);
+            self.fd_table.resize(fd + 1, None);
+            fd
         }
     }
+
+    /// Return the open file of `fd`.
+    pub fn get_file(&self, fd: usize) -> Option<Arc<dyn File>> {
+        Some(self.fd_table.get(fd)?.as_ref()?.file.clone())
+    }
+
+    pub fn alloc_tid(&mut self) -> usize {
+        self.tid_allocator.alloc()
+    }
+
+    pub fn dealloc_tid(&mut self, tid: usize) {
+        self.tid_allocator.dealloc(tid)
+    }
+
+    /// The number of threads which have not exited.
+    pub fn running_thread_count(&self) -> usize {
+        self.threads
+            .iter()
+            .flatten()
+            .filter(|t| t.borrow_inner_mut().exit_code.is_none())
+            .count()
+    }
+
+    pub fn get_thread(&self, tid: usize) -> Arc<ThreadControlBlock> {
+        self.threads[tid].as_ref().unwrap().clone()
+    }
 }
@@ -1,46 +1,53 @@
 use alloc::vec::Vec;
 
-use crate::sync::UPSafeCell;
+use crate::sync::SpinLock;
 use lazy_static::lazy_static;
 lazy_static! {
-    pub static ref PID_ALLOCATOR: UPSafeCell<PidAllocator> =
-        unsafe { UPSafeCell::new(PidAllocator::new()) };
+    pub static ref PID_ALLOCATOR: SpinLock<RecycleAllocator> =
+        SpinLock::new(RecycleAllocator::new());
 }
 
-pub struct PidAllocator {
-    next_pid: usize,
+/// Allocates small integer ids (pids, tids, kernel stack ids), reusing freed ones first.
+pub struct RecycleAllocator {
+    next_id: usize,
     recycled: Vec<usize>,
 }
 
-impl PidAllocator {
+impl RecycleAllocator {
     pub fn new() -> Self {
         Self {
-            next_pid: 0,
+            next_id: 0,
             recycled: Vec::new(),
         }
     }
 
-    pub fn alloc(&mut self) -> PidTracker {
-        if let Some(pid) = self.recycled.pop() {
-            PidTracker(pid)
+    pub fn alloc(&mut self) -> usize {
+        if let Some(id) = self.recycled.pop() {
+            id
         } else {
-            let pid = self.next_pid;
-            self.next_pid += 1;
-            PidTracker(pid)
+            let id = self.next_id;
+            self.next_id += 1;
+            id
         }
     }
 
-    pub fn free(&mut self, pid: usize) {
-        if pid > 0 && !self.recycled.contains(&pid) {
-            self.recycled.push(pid);
-        }
+    pub fn dealloc(&mut self, id: usize) {
+        assert!(id < self.next_id, "id {id} has not been allocated");
+        assert!(!self.recycled.contains(&id), "id {id} has been deallocated");
+        self.recycled.push(id);
     }
 }
 
 pub struct PidTracker(pub usize);
 
+impl PidTracker {
+    pub fn new() -> Self {
+        Self(PID_ALLOCATOR.lock().alloc())
+    }
+}
+
 impl Drop for PidTracker {
     fn drop(&mut self) {
-        PID_ALLOCATOR.borrow_mut().free(self.0);
+        PID_ALLOCATOR.lock().dealloc(self.0);
     }
 }
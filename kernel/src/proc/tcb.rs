@@ -0,0 +1,231 @@
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use alloc::sync::{Arc, Weak};
+
+use crate::{
+    config::{PAGE_SIZE, TRAP_FRAME, USER_STACK_SIZE},
+    memory::{MapPermission, MemorySpace, PhysPageNum, VirtAddr},
+    sync::{SpinLock, SpinLockGuard},
+    trap::TrapFrame,
+};
+
+use super::{ProcControlBlock, ThreadContext, kernel_stack::KernelStack, try_current_thread};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadStatus {
+    Ready,
+    Running,
+    Blocked,
+}
+
+pub struct ThreadControlBlock {
+    proc: Weak<ProcControlBlock>,
+    kernel_stack: KernelStack,
+    /// Whether the thread is running on a hart or being switched out.
+    on_cpu: AtomicBool,
+    /// Depth of nested `Uninterruptible` sections of the thread.
+    uninterruptible: AtomicUsize,
+    inner: SpinLock<ThreadControlBlockInner>,
+}
+
+pub struct ThreadControlBlockInner {
+    pub res: Option<ThreadUserRes>,
+    pub trap_frame_ppn: PhysPageNum,
+    pub ctx: ThreadContext,
+    pub status: ThreadStatus,
+    pub exit_code: Option<i32>,
+}
+
+/// Return the virtual address of the trap frame of thread `tid`.
+fn trap_frame_bottom_from_tid(tid: usize) -> usize {
+    TRAP_FRAME - tid * PAGE_SIZE
+}
+
+/// Return the virtual address of the bottom of the user stack of thread `tid`.
+fn user_stack_bottom_from_tid(ustack_base: usize, tid: usize) -> usize {
+    // every user stack is preceded by a guard page
+    ustack_base + tid * (PAGE_SIZE + USER_STACK_SIZE)
+}
+
+/// Resources of a thread in the user memory space of its process.
+pub struct ThreadUserRes {
+    pub tid: usize,
+    pub ustack_base: usize,
+    proc: Weak<ProcControlBlock>,
+}
+
+impl ThreadUserRes {
+    pub fn new(proc: &Arc<ProcControlBlock>, ustack_base: usize, alloc_user_res: bool) -> Self {
+        let tid = proc.borrow_inner_mut().alloc_tid();
+        let res = Self {
+            tid,
+            ustack_base,
+            proc: Arc::downgrade(proc),
+        };
+        if alloc_user_res {
+            res.alloc_user_res();
+        }
+        res
+    }
+
+    /// Map the user stack and the trap frame of this thread.
+    pub fn alloc_user_res(&self) {
+        let proc = self.proc.upgrade().unwrap();
+        let mut proc_inner = proc.borrow_inner_mut();
+        let ustack_bottom = user_stack_bottom_from_tid(self.ustack_base, self.tid);
+        proc_inner.memory_space.insert_framed_area(
+            VirtAddr::new(ustack_bottom),
+            VirtAddr::new(ustack_bottom + USER_STACK_SIZE),
+            MapPermission::R | MapPermission::W | MapPermission::U,
+        );
+        let trap_frame_bottom = trap_frame_bottom_from_tid(self.tid);
+        proc_inner.memory_space.insert_framed_area(
+            VirtAddr::new(trap_frame_bottom),
+            VirtAddr::new(trap_frame_bottom + PAGE_SIZE),
+            MapPermission::R | MapPermission::W,
+        );
+    }
+
+    fn dealloc_user_res(&self) {
+        let proc = self.proc.upgrade().unwrap();
+        self.unmap_user_res(&mut proc.borrow_inner_mut().memory_space);
+    }
+
+    /// Unmap the user stack and the trap frame of this thread from `memory_space`, which may be
+    /// a copy of the memory space of its process.
+    pub fn unmap_user_res(&self, memory_space: &mut MemorySpace) {
+        let ustack_bottom = user_stack_bottom_from_tid(self.ustack_base, self.tid);
+        memory_space.remove_area_with_start_vpn(VirtAddr::new(ustack_bottom).page_number());
+        let trap_frame_bottom = trap_frame_bottom_from_tid(self.tid);
+        memory_space.remove_area_with_start_vpn(VirtAddr::new(trap_frame_bottom).page_number());
+    }
+
+    pub fn trap_frame_user_va(&self) -> usize {
+        trap_frame_bottom_from_tid(self.tid)
+    }
+
+    pub fn trap_frame_ppn(&self) -> PhysPageNum {
+        let proc = self.proc.upgrade().unwrap();
+        let proc_inner = proc.borrow_inner_mut();
+        proc_inner
+            .memory_space
+            .translate(VirtAddr::new(self.trap_frame_user_va()).page_number())
+            .unwrap()
+            .ppn()
+    }
+
+    pub fn ustack_top(&self) -> usize {
+        user_stack_bottom_from_tid(self.ustack_base, self.tid) + USER_STACK_SIZE
+    }
+}
+
+impl Drop for ThreadUserRes {
+    fn drop(&mut self) {
+        // the whole memory space may have been released together with the process
+        if self.proc.strong_count() > 0 {
+            self.dealloc_user_res();
+            self.proc
+                .upgrade()
+                .unwrap()
+                .borrow_inner_mut()
+                .dealloc_tid(self.tid);
+        }
+    }
+}
+
+impl ThreadControlBlock {
+    pub fn new(proc: &Arc<ProcControlBlock>, ustack_base: usize, alloc_user_res: bool) -> Self {
+        let res = ThreadUserRes::new(proc, ustack_base, alloc_user_res);
+        let trap_frame_ppn = res.trap_frame_ppn();
+        let kernel_stack = KernelStack::new();
+        let kernel_stack_top = kernel_stack.get_top();
+        Self {
+            proc: Arc::downgrade(proc),
+            kernel_stack,
+            on_cpu: AtomicBool::new(false),
+            uninterruptible: AtomicUsize::new(0),
+            inner: SpinLock::new(ThreadControlBlockInner {
+                res: Some(res),
+                trap_frame_ppn,
+                ctx: ThreadContext::goto_trap_return(kernel_stack_top),
+                status: ThreadStatus::Ready,
+                exit_code: None,
+            }),
+        }
+    }
+
+    pub fn borrow_inner_mut(&self) -> SpinLockGuard<'_, ThreadControlBlockInner> {
+        self.inner.lock()
+    }
+
+    pub fn proc(&self) -> Arc<ProcControlBlock> {
+        self.proc
+            .upgrade()
+            .expect("Process of the thread has been released")
+    }
+
+    /// Whether the thread belongs to `proc`, which works even if its process has been released.
+    pub fn belongs_to(&self, proc: &Arc<ProcControlBlock>) -> bool {
+        core::ptr::eq(self.proc.as_ptr(), Arc::as_ptr(proc))
+    }
+
+    pub fn tid(&self) -> usize {
+        self.inner.lock().res.as_ref().unwrap().tid
+    }
+
+    pub fn is_on_cpu(&self) -> bool {
+        self.on_cpu.load(Ordering::Acquire)
+    }
+
+    pub fn set_on_cpu(&self, on_cpu: bool) {
+        self.on_cpu.store(on_cpu, Ordering::Release);
+    }
+
+    /// Whether the thread must finish its work in the kernel before its process can exit.
+    pub fn is_uninterruptible(&self) -> bool {
+        self.uninterruptible.load(Ordering::Acquire) > 0
+    }
+
+    pub fn kernel_stack_top(&self) -> usize {
+        self.kernel_stack.get_top()
+    }
+
+    pub fn get_user_token(&self) -> usize {
+        self.proc().borrow_inner_mut().get_token()
+    }
+}
+
+/// Keep the current thread from being stopped by an exiting process until dropped.
+///
+/// Kernel work which must not be abandoned halfway, like holding a sleep lock or waiting for
+/// a disk request, runs in such a section so that the thread is not killed while blocked.
+pub struct Uninterruptible(Option<Arc<ThreadControlBlock>>);
+
+impl Uninterruptible {
+    pub fn new() -> Self {
+        // there is no thread to protect before the processes start
+        let thread = try_current_thread();
+        if let Some(thread) = &thread {
+            thread.uninterruptible.fetch_add(1, Ordering::AcqRel);
+        }
+        Self(thread)
+    }
+}
+
+impl Drop for Uninterruptible {
+    fn drop(&mut self) {
+        if let Some(thread) = &self.0 {
+            thread.uninterruptible.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+impl ThreadControlBlockInner {
+    pub fn get_trap_frame_mut(&self) -> &'static mut TrapFrame {
+        self.trap_frame_ppn.get_mut()
+    }
+
+    pub fn trap_frame_user_va(&self) -> usize {
+        self.res.as_ref().unwrap().trap_frame_user_va()
+    }
+}
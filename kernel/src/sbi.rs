@@ -1,8 +1,12 @@
 use sbi_rt::{NoReason, Shutdown, SystemFailure, system_reset};
 
-use crate::proc::suspend_current_and_run_next;
+use crate::drivers::UART;
 
 pub fn shutdown(failure: bool) -> ! {
+    // do not lose the output still queued in the UART
+    if UART.is_ready() {
+        UART.flush();
+    }
     if failure {
         system_reset(Shutdown, SystemFailure);
     } else {
@@ -10,26 +14,3 @@ pub fn shutdown(failure: bool) -> ! {
     }
     unreachable!()
 }
-
-pub fn get_char_blocking() -> u8 {
-    loop {
-        #[allow(deprecated)] // TODO: do not use deprecated SBI calls
-        let res = sbi_rt::legacy::console_getchar();
-        if res != 0 {
-            return (res & 0xFF) as u8;
-        }
-        suspend_current_and_run_next();
-    }
-}
-
-// pub fn read_chars_blocking(len: usize) -> Vec<u8> {
-//     let mut kernel_buffer = Vec::with_capacity(len);
-//     loop {
-//         let ch = get_char_blocking();
-//         kernel_buffer.push(ch);
-//         if kernel_buffer.len() >= len {
-//             break;
-//         }
-//     }
-//     kernel_buffer
-// }
@@ -0,0 +1,66 @@
+//! Multiprocessor support
+
+use core::{
+    arch::asm,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use log::info;
+use sbi_rt::HartMask;
+
+use crate::config::MAX_HART_NUM;
+
+/// Bit mask of the harts which have finished their initialization.
+static ONLINE_HARTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Return the id of the current hart, which is kept in `tp` while running in the kernel.
+pub fn hart_id() -> usize {
+    let hart_id;
+    unsafe {
+        asm!("mv {}, tp", out(reg) hart_id);
+    }
+    hart_id
+}
+
+/// Mark the current hart as ready to receive IPIs.
+pub fn set_online() {
+    ONLINE_HARTS.fetch_or(1 << hart_id(), Ordering::SeqCst);
+}
+
+/// Start all harts except the current one at physical address `entry` with SBI HSM.
+pub fn start_other_harts(entry: usize) {
+    let boot_hart_id = hart_id();
+    for id in (0..MAX_HART_NUM).filter(|&id| id != boot_hart_id) {
+        // fails if the hart does not exist
+        if sbi_rt::hart_start(id, entry, 0).error == 0 {
+            info!("Starting hart {id}");
+        }
+    }
+}
+
+/// Return the online harts except the current one.
+fn other_harts() -> usize {
+    ONLINE_HARTS.load(Ordering::SeqCst) & !(1 << hart_id())
+}
+
+/// Send a supervisor software interrupt to all other harts.
+pub fn send_ipi_to_others() {
+    let mask = other_harts();
+    if mask != 0 {
+        sbi_rt::send_ipi(HartMask::from_mask_base(mask, 0));
+    }
+}
+
+/// Flush the TLB entries of virtual addresses `[start, start + size)` on all harts.
+///
+/// The SBI implementation delivers the request to other harts with IPIs and returns once
+/// they are all done, so the unmapped frames can be reused safely afterwards.
+pub fn tlb_shootdown(start: usize, size: usize) {
+    unsafe {
+        asm!("sfence.vma");
+    }
+    let mask = other_harts();
+    if mask != 0 {
+        sbi_rt::remote_sfence_vma(HartMask::from_mask_base(mask, 0), start, size);
+    }
+}
@@ -0,0 +1,50 @@
+//! Condition variables used by user threads
+
+use alloc::{collections::VecDeque, sync::Arc};
+
+use crate::proc::{ThreadControlBlock, block_current_and_run_next, current_thread, wakeup_thread};
+
+use super::{Mutex, SpinLock};
+
+pub struct Condvar {
+    inner: SpinLock<CondvarInner>,
+}
+
+struct CondvarInner {
+    wait_queue: VecDeque<Arc<ThreadControlBlock>>,
+}
+
+impl Condvar {
+    pub fn new() -> Self {
+        Self {
+            inner: SpinLock::new(CondvarInner {
+                wait_queue: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Wake up one of the waiting threads.
+    pub fn signal(&self) {
+        let mut inner = self.inner.lock();
+        if let Some(thread) = inner.wait_queue.pop_front() {
+            wakeup_thread(thread);
+        }
+    }
+
+    /// Release `mutex` and block until signaled, `mutex` is locked again before returning.
+    ///
+    /// Fails without waiting if `mutex` cannot be unlocked.
+    pub fn wait(&self, mutex: Arc<dyn Mutex>) -> Result<(), isize> {
+        // enqueue before unlocking so that a signal after unlocking is not lost
+        let mut inner = self.inner.lock();
+        inner.wait_queue.push_back(current_thread());
+        if let Err(errno) = mutex.unlock() {
+            inner.wait_queue.pop_back();
+            return Err(errno);
+        }
+        drop(inner);
+        block_current_and_run_next();
+        mutex.lock();
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+//! Deadlock detection for the mutexes and semaphores of a process
+//!
+//! Every lock or down operation is recorded as a request before the thread blocks. When the
+//! detection is enabled, a request is refused if it leaves the process in a state where some
+//! threads can never finish, which is checked with the safety algorithm of the banker's
+//! algorithm using the outstanding requests as the need of each thread.
+
+use alloc::{vec, vec::Vec};
+use common::errno::{EDEADLK, EPERM};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    Mutex(usize),
+    Semaphore(usize),
+}
+
+/// Allocation state of one kind of resource, indexed by resource id and tid.
+#[derive(Default)]
+struct ResourceTable {
+    available: Vec<usize>,
+    allocation: Vec<Vec<usize>>,
+    need: Vec<Vec<usize>>,
+}
+
+impl ResourceTable {
+    fn add_resource(&mut self, id: usize, count: usize) {
+        if self.available.len() <= id {
+            self.available.resize(id + 1, 0);
+            for row in self.allocation.iter_mut().chain(self.need.iter_mut()) {
+                row.resize(id + 1, 0);
+            }
+        }
+        self.available[id] = count;
+    }
+
+    fn ensure_thread(&mut self, tid: usize) {
+        let columns = self.available.len();
+        if self.allocation.len() <= tid {
+            self.allocation.resize(tid + 1, vec![0; columns]);
+            self.need.resize(tid + 1, vec![0; columns]);
+        }
+    }
+
+    fn threads(&self) -> usize {
+        self.allocation.len()
+    }
+
+    /// Whether all requests of `tid` can be satisfied with `work`.
+    fn can_finish(&self, tid: usize, work: &[usize]) -> bool {
+        self.need
+            .get(tid)
+            .is_none_or(|need| need.iter().zip(work).all(|(n, w)| n <= w))
+    }
+
+    /// Give back the resources allocated to `tid` to `work`.
+    fn release_all(&self, tid: usize, work: &mut [usize]) {
+        if let Some(allocation) = self.allocation.get(tid) {
+            for (w, a) in work.iter_mut().zip(allocation) {
+                *w += a;
+            }
+        }
+    }
+}
+
+pub struct DeadlockDetector {
+    enabled: bool,
+    mutexes: ResourceTable,
+    semaphores: ResourceTable,
+}
+
+impl DeadlockDetector {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            mutexes: ResourceTable::default(),
+            semaphores: ResourceTable::default(),
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    fn table(&mut self, res: Resource) -> (&mut ResourceTable, usize) {
+        match res {
+            Resource::Mutex(id) => (&mut self.mutexes, id),
+            Resource::Semaphore(id) => (&mut self.semaphores, id),
+        }
+    }
+
+    pub fn add_resource(&mut self, res: Resource, count: usize) {
+        let (table, id) = self.table(res);
+        table.add_resource(id, count);
+    }
+
+    /// Record that thread `tid` requests one unit of `res`.
+    ///
+    /// Return false and forget the request if the detection is enabled and the request
+    /// would lead to a deadlock.
+    pub fn request(&mut self, tid: usize, res: Resource) -> bool {
+        let (table, id) = self.table(res);
+        table.ensure_thread(tid);
+        table.need[tid][id] += 1;
+        if self.enabled && !self.is_safe() {
+            let (table, id) = self.table(res);
+            table.need[tid][id] -= 1;
+            return false;
+        }
+        true
+    }
+
+    /// Record that the request of thread `tid` for `res` has been granted.
+    pub fn acquired(&mut self, tid: usize, res: Resource) {
+        let (table, id) = self.table(res);
+        table.ensure_thread(tid);
+        table.need[tid][id] -= 1;
+        table.allocation[tid][id] += 1;
+        table.available[id] -= 1;
+    }
+
+    /// Record that thread `tid` gives back one unit of `res`.
+    pub fn release(&mut self, tid: usize, res: Resource) {
+        let (table, id) = self.table(res);
+        table.ensure_thread(tid);
+        // a semaphore may be released by a thread which has never acquired it
+        if table.allocation[tid][id] > 0 {
+            table.allocation[tid][id] -= 1;
+        }
+        table.available[id] += 1;
+    }
+
+    /// Record that thread `tid` gives back the unit of `res` it holds and requests it again, as
+    /// waiting on a condition variable does.
+    ///
+    /// Fail with `EPERM` if `tid` does not hold `res`, or with `EDEADLK` if the detection is
+    /// enabled and the request would lead to a deadlock, changing nothing in both cases.
+    pub fn release_and_request(&mut self, tid: usize, res: Resource) -> Result<(), isize> {
+        let (table, id) = self.table(res);
+        table.ensure_thread(tid);
+        if table.allocation[tid][id] == 0 {
+            return Err(EPERM);
+        }
+        table.allocation[tid][id] -= 1;
+        table.available[id] += 1;
+        table.need[tid][id] += 1;
+        if self.enabled && !self.is_safe() {
+            self.acquired(tid, res);
+            return Err(EDEADLK);
+        }
+        Ok(())
+    }
+
+    fn is_safe(&self) -> bool {
+        let mut mutex_work = self.mutexes.available.clone();
+        let mut semaphore_work = self.semaphores.available.clone();
+        let threads = self.mutexes.threads().max(self.semaphores.threads());
+        let mut finish = vec![false; threads];
+        while let Some(tid) = (0..threads).find(|&tid| {
+            !finish[tid]
+                && self.mutexes.can_finish(tid, &mutex_work)
+                && self.semaphores.can_finish(tid, &semaphore_work)
+        }) {
+            self.mutexes.release_all(tid, &mut mutex_work);
+            self.semaphores.release_all(tid, &mut semaphore_work);
+            finish[tid] = true;
+        }
+        finish.into_iter().all(|f| f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DeadlockDetector, Resource};
+
+    #[test_case]
+    fn test_deadlock_detect() {
+        let mut detector = DeadlockDetector::new();
+        detector.set_enabled(true);
+        let (a, b) = (Resource::Mutex(0), Resource::Mutex(1));
+        detector.add_resource(a, 1);
+        detector.add_resource(b, 1);
+        // thread 0 holds a, thread 1 holds b
+        assert!(detector.request(0, a));
+        detector.acquired(0, a);
+        assert!(detector.request(1, b));
+        detector.acquired(1, b);
+        // thread 1 waits for a, thread 0 must not wait for b
+        assert!(detector.request(1, a));
+        assert!(!detector.request(0, b));
+        // once a is released thread 1 can take it
+        detector.release(0, a);
+        detector.acquired(1, a);
+        assert!(detector.request(0, b));
+    }
+}
@@ -0,0 +1,106 @@
+//! Futex wait queues
+//!
+//! The queues are keyed by the physical address of the futex word, so that processes sharing
+//! a mapping wait on the same queue.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+};
+use lazy_static::lazy_static;
+
+use crate::{
+    memory::PhysAddr,
+    proc::{ThreadControlBlock, block_current_and_run_next, current_thread, wakeup_thread},
+    timer::{add_timer, remove_timer},
+};
+
+use super::SpinLock;
+
+/// A thread waiting on a futex.
+struct Waiter {
+    thread: Arc<ThreadControlBlock>,
+    /// Whether the thread is also waiting on a timer.
+    timed: bool,
+}
+
+lazy_static! {
+    static ref FUTEX_QUEUES: SpinLock<BTreeMap<usize, VecDeque<Waiter>>> =
+        SpinLock::new(BTreeMap::new());
+}
+
+pub enum FutexWait {
+    /// The futex word does not hold the expected value.
+    Mismatch,
+    Woken,
+    TimedOut,
+}
+
+/// Block the current thread on the futex word at physical address `key` if it holds `val`,
+/// until it is woken up by `futex_wake` or `expire_ms` is reached.
+pub fn futex_wait(key: usize, val: u32, expire_ms: Option<usize>) -> FutexWait {
+    let thread = current_thread();
+    let mut queues = FUTEX_QUEUES.lock();
+    // checked with the queues locked, so that a wakeup after the check is not lost
+    let word = PhysAddr::new(key).get_ref::<AtomicU32>();
+    if word.load(Ordering::SeqCst) != val {
+        return FutexWait::Mismatch;
+    }
+    // the timer is added before a waker can see the waiter, which would take it as fired
+    if let Some(expire_ms) = expire_ms {
+        add_timer(expire_ms, Arc::clone(&thread));
+    }
+    queues.entry(key).or_default().push_back(Waiter {
+        thread: Arc::clone(&thread),
+        timed: expire_ms.is_some(),
+    });
+    drop(queues);
+    drop(thread);
+    block_current_and_run_next();
+
+    // still being in the queue means that we have been woken up by the timer
+    let thread = current_thread();
+    let mut queues = FUTEX_QUEUES.lock();
+    let Some(queue) = queues.get_mut(&key) else {
+        return FutexWait::Woken;
+    };
+    let Some(pos) = queue.iter().position(|w| Arc::ptr_eq(&w.thread, &thread)) else {
+        return FutexWait::Woken;
+    };
+    queue.remove(pos);
+    if queue.is_empty() {
+        queues.remove(&key);
+    }
+    FutexWait::TimedOut
+}
+
+/// Wake up at most `count` threads waiting on the futex at physical address `key`, return the
+/// number of woken threads.
+pub fn futex_wake(key: usize, count: usize) -> usize {
+    let mut queues = FUTEX_QUEUES.lock();
+    let Some(queue) = queues.get_mut(&key) else {
+        return 0;
+    };
+    let mut woken = 0;
+    while woken < count {
+        let Some(waiter) = queue.pop_front() else {
+            break;
+        };
+        // the timer has fired and woken up the thread already
+        if waiter.timed && !remove_timer(&waiter.thread) {
+            continue;
+        }
+        // the process of the thread may have exited
+        if waiter.thread.borrow_inner_mut().res.is_none() {
+            continue;
+        }
+        wakeup_thread(waiter.thread);
+        woken += 1;
+    }
+    if queue.is_empty() {
+        queues.remove(&key);
+    }
+    woken
+}
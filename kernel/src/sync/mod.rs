@@ -0,0 +1,19 @@
+//! Synchronization primitives
+
+mod condvar;
+mod deadlock;
+mod futex;
+mod mutex;
+mod semaphore;
+mod sleep;
+mod spin;
+mod wait_queue;
+
+pub use self::condvar::Condvar;
+pub use self::deadlock::{DeadlockDetector, Resource};
+pub use self::futex::{FutexWait, futex_wait, futex_wake};
+pub use self::mutex::{Mutex, MutexBlocking};
+pub use self::semaphore::Semaphore;
+pub use self::sleep::SleepLock;
+pub use self::spin::{SpinLock, SpinLockGuard};
+pub use self::wait_queue::{WaitQueue, Waiter};
@@ -0,0 +1,62 @@
+//! Mutexes used by user threads
+
+use alloc::{collections::VecDeque, sync::Arc};
+use common::errno::EPERM;
+
+use crate::proc::{ThreadControlBlock, block_current_and_run_next, current_thread, wakeup_thread};
+
+use super::SpinLock;
+
+pub trait Mutex: Send + Sync {
+    fn lock(&self);
+    /// Unlock the mutex, which fails with `EPERM` if it is not locked.
+    fn unlock(&self) -> Result<(), isize>;
+}
+
+/// A mutex that blocks the waiting threads until it is unlocked.
+pub struct MutexBlocking {
+    inner: SpinLock<MutexBlockingInner>,
+}
+
+struct MutexBlockingInner {
+    locked: bool,
+    wait_queue: VecDeque<Arc<ThreadControlBlock>>,
+}
+
+impl MutexBlocking {
+    pub fn new() -> Self {
+        Self {
+            inner: SpinLock::new(MutexBlockingInner {
+                locked: false,
+                wait_queue: VecDeque::new(),
+            }),
+        }
+    }
+}
+
+impl Mutex for MutexBlocking {
+    fn lock(&self) {
+        let mut inner = self.inner.lock();
+        if inner.locked {
+            inner.wait_queue.push_back(current_thread());
+            drop(inner);
+            // the lock is handed over to us by `unlock`
+            block_current_and_run_next();
+        } else {
+            inner.locked = true;
+        }
+    }
+
+    fn unlock(&self) -> Result<(), isize> {
+        let mut inner = self.inner.lock();
+        if !inner.locked {
+            return Err(EPERM);
+        }
+        if let Some(waking_thread) = inner.wait_queue.pop_front() {
+            wakeup_thread(waking_thread);
+        } else {
+            inner.locked = false;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,48 @@
+//! Counting semaphores used by user threads
+
+use alloc::{collections::VecDeque, sync::Arc};
+
+use crate::proc::{ThreadControlBlock, block_current_and_run_next, current_thread, wakeup_thread};
+
+use super::SpinLock;
+
+pub struct Semaphore {
+    inner: SpinLock<SemaphoreInner>,
+}
+
+struct SemaphoreInner {
+    /// Number of available resources, or the number of waiters if negative.
+    count: isize,
+    wait_queue: VecDeque<Arc<ThreadControlBlock>>,
+}
+
+impl Semaphore {
+    pub fn new(res_count: usize) -> Self {
+        Self {
+            inner: SpinLock::new(SemaphoreInner {
+                count: res_count as isize,
+                wait_queue: VecDeque::new(),
+            }),
+        }
+    }
+
+    pub fn up(&self) {
+        let mut inner = self.inner.lock();
+        inner.count += 1;
+        if inner.count <= 0 {
+            if let Some(thread) = inner.wait_queue.pop_front() {
+                wakeup_thread(thread);
+            }
+        }
+    }
+
+    pub fn down(&self) {
+        let mut inner = self.inner.lock();
+        inner.count -= 1;
+        if inner.count < 0 {
+            inner.wait_queue.push_back(current_thread());
+            drop(inner);
+            block_current_and_run_next();
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Sleep locks for kernel data held across blocking operations
+
+use core::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+};
+
+use alloc::{collections::VecDeque, sync::Arc};
+
+use crate::proc::{
+    ThreadControlBlock, Uninterruptible, block_current_and_run_next, current_thread, wakeup_thread,
+};
+
+use super::SpinLock;
+
+/// A mutual exclusion lock which blocks the waiting threads instead of spinning.
+///
+/// The holder may block, for example waiting for the disk, and it is never stopped by an
+/// exiting process before releasing the lock.
+pub struct SleepLock<T> {
+    inner: SpinLock<SleepLockInner>,
+    data: UnsafeCell<T>,
+}
+
+struct SleepLockInner {
+    locked: bool,
+    wait_queue: VecDeque<Arc<ThreadControlBlock>>,
+}
+
+unsafe impl<T: Send> Sync for SleepLock<T> {}
+unsafe impl<T: Send> Send for SleepLock<T> {}
+
+impl<T> SleepLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            inner: SpinLock::new(SleepLockInner {
+                locked: false,
+                wait_queue: VecDeque::new(),
+            }),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Acquire the lock, blocking until it is available.
+    pub fn lock(&self) -> SleepLockGuard<'_, T> {
+        loop {
+            let mut inner = self.inner.lock();
+            if !inner.locked {
+                inner.locked = true;
+                break;
+            }
+            inner.wait_queue.push_back(current_thread());
+            drop(inner);
+            block_current_and_run_next();
+        }
+        SleepLockGuard {
+            lock: self,
+            _uninterruptible: Uninterruptible::new(),
+        }
+    }
+}
+
+pub struct SleepLockGuard<'a, T> {
+    lock: &'a SleepLock<T>,
+    _uninterruptible: Uninterruptible,
+}
+
+impl<T> Deref for SleepLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for SleepLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for SleepLockGuard<'_, T> {
+    fn drop(&mut self) {
+        let mut inner = self.lock.inner.lock();
+        inner.locked = false;
+        // waiters of an exited process never come back, so let all of them retry
+        for thread in inner.wait_queue.drain(..) {
+            wakeup_thread(thread);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! Spin locks for multiprocessor
+
+use core::{
+    cell::UnsafeCell,
+    hint::spin_loop,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::proc::{pop_off, push_off};
+
+/// A mutual exclusion lock which busy-waits until it is released.
+///
+/// Interrupts of the current hart are disabled while the lock is held, so that an interrupt
+/// handler never spins on a lock held by the code it interrupted.
+pub struct SpinLock<T> {
+    locked: AtomicBool,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for SpinLock<T> {}
+unsafe impl<T: Send> Send for SpinLock<T> {}
+
+impl<T> SpinLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Acquire the lock, spinning until it is available.
+    pub fn lock(&self) -> SpinLockGuard<'_, T> {
+        push_off();
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            while self.locked.load(Ordering::Relaxed) {
+                spin_loop();
+            }
+        }
+        SpinLockGuard { lock: self }
+    }
+}
+
+pub struct SpinLockGuard<'a, T> {
+    lock: &'a SpinLock<T>,
+}
+
+impl<T> Deref for SpinLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for SpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for SpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+        pop_off();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use riscv::register::sstatus;
+
+    use super::SpinLock;
+
+    #[test_case]
+    fn test_spin_lock() {
+        let lock = SpinLock::new(0);
+        let enabled = sstatus::read().sie();
+        {
+            let mut guard = lock.lock();
+            *guard += 1;
+            assert!(!sstatus::read().sie());
+        }
+        assert_eq!(*lock.lock(), 1);
+        // the interrupt state is restored once the lock is released
+        assert_eq!(sstatus::read().sie(), enabled);
+    }
+}
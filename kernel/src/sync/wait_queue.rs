@@ -0,0 +1,108 @@
+//! Wait queues of threads waiting for the state of files to change
+//!
+//! A thread may wait on several queues at once and on a timer, so each wait is a `Waiter`
+//! which wakes up the thread only once, whichever queue or timer comes first. Every wait is
+//! also woken up by the signals sent to the process, so that blocking syscalls can fail with
+//! `EINTR`.
+
+use alloc::{collections::VecDeque, sync::Arc};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{
+    proc::{
+        ThreadControlBlock, block_current_and_run_next, current_proc, current_thread, wakeup_thread,
+    },
+    timer::{add_timer, remove_timer},
+};
+
+/// A wait of the current thread.
+pub struct Waiter {
+    thread: Arc<ThreadControlBlock>,
+    /// Whether the thread is also waiting on a timer.
+    timed: bool,
+    woken: AtomicBool,
+}
+
+/// Waiters, usually guarded by the lock of the state they wait on.
+#[derive(Default)]
+pub struct WaitQueue {
+    waiters: VecDeque<Arc<Waiter>>,
+}
+
+impl Waiter {
+    /// Start waiting, until `expire_ms` if any.
+    pub fn new(expire_ms: Option<usize>) -> Arc<Self> {
+        let thread = current_thread();
+        if let Some(expire_ms) = expire_ms {
+            add_timer(expire_ms, Arc::clone(&thread));
+        }
+        Arc::new(Self {
+            thread,
+            timed: expire_ms.is_some(),
+            woken: AtomicBool::new(false),
+        })
+    }
+
+    /// Wake up the thread unless it has been woken up already.
+    pub fn wake(&self) {
+        if self.woken.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        // the timer has fired and woken up the thread already
+        if self.timed && !remove_timer(&self.thread) {
+            return;
+        }
+        wakeup_thread(Arc::clone(&self.thread));
+    }
+
+    fn is_woken(&self) -> bool {
+        self.woken.load(Ordering::Acquire)
+    }
+
+    /// Block until woken up by `wake`, by the timer or by a signal. It must be called without
+    /// the state waited on locked, as the process is locked before such states elsewhere.
+    pub fn wait(self: &Arc<Self>) {
+        let proc = current_proc();
+        let mut inner = proc.borrow_inner_mut();
+        inner.signal_waiters.push(self);
+        // a signal sent before the registration does not wake us up
+        let interrupted = !inner.unblocked_signals().is_empty();
+        drop(inner);
+        drop(proc);
+        if interrupted {
+            self.wake();
+        }
+        block_current_and_run_next();
+        // not woken up again by the queues if woken up by the timer
+        self.woken.store(true, Ordering::Release);
+    }
+
+    /// Stop waiting without blocking.
+    pub fn cancel(&self) {
+        let woken = self.woken.swap(true, Ordering::AcqRel);
+        if woken || (self.timed && !remove_timer(&self.thread)) {
+            // the thread has been made ready already, which has to be consumed
+            block_current_and_run_next();
+        }
+    }
+}
+
+impl WaitQueue {
+    pub const fn new() -> Self {
+        Self {
+            waiters: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, waiter: &Arc<Waiter>) {
+        // the waits which are over are dropped here if they have not been woken up by us
+        self.waiters.retain(|waiter| !waiter.is_woken());
+        self.waiters.push_back(Arc::clone(waiter));
+    }
+
+    pub fn wake_all(&mut self) {
+        for waiter in self.waiters.drain(..) {
+            waiter.wake();
+        }
+    }
+}
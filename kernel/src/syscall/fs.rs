@@ -1,11 +1,27 @@
 //! File and filesystem-related syscalls
 
+use alloc::{string::String, sync::Arc, vec::Vec};
+use common::{
+    errno::{EBADF, EFAULT, EINVAL, EISDIR, ENOTDIR},
+    fs::{
+        AT_REMOVEDIR, EFD_SEMAPHORE, F_DUPFD, F_DUPFD_CLOEXEC, F_GETFD, F_GETFL, F_SETFD, F_SETFL,
+        FD_CLOEXEC, FileType, S_IFMT, Stat,
+    },
+    poll::{FD_SETSIZE, FdSet, PollEvents, PollFd},
+    sig::SignalFlags,
+    time::{CLOCK_MONOTONIC, CLOCK_REALTIME, ItimerSpec, TFD_TIMER_ABSTIME, TimeSpec},
+};
 use log::trace;
 
 use crate::{
-    fs::{OpenFlags, Pipe, open_file},
-    memory::VirtAddr,
+    config::MAX_FDS,
+    fs::{
+        EventFd, File, FileDescriptor, OpenFlags, Pipe, SignalFd, TimerFd, create, lookup, mount,
+        open, poll, umount, unlink,
+    },
+    memory::{PageTable, VirtAddr},
     proc::current_proc,
+    timer::get_time_ms,
 };
 
 /// write buf of length `len`  to a file with `fd`
@@ -13,20 +29,15 @@ pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
     trace!("sys_write: fd = {fd}, buf = {buf:p}, len = {len}");
     let proc = current_proc();
     let pt = proc.page_table();
-    let inner = proc.borrow_inner_mut();
-
-    if fd >= inner.fd_table.len() {
+    let Some(file) = proc.borrow_inner_mut().get_file(fd) else {
+        return -1;
+    };
+    if !file.writable() {
         return -1;
     }
-    if let Some(file) = &inner.fd_table[fd] {
-        let file = file.clone();
-        if !file.writable() {
-            return -1;
-        }
-        drop(inner);
-        file.write(pt.translate_bytes_buffer(VirtAddr::new(buf as usize), len)) as isize
-    } else {
-        -1
+    match file.write(pt.translate_bytes_buffer(VirtAddr::new(buf as usize), len)) {
+        Ok(len) => len as isize,
+        Err(errno) => -errno,
     }
 }
 
@@ -34,35 +45,123 @@ pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
     trace!("sys_read: fd = {fd}, buf = {buf:p}, len = {len}");
     let proc = current_proc();
     let pt = proc.page_table();
-    let inner = proc.borrow_inner_mut();
-
-    if fd >= inner.fd_table.len() {
+    let Some(file) = proc.borrow_inner_mut().get_file(fd) else {
+        return -1;
+    };
+    if !file.readable() {
         return -1;
     }
-    if let Some(file) = &inner.fd_table[fd] {
-        let file = file.clone();
-        if !file.readable() {
-            return -1;
-        }
-        drop(inner);
-        file.read(pt.translate_bytes_buffer(VirtAddr::new(buf as usize), len)) as isize
-    } else {
-        -1
+    match file.read(pt.translate_bytes_buffer(VirtAddr::new(buf as usize), len)) {
+        Ok(len) => len as isize,
+        Err(errno) => -errno,
     }
 }
 
+/// Open `path`, the fd is closed by `exec` with `O_CLOEXEC`.
 pub fn sys_open(path: *const u8, flags: u32) -> isize {
+    let Some(flags) = OpenFlags::from_bits(flags) else {
+        return -EINVAL;
+    };
     let proc = current_proc();
     let pt = proc.page_table();
     let path = pt.read_c_str(path).unwrap();
-    if let Some(inode) = open_file(path.as_str(), OpenFlags::from_bits(flags).unwrap()) {
-        let mut inner = proc.borrow_inner_mut();
-        let fd = inner.alloc_fd();
-        inner.fd_table[fd] = Some(inode);
-        fd as isize
-    } else {
-        -1
+    match open(path.as_str(), flags) {
+        Ok(inode) => {
+            let mut inner = proc.borrow_inner_mut();
+            let fd = inner.alloc_fd();
+            let cloexec = flags.contains(OpenFlags::CLOEXEC);
+            inner.fd_table[fd] = Some(FileDescriptor::new(inode, cloexec));
+            fd as isize
+        }
+        Err(errno) => -errno,
+    }
+}
+
+/// Create the special file `path` of the type in `mode`, only FIFOs are supported. Paths are
+/// resolved from the root whatever `dirfd` is, and `dev` is ignored.
+pub fn sys_mknodat(_dirfd: isize, path: *const u8, mode: u32, _dev: usize) -> isize {
+    if mode & S_IFMT != FileType::Fifo.mode() {
+        return -EINVAL;
+    }
+    let Some(path) = current_proc().page_table().read_c_str(path) else {
+        return -EFAULT;
+    };
+    trace!("sys_mknodat: path = {path}, mode = {mode:#o}");
+    match create(&path, FileType::Fifo) {
+        Ok(_) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+/// Create directory `path`, paths are resolved from the root whatever `dirfd` is. There are
+/// no permissions, so `mode` is ignored.
+pub fn sys_mkdirat(_dirfd: isize, path: *const u8, _mode: u32) -> isize {
+    let Some(path) = current_proc().page_table().read_c_str(path) else {
+        return -EFAULT;
+    };
+    trace!("sys_mkdirat: path = {path}");
+    match create(&path, FileType::Directory) {
+        Ok(_) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+/// Remove file `path`, or directory `path` with `AT_REMOVEDIR`. Paths are resolved from the
+/// root whatever `dirfd` is.
+pub fn sys_unlinkat(_dirfd: isize, path: *const u8, flags: u32) -> isize {
+    if flags & !AT_REMOVEDIR != 0 {
+        return -EINVAL;
+    }
+    let Some(path) = current_proc().page_table().read_c_str(path) else {
+        return -EFAULT;
+    };
+    trace!("sys_unlinkat: path = {path}, flags = {flags:#x}");
+    let is_dir = match lookup(&path) {
+        Ok(dentry) => dentry.inode.file_type() == FileType::Directory,
+        Err(errno) => return -errno,
+    };
+    match (is_dir, flags & AT_REMOVEDIR != 0) {
+        (true, false) => return -EISDIR,
+        (false, true) => return -ENOTDIR,
+        _ => {}
     }
+    match unlink(&path) {
+        Ok(()) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+/// Read the entries of directory `fd` into `buf` as `Dirent64` records, return the number of
+/// bytes read, 0 at the end.
+pub fn sys_getdents64(fd: usize, buf: *mut u8, len: usize) -> isize {
+    trace!("sys_getdents64: fd = {fd}, buf = {buf:p}, len = {len}");
+    let proc = current_proc();
+    let pt = proc.page_table();
+    let Some(file) = proc.borrow_inner_mut().get_file(fd) else {
+        return -1;
+    };
+    file.getdents(pt.translate_bytes_buffer(VirtAddr::new(buf as usize), len))
+}
+
+pub fn sys_fstat(fd: usize, stat: *mut Stat) -> isize {
+    trace!("sys_fstat: fd = {fd}, stat = {stat:p}");
+    let proc = current_proc();
+    let pt = proc.page_table();
+    let Some(file) = proc.borrow_inner_mut().get_file(fd) else {
+        return -1;
+    };
+    *pt.translate_mut_ptr(stat) = file.stat();
+    0
+}
+
+/// Perform the device-specific `request` on file `fd`, return -ENOTTY if it is not a device.
+pub fn sys_ioctl(fd: usize, request: usize, arg: usize) -> isize {
+    trace!("sys_ioctl: fd = {fd}, request = {request:#x}, arg = {arg:#x}");
+    let proc = current_proc();
+    let Some(file) = proc.borrow_inner_mut().get_file(fd) else {
+        return -1;
+    };
+    file.ioctl(request, arg)
 }
 
 pub fn sys_close(fd: usize) -> isize {
@@ -78,18 +177,417 @@ pub fn sys_close(fd: usize) -> isize {
     0
 }
 
-pub fn sys_pipe(pipe: *mut usize) -> isize {
+/// Duplicate `fd` to the lowest free fd, which is not closed by `exec`.
+pub fn sys_dup(fd: usize) -> isize {
+    trace!("sys_dup: fd = {fd}");
+    let proc = current_proc();
+    let mut inner = proc.borrow_inner_mut();
+    let Some(file) = inner.get_file(fd) else {
+        return -EBADF;
+    };
+    let new_fd = inner.alloc_fd();
+    inner.fd_table[new_fd] = Some(FileDescriptor::new(file, false));
+    new_fd as isize
+}
+
+/// Duplicate `old_fd` to `new_fd`, closing the file open there. `flags` may only be
+/// `O_CLOEXEC`, and `new_fd` must be below `MAX_FDS`.
+pub fn sys_dup3(old_fd: usize, new_fd: usize, flags: u32) -> isize {
+    trace!("sys_dup3: old_fd = {old_fd}, new_fd = {new_fd}, flags = {flags:#x}");
+    let Some(flags) = OpenFlags::from_bits(flags) else {
+        return -EINVAL;
+    };
+    if old_fd == new_fd || !OpenFlags::CLOEXEC.contains(flags) {
+        return -EINVAL;
+    }
+    let proc = current_proc();
+    let mut inner = proc.borrow_inner_mut();
+    let Some(file) = inner.get_file(old_fd) else {
+        return -EBADF;
+    };
+    if new_fd >= MAX_FDS {
+        return -EBADF;
+    }
+    if new_fd >= inner.fd_table.len() {
+        inner.fd_table.resize(new_fd + 1, None);
+    }
+    inner.fd_table[new_fd] = Some(FileDescriptor::new(
+        file,
+        flags.contains(OpenFlags::CLOEXEC),
+    ));
+    new_fd as isize
+}
+
+/// Manipulate `fd`: duplicate it, or get and set its flags or the status flags of its file.
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    trace!("sys_fcntl: fd = {fd}, cmd = {cmd}, arg = {arg:#x}");
+    let proc = current_proc();
+    let mut inner = proc.borrow_inner_mut();
+    let Some(Some(entry)) = inner.fd_table.get_mut(fd) else {
+        return -EBADF;
+    };
+    match cmd {
+        F_DUPFD | F_DUPFD_CLOEXEC => {
+            if arg >= MAX_FDS {
+                return -EINVAL;
+            }
+            let file = entry.file.clone();
+            let new_fd = inner.alloc_fd_from(arg);
+            inner.fd_table[new_fd] = Some(FileDescriptor::new(file, cmd == F_DUPFD_CLOEXEC));
+            new_fd as isize
+        }
+        F_GETFD => {
+            if entry.cloexec {
+                FD_CLOEXEC as isize
+            } else {
+                0
+            }
+        }
+        F_SETFD => {
+            entry.cloexec = arg & FD_CLOEXEC != 0;
+            0
+        }
+        F_GETFL => {
+            let file = &entry.file;
+            let mode = OpenFlags::access_mode(file.readable(), file.writable());
+            (mode | file.status().get()).bits() as isize
+        }
+        F_SETFL => {
+            // the access mode and the creation flags are ignored
+            let flags = OpenFlags::from_bits_truncate(arg as u32);
+            entry.file.status().set(flags);
+            0
+        }
+        _ => entry.file.fcntl(cmd, arg),
+    }
+}
+
+/// Create a pipe, `flags` may be `O_CLOEXEC` and `O_NONBLOCK`.
+pub fn sys_pipe2(pipe: *mut usize, flags: u32) -> isize {
+    let Some(flags) = OpenFlags::from_bits(flags) else {
+        return -EINVAL;
+    };
+    if !(OpenFlags::CLOEXEC | OpenFlags::NONBLOCK).contains(flags) {
+        return -EINVAL;
+    }
+    let cloexec = flags.contains(OpenFlags::CLOEXEC);
     let proc = current_proc();
     let pt = proc.page_table();
     let mut inner = proc.borrow_inner_mut();
-    let (pipe_read, pipe_write) = Pipe::new();
+    let (pipe_read, pipe_write) = match Pipe::new(flags) {
+        Ok(ends) => ends,
+        Err(errno) => return -errno,
+    };
     let read_fd = inner.alloc_fd();
-    inner.fd_table[read_fd] = Some(pipe_read);
+    inner.fd_table[read_fd] = Some(FileDescriptor::new(pipe_read, cloexec));
     let write_fd = inner.alloc_fd();
-    inner.fd_table[write_fd] = Some(pipe_write);
+    inner.fd_table[write_fd] = Some(FileDescriptor::new(pipe_write, cloexec));
     unsafe {
         *pt.translate_mut_ptr(pipe) = read_fd;
         *pt.translate_mut_ptr(pipe.add(1)) = write_fd;
     }
     0
 }
+
+/// Parse the flags of the syscalls creating eventfds, timerfds and signalfds, which may only
+/// have `O_CLOEXEC` and `O_NONBLOCK`.
+fn fd_flags(flags: u32) -> Result<OpenFlags, isize> {
+    OpenFlags::from_bits(flags)
+        .filter(|&flags| (OpenFlags::CLOEXEC | OpenFlags::NONBLOCK).contains(flags))
+        .ok_or(EINVAL)
+}
+
+/// Install `file` in the fd table of the current process, return the fd.
+fn install_fd(file: Arc<dyn File>, flags: OpenFlags) -> isize {
+    let proc = current_proc();
+    let mut inner = proc.borrow_inner_mut();
+    let fd = inner.alloc_fd();
+    let cloexec = flags.contains(OpenFlags::CLOEXEC);
+    inner.fd_table[fd] = Some(FileDescriptor::new(file, cloexec));
+    fd as isize
+}
+
+/// Create an eventfd whose counter starts at `initval`, reads decrement it by one instead of
+/// resetting it with `EFD_SEMAPHORE`.
+pub fn sys_eventfd2(initval: u32, flags: u32) -> isize {
+    trace!("sys_eventfd2: initval = {initval}, flags = {flags:#o}");
+    let open_flags = match fd_flags(flags & !EFD_SEMAPHORE) {
+        Ok(flags) => flags,
+        Err(errno) => return -errno,
+    };
+    let semaphore = flags & EFD_SEMAPHORE != 0;
+    install_fd(
+        EventFd::new(initval.into(), semaphore, open_flags),
+        open_flags,
+    )
+}
+
+/// Create a disarmed timerfd. Both clocks count the time since boot.
+pub fn sys_timerfd_create(clockid: usize, flags: u32) -> isize {
+    trace!("sys_timerfd_create: clockid = {clockid}, flags = {flags:#o}");
+    if clockid != CLOCK_REALTIME && clockid != CLOCK_MONOTONIC {
+        return -EINVAL;
+    }
+    match fd_flags(flags) {
+        Ok(flags) => install_fd(TimerFd::new(flags), flags),
+        Err(errno) => -errno,
+    }
+}
+
+fn timerfd_file(fd: usize) -> Result<Arc<dyn File>, isize> {
+    let file = current_proc()
+        .borrow_inner_mut()
+        .get_file(fd)
+        .ok_or(EBADF)?;
+    if file.as_timerfd().is_none() {
+        return Err(EINVAL);
+    }
+    Ok(file)
+}
+
+/// Arm timerfd `fd` with `new_value`, which is absolute with `TFD_TIMER_ABSTIME`, and store
+/// the previous setting in `old_value` unless null.
+pub fn sys_timerfd_settime(
+    fd: usize,
+    flags: u32,
+    new_value: *const ItimerSpec,
+    old_value: *mut ItimerSpec,
+) -> isize {
+    trace!("sys_timerfd_settime: fd = {fd}, flags = {flags:#x}");
+    if flags & !TFD_TIMER_ABSTIME != 0 {
+        return -EINVAL;
+    }
+    let file = match timerfd_file(fd) {
+        Ok(file) => file,
+        Err(errno) => return -errno,
+    };
+    let pt = current_proc().page_table();
+    let new_value = *pt.translate_ptr(new_value);
+    match file
+        .as_timerfd()
+        .unwrap()
+        .set(new_value, flags & TFD_TIMER_ABSTIME != 0)
+    {
+        Ok(old) => {
+            if !old_value.is_null() {
+                *pt.translate_mut_ptr(old_value) = old;
+            }
+            0
+        }
+        Err(errno) => -errno,
+    }
+}
+
+pub fn sys_timerfd_gettime(fd: usize, curr_value: *mut ItimerSpec) -> isize {
+    trace!("sys_timerfd_gettime: fd = {fd}");
+    let file = match timerfd_file(fd) {
+        Ok(file) => file,
+        Err(errno) => return -errno,
+    };
+    *current_proc().page_table().translate_mut_ptr(curr_value) = file.as_timerfd().unwrap().get();
+    0
+}
+
+/// Create a signalfd reading the signals of `mask` if `fd` is -1, or set the mask of signalfd
+/// `fd`. `SIGKILL` and `SIGSTOP` are never read.
+pub fn sys_signalfd4(fd: isize, mask: *const i32, sizemask: usize, flags: u32) -> isize {
+    trace!("sys_signalfd4: fd = {fd}, flags = {flags:#o}");
+    if sizemask != size_of::<SignalFlags>() {
+        return -EINVAL;
+    }
+    let flags = match fd_flags(flags) {
+        Ok(flags) => flags,
+        Err(errno) => return -errno,
+    };
+    let proc = current_proc();
+    let mask = SignalFlags::from_bits_truncate(*proc.page_table().translate_ptr(mask))
+        - (SignalFlags::SIGKILL | SignalFlags::SIGSTOP);
+    if fd == -1 {
+        return install_fd(SignalFd::new(mask, flags), flags);
+    }
+    let Some(file) = proc.borrow_inner_mut().get_file(fd as usize) else {
+        return -EBADF;
+    };
+    let Some(signalfd) = file.as_signalfd() else {
+        return -EINVAL;
+    };
+    signalfd.set_mask(mask);
+    fd
+}
+
+/// Return the time in milliseconds when a wait for the relative `timeout` expires, `None` to
+/// wait forever if it is null.
+fn expire_ms(pt: &PageTable, timeout: *const TimeSpec) -> Option<usize> {
+    (!timeout.is_null()).then(|| get_time_ms().saturating_add(pt.translate_ptr(timeout).as_ms()))
+}
+
+/// Wait until one of the `nfds` files of `fds` is ready for its events, or until `timeout`.
+/// Return the number of files with events returned, which is 0 if it has timed out. The
+/// blocked signals are not replaced during the wait, so `sigmask` is ignored.
+pub fn sys_ppoll(
+    fds: *mut PollFd,
+    nfds: usize,
+    timeout: *const TimeSpec,
+    _sigmask: usize,
+) -> isize {
+    trace!("sys_ppoll: fds = {fds:p}, nfds = {nfds}, timeout = {timeout:p}");
+    let proc = current_proc();
+    let pt = proc.page_table();
+    // each `PollFd` is aligned, so it never crosses a page
+    let mut fds: Vec<_> = (0..nfds)
+        .map(|i| pt.translate_mut_ptr(fds.wrapping_add(i)))
+        .collect();
+    let mut expire_ms = expire_ms(&pt, timeout);
+    let mut files = Vec::new();
+    let inner = proc.borrow_inner_mut();
+    for (i, fd) in fds.iter_mut().enumerate() {
+        fd.revents = PollEvents::empty();
+        // negative fds are ignored
+        let Ok(fd_num) = usize::try_from(fd.fd) else {
+            continue;
+        };
+        match inner.get_file(fd_num) {
+            Some(file) => files.push((i, file)),
+            None => {
+                fd.revents = PollEvents::NVAL;
+                expire_ms = Some(0);
+            }
+        }
+    }
+    drop(inner);
+    drop(pt);
+    drop(proc);
+    let polled: Vec<_> = files
+        .iter()
+        .map(|(i, file)| (Arc::clone(file), fds[*i].events))
+        .collect();
+    let ready = match poll(&polled, expire_ms) {
+        Ok(ready) => ready,
+        Err(errno) => return -errno,
+    };
+    for ((i, _), events) in files.iter().zip(ready) {
+        fds[*i].revents = events;
+    }
+    fds.iter().filter(|fd| !fd.revents.is_empty()).count() as isize
+}
+
+/// Wait until one of the files of the first `nfds` fds of `readfds` is readable or one of
+/// `writefds` is writable, or until `timeout`. The sets are replaced by the ready fds, whose
+/// number is returned. No exceptional conditions are reported in `exceptfds`, and `sigmask` is
+/// ignored.
+pub fn sys_pselect6(
+    nfds: usize,
+    readfds: *mut FdSet,
+    writefds: *mut FdSet,
+    exceptfds: *mut FdSet,
+    timeout: *const TimeSpec,
+    _sigmask: usize,
+) -> isize {
+    trace!("sys_pselect6: nfds = {nfds}, timeout = {timeout:p}");
+    if nfds > FD_SETSIZE {
+        return -EINVAL;
+    }
+    let proc = current_proc();
+    let pt = proc.page_table();
+    // the words of a set are read and written one by one as the set may cross a page
+    let word = |set: *mut FdSet, fd: usize| {
+        let (index, bit) = FdSet::position(fd);
+        (!set.is_null()).then(|| {
+            let word = unsafe { (&raw mut (*set).words[index]) };
+            (pt.translate_mut_ptr(word), bit)
+        })
+    };
+    let is_set =
+        |set: *mut FdSet, fd: usize| word(set, fd).is_some_and(|(word, bit)| *word & bit != 0);
+    let expire_ms = expire_ms(&pt, timeout);
+    let inner = proc.borrow_inner_mut();
+    let mut polled = Vec::new();
+    for fd in 0..nfds {
+        let mut events = PollEvents::empty();
+        events.set(PollEvents::IN, is_set(readfds, fd));
+        events.set(PollEvents::OUT, is_set(writefds, fd));
+        if events.is_empty() {
+            continue;
+        }
+        let Some(file) = inner.get_file(fd) else {
+            return -EBADF;
+        };
+        polled.push((fd, file, events));
+    }
+    drop(inner);
+    drop(proc);
+    let files: Vec<_> = polled
+        .iter()
+        .map(|(_, file, events)| (Arc::clone(file), *events))
+        .collect();
+    let ready = match poll(&files, expire_ms) {
+        Ok(ready) => ready,
+        Err(errno) => return -errno,
+    };
+    for fd in 0..nfds {
+        for set in [readfds, writefds, exceptfds] {
+            if let Some((word, bit)) = word(set, fd) {
+                *word &= !bit;
+            }
+        }
+    }
+    let mut count = 0;
+    for ((fd, _, requested), ready) in polled.iter().zip(ready) {
+        // a hangup or an error makes a read or a write return without blocking
+        let done = PollEvents::ERR | PollEvents::HUP;
+        for (set, event) in [(readfds, PollEvents::IN), (writefds, PollEvents::OUT)] {
+            if requested.contains(event)
+                && ready.intersects(event | done)
+                && let Some((word, bit)) = word(set, *fd)
+            {
+                *word |= bit;
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+/// Mount the filesystem of type `fstype` from `source` at directory `target`, `fstype` is
+/// easy_fs if null. `flags` and `data` are ignored.
+pub fn sys_mount(source: *const u8, target: *const u8, fstype: *const u8) -> isize {
+    let proc = current_proc();
+    let pt = proc.page_table();
+    let (Some(source), Some(target)) = (pt.read_c_str(source), pt.read_c_str(target)) else {
+        return -EFAULT;
+    };
+    let fstype = if fstype.is_null() {
+        String::from("easy_fs")
+    } else {
+        let Some(fstype) = pt.read_c_str(fstype) else {
+            return -EFAULT;
+        };
+        fstype
+    };
+    trace!("sys_mount: source = {source}, target = {target}, fstype = {fstype}");
+    drop(pt);
+    drop(proc);
+    match mount(&source, &target, &fstype) {
+        Ok(()) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+/// Unmount the filesystem at `target`, no `flags` are supported.
+pub fn sys_umount2(target: *const u8, flags: u32) -> isize {
+    if flags != 0 {
+        return -EINVAL;
+    }
+    let proc = current_proc();
+    let pt = proc.page_table();
+    let Some(target) = pt.read_c_str(target) else {
+        return -EFAULT;
+    };
+    trace!("sys_umount2: target = {target}");
+    drop(pt);
+    drop(proc);
+    match umount(&target) {
+        Ok(()) => 0,
+        Err(errno) => -errno,
+    }
+}
@@ -0,0 +1,263 @@
+//! Shared memory and message queue syscalls
+use alloc::sync::Arc;
+use common::{
+    errno::{EBADF, EFAULT, EINVAL, ENOMEM},
+    ipc::{IPC_RMID, IPC_SET, IPC_STAT, SHM_RDONLY, SHM_RND, ShmIdDs},
+    mqueue::MqAttr,
+    time::TimeSpec,
+};
+use log::trace;
+
+use crate::{
+    config::{PAGE_SIZE, USER_SHM_BASE, USER_SHM_END},
+    fs::{File, FileDescriptor, OpenFlags},
+    ipc::{mqueue, shm},
+    memory::{MapPermission, MapType, PageTable, VirtAddr, VirtPageNum},
+    proc::current_proc,
+};
+
+/// Return the id of the shared memory segment of `key`, see `shm::get`.
+pub fn sys_shmget(key: i32, size: usize, flags: u32) -> isize {
+    trace!("sys_shmget: key = {key}, size = {size:#x}, flags = {flags:#o}");
+    match shm::get(key, size, flags, current_proc().pid()) {
+        Ok(id) => id as isize,
+        Err(errno) => -errno,
+    }
+}
+
+/// Attach segment `id` at `addr`, or at a free address if null, and return the address.
+///
+/// Segments are attached between `USER_SHM_BASE` and `USER_SHM_END`, away from the stacks and
+/// the trap frames of threads created later.
+pub fn sys_shmat(id: usize, addr: usize, flags: u32) -> isize {
+    trace!("sys_shmat: id = {id}, addr = {addr:#x}, flags = {flags:#o}");
+    let addr = if flags & SHM_RND != 0 {
+        addr & !(PAGE_SIZE - 1)
+    } else {
+        addr
+    };
+    if addr % PAGE_SIZE != 0 || (addr != 0 && !(USER_SHM_BASE..USER_SHM_END).contains(&addr)) {
+        return -EINVAL;
+    }
+    let frames = match shm::frames(id) {
+        Ok(frames) => frames,
+        Err(errno) => return -errno,
+    };
+    let mut permission = MapPermission::R | MapPermission::U;
+    if flags & SHM_RDONLY == 0 {
+        permission |= MapPermission::W;
+    }
+    let proc = current_proc();
+    let mut inner = proc.borrow_inner_mut();
+    let end_vpn = VirtPageNum::new(USER_SHM_END / PAGE_SIZE);
+    let start_vpn = if addr == 0 {
+        let start_vpn = inner
+            .memory_space
+            .find_free_range(VirtAddr::new(USER_SHM_BASE).page_number(), frames.pages());
+        if start_vpn + frames.pages() > end_vpn {
+            return -ENOMEM;
+        }
+        start_vpn
+    } else {
+        let start_vpn = VirtAddr::new(addr).page_number();
+        if start_vpn + frames.pages() > end_vpn
+            || !inner
+                .memory_space
+                .is_free(start_vpn..start_vpn + frames.pages())
+        {
+            return -EINVAL;
+        }
+        start_vpn
+    };
+    inner
+        .memory_space
+        .insert_shared_area(start_vpn, frames, permission);
+    usize::from(VirtAddr::from(start_vpn)) as isize
+}
+
+/// Detach the segment attached at `addr`.
+pub fn sys_shmdt(addr: usize) -> isize {
+    trace!("sys_shmdt: addr = {addr:#x}");
+    if addr % PAGE_SIZE != 0 {
+        return -EINVAL;
+    }
+    let start_vpn = VirtAddr::new(addr).page_number();
+    let proc = current_proc();
+    let mut inner = proc.borrow_inner_mut();
+    if !inner
+        .memory_space
+        .areas()
+        .iter()
+        .any(|area| area.start_vpn() == start_vpn && area.map_type == MapType::Shared)
+    {
+        return -EINVAL;
+    }
+    inner.memory_space.remove_area_with_start_vpn(start_vpn);
+    0
+}
+
+pub fn sys_shmctl(id: usize, cmd: usize, buf: *mut ShmIdDs) -> isize {
+    trace!("sys_shmctl: id = {id}, cmd = {cmd}");
+    let result = match cmd {
+        IPC_STAT => shm::stat(id).map(|ds| {
+            *current_proc()
+                .borrow_inner_mut()
+                .memory_space
+                .translate_mut_ptr(buf) = ds;
+        }),
+        IPC_SET => {
+            let mode = current_proc()
+                .borrow_inner_mut()
+                .memory_space
+                .translate_mut_ptr(buf)
+                .mode;
+            shm::set_mode(id, mode)
+        }
+        IPC_RMID => shm::remove(id),
+        _ => Err(EINVAL),
+    };
+    match result {
+        Ok(()) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+/// Return the open file of `fd`, which must be a message queue.
+fn mqueue_file(fd: usize) -> Result<Arc<dyn File>, isize> {
+    let file = current_proc()
+        .borrow_inner_mut()
+        .get_file(fd)
+        .ok_or(EBADF)?;
+    if file.as_mqueue().is_none() {
+        return Err(EBADF);
+    }
+    Ok(file)
+}
+
+/// The time since boot at which `abs_timeout` expires, as there is no other clock.
+fn expire_ms(pt: &PageTable, abs_timeout: *const TimeSpec) -> Option<usize> {
+    (!abs_timeout.is_null()).then(|| pt.translate_ptr(abs_timeout).as_ms())
+}
+
+/// Open the message queue `name`, without its leading slash, and return its fd. A queue
+/// created with `O_CREAT` has the capacity in `attr`, or the default one if null. There are
+/// no permissions, so `mode` is ignored.
+pub fn sys_mq_open(name: *const u8, flags: u32, _mode: u32, attr: *const MqAttr) -> isize {
+    let Some(flags) = OpenFlags::from_bits(flags) else {
+        return -EINVAL;
+    };
+    let proc = current_proc();
+    let pt = proc.page_table();
+    let Some(name) = pt.read_c_str(name) else {
+        return -EFAULT;
+    };
+    trace!("sys_mq_open: name = {name}, flags = {flags:?}");
+    let attr =
+        (flags.contains(OpenFlags::CREATE) && !attr.is_null()).then(|| *pt.translate_ptr(attr));
+    match mqueue::open(&name, flags, attr) {
+        Ok(file) => {
+            let mut inner = proc.borrow_inner_mut();
+            let fd = inner.alloc_fd();
+            let cloexec = flags.contains(OpenFlags::CLOEXEC);
+            inner.fd_table[fd] = Some(FileDescriptor::new(file, cloexec));
+            fd as isize
+        }
+        Err(errno) => -errno,
+    }
+}
+
+pub fn sys_mq_unlink(name: *const u8) -> isize {
+    let Some(name) = current_proc().page_table().read_c_str(name) else {
+        return -EFAULT;
+    };
+    trace!("sys_mq_unlink: name = {name}");
+    match mqueue::unlink(&name) {
+        Ok(()) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+/// Send the `len` bytes at `msg` with `priority`, waiting for room until `abs_timeout` unless
+/// null or the queue is nonblocking.
+pub fn sys_mq_timedsend(
+    fd: usize,
+    msg: *const u8,
+    len: usize,
+    priority: u32,
+    abs_timeout: *const TimeSpec,
+) -> isize {
+    trace!("sys_mq_timedsend: fd = {fd}, len = {len}, priority = {priority}");
+    let file = match mqueue_file(fd) {
+        Ok(file) => file,
+        Err(errno) => return -errno,
+    };
+    if !file.writable() {
+        return -EBADF;
+    }
+    let pt = current_proc().page_table();
+    let message = pt
+        .translate_bytes_buffer(VirtAddr::new(msg as usize), len)
+        .to_vec();
+    let expire_ms = expire_ms(&pt, abs_timeout);
+    match file.as_mqueue().unwrap().send(message, priority, expire_ms) {
+        Ok(()) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+/// Receive a message into the `len` bytes at `msg`, which must hold the largest message of
+/// the queue, and write its priority to `priority` if not null. Return its length.
+pub fn sys_mq_timedreceive(
+    fd: usize,
+    msg: *mut u8,
+    len: usize,
+    priority: *mut u32,
+    abs_timeout: *const TimeSpec,
+) -> isize {
+    trace!("sys_mq_timedreceive: fd = {fd}, len = {len}");
+    let file = match mqueue_file(fd) {
+        Ok(file) => file,
+        Err(errno) => return -errno,
+    };
+    if !file.readable() {
+        return -EBADF;
+    }
+    let pt = current_proc().page_table();
+    let buf = pt.translate_bytes_buffer(VirtAddr::new(msg as usize), len);
+    let expire_ms = expire_ms(&pt, abs_timeout);
+    match file.as_mqueue().unwrap().receive(buf, expire_ms) {
+        Ok((len, message_priority)) => {
+            if !priority.is_null() {
+                *pt.translate_mut_ptr(priority) = message_priority;
+            }
+            len as isize
+        }
+        Err(errno) => -errno,
+    }
+}
+
+/// Write the attributes of the queue to `old_attr` if not null, then set its `O_NONBLOCK`
+/// flag from `new_attr` if not null.
+pub fn sys_mq_getsetattr(fd: usize, new_attr: *const MqAttr, old_attr: *mut MqAttr) -> isize {
+    trace!("sys_mq_getsetattr: fd = {fd}");
+    let file = match mqueue_file(fd) {
+        Ok(file) => file,
+        Err(errno) => return -errno,
+    };
+    let pt = current_proc().page_table();
+    let new_attr = (!new_attr.is_null()).then(|| *pt.translate_ptr(new_attr));
+    if let Some(new_attr) = new_attr
+        && new_attr.flags & !(OpenFlags::NONBLOCK.bits() as i64) != 0
+    {
+        return -EINVAL;
+    }
+    if !old_attr.is_null() {
+        *pt.translate_mut_ptr(old_attr) = file.as_mqueue().unwrap().attr();
+    }
+    if let Some(new_attr) = new_attr {
+        let mut flags = file.status().get();
+        flags.set(OpenFlags::NONBLOCK, new_attr.flags != 0);
+        file.status().set(flags);
+    }
+    0
+}
@@ -1,21 +1,167 @@
-use common::syscall_id::*;
+use common::{
+    fs::Stat,
+    ipc::ShmIdDs,
+    mqueue::MqAttr,
+    poll::{FdSet, PollFd},
+    syscall_id::*,
+    time::{ItimerSpec, TimeSpec},
+};
 use log::warn;
 
 mod fs;
+mod ipc;
+mod net;
 mod process;
+mod sync;
+mod thread;
 
-pub fn syscall(syscall_id: usize, args: [usize; 3]) -> Option<isize> {
+pub fn syscall(syscall_id: usize, args: [usize; 6]) -> Option<isize> {
     let ret = match syscall_id {
+        SYSCALL_EVENTFD2 => fs::sys_eventfd2(args[0] as u32, args[1] as u32),
+        SYSCALL_DUP => fs::sys_dup(args[0]),
+        SYSCALL_DUP3 => fs::sys_dup3(args[0], args[1], args[2] as u32),
+        SYSCALL_FCNTL => fs::sys_fcntl(args[0], args[1], args[2]),
+        SYSCALL_IOCTL => fs::sys_ioctl(args[0], args[1], args[2]),
+        SYSCALL_MKNODAT => fs::sys_mknodat(
+            args[0] as isize,
+            args[1] as *const u8,
+            args[2] as u32,
+            args[3],
+        ),
+        SYSCALL_MKDIRAT => fs::sys_mkdirat(args[0] as isize, args[1] as *const u8, args[2] as u32),
+        SYSCALL_UNLINKAT => {
+            fs::sys_unlinkat(args[0] as isize, args[1] as *const u8, args[2] as u32)
+        }
+        SYSCALL_UMOUNT2 => fs::sys_umount2(args[0] as *const u8, args[1] as u32),
+        SYSCALL_MOUNT => fs::sys_mount(
+            args[0] as *const u8,
+            args[1] as *const u8,
+            args[2] as *const u8,
+        ),
         SYSCALL_OPEN => fs::sys_open(args[0] as *const u8, args[1] as u32),
         SYSCALL_CLOSE => fs::sys_close(args[0]),
-        SYSCALL_PIPE => fs::sys_pipe(args[0] as *mut usize),
+        SYSCALL_PIPE2 => fs::sys_pipe2(args[0] as *mut usize, args[1] as u32),
+        SYSCALL_GETDENTS64 => fs::sys_getdents64(args[0], args[1] as *mut u8, args[2]),
         SYSCALL_READ => fs::sys_read(args[0], args[1] as *const u8, args[2]),
         SYSCALL_WRITE => fs::sys_write(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_PSELECT6 => fs::sys_pselect6(
+            args[0],
+            args[1] as *mut FdSet,
+            args[2] as *mut FdSet,
+            args[3] as *mut FdSet,
+            args[4] as *const TimeSpec,
+            args[5],
+        ),
+        SYSCALL_PPOLL => fs::sys_ppoll(
+            args[0] as *mut PollFd,
+            args[1],
+            args[2] as *const TimeSpec,
+            args[3],
+        ),
+        SYSCALL_SIGNALFD4 => fs::sys_signalfd4(
+            args[0] as isize,
+            args[1] as *const i32,
+            args[2],
+            args[3] as u32,
+        ),
+        SYSCALL_FSTAT => fs::sys_fstat(args[0], args[1] as *mut Stat),
+        SYSCALL_TIMERFD_CREATE => fs::sys_timerfd_create(args[0], args[1] as u32),
+        SYSCALL_TIMERFD_SETTIME => fs::sys_timerfd_settime(
+            args[0],
+            args[1] as u32,
+            args[2] as *const ItimerSpec,
+            args[3] as *mut ItimerSpec,
+        ),
+        SYSCALL_TIMERFD_GETTIME => fs::sys_timerfd_gettime(args[0], args[1] as *mut ItimerSpec),
+        SYSCALL_MQ_OPEN => ipc::sys_mq_open(
+            args[0] as *const u8,
+            args[1] as u32,
+            args[2] as u32,
+            args[3] as *const MqAttr,
+        ),
+        SYSCALL_MQ_UNLINK => ipc::sys_mq_unlink(args[0] as *const u8),
+        SYSCALL_MQ_TIMEDSEND => ipc::sys_mq_timedsend(
+            args[0],
+            args[1] as *const u8,
+            args[2],
+            args[3] as u32,
+            args[4] as *const TimeSpec,
+        ),
+        SYSCALL_MQ_TIMEDRECEIVE => ipc::sys_mq_timedreceive(
+            args[0],
+            args[1] as *mut u8,
+            args[2],
+            args[3] as *mut u32,
+            args[4] as *const TimeSpec,
+        ),
+        SYSCALL_MQ_GETSETATTR => {
+            ipc::sys_mq_getsetattr(args[0], args[1] as *const MqAttr, args[2] as *mut MqAttr)
+        }
+        SYSCALL_SHMGET => ipc::sys_shmget(args[0] as i32, args[1], args[2] as u32),
+        SYSCALL_SHMCTL => ipc::sys_shmctl(args[0], args[1], args[2] as *mut ShmIdDs),
+        SYSCALL_SHMAT => ipc::sys_shmat(args[0], args[1], args[2] as u32),
+        SYSCALL_SHMDT => ipc::sys_shmdt(args[0]),
+        SYSCALL_SOCKET => net::sys_socket(args[0], args[1] as u32, args[2]),
+        SYSCALL_SOCKETPAIR => {
+            net::sys_socketpair(args[0], args[1] as u32, args[2], args[3] as *mut usize)
+        }
+        SYSCALL_BIND => net::sys_bind(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_LISTEN => net::sys_listen(args[0], args[1]),
+        SYSCALL_ACCEPT => net::sys_accept4(args[0], args[1] as *mut u8, args[2] as *mut u32, 0),
+        SYSCALL_ACCEPT4 => net::sys_accept4(
+            args[0],
+            args[1] as *mut u8,
+            args[2] as *mut u32,
+            args[3] as u32,
+        ),
+        SYSCALL_CONNECT => net::sys_connect(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_SENDTO => net::sys_sendto(
+            args[0],
+            args[1] as *const u8,
+            args[2],
+            args[3] as u32,
+            args[4] as *const u8,
+            args[5],
+        ),
+        SYSCALL_RECVFROM => net::sys_recvfrom(
+            args[0],
+            args[1] as *mut u8,
+            args[2],
+            args[3] as u32,
+            args[4] as *mut u8,
+            args[5] as *mut u32,
+        ),
         SYSCALL_EXIT => process::sys_exit(args[0] as i32),
+        SYSCALL_EXIT_GROUP => process::sys_exit_group(args[0] as i32),
+        SYSCALL_FUTEX => sync::sys_futex(
+            args[0] as *const u32,
+            args[1],
+            args[2],
+            args[3] as *const TimeSpec,
+        ),
         SYSCALL_YIELD => process::sys_yield(),
+        SYSCALL_RT_SIGPROCMASK => process::sys_rt_sigprocmask(
+            args[0],
+            args[1] as *const i32,
+            args[2] as *mut i32,
+            args[3],
+        ),
         SYSCALL_FORK => process::sys_fork(),
         SYSCALL_EXEC => process::sys_exec(args[0] as *const u8, args[1] as *const usize),
         SYSCALL_WAITPID => process::sys_waitpid(args[0] as isize, args[1] as *mut i32),
+        SYSCALL_THREAD_CREATE => thread::sys_thread_create(args[0], args[1]),
+        SYSCALL_GETTID => thread::sys_gettid(),
+        SYSCALL_WAITTID => thread::sys_waittid(args[0]),
+        SYSCALL_MUTEX_CREATE => sync::sys_mutex_create(),
+        SYSCALL_MUTEX_LOCK => sync::sys_mutex_lock(args[0]),
+        SYSCALL_MUTEX_UNLOCK => sync::sys_mutex_unlock(args[0]),
+        SYSCALL_SEMAPHORE_CREATE => sync::sys_semaphore_create(args[0]),
+        SYSCALL_SEMAPHORE_UP => sync::sys_semaphore_up(args[0]),
+        SYSCALL_SEMAPHORE_DOWN => sync::sys_semaphore_down(args[0]),
+        SYSCALL_CONDVAR_CREATE => sync::sys_condvar_create(),
+        SYSCALL_CONDVAR_SIGNAL => sync::sys_condvar_signal(args[0]),
+        SYSCALL_CONDVAR_WAIT => sync::sys_condvar_wait(args[0], args[1]),
+        SYSCALL_ENABLE_DEADLOCK_DETECT => sync::sys_enable_deadlock_detect(args[0]),
         _ => {
             warn!("Unknown syscall: {syscall_id}");
             return None;
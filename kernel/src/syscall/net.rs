@@ -0,0 +1,220 @@
+//! Socket syscalls
+
+use alloc::sync::Arc;
+use common::{
+    errno::{EAFNOSUPPORT, EBADF, EFAULT, EINVAL, ENOTSOCK, EPROTONOSUPPORT},
+    socket::{AF_UNIX, MsgFlags, SOCK_TYPE_MASK},
+};
+use log::trace;
+
+use crate::{
+    fs::{File, FileDescriptor, OpenFlags},
+    memory::{PageTable, VirtAddr},
+    net::{self, SocketAddr, SocketType, UnixSocket},
+    proc::current_proc,
+};
+
+/// The longest address read from user space.
+const MAX_ADDR_LEN: usize = 128;
+
+/// Split the `type` argument of `socket` into the socket type and the status flags.
+fn socket_type(type_: u32) -> Result<(SocketType, OpenFlags), isize> {
+    let socket_type = SocketType::from_raw(type_ & SOCK_TYPE_MASK).ok_or(EINVAL)?;
+    let flags = OpenFlags::from_bits(type_ & !SOCK_TYPE_MASK).ok_or(EINVAL)?;
+    if !(OpenFlags::CLOEXEC | OpenFlags::NONBLOCK).contains(flags) {
+        return Err(EINVAL);
+    }
+    Ok((socket_type, flags))
+}
+
+/// Return the open file of `fd`, which must be a socket.
+fn socket_file(fd: usize) -> Result<Arc<dyn File>, isize> {
+    let file = current_proc()
+        .borrow_inner_mut()
+        .get_file(fd)
+        .ok_or(EBADF)?;
+    if file.as_socket().is_none() {
+        return Err(ENOTSOCK);
+    }
+    Ok(file)
+}
+
+fn read_addr(pt: &PageTable, addr: *const u8, len: usize) -> Result<SocketAddr, isize> {
+    if addr.is_null() {
+        return Err(EFAULT);
+    }
+    if len > MAX_ADDR_LEN {
+        return Err(EINVAL);
+    }
+    let bytes = pt
+        .translate_bytes_buffer(VirtAddr::new(addr as usize), len)
+        .to_vec();
+    SocketAddr::from_bytes(&bytes)
+}
+
+/// Write `addr` to `ptr` if not null, truncated to the length at `len`, which is set to the
+/// length of `addr`.
+fn write_addr(pt: &PageTable, addr: &SocketAddr, ptr: *mut u8, len: *mut u32) {
+    if ptr.is_null() {
+        return;
+    }
+    let bytes = addr.to_bytes();
+    let len = pt.translate_mut_ptr(len);
+    pt.copy_out(
+        VirtAddr::new(ptr as usize),
+        &bytes[..bytes.len().min(*len as usize)],
+    );
+    *len = bytes.len() as u32;
+}
+
+fn install(file: Arc<dyn File>, cloexec: bool) -> usize {
+    let proc = current_proc();
+    let mut inner = proc.borrow_inner_mut();
+    let fd = inner.alloc_fd();
+    inner.fd_table[fd] = Some(FileDescriptor::new(file, cloexec));
+    fd
+}
+
+/// Create a socket, `type_` may be ORed with `SOCK_NONBLOCK` and `SOCK_CLOEXEC`.
+pub fn sys_socket(domain: usize, type_: u32, protocol: usize) -> isize {
+    trace!("sys_socket: domain = {domain}, type = {type_:#x}, protocol = {protocol}");
+    let (socket_type, flags) = match socket_type(type_) {
+        Ok(type_) => type_,
+        Err(errno) => return -errno,
+    };
+    match net::socket(domain as u16, socket_type, protocol, flags) {
+        Ok(socket) => install(socket, flags.contains(OpenFlags::CLOEXEC)) as isize,
+        Err(errno) => -errno,
+    }
+}
+
+/// Create two local sockets connected to each other.
+pub fn sys_socketpair(domain: usize, type_: u32, protocol: usize, sv: *mut usize) -> isize {
+    trace!("sys_socketpair: domain = {domain}, type = {type_:#x}, protocol = {protocol}");
+    let (socket_type, flags) = match socket_type(type_) {
+        Ok(type_) => type_,
+        Err(errno) => return -errno,
+    };
+    if domain != AF_UNIX as usize {
+        return -EAFNOSUPPORT;
+    }
+    if protocol != 0 {
+        return -EPROTONOSUPPORT;
+    }
+    let (first, second) = UnixSocket::pair(socket_type, flags);
+    let cloexec = flags.contains(OpenFlags::CLOEXEC);
+    let first = install(first, cloexec);
+    let second = install(second, cloexec);
+    let pt = current_proc().page_table();
+    unsafe {
+        *pt.translate_mut_ptr(sv) = first;
+        *pt.translate_mut_ptr(sv.add(1)) = second;
+    }
+    0
+}
+
+pub fn sys_bind(fd: usize, addr: *const u8, len: usize) -> isize {
+    trace!("sys_bind: fd = {fd}, addr = {addr:p}, len = {len}");
+    let result = socket_file(fd).and_then(|file| {
+        let addr = read_addr(&current_proc().page_table(), addr, len)?;
+        file.as_socket().unwrap().bind(addr)
+    });
+    match result {
+        Ok(()) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+pub fn sys_listen(fd: usize, backlog: usize) -> isize {
+    trace!("sys_listen: fd = {fd}, backlog = {backlog}");
+    match socket_file(fd).and_then(|file| file.as_socket().unwrap().listen(backlog)) {
+        Ok(()) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+/// Accept a connection, writing the address of the peer to `addr` if not null. `flags` may
+/// be `SOCK_NONBLOCK` and `SOCK_CLOEXEC`.
+pub fn sys_accept4(fd: usize, addr: *mut u8, len: *mut u32, flags: u32) -> isize {
+    trace!("sys_accept4: fd = {fd}, addr = {addr:p}, flags = {flags:#x}");
+    let Some(flags) = OpenFlags::from_bits(flags) else {
+        return -EINVAL;
+    };
+    if !(OpenFlags::CLOEXEC | OpenFlags::NONBLOCK).contains(flags) {
+        return -EINVAL;
+    }
+    let (socket, peer) = match socket_file(fd).and_then(|file| file.as_socket().unwrap().accept()) {
+        Ok(accepted) => accepted,
+        Err(errno) => return -errno,
+    };
+    socket.status().set(flags);
+    write_addr(&current_proc().page_table(), &peer, addr, len);
+    install(socket, flags.contains(OpenFlags::CLOEXEC)) as isize
+}
+
+pub fn sys_connect(fd: usize, addr: *const u8, len: usize) -> isize {
+    trace!("sys_connect: fd = {fd}, addr = {addr:p}, len = {len}");
+    let result = socket_file(fd).and_then(|file| {
+        let addr = read_addr(&current_proc().page_table(), addr, len)?;
+        file.as_socket().unwrap().connect(addr)
+    });
+    match result {
+        Ok(()) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+/// Send `buf` to the connected peer, or to `addr` if not null.
+pub fn sys_sendto(
+    fd: usize,
+    buf: *const u8,
+    len: usize,
+    flags: u32,
+    addr: *const u8,
+    addr_len: usize,
+) -> isize {
+    trace!("sys_sendto: fd = {fd}, len = {len}, flags = {flags:#x}, addr = {addr:p}");
+    let Some(flags) = MsgFlags::from_bits(flags) else {
+        return -EINVAL;
+    };
+    let result = socket_file(fd).and_then(|file| {
+        let pt = current_proc().page_table();
+        let addr = if addr.is_null() {
+            None
+        } else {
+            Some(read_addr(&pt, addr, addr_len)?)
+        };
+        let buf = pt.translate_bytes_buffer(VirtAddr::new(buf as usize), len);
+        file.as_socket().unwrap().send_to(buf, flags, addr)
+    });
+    match result {
+        Ok(len) => len as isize,
+        Err(errno) => -errno,
+    }
+}
+
+/// Receive to `buf`, writing the address of the sender to `addr` if not null.
+pub fn sys_recvfrom(
+    fd: usize,
+    buf: *mut u8,
+    len: usize,
+    flags: u32,
+    addr: *mut u8,
+    addr_len: *mut u32,
+) -> isize {
+    trace!("sys_recvfrom: fd = {fd}, len = {len}, flags = {flags:#x}, addr = {addr:p}");
+    let Some(flags) = MsgFlags::from_bits(flags) else {
+        return -EINVAL;
+    };
+    let result = socket_file(fd).and_then(|file| {
+        let pt = current_proc().page_table();
+        let buf = pt.translate_bytes_buffer(VirtAddr::new(buf as usize), len);
+        let (len, sender) = file.as_socket().unwrap().recv_from(buf, flags)?;
+        write_addr(&pt, &sender, addr, addr_len);
+        Ok(len)
+    });
+    match result {
+        Ok(len) => len as isize,
+        Err(errno) => -errno,
+    }
+}
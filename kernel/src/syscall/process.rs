@@ -1,17 +1,32 @@
 //! App management syscalls
 use alloc::{string::String, sync::Arc, vec::Vec};
+use common::{
+    errno::EINVAL,
+    fs::FileType,
+    sig::{SIG_BLOCK, SIG_SETMASK, SIG_UNBLOCK, SignalFlags},
+};
 use log::trace;
 
 use crate::{
-    fs::{OpenFlags, open_file},
-    proc::{PROC_MANAGER, current_proc, exit_current_and_run_next, suspend_current_and_run_next},
+    fs::{File, OpenFlags, open_file},
+    proc::{
+        PROC_MANAGER, ProcControlBlock, block_current_and_run_next, current_proc, current_thread,
+        exit_current_and_run_next, exit_group_and_run_next, suspend_current_and_run_next,
+    },
 };
 
-/// task exits and submit an exit code
+/// thread exits and submit an exit code, the process exits if it is the main thread
 pub fn sys_exit(exit_code: i32) -> ! {
     trace!("sys_exit: exit_code = {exit_code}");
     exit_current_and_run_next(exit_code);
-    unreachable!("Process should not return from sys_exit");
+    unreachable!("Thread should not return from sys_exit");
+}
+
+/// all threads of the process exit
+pub fn sys_exit_group(exit_code: i32) -> ! {
+    trace!("sys_exit_group: exit_code = {exit_code}");
+    exit_group_and_run_next(exit_code);
+    unreachable!("Process should not return from sys_exit_group");
 }
 
 pub fn sys_yield() -> isize {
@@ -22,13 +37,17 @@ pub fn sys_yield() -> isize {
 pub fn sys_fork() -> isize {
     trace!("sys_fork");
     let parent = current_proc();
-    let child = parent.fork();
+    let child = match parent.fork() {
+        Ok(child) => child,
+        Err(errno) => return -errno,
+    };
 
     let child_pid = child.pid();
-    let child_trap_frame = child.borrow_inner_mut().get_trap_frame_mut();
+    let child_thread = child.borrow_inner_mut().get_thread(0);
+    let child_trap_frame = child_thread.borrow_inner_mut().get_trap_frame_mut();
     child_trap_frame.x[10] = 0;
 
-    PROC_MANAGER.borrow_mut().push(child);
+    PROC_MANAGER.lock().push(child_thread);
 
     child_pid as isize
 }
@@ -47,36 +66,74 @@ pub fn sys_exec(path: *const u8, mut args: *const usize) -> isize {
         unsafe { args = args.add(1) };
     }
     trace!("sys_exec: path = {name}, args = {args_vec:?}");
-    if let Some(app_inode) = open_file(name.as_str(), OpenFlags::RDONLY) {
-        proc.exec(app_inode.read_all(), args_vec)
-    } else {
-        -1
+    match open_file(name.as_str(), OpenFlags::RDONLY) {
+        Ok(app_inode) if app_inode.stat().file_type() == Some(FileType::Regular) => {
+            proc.exec(app_inode.read_all(), args_vec)
+        }
+        _ => -1,
     }
 }
 
 /// If there is not a child process whose pid is same as given, return -1.
-/// Else if there is a child process but it is still running, return -2.
+/// Else block until such a child process exits and return its pid.
 pub fn sys_waitpid(pid: isize, status: *mut i32) -> isize {
-    let proc = current_proc();
-    let mut proc_inner = proc.borrow_inner_mut();
-
-    let Some(idx) = proc_inner
-        .children
-        .iter()
-        .enumerate()
-        .find_map(|(idx, pcb)| (pid == -1 || pcb.pid() == pid as usize).then_some(idx))
-    else {
-        // No child process matches the given pid
-        return -1;
-    };
-    if !proc_inner.children[idx].borrow_inner_mut().is_zombie() {
-        return -2; // Child process is still running
+    let matches = |pcb: &Arc<ProcControlBlock>| pid == -1 || pcb.pid() == pid as usize;
+    loop {
+        let proc = current_proc();
+        let mut proc_inner = proc.borrow_inner_mut();
+        if !proc_inner.children.iter().any(matches) {
+            // No child process matches the given pid
+            return -1;
+        }
+        if let Some(idx) = proc_inner
+            .children
+            .iter()
+            .position(|pcb| matches(pcb) && pcb.borrow_inner_mut().is_zombie())
+        {
+            // the exiting thread of the child may still hold it on another hart
+            let child = proc_inner.children.remove(idx);
+            let proc_pid = child.pid();
+            let exit_code = child.borrow_inner_mut().exit_code;
+            *proc_inner.memory_space.translate_mut_ptr(status) = exit_code;
+            return proc_pid as isize;
+        }
+        // checked with the process locked, so that an exit after the check is not missed
+        proc_inner.child_exit_waiters.push(current_thread());
+        drop(proc_inner);
+        drop(proc);
+        block_current_and_run_next();
     }
+}
 
-    let child = proc_inner.children.remove(idx);
-    assert_eq!(Arc::strong_count(&child), 1);
-    let proc_pid = child.pid();
-    let exit_code = child.borrow_inner_mut().exit_code;
-    *proc_inner.memory_space.translate_mut_ptr(status) = exit_code;
-    proc_pid as isize
+/// Change the blocked signals by `how` with `set` unless null, after storing them in `oldset`
+/// unless null. Blocked signals stay pending until read from a signalfd, `SIGKILL` and
+/// `SIGSTOP` cannot be blocked.
+pub fn sys_rt_sigprocmask(
+    how: usize,
+    set: *const i32,
+    oldset: *mut i32,
+    sigsetsize: usize,
+) -> isize {
+    trace!("sys_rt_sigprocmask: how = {how}");
+    if sigsetsize != size_of::<SignalFlags>() {
+        return -EINVAL;
+    }
+    let proc = current_proc();
+    let pt = proc.page_table();
+    let mut inner = proc.borrow_inner_mut();
+    let old = inner.blocked;
+    if !set.is_null() {
+        let set = SignalFlags::from_bits_truncate(*pt.translate_ptr(set))
+            - (SignalFlags::SIGKILL | SignalFlags::SIGSTOP);
+        inner.blocked = match how {
+            SIG_BLOCK => old | set,
+            SIG_UNBLOCK => old - set,
+            SIG_SETMASK => set,
+            _ => return -EINVAL,
+        };
+    }
+    if !oldset.is_null() {
+        *pt.translate_mut_ptr(oldset) = old.bits();
+    }
+    0
 }
@@ -0,0 +1,239 @@
+//! Synchronization syscalls for the threads of a process
+use alloc::{sync::Arc, vec::Vec};
+use common::{
+    errno::{EAGAIN, EDEADLK, EFAULT, EINVAL, EPERM, ETIMEDOUT},
+    futex::{FUTEX_WAIT, FUTEX_WAKE},
+    time::TimeSpec,
+};
+use log::trace;
+
+use crate::{
+    memory::VirtAddr,
+    proc::{current_proc, current_thread},
+    sync::{Condvar, FutexWait, Mutex, MutexBlocking, Resource, Semaphore, futex_wait, futex_wake},
+    timer::get_time_ms,
+};
+
+/// Put `item` into the first free slot of `list`, return the index of the slot.
+fn insert_into_list<T>(list: &mut Vec<Option<T>>, item: T) -> usize {
+    if let Some(id) = list.iter().position(|slot| slot.is_none()) {
+        list[id] = Some(item);
+        id
+    } else {
+        list.push(Some(item));
+        list.len() - 1
+    }
+}
+
+/// Create a mutex, return its id.
+pub fn sys_mutex_create() -> isize {
+    let proc = current_proc();
+    let mut inner = proc.borrow_inner_mut();
+    let mutex: Arc<dyn Mutex> = Arc::new(MutexBlocking::new());
+    let id = insert_into_list(&mut inner.mutex_list, mutex);
+    inner.deadlock_detector.add_resource(Resource::Mutex(id), 1);
+    trace!("sys_mutex_create: id = {id}");
+    id as isize
+}
+
+/// Lock the mutex, return -EDEADLK if the deadlock detection is enabled and it would deadlock.
+pub fn sys_mutex_lock(mutex_id: usize) -> isize {
+    let tid = current_thread().tid();
+    let proc = current_proc();
+    let mut inner = proc.borrow_inner_mut();
+    let Some(Some(mutex)) = inner.mutex_list.get(mutex_id) else {
+        return -1;
+    };
+    let mutex = Arc::clone(mutex);
+    if !inner
+        .deadlock_detector
+        .request(tid, Resource::Mutex(mutex_id))
+    {
+        return -EDEADLK;
+    }
+    drop(inner);
+    mutex.lock();
+    proc.borrow_inner_mut()
+        .deadlock_detector
+        .acquired(tid, Resource::Mutex(mutex_id));
+    0
+}
+
+/// Unlock the mutex, return -EPERM if it is not locked.
+pub fn sys_mutex_unlock(mutex_id: usize) -> isize {
+    let tid = current_thread().tid();
+    let proc = current_proc();
+    let mut inner = proc.borrow_inner_mut();
+    let Some(Some(mutex)) = inner.mutex_list.get(mutex_id) else {
+        return -1;
+    };
+    // the process stays locked so that a thread woken up records its acquisition after the
+    // release
+    if mutex.unlock().is_err() {
+        return -EPERM;
+    }
+    inner
+        .deadlock_detector
+        .release(tid, Resource::Mutex(mutex_id));
+    0
+}
+
+/// Create a semaphore with `res_count` resources, return its id.
+pub fn sys_semaphore_create(res_count: usize) -> isize {
+    let proc = current_proc();
+    let mut inner = proc.borrow_inner_mut();
+    let id = insert_into_list(
+        &mut inner.semaphore_list,
+        Arc::new(Semaphore::new(res_count)),
+    );
+    inner
+        .deadlock_detector
+        .add_resource(Resource::Semaphore(id), res_count);
+    trace!("sys_semaphore_create: id = {id}, res_count = {res_count}");
+    id as isize
+}
+
+pub fn sys_semaphore_up(sem_id: usize) -> isize {
+    let tid = current_thread().tid();
+    let proc = current_proc();
+    let mut inner = proc.borrow_inner_mut();
+    let Some(Some(sem)) = inner.semaphore_list.get(sem_id) else {
+        return -1;
+    };
+    let sem = Arc::clone(sem);
+    inner
+        .deadlock_detector
+        .release(tid, Resource::Semaphore(sem_id));
+    drop(inner);
+    sem.up();
+    0
+}
+
+/// Take a resource of the semaphore, return -EDEADLK if the deadlock detection is enabled and
+/// it would deadlock.
+pub fn sys_semaphore_down(sem_id: usize) -> isize {
+    let tid = current_thread().tid();
+    let proc = current_proc();
+    let mut inner = proc.borrow_inner_mut();
+    let Some(Some(sem)) = inner.semaphore_list.get(sem_id) else {
+        return -1;
+    };
+    let sem = Arc::clone(sem);
+    if !inner
+        .deadlock_detector
+        .request(tid, Resource::Semaphore(sem_id))
+    {
+        return -EDEADLK;
+    }
+    drop(inner);
+    sem.down();
+    proc.borrow_inner_mut()
+        .deadlock_detector
+        .acquired(tid, Resource::Semaphore(sem_id));
+    0
+}
+
+/// Create a condition variable, return its id.
+pub fn sys_condvar_create() -> isize {
+    let proc = current_proc();
+    let mut inner = proc.borrow_inner_mut();
+    let id = insert_into_list(&mut inner.condvar_list, Arc::new(Condvar::new()));
+    trace!("sys_condvar_create: id = {id}");
+    id as isize
+}
+
+pub fn sys_condvar_signal(condvar_id: usize) -> isize {
+    let proc = current_proc();
+    let inner = proc.borrow_inner_mut();
+    let Some(Some(condvar)) = inner.condvar_list.get(condvar_id) else {
+        return -1;
+    };
+    let condvar = Arc::clone(condvar);
+    drop(inner);
+    condvar.signal();
+    0
+}
+
+/// Release the mutex and wait for the condition variable, the mutex is locked again on return.
+///
+/// Return -EPERM if the mutex is not held, or -EDEADLK if the deadlock detection is enabled and
+/// waiting for the mutex again would deadlock.
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+    let tid = current_thread().tid();
+    let proc = current_proc();
+    let mut inner = proc.borrow_inner_mut();
+    let (Some(Some(condvar)), Some(Some(mutex))) = (
+        inner.condvar_list.get(condvar_id),
+        inner.mutex_list.get(mutex_id),
+    ) else {
+        return -1;
+    };
+    let condvar = Arc::clone(condvar);
+    let mutex = Arc::clone(mutex);
+    if let Err(errno) = inner
+        .deadlock_detector
+        .release_and_request(tid, Resource::Mutex(mutex_id))
+    {
+        return -errno;
+    }
+    drop(inner);
+    let result = condvar.wait(mutex);
+    // on failure the mutex has been unlocked by another thread and is still recorded as ours
+    proc.borrow_inner_mut()
+        .deadlock_detector
+        .acquired(tid, Resource::Mutex(mutex_id));
+    match result {
+        Ok(()) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+/// Enable (1) or disable (0) the deadlock detection for the current process.
+pub fn sys_enable_deadlock_detect(enabled: usize) -> isize {
+    match enabled {
+        0 | 1 => {
+            current_proc()
+                .borrow_inner_mut()
+                .deadlock_detector
+                .set_enabled(enabled == 1);
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// Wait on or wake up the futex at `uaddr`.
+///
+/// `FUTEX_WAIT` blocks if `*uaddr == val` until woken up or `timeout` (if not null) has
+/// elapsed. `FUTEX_WAKE` wakes up at most `val` waiters and returns the number of woken ones.
+pub fn sys_futex(uaddr: *const u32, op: usize, val: usize, timeout: *const TimeSpec) -> isize {
+    trace!("sys_futex: uaddr = {uaddr:?}, op = {op}, val = {val}");
+    if uaddr as usize % core::mem::size_of::<u32>() != 0 {
+        return -EINVAL;
+    }
+    let proc = current_proc();
+    let page_table = proc.page_table();
+    // the futex word never crosses a page as it is aligned
+    let Some(pa) = VirtAddr::try_new(uaddr as usize)
+        .ok()
+        .and_then(|va| page_table.translate_va(va))
+    else {
+        return -EFAULT;
+    };
+    let key = usize::from(pa);
+    match op {
+        FUTEX_WAIT => {
+            let expire_ms = (!timeout.is_null())
+                .then(|| get_time_ms().saturating_add(page_table.translate_ptr(timeout).as_ms()));
+            drop(page_table);
+            drop(proc);
+            match futex_wait(key, val as u32, expire_ms) {
+                FutexWait::Mismatch => -EAGAIN,
+                FutexWait::Woken => 0,
+                FutexWait::TimedOut => -ETIMEDOUT,
+            }
+        }
+        FUTEX_WAKE => futex_wake(key, val) as isize,
+        _ => -EINVAL,
+    }
+}
@@ -0,0 +1,67 @@
+//! Thread management syscalls
+use alloc::sync::Arc;
+use log::trace;
+
+use crate::{
+    memory::KERNEL_SPACE,
+    proc::{PROC_MANAGER, ThreadControlBlock, current_thread},
+    trap::{TrapFrame, trap_handler},
+};
+
+/// Create a thread in the current process running `entry(arg)`, return its tid.
+pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
+    trace!("sys_thread_create: entry = {entry:#x}, arg = {arg:#x}");
+    let thread = current_thread();
+    let proc = thread.proc();
+    let ustack_base = thread.borrow_inner_mut().res.as_ref().unwrap().ustack_base;
+    let new_thread = Arc::new(ThreadControlBlock::new(&proc, ustack_base, true));
+    let new_thread_inner = new_thread.borrow_inner_mut();
+    let new_tid = new_thread_inner.res.as_ref().unwrap().tid;
+    let mut tf = TrapFrame::new(
+        entry,
+        new_thread_inner.res.as_ref().unwrap().ustack_top(),
+        KERNEL_SPACE.lock().token(),
+        new_thread.kernel_stack_top(),
+        trap_handler as usize,
+    );
+    tf.x[10] = arg;
+    *new_thread_inner.get_trap_frame_mut() = tf;
+    drop(new_thread_inner);
+
+    let mut proc_inner = proc.borrow_inner_mut();
+    while proc_inner.threads.len() <= new_tid {
+        proc_inner.threads.push(None);
+    }
+    proc_inner.threads[new_tid] = Some(new_thread.clone());
+    drop(proc_inner);
+    PROC_MANAGER.lock().push(new_thread);
+    new_tid as isize
+}
+
+pub fn sys_gettid() -> isize {
+    current_thread().tid() as isize
+}
+
+/// If there is not a thread whose tid is same as given, return -1.
+/// Else if the thread is still running, return -2.
+/// Else return the exit code of the thread.
+pub fn sys_waittid(tid: usize) -> isize {
+    let thread = current_thread();
+    if thread.tid() == tid {
+        // a thread cannot wait for itself
+        return -1;
+    }
+    let proc = thread.proc();
+    let mut proc_inner = proc.borrow_inner_mut();
+    let Some(Some(waited)) = proc_inner.threads.get(tid) else {
+        return -1;
+    };
+    let Some(exit_code) = waited.borrow_inner_mut().exit_code else {
+        return -2; // Thread is still running
+    };
+    // dropping the thread releases its tid, user stack and kernel stack
+    let waited = proc_inner.threads[tid].take().unwrap();
+    drop(proc_inner);
+    drop(waited);
+    exit_code as isize
+}
@@ -1,20 +1,32 @@
 //! RISC-V timer-related functionality
 
-use crate::config::CLOCK_FREQ;
+use core::{
+    cmp::Ordering,
+    sync::atomic::{AtomicUsize, Ordering as AtomicOrdering},
+};
+
+use alloc::{boxed::Box, collections::BinaryHeap, sync::Arc, vec::Vec};
+use lazy_static::lazy_static;
 use riscv::register::{sie, time};
 use sbi_rt::set_timer;
 
-const TICKS_PER_SEC: usize = 100;
-// const MSEC_PER_SEC: usize = 1000;
+use crate::{
+    config::CLOCK_FREQ,
+    proc::{ThreadControlBlock, wakeup_thread},
+    sync::SpinLock,
+};
+
+pub const TICKS_PER_SEC: usize = 100;
+pub const MSEC_PER_SEC: usize = 1000;
 
 pub fn get_time() -> usize {
     time::read()
 }
 
-// /// get current time in microseconds
-// pub fn get_time_ms() -> usize {
-//     time::read() / (CLOCK_FREQ / MSEC_PER_SEC)
-// }
+/// get current time in milliseconds
+pub fn get_time_ms() -> usize {
+    time::read() / (CLOCK_FREQ / MSEC_PER_SEC)
+}
 
 /// set the next timer interrupt
 pub fn set_next_trigger() {
@@ -27,3 +39,106 @@ pub fn init() {
     }
     set_next_trigger();
 }
+
+/// What happens when a timer expires.
+enum TimerEvent {
+    /// Wake up a blocked thread.
+    Wakeup(Arc<ThreadControlBlock>),
+    /// Run a callback, such as the expiration of a timerfd.
+    Callback(TimerId, Box<dyn FnOnce(TimerId) + Send>),
+}
+
+/// Identifies a callback timer, to remove it before it expires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerId(usize);
+
+struct Timer {
+    expire_ms: usize,
+    event: TimerEvent,
+}
+
+impl PartialEq for Timer {
+    fn eq(&self, other: &Self) -> bool {
+        self.expire_ms == other.expire_ms
+    }
+}
+
+impl Eq for Timer {}
+
+impl PartialOrd for Timer {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Timer {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed so that the earliest timer is on the top of the max-heap
+        other.expire_ms.cmp(&self.expire_ms)
+    }
+}
+
+lazy_static! {
+    static ref TIMERS: SpinLock<BinaryHeap<Timer>> = SpinLock::new(BinaryHeap::new());
+}
+
+static NEXT_TIMER_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Wake up `thread` at `expire_ms` unless the timer is removed before.
+pub fn add_timer(expire_ms: usize, thread: Arc<ThreadControlBlock>) {
+    TIMERS.lock().push(Timer {
+        expire_ms,
+        event: TimerEvent::Wakeup(thread),
+    });
+}
+
+/// Remove the timers of `thread`, return false if there is none.
+pub fn remove_timer(thread: &Arc<ThreadControlBlock>) -> bool {
+    let mut timers = TIMERS.lock();
+    let len = timers.len();
+    timers.retain(
+        |timer| !matches!(&timer.event, TimerEvent::Wakeup(other) if Arc::ptr_eq(other, thread)),
+    );
+    timers.len() != len
+}
+
+/// Run `callback` with the id returned at `expire_ms`, unless the timer is removed before. It
+/// runs in the timer interrupt without the timers locked, so it may add timers.
+pub fn add_callback_timer(
+    expire_ms: usize,
+    callback: impl FnOnce(TimerId) + Send + 'static,
+) -> TimerId {
+    let id = TimerId(NEXT_TIMER_ID.fetch_add(1, AtomicOrdering::Relaxed));
+    TIMERS.lock().push(Timer {
+        expire_ms,
+        event: TimerEvent::Callback(id, Box::new(callback)),
+    });
+    id
+}
+
+/// Remove the callback timer `id` if it has not expired.
+pub fn remove_callback_timer(id: TimerId) {
+    TIMERS
+        .lock()
+        .retain(|timer| !matches!(timer.event, TimerEvent::Callback(other, _) if other == id));
+}
+
+/// Wake up the threads and run the callbacks whose timers have expired.
+pub fn check_timer() {
+    let current_ms = get_time_ms();
+    let mut callbacks = Vec::new();
+    let mut timers = TIMERS.lock();
+    while timers
+        .peek()
+        .is_some_and(|timer| timer.expire_ms <= current_ms)
+    {
+        match timers.pop().unwrap().event {
+            TimerEvent::Wakeup(thread) => wakeup_thread(thread),
+            TimerEvent::Callback(id, callback) => callbacks.push((id, callback)),
+        }
+    }
+    drop(timers);
+    for (id, callback) in callbacks {
+        callback(id);
+    }
+}
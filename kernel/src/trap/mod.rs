@@ -3,28 +3,33 @@ use core::arch::{asm, global_asm};
 use log::error;
 use riscv::register::{
     scause::{self, Exception, Interrupt, Trap},
-    stval, stvec,
+    sepc, sie, sip, stval, stvec,
     utvec::TrapMode,
 };
 
 pub use self::trap_frame::TrapFrame;
 use crate::{
-    config::{TRAMPOLINE, TRAP_FRAME},
+    config::TRAMPOLINE,
+    drivers::handle_external_irq,
     proc::{
-        current_token, current_trap_frame_mut, exit_current_and_run_next,
-        suspend_current_and_run_next,
+        current_proc, current_token, current_trap_frame_mut, current_trap_frame_user_va,
+        exit_group_and_run_next, stop_current_and_run_next, suspend_current_and_run_next,
     },
+    smp::hart_id,
     syscall::syscall,
-    timer::set_next_trigger,
+    timer::{check_timer, set_next_trigger},
 };
 
 mod trap_frame;
 
 global_asm!(include_str!("trap.S"));
 
-/// initialize CSR `stvec` as the entry of `uservec`
+/// initialize CSR `stvec` as the entry of `kernelvec` and enable IPIs on the current hart
 pub fn init() {
     set_kernel_trap_entry();
+    unsafe {
+        sie::set_ssoft();
+    }
 }
 
 #[unsafe(no_mangle)]
@@ -38,15 +43,14 @@ pub fn trap_handler() -> ! {
             let trap_frame = current_trap_frame_mut();
             // 系统调用，恢复到用户态后不需要重复执行，将 sepc 加 4 设置为 ecall 之后的一条指令
             trap_frame.sepc += 4;
-            let result = syscall(
-                trap_frame.x[17],
-                [trap_frame.x[10], trap_frame.x[11], trap_frame.x[12]],
-            );
+            let mut args = [0; 6];
+            args.copy_from_slice(&trap_frame.x[10..16]);
+            let result = syscall(trap_frame.x[17], args);
             if let Some(result) = result {
                 let trap_frame = current_trap_frame_mut();
                 trap_frame.x[10] = result as usize;
             } else {
-                exit_current_and_run_next(-1);
+                exit_group_and_run_next(-1);
             }
         }
         Trap::Exception(Exception::StoreFault)
@@ -60,16 +64,26 @@ pub fn trap_handler() -> ! {
                 stval,
                 scause.cause()
             );
-            exit_current_and_run_next(-1)
+            exit_group_and_run_next(-1)
         }
         Trap::Exception(Exception::IllegalInstruction) => {
             error!("IllegalInstruction in application, kernel killed it.");
-            exit_current_and_run_next(-3);
+            exit_group_and_run_next(-3);
         }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
             set_next_trigger();
+            check_timer();
             suspend_current_and_run_next();
         }
+        Trap::Interrupt(Interrupt::SupervisorExternal) => {
+            handle_external_irq();
+        }
+        Trap::Interrupt(Interrupt::SupervisorSoft) => {
+            // IPIs only ask the current thread to check whether its process has exited
+            unsafe {
+                sip::clear_ssoft();
+            }
+        }
         _ => {
             panic!(
                 "Unsupported trap {:?}, stval = {:#x}!",
@@ -78,6 +92,20 @@ pub fn trap_handler() -> ! {
             );
         }
     }
+    let proc = current_proc();
+    let proc_inner = proc.borrow_inner_mut();
+    let is_zombie = proc_inner.is_zombie();
+    let signal = proc_inner.unblocked_signals().iter().next();
+    drop(proc_inner);
+    drop(proc);
+    // the process may have been exited by a thread on another hart
+    if is_zombie {
+        stop_current_and_run_next();
+    }
+    // signals are not handled by user programs, all unblocked ones terminate the process
+    if let Some(signal) = signal {
+        exit_group_and_run_next(-signal.to_number());
+    }
     trap_return();
 }
 
@@ -85,7 +113,9 @@ pub fn trap_handler() -> ! {
 /// The First user process will call this function to enter to user space.
 pub fn trap_return() -> ! {
     set_user_trap_entry();
-    let trap_cx_ptr = TRAP_FRAME;
+    // the thread may run on a different hart from last time
+    current_trap_frame_mut().kernel_hartid = hart_id();
+    let trap_cx_ptr = current_trap_frame_user_va();
     let user_satp = current_token();
 
     unsafe extern "C" {
@@ -109,13 +139,36 @@ pub fn trap_return() -> ! {
 }
 
 #[unsafe(no_mangle)]
-pub fn trap_from_kernel() -> ! {
-    panic!("a trap from kernel!");
+/// handle an interrupt taken by an idle hart, anything else trapping in the kernel is a bug
+pub fn trap_from_kernel() {
+    let scause = scause::read();
+    match scause.cause() {
+        Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            set_next_trigger();
+            check_timer();
+        }
+        Trap::Interrupt(Interrupt::SupervisorExternal) => handle_external_irq(),
+        Trap::Interrupt(Interrupt::SupervisorSoft) => unsafe {
+            sip::clear_ssoft();
+        },
+        _ => {
+            panic!(
+                "a trap from kernel! {:?}, stval = {:#x}, sepc = {:#x}",
+                scause.cause(),
+                stval::read(),
+                sepc::read()
+            );
+        }
+    }
 }
 
 fn set_kernel_trap_entry() {
+    unsafe extern "C" {
+        /// The entry point for kernel traps.
+        fn kernelvec();
+    }
     unsafe {
-        stvec::write(trap_from_kernel as usize, TrapMode::Direct);
+        stvec::write(kernelvec as usize, TrapMode::Direct);
     }
 }
 
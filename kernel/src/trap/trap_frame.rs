@@ -15,6 +15,8 @@ pub struct TrapFrame {
     pub kernel_sp: usize, // offset 35*8
     /// the trap handler address, which is used to handle traps in user mode
     pub trap_handler: usize, // offset 36*8
+    /// the id of the hart which the thread runs on, which is kept in tp in the kernel
+    pub kernel_hartid: usize, // offset 37*8
 }
 
 impl TrapFrame {
@@ -35,6 +37,7 @@ impl TrapFrame {
             kernel_satp,
             kernel_sp,
             trap_handler,
+            kernel_hartid: 0,
         };
         tf.set_sp(sp);
         tf
@@ -1,19 +1,26 @@
 #!/bin/bash
 GDB=${GDB:-0}
+SMP=${SMP:-4}
 if [ "$CARGO_MANIFEST_DIR" = "" ]; then
   BASE_DIR="$(pwd)"
 else
   BASE_DIR="$(dirname "$CARGO_MANIFEST_DIR")"
 fi
 FS_IMG="${BASE_DIR}/kernel/fs.img"
+SCRATCH_IMG="${BASE_DIR}/kernel/scratch.img"
 RUSTSBI="${BASE_DIR}/bootloader/rustsbi.bin"
 CMD="
 qemu-system-riscv64\
  -nographic\
  -machine virt\
+ -smp $SMP\
  -bios $RUSTSBI\
  -drive file=$FS_IMG,if=none,format=raw,id=x0\
  -device virtio-blk-device,drive=x0,bus=virtio-mmio-bus.0\
+ -drive file=$SCRATCH_IMG,if=none,format=raw,id=x1\
+ -device virtio-blk-device,drive=x1,bus=virtio-mmio-bus.1\
+ -netdev user,id=net0,hostfwd=tcp::5555-:5555,hostfwd=udp::5555-:5555\
+ -device virtio-net-device,netdev=net0,bus=virtio-mmio-bus.2\
  -kernel $*
 "
 if [ "$GDB" -eq 1 ]; then
@@ -8,11 +8,17 @@ use user_lib::{OpenFlags, open, read};
 #[macro_use]
 extern crate user_lib;
 
+const STDIN: usize = 0;
+
+/// Print the file given, or the standard input without arguments.
 #[unsafe(no_mangle)]
 fn main(_argc: usize, argv: &[&str]) -> i32 {
-    assert_eq!(argv.len(), 2);
-    let fd = open(argv[1], OpenFlags::RDONLY);
-    if fd == -1 {
+    assert!(argv.len() <= 2);
+    let fd = match argv.get(1) {
+        Some(path) => open(path, OpenFlags::RDONLY),
+        None => STDIN as isize,
+    };
+    if fd < 0 {
         println!("cat: {}: No such file or directory", argv[1]);
         return -1;
     }
@@ -0,0 +1,79 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    OpenFlags, close, dup2, exec, fcntl, fork,
+    fs::{F_GETFD, FD_CLOEXEC},
+    open, pipe2, read, waitpid, write,
+};
+
+const STDIN: usize = 0;
+const STDOUT: usize = 1;
+
+/// Run `cat` with its standard input and output connected to `stdin` and `stdout`.
+fn spawn_cat(stdin: usize, stdout: usize) -> usize {
+    let pid = fork();
+    if pid == 0 {
+        dup2(stdin, STDIN);
+        dup2(stdout, STDOUT);
+        exec("cat\0", &[c"cat".as_ptr().cast(), core::ptr::null()]);
+        panic!("exec failed");
+    }
+    pid as usize
+}
+
+/// Read `fd` until EOF.
+fn read_to_end(fd: usize, buffer: &mut [u8]) -> usize {
+    let mut len = 0;
+    loop {
+        let n = read(fd, &mut buffer[len..]);
+        assert!(n >= 0);
+        if n == 0 {
+            return len;
+        }
+        len += n as usize;
+    }
+}
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    // the flag is set by open and pipe2 only when asked for
+    let fd = open("/dev/null\0", OpenFlags::RDONLY | OpenFlags::CLOEXEC);
+    assert!(fd > 0);
+    assert_eq!(fcntl(fd as usize, F_GETFD, 0), FD_CLOEXEC as isize);
+    close(fd as usize);
+    let fd = open("/dev/null\0", OpenFlags::RDONLY);
+    assert_eq!(fcntl(fd as usize, F_GETFD, 0), 0);
+    close(fd as usize);
+
+    // writer | cat | reader, with the ends of the pipes left open in cat unless close-on-exec
+    let mut input = [0usize; 2];
+    let mut output = [0usize; 2];
+    assert_eq!(pipe2(&mut input, OpenFlags::CLOEXEC), 0);
+    assert_eq!(pipe2(&mut output, OpenFlags::CLOEXEC), 0);
+    assert_eq!(fcntl(input[1], F_GETFD, 0), FD_CLOEXEC as isize);
+    let pid = spawn_cat(input[0], output[1]);
+    close(input[0]);
+    close(output[1]);
+
+    let text = "through cat";
+    assert_eq!(write(input[1], text.as_bytes()), text.len() as isize);
+    // cat sees EOF once the only write end left is closed
+    close(input[1]);
+    let mut buffer = [0u8; 64];
+    let len = read_to_end(output[0], &mut buffer);
+    close(output[0]);
+    // cat ends with a newline
+    assert_eq!(&buffer[..len], b"through cat\n");
+
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid as isize);
+    assert_eq!(exit_code, 0);
+    println!("cloexec_test passed!");
+    0
+}
@@ -0,0 +1,95 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{
+    enable_deadlock_detect, errno::EDEADLK, exit, mutex_create, mutex_lock, mutex_unlock,
+    semaphore_create, semaphore_down, semaphore_up, thread_create, waittid, yield_,
+};
+
+static MUTEX_A: AtomicUsize = AtomicUsize::new(0);
+static MUTEX_B: AtomicUsize = AtomicUsize::new(0);
+static SEM: AtomicUsize = AtomicUsize::new(0);
+static STAGE: AtomicUsize = AtomicUsize::new(0);
+
+/// Take B, then try to take A which is held by the main thread.
+fn lock_b_then_a(_: usize) -> ! {
+    let a = MUTEX_A.load(Ordering::Relaxed);
+    let b = MUTEX_B.load(Ordering::Relaxed);
+    assert_eq!(mutex_lock(b), 0);
+    STAGE.store(1, Ordering::Relaxed);
+    // blocks until the main thread gives up A
+    assert_eq!(mutex_lock(a), 0);
+    assert_eq!(mutex_unlock(a), 0);
+    assert_eq!(mutex_unlock(b), 0);
+    exit(0);
+    unreachable!()
+}
+
+fn test_mutex_deadlock() {
+    let a = mutex_create() as usize;
+    let b = mutex_create() as usize;
+    MUTEX_A.store(a, Ordering::Relaxed);
+    MUTEX_B.store(b, Ordering::Relaxed);
+    STAGE.store(0, Ordering::Relaxed);
+
+    assert_eq!(mutex_lock(a), 0);
+    let tid = thread_create(lock_b_then_a as usize, 0) as usize;
+    while STAGE.load(Ordering::Relaxed) == 0 {
+        yield_();
+    }
+    // let the other thread block on A
+    yield_();
+    assert_eq!(mutex_lock(b), -EDEADLK);
+    assert_eq!(mutex_unlock(a), 0);
+    assert_eq!(waittid(tid), 0);
+    println!("mutex deadlock test passed!");
+}
+
+/// Take the semaphore, then try to take A which is held by the main thread.
+fn down_then_lock(_: usize) -> ! {
+    let a = MUTEX_A.load(Ordering::Relaxed);
+    let sem = SEM.load(Ordering::Relaxed);
+    assert_eq!(semaphore_down(sem), 0);
+    STAGE.store(1, Ordering::Relaxed);
+    assert_eq!(mutex_lock(a), 0);
+    assert_eq!(mutex_unlock(a), 0);
+    assert_eq!(semaphore_up(sem), 0);
+    exit(0);
+    unreachable!()
+}
+
+fn test_semaphore_deadlock() {
+    let a = MUTEX_A.load(Ordering::Relaxed);
+    let sem = semaphore_create(1) as usize;
+    SEM.store(sem, Ordering::Relaxed);
+    STAGE.store(0, Ordering::Relaxed);
+
+    assert_eq!(mutex_lock(a), 0);
+    let tid = thread_create(down_then_lock as usize, 0) as usize;
+    while STAGE.load(Ordering::Relaxed) == 0 {
+        yield_();
+    }
+    yield_();
+    assert_eq!(semaphore_down(sem), -EDEADLK);
+    assert_eq!(mutex_unlock(a), 0);
+    assert_eq!(waittid(tid), 0);
+    // no deadlock is possible once the other thread has finished
+    assert_eq!(semaphore_down(sem), 0);
+    assert_eq!(semaphore_up(sem), 0);
+    println!("semaphore deadlock test passed!");
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    assert_eq!(enable_deadlock_detect(true), 0);
+    test_mutex_deadlock();
+    test_semaphore_deadlock();
+    println!("deadlock_test passed!");
+    0
+}
@@ -0,0 +1,80 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    OpenFlags, close,
+    errno::EPERM,
+    fs::{FileType, Stat},
+    fstat, open, read, unlink, write,
+};
+
+fn open_device(path: &str, flags: OpenFlags) -> usize {
+    let fd = open(path, flags);
+    assert!(fd > 0);
+    fd as usize
+}
+
+fn file_type(fd: usize) -> Option<FileType> {
+    let mut stat = Stat::default();
+    assert_eq!(fstat(fd, &mut stat), 0);
+    stat.file_type()
+}
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    let mut buffer = [0x5au8; 64];
+
+    // opened like the shell redirects the output
+    let null = open_device("/dev/null\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert_eq!(file_type(null), Some(FileType::CharDevice));
+    assert_eq!(write(null, &buffer), buffer.len() as isize);
+    close(null);
+    let null = open_device("/dev/null\0", OpenFlags::RDONLY);
+    assert_eq!(read(null, &mut buffer), 0);
+    close(null);
+
+    let zero = open_device("/dev/zero\0", OpenFlags::RDONLY);
+    assert_eq!(read(zero, &mut buffer), buffer.len() as isize);
+    assert!(buffer.iter().all(|&b| b == 0));
+    close(zero);
+
+    let random = open_device("/dev/random\0", OpenFlags::RDONLY);
+    let mut other = [0u8; 64];
+    read(random, &mut buffer);
+    read(random, &mut other);
+    assert_ne!(buffer, other);
+    close(random);
+
+    let console = open_device("/dev/console\0", OpenFlags::RDWR);
+    assert_eq!(file_type(console), Some(FileType::CharDevice));
+    let greeting = "Hello, /dev/console!\n";
+    assert_eq!(write(console, greeting.as_bytes()), greeting.len() as isize);
+    close(console);
+
+    // writing a block back leaves the disk unchanged
+    let mut block = [0u8; 512];
+    let disk = open_device("/dev/vdb\0", OpenFlags::RDONLY);
+    assert_eq!(file_type(disk), Some(FileType::BlockDevice));
+    assert_eq!(read(disk, &mut block), block.len() as isize);
+    close(disk);
+    let disk = open_device("/dev/vdb\0", OpenFlags::WRONLY);
+    assert_eq!(write(disk, &block[..100]), 100);
+    assert_eq!(write(disk, &block[100..]), 412);
+    close(disk);
+    let mut again = [0u8; 512];
+    let disk = open_device("/dev/vdb\0", OpenFlags::RDONLY);
+    assert_eq!(read(disk, &mut again), again.len() as isize);
+    close(disk);
+    assert_eq!(block, again);
+
+    assert!(open("/dev/nonexistent\0", OpenFlags::CREATE | OpenFlags::WRONLY) < 0);
+    assert_eq!(unlink("/dev/null\0"), -EPERM);
+
+    println!("devfs_test passed!");
+    0
+}
@@ -0,0 +1,112 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    OpenFlags, close, dup, dup2, dup3,
+    errno::{EBADF, EINVAL},
+    fcntl,
+    fs::{F_DUPFD, F_DUPFD_CLOEXEC, F_GETFD, F_GETFL, F_SETFD, F_SETFL, FD_CLOEXEC},
+    open, pipe, read, unlink, write,
+};
+
+const FILE: &str = "/tmp/dup_test\0";
+
+fn read_all(path: &str) -> usize {
+    let fd = open(path, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buffer = [0u8; 64];
+    let len = read(fd as usize, &mut buffer);
+    close(fd as usize);
+    len as usize
+}
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    let fd = open(FILE, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+
+    // the duplicates share the offset
+    let new_fd = dup(fd);
+    assert!(new_fd > fd as isize);
+    let new_fd = new_fd as usize;
+    assert_eq!(write(fd, b"hello"), 5);
+    assert_eq!(write(new_fd, b" world"), 6);
+    assert_eq!(read_all(FILE), 11);
+    assert_eq!(close(new_fd), 0);
+
+    // dup2 replaces an open fd and grows the table
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    assert_eq!(dup2(fd, pipe_fd[0]), pipe_fd[0] as isize);
+    assert_eq!(write(pipe_fd[0], b"!"), 1);
+    assert_eq!(read_all(FILE), 12);
+    assert_eq!(dup2(fd, 20), 20);
+    assert_eq!(dup2(20, 20), 20);
+    assert_eq!(close(20), 0);
+    assert_eq!(dup2(20, 20), -EBADF);
+    assert_eq!(dup(20), -EBADF);
+    assert_eq!(dup3(fd, fd, OpenFlags::empty()), -EINVAL);
+    assert_eq!(dup3(fd, 21, OpenFlags::RDWR), -EINVAL);
+    // fds are limited
+    assert_eq!(dup3(fd, usize::MAX, OpenFlags::empty()), -EBADF);
+    assert_eq!(fcntl(fd, F_DUPFD, usize::MAX), -EINVAL);
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+
+    // the fd flags belong to each fd
+    assert_eq!(dup3(fd, 21, OpenFlags::CLOEXEC), 21);
+    assert_eq!(fcntl(21, F_GETFD, 0), FD_CLOEXEC as isize);
+    assert_eq!(fcntl(fd, F_GETFD, 0), 0);
+    assert_eq!(fcntl(21, F_SETFD, 0), 0);
+    assert_eq!(fcntl(21, F_GETFD, 0), 0);
+    assert_eq!(fcntl(fd, F_DUPFD_CLOEXEC, 30), 30);
+    assert_eq!(fcntl(30, F_GETFD, 0), FD_CLOEXEC as isize);
+    assert_eq!(fcntl(fd, F_DUPFD, 30), 31);
+    assert_eq!(fcntl(31, F_GETFD, 0), 0);
+    close(30);
+    close(31);
+
+    // the status flags belong to the open file
+    assert_eq!(fcntl(fd, F_GETFL, 0), OpenFlags::WRONLY.bits() as isize);
+    assert_eq!(fcntl(21, F_SETFL, OpenFlags::APPEND.bits() as usize), 0);
+    assert_eq!(
+        fcntl(fd, F_GETFL, 0),
+        (OpenFlags::WRONLY | OpenFlags::APPEND).bits() as isize
+    );
+    // the access mode cannot be changed
+    assert_eq!(fcntl(fd, F_SETFL, OpenFlags::RDWR.bits() as usize), 0);
+    assert_eq!(fcntl(fd, F_GETFL, 0), OpenFlags::WRONLY.bits() as isize);
+    assert_eq!(fcntl(fd, F_SETFL, OpenFlags::APPEND.bits() as usize), 0);
+    close(21);
+    assert_eq!(fcntl(21, F_GETFL, 0), -EBADF);
+
+    // an appending file writes at the end whatever the offset of another open file
+    let other = open(FILE, OpenFlags::WRONLY);
+    assert!(other > 0);
+    assert_eq!(write(other as usize, b"HELLO"), 5);
+    assert_eq!(write(fd, b"?"), 1);
+    assert_eq!(read_all(FILE), 13);
+    close(other as usize);
+    close(fd);
+
+    // opening an existing file to append to it keeps its data
+    let log = open(
+        FILE,
+        OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::APPEND,
+    );
+    assert!(log > 0);
+    assert_eq!(read_all(FILE), 13);
+    assert_eq!(write(log as usize, b"."), 1);
+    assert_eq!(read_all(FILE), 14);
+    close(log as usize);
+
+    assert_eq!(unlink(FILE), 0);
+    println!("dup_test passed!");
+    0
+}
@@ -0,0 +1,71 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::net::Ipv4Addr;
+
+use user_lib::{
+    close, connect, read, socket,
+    socket::{AF_INET, SOCK_DGRAM, SOCK_STREAM, SockAddrIn},
+    write,
+};
+
+const DEFAULT_PORT: u16 = 5555;
+
+/// Send a message to an echo server, over TCP or UDP with `-u`, and print the reply. The host
+/// is 10.0.2.2 under QEMU user networking.
+#[unsafe(no_mangle)]
+fn main(_argc: usize, argv: &[&str]) -> i32 {
+    let mut args = argv.iter().skip(1).peekable();
+    let udp = args.next_if_eq(&&"-u").is_some();
+    let Some(addr) = args.next().and_then(|addr| addr.parse::<Ipv4Addr>().ok()) else {
+        println!("usage: echo_client [-u] address [port] [message]");
+        return -1;
+    };
+    let port = match args.next_if(|port| port.parse::<u16>().is_ok()) {
+        Some(port) => port.parse().unwrap(),
+        None => DEFAULT_PORT,
+    };
+    let message = args.next().copied().unwrap_or("hello");
+    let fd = socket(AF_INET, if udp { SOCK_DGRAM } else { SOCK_STREAM });
+    if fd < 0 {
+        println!("echo_client: socket failed: {}", fd);
+        return -1;
+    }
+    let fd = fd as usize;
+    let result = connect(fd, &SockAddrIn::new(addr.octets(), port));
+    if result < 0 {
+        println!(
+            "echo_client: connect to {}:{} failed: {}",
+            addr, port, result
+        );
+        return -1;
+    }
+    if write(fd, message.as_bytes()) < 0 {
+        println!("echo_client: send failed");
+        return -1;
+    }
+    // a stream may return the echo in pieces
+    let mut buffer = [0u8; 1024];
+    let mut received = 0;
+    while received < message.len() {
+        let len = read(fd, &mut buffer[received..]);
+        if len <= 0 {
+            break;
+        }
+        received += len as usize;
+        if udp {
+            break;
+        }
+    }
+    println!(
+        "{}",
+        core::str::from_utf8(&buffer[..received]).unwrap_or("<binary>")
+    );
+    close(fd);
+    0
+}
@@ -0,0 +1,77 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    accept, bind, close, listen, read, recvfrom, sendto, socket,
+    socket::{AF_INET, INADDR_ANY, MsgFlags, SOCK_DGRAM, SOCK_STREAM, SockAddrIn},
+    write,
+};
+
+const DEFAULT_PORT: u16 = 5555;
+
+/// Echo what clients send on a TCP port, serving them one after another, or the datagrams sent
+/// to a UDP port with `-u`. QEMU forwards port 5555 of the host to the guest.
+#[unsafe(no_mangle)]
+fn main(_argc: usize, argv: &[&str]) -> i32 {
+    let mut udp = false;
+    let mut port = DEFAULT_PORT;
+    for arg in argv.iter().skip(1) {
+        match (*arg, arg.parse()) {
+            ("-u", _) => udp = true,
+            (_, Ok(n)) => port = n,
+            _ => {
+                println!("usage: echo_server [-u] [port]");
+                return -1;
+            }
+        }
+    }
+    let fd = socket(AF_INET, if udp { SOCK_DGRAM } else { SOCK_STREAM });
+    if fd < 0 {
+        println!("echo_server: socket failed: {}", fd);
+        return -1;
+    }
+    let fd = fd as usize;
+    let result = bind(fd, &SockAddrIn::new(INADDR_ANY, port));
+    if result < 0 {
+        println!("echo_server: bind to port {} failed: {}", port, result);
+        return -1;
+    }
+    let mut buffer = [0u8; 1024];
+    if udp {
+        println!("echo_server: echoing datagrams on port {}", port);
+        loop {
+            let mut from = SockAddrIn::default();
+            let len = recvfrom(fd, &mut buffer, MsgFlags::empty(), &mut from);
+            if len < 0 {
+                println!("echo_server: recvfrom failed: {}", len);
+                return -1;
+            }
+            sendto(fd, &buffer[..len as usize], MsgFlags::empty(), &from);
+        }
+    }
+    if listen(fd, 4) < 0 {
+        println!("echo_server: listen failed");
+        return -1;
+    }
+    println!("echo_server: listening on port {}", port);
+    loop {
+        let client = accept(fd);
+        if client < 0 {
+            println!("echo_server: accept failed: {}", client);
+            return -1;
+        }
+        let client = client as usize;
+        loop {
+            let len = read(client, &mut buffer);
+            if len <= 0 || write(client, &buffer[..len as usize]) < 0 {
+                break;
+            }
+        }
+        close(client);
+    }
+}
@@ -0,0 +1,209 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    OpenFlags, close,
+    errno::{EAGAIN, EINVAL, EPIPE},
+    eventfd, exit, fork,
+    fs::EFD_SEMAPHORE,
+    pipe, poll,
+    poll::{PollEvents, PollFd},
+    read,
+    sig::{SIG_BLOCK, SIG_SETMASK, SignalFlags, SignalfdSiginfo},
+    signalfd, sigprocmask,
+    time::{CLOCK_MONOTONIC, ItimerSpec, TimeSpec},
+    timerfd_create, timerfd_gettime, timerfd_settime, wait_exit_code, write,
+};
+
+fn read_u64(fd: usize) -> u64 {
+    let mut buf = [0u8; 8];
+    assert_eq!(read(fd, &mut buf), 8);
+    u64::from_ne_bytes(buf)
+}
+
+fn write_u64(fd: usize, value: u64) -> isize {
+    write(fd, &value.to_ne_bytes())
+}
+
+/// Read a record from signalfd `fd`, return its signal number or the error.
+fn read_signal(fd: usize) -> Result<u32, isize> {
+    let mut buf = [0u8; size_of::<SignalfdSiginfo>()];
+    match read(fd, &mut buf) {
+        n if n < 0 => Err(n),
+        n => {
+            assert_eq!(n as usize, buf.len());
+            Ok(u32::from_ne_bytes(buf[..4].try_into().unwrap()))
+        }
+    }
+}
+
+fn timer(value_ms: usize, interval_ms: usize) -> ItimerSpec {
+    ItimerSpec {
+        interval: TimeSpec::from_ms(interval_ms),
+        value: TimeSpec::from_ms(value_ms),
+    }
+}
+
+/// Write to a pipe without readers, which raises `SIGPIPE`.
+fn raise_sigpipe() {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    close(fds[0]);
+    assert_eq!(write(fds[1], b"lost"), -EPIPE);
+    close(fds[1]);
+}
+
+fn test_eventfd() {
+    let fd = eventfd(0, OpenFlags::NONBLOCK.bits()) as usize;
+    let mut buf = [0u8; 8];
+    assert_eq!(read(fd, &mut buf), -EAGAIN);
+    assert_eq!(write_u64(fd, 3), 8);
+    assert_eq!(write_u64(fd, 4), 8);
+    assert_eq!(read_u64(fd), 7);
+    assert_eq!(write_u64(fd, u64::MAX), -EINVAL);
+    assert_eq!(read(fd, &mut buf[..4]), -EINVAL);
+    // the counter cannot exceed u64::MAX - 1
+    assert_eq!(write_u64(fd, u64::MAX - 1), 8);
+    assert_eq!(write_u64(fd, 1), -EAGAIN);
+    assert_eq!(read_u64(fd), u64::MAX - 1);
+    close(fd);
+
+    let fd = eventfd(2, EFD_SEMAPHORE | OpenFlags::NONBLOCK.bits()) as usize;
+    assert_eq!(read_u64(fd), 1);
+    assert_eq!(read_u64(fd), 1);
+    assert_eq!(read(fd, &mut buf), -EAGAIN);
+    close(fd);
+
+    // a blocked reader is woken up by a write
+    let fd = eventfd(0, 0) as usize;
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(write_u64(fd, 5), 8);
+        exit(0);
+    }
+    assert_eq!(read_u64(fd), 5);
+    assert_eq!(wait_exit_code(pid as usize), 0);
+    close(fd);
+}
+
+fn test_timerfd() {
+    let fd = timerfd_create(CLOCK_MONOTONIC, OpenFlags::NONBLOCK) as usize;
+    let mut spec = timer(1, 1);
+    assert_eq!(timerfd_gettime(fd, &mut spec), 0);
+    assert_eq!(spec, ItimerSpec::default());
+    let mut buf = [0u8; 8];
+    assert_eq!(read(fd, &mut buf), -EAGAIN);
+
+    // one-shot
+    assert_eq!(timerfd_settime(fd, 0, &timer(30, 0), None), 0);
+    assert_eq!(timerfd_gettime(fd, &mut spec), 0);
+    assert!(spec.value.as_ms() > 0 && spec.value.as_ms() <= 30);
+    let mut fds = [PollFd {
+        fd: fd as i32,
+        events: PollEvents::IN,
+        revents: PollEvents::empty(),
+    }];
+    assert_eq!(poll(&mut fds, 1000), 1);
+    assert_eq!(fds[0].revents, PollEvents::IN);
+    assert_eq!(read_u64(fd), 1);
+    assert_eq!(read(fd, &mut buf), -EAGAIN);
+    assert_eq!(timerfd_gettime(fd, &mut spec), 0);
+    assert_eq!(spec, ItimerSpec::default());
+    close(fd);
+
+    // periodic, read while blocking
+    let fd = timerfd_create(CLOCK_MONOTONIC, OpenFlags::empty()) as usize;
+    assert_eq!(timerfd_settime(fd, 0, &timer(10, 10), None), 0);
+    assert!(read_u64(fd) >= 1);
+    // expirations accumulate until read
+    assert_eq!(poll(&mut [], 50), 0);
+    assert!(read_u64(fd) >= 3);
+    let mut old = ItimerSpec::default();
+    assert_eq!(timerfd_settime(fd, 0, &timer(0, 0), Some(&mut old)), 0);
+    assert_eq!(old.interval, TimeSpec::from_ms(10));
+    close(fd);
+}
+
+fn test_signalfd() {
+    assert_eq!(sigprocmask(SIG_BLOCK, Some(SignalFlags::SIGPIPE), None), 0);
+    let fd = signalfd(-1, SignalFlags::SIGPIPE, OpenFlags::NONBLOCK) as usize;
+    assert_eq!(read_signal(fd), Err(-EAGAIN));
+    // a blocked signal stays pending instead of terminating the process
+    raise_sigpipe();
+    assert_eq!(read_signal(fd), Ok(SignalFlags::SIGPIPE.to_number() as u32));
+    assert_eq!(read_signal(fd), Err(-EAGAIN));
+    let mut short = [0u8; 8];
+    assert_eq!(read(fd, &mut short), -EINVAL);
+
+    // the blocked signals are inherited
+    let pid = fork();
+    if pid == 0 {
+        raise_sigpipe();
+        exit(0);
+    }
+    assert_eq!(wait_exit_code(pid as usize), 0);
+    close(fd);
+}
+
+/// Handle an eventfd, a timerfd and a signalfd in one event loop.
+fn test_event_loop() {
+    let event = eventfd(0, OpenFlags::NONBLOCK.bits()) as usize;
+    let timer_fd = timerfd_create(CLOCK_MONOTONIC, OpenFlags::NONBLOCK) as usize;
+    let signal = signalfd(-1, SignalFlags::SIGPIPE, OpenFlags::NONBLOCK) as usize;
+    let mut fds = [event, timer_fd, signal].map(|fd| PollFd {
+        fd: fd as i32,
+        events: PollEvents::IN,
+        revents: PollEvents::empty(),
+    });
+    assert_eq!(poll(&mut fds, 0), 0);
+
+    assert_eq!(write_u64(event, 1), 8);
+    assert_eq!(timerfd_settime(timer_fd, 0, &timer(20, 0), None), 0);
+    raise_sigpipe();
+    let mut handled = [false; 3];
+    while handled.contains(&false) {
+        assert!(poll(&mut fds, 1000) > 0);
+        if fds[0].revents.contains(PollEvents::IN) {
+            assert_eq!(read_u64(event), 1);
+            handled[0] = true;
+        }
+        if fds[1].revents.contains(PollEvents::IN) {
+            assert_eq!(read_u64(timer_fd), 1);
+            handled[1] = true;
+        }
+        if fds[2].revents.contains(PollEvents::IN) {
+            assert_eq!(
+                read_signal(signal),
+                Ok(SignalFlags::SIGPIPE.to_number() as u32)
+            );
+            handled[2] = true;
+        }
+    }
+    assert_eq!(poll(&mut fds, 0), 0);
+    close(event);
+    close(timer_fd);
+    close(signal);
+}
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    test_eventfd();
+    test_timerfd();
+    test_signalfd();
+    test_event_loop();
+
+    let mut old = SignalFlags::empty();
+    assert_eq!(
+        sigprocmask(SIG_SETMASK, Some(SignalFlags::empty()), Some(&mut old)),
+        0
+    );
+    assert_eq!(old, SignalFlags::SIGPIPE);
+
+    println!("fd_events_test passed!");
+    0
+}
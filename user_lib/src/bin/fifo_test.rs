@@ -0,0 +1,78 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    OpenFlags, close,
+    errno::{EAGAIN, EEXIST, ENXIO},
+    fork,
+    fs::{FileType, Stat},
+    fstat, mkfifo, open, read, unlink, waitpid, write,
+};
+
+/// On the easy_fs, the tmpfs has no FIFOs.
+const FIFO: &str = "/fifo_test\0";
+
+fn open_fifo(flags: OpenFlags) -> usize {
+    let fd = open(FIFO, flags);
+    assert!(fd > 0);
+    fd as usize
+}
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    assert_eq!(mkfifo(FIFO), 0);
+    assert_eq!(mkfifo(FIFO), -EEXIST);
+
+    // without a reader a non-blocking write end cannot be opened
+    assert_eq!(open(FIFO, OpenFlags::WRONLY | OpenFlags::NONBLOCK), -ENXIO);
+    let reader = open_fifo(OpenFlags::RDONLY | OpenFlags::NONBLOCK);
+    let mut stat = Stat::default();
+    assert_eq!(fstat(reader, &mut stat), 0);
+    assert_eq!(stat.file_type(), Some(FileType::Fifo));
+    // no writer yet, so EOF
+    let mut buffer = [0u8; 64];
+    assert_eq!(read(reader, &mut buffer), 0);
+
+    // the data goes through the buffer shared by the open files
+    let writer = open_fifo(OpenFlags::WRONLY | OpenFlags::NONBLOCK);
+    assert_eq!(read(reader, &mut buffer), -EAGAIN);
+    assert_eq!(write(writer, b"named"), 5);
+    assert_eq!(read(reader, &mut buffer), 5);
+    assert_eq!(&buffer[..5], b"named");
+    close(writer);
+    assert_eq!(read(reader, &mut buffer), 0);
+    close(reader);
+
+    // blocking opens wait for each other
+    let pid = fork();
+    if pid == 0 {
+        let writer = open_fifo(OpenFlags::WRONLY);
+        assert_eq!(write(writer, b"from the child"), 14);
+        close(writer);
+        return 0;
+    }
+    let reader = open_fifo(OpenFlags::RDONLY);
+    let mut len = 0;
+    loop {
+        let n = read(reader, &mut buffer[len..]);
+        assert!(n >= 0);
+        if n == 0 {
+            break;
+        }
+        len += n as usize;
+    }
+    assert_eq!(&buffer[..len], b"from the child");
+    close(reader);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    assert_eq!(unlink(FIFO), 0);
+    println!("fifo_test passed!");
+    0
+}
@@ -0,0 +1,46 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::read_file;
+
+/// Return the value of `field` of `/proc/meminfo` in kB.
+fn field(meminfo: &str, field: &str) -> usize {
+    meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix(field)?.strip_prefix(':'))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+        .unwrap_or(0)
+}
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    let Some(meminfo) = read_file("/proc/meminfo\0") else {
+        println!("free: cannot read /proc/meminfo");
+        return -1;
+    };
+    let total = field(&meminfo, "MemTotal");
+    let free = field(&meminfo, "MemFree");
+    let heap_total = field(&meminfo, "HeapTotal");
+    let heap_used = field(&meminfo, "HeapUsed");
+    println!("{:>5} {:>10} {:>10} {:>10}", "kB", "total", "used", "free");
+    println!(
+        "{:>5} {:>10} {:>10} {:>10}",
+        "Mem:",
+        total,
+        total - free,
+        free
+    );
+    println!(
+        "{:>5} {:>10} {:>10} {:>10}",
+        "Heap:",
+        heap_total,
+        heap_used,
+        heap_total - heap_used
+    );
+    0
+}
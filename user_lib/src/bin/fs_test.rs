@@ -12,7 +12,10 @@ extern crate user_lib;
 pub fn main() -> i32 {
     let test_str = "Hello, world!";
     let file_a = "file_a\0";
-    let fd = open(file_a, OpenFlags::CREATE | OpenFlags::WRONLY);
+    let fd = open(
+        file_a,
+        OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC,
+    );
     assert!(fd > 0);
     let fd = fd as usize;
     write(fd, test_str.as_bytes());
@@ -0,0 +1,77 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicU32, Ordering};
+use user_lib::{
+    errno::{EAGAIN, ETIMEDOUT},
+    exit, futex_wait, futex_wake,
+    sync::Mutex,
+    thread_create,
+    time::TimeSpec,
+    waittid, yield_,
+};
+
+const THREAD_COUNT: usize = 4;
+const ITERATIONS: usize = 100;
+
+static COUNTER: Mutex<usize> = Mutex::new(0);
+
+fn mutex_worker(_: usize) -> ! {
+    for _ in 0..ITERATIONS {
+        let mut counter = COUNTER.lock();
+        let value = *counter;
+        // give other threads a chance to contend for the lock
+        yield_();
+        *counter = value + 1;
+    }
+    exit(0);
+    unreachable!()
+}
+
+fn test_mutex() {
+    let tids: [usize; THREAD_COUNT] =
+        core::array::from_fn(|id| thread_create(mutex_worker as usize, id) as usize);
+    for tid in tids {
+        assert_eq!(waittid(tid), 0);
+    }
+    assert_eq!(*COUNTER.lock(), THREAD_COUNT * ITERATIONS);
+    println!("futex mutex test passed!");
+}
+
+static FLAG: AtomicU32 = AtomicU32::new(0);
+
+fn waker(_: usize) -> ! {
+    FLAG.store(1, Ordering::Release);
+    futex_wake(&FLAG, 1);
+    exit(0);
+    unreachable!()
+}
+
+fn test_wait_wake() {
+    // the value does not match
+    assert_eq!(futex_wait(&FLAG, 1, None), -EAGAIN);
+    assert_eq!(
+        futex_wait(&FLAG, 0, Some(&TimeSpec::from_ms(10))),
+        -ETIMEDOUT
+    );
+    let tid = thread_create(waker as usize, 0) as usize;
+    // the waker may run before we wait, in which case the value does not match
+    while FLAG.load(Ordering::Acquire) == 0 {
+        futex_wait(&FLAG, 0, None);
+    }
+    assert_eq!(waittid(tid), 0);
+    println!("futex wait/wake test passed!");
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    test_mutex();
+    test_wait_wake();
+    println!("futex_test passed!");
+    0
+}
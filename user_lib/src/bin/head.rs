@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+use user_lib::{OpenFlags, open, read, write};
+
+#[macro_use]
+extern crate user_lib;
+
+const STDIN: usize = 0;
+const STDOUT: usize = 1;
+
+/// Print the first lines of the file given, or of the standard input without a file, 10
+/// unless given by `-n`.
+#[unsafe(no_mangle)]
+fn main(_argc: usize, argv: &[&str]) -> i32 {
+    let mut args = argv.iter().skip(1);
+    let mut lines = 10;
+    let mut path = None;
+    while let Some(arg) = args.next() {
+        match *arg {
+            "-n" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) => lines = n,
+                None => {
+                    println!("usage: head [-n lines] [file]");
+                    return -1;
+                }
+            },
+            _ => path = Some(*arg),
+        }
+    }
+    let fd = match path {
+        Some(path) => open(path, OpenFlags::RDONLY),
+        None => STDIN as isize,
+    };
+    if fd < 0 {
+        println!("head: {}: No such file or directory", path.unwrap());
+        return -1;
+    }
+    let mut buffer = [0u8; 64];
+    while lines > 0 {
+        let len = read(fd as usize, &mut buffer);
+        if len <= 0 {
+            break;
+        }
+        let mut data = &buffer[..len as usize];
+        // stop after the last line, the rest of the input is left unread
+        while lines > 0 && !data.is_empty() {
+            let end = match data.iter().position(|&byte| byte == b'\n') {
+                Some(newline) => {
+                    lines -= 1;
+                    newline + 1
+                }
+                None => data.len(),
+            };
+            write(STDOUT, &data[..end]);
+            data = &data[end..];
+        }
+    }
+    0
+}
@@ -0,0 +1,125 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    accept, bind, close, connect,
+    errno::{EADDRINUSE, EADDRNOTAVAIL, EAGAIN, ECONNREFUSED, EDESTADDRREQ},
+    exit, fork, listen, read, recv, recvfrom, send, sendto, socket,
+    socket::{
+        AF_INET, INADDR_LOOPBACK, MsgFlags, SOCK_DGRAM, SOCK_NONBLOCK, SOCK_STREAM, SockAddrIn,
+    },
+    wait_exit_code, write,
+};
+
+const TCP_PORT: u16 = 7000;
+const UDP_PORT: u16 = 7001;
+/// A port nothing listens on.
+const CLOSED_PORT: u16 = 7002;
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    let mut buffer = [0u8; 64];
+
+    // a TCP listener on the loopback interface, with a client in another process
+    let addr = SockAddrIn::new(INADDR_LOOPBACK, TCP_PORT);
+    let listener = socket(AF_INET, SOCK_STREAM | SOCK_NONBLOCK);
+    assert!(listener >= 0);
+    let listener = listener as usize;
+    assert_eq!(
+        bind(listener, &SockAddrIn::new([192, 0, 2, 1], TCP_PORT)),
+        -EADDRNOTAVAIL
+    );
+    assert_eq!(bind(listener, &addr), 0);
+    assert_eq!(listen(listener, 4), 0);
+    assert_eq!(accept(listener), -EAGAIN);
+    let other = socket(AF_INET, SOCK_STREAM) as usize;
+    assert_eq!(bind(other, &addr), -EADDRINUSE);
+    assert_eq!(
+        connect(other, &SockAddrIn::new(INADDR_LOOPBACK, CLOSED_PORT)),
+        -ECONNREFUSED
+    );
+    close(other);
+    let pid = fork();
+    if pid == 0 {
+        close(listener);
+        let client = socket(AF_INET, SOCK_STREAM) as usize;
+        assert_eq!(connect(client, &addr), 0);
+        assert_eq!(write(client, b"hello"), 5);
+        let mut buffer = [0u8; 16];
+        assert_eq!(read(client, &mut buffer), 5);
+        assert_eq!(&buffer[..5], b"HELLO");
+        exit(0);
+    }
+    let server = loop {
+        match accept(listener) {
+            fd if fd == -EAGAIN => continue,
+            fd => break fd,
+        }
+    };
+    assert!(server >= 0);
+    let server = server as usize;
+    let mut len = 0;
+    while len < 5 {
+        let n = recv(server, &mut buffer[len..5], MsgFlags::empty());
+        assert!(n > 0);
+        len += n as usize;
+    }
+    buffer[..5].make_ascii_uppercase();
+    assert_eq!(send(server, &buffer[..5], MsgFlags::empty()), 5);
+    assert_eq!(wait_exit_code(pid as usize), 0);
+    assert_eq!(
+        read(server, &mut buffer),
+        0,
+        "EOF once the client is closed"
+    );
+    close(server);
+    close(listener);
+
+    // UDP datagrams, replied to the address of their sender
+    let receiver = socket(AF_INET, SOCK_DGRAM) as usize;
+    let sender = socket(AF_INET, SOCK_DGRAM) as usize;
+    let receiver_addr = SockAddrIn::new(INADDR_LOOPBACK, UDP_PORT);
+    assert_eq!(bind(receiver, &receiver_addr), 0);
+    assert_eq!(send(sender, b"lost", MsgFlags::empty()), -EDESTADDRREQ);
+    assert_eq!(
+        sendto(sender, b"first", MsgFlags::empty(), &receiver_addr),
+        5
+    );
+    let mut from = SockAddrIn::default();
+    assert_eq!(
+        recvfrom(receiver, &mut buffer[..3], MsgFlags::empty(), &mut from),
+        3,
+        "a datagram is truncated to the buffer"
+    );
+    assert_eq!(&buffer[..3], b"fir");
+    assert_eq!(from.addr, INADDR_LOOPBACK);
+    assert!(from.port() != 0, "the sender is bound to an ephemeral port");
+    assert_eq!(sendto(receiver, b"reply", MsgFlags::empty(), &from), 5);
+    assert_eq!(recv(sender, &mut buffer, MsgFlags::empty()), 5);
+    assert_eq!(&buffer[..5], b"reply");
+    // a connected socket only receives from its peer
+    assert_eq!(connect(receiver, &from), 0);
+    let stranger = socket(AF_INET, SOCK_DGRAM) as usize;
+    assert_eq!(
+        sendto(stranger, b"ignored", MsgFlags::empty(), &receiver_addr),
+        7
+    );
+    assert_eq!(recv(receiver, &mut buffer, MsgFlags::DONTWAIT), -EAGAIN);
+    assert_eq!(
+        sendto(sender, b"second", MsgFlags::empty(), &receiver_addr),
+        6
+    );
+    assert_eq!(recv(receiver, &mut buffer, MsgFlags::empty()), 6);
+    assert_eq!(&buffer[..6], b"second");
+    close(stranger);
+    close(sender);
+    close(receiver);
+
+    println!("inet_socket_test passed!");
+    0
+}
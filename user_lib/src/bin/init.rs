@@ -3,13 +3,19 @@
 #![feature(custom_test_frameworks)]
 #![test_runner(user_lib::test_utils::test_runner)]
 
-use user_lib::{exec, fork, wait, yield_};
+use user_lib::{OpenFlags, exec, fork, open, wait, yield_};
 
 #[macro_use]
 extern crate user_lib;
 
 #[unsafe(no_mangle)]
 fn main() -> i32 {
+    // stdin, stdout and stderr, inherited by all the processes
+    for fd in 0..3 {
+        if open("/dev/console\0", OpenFlags::RDWR) != fd {
+            return -1;
+        }
+    }
     if fork() == 0 {
         exec("shell\0", &[core::ptr::null()]);
     } else {
@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    OpenFlags, close,
+    errno::{EBUSY, EINVAL, ENOENT},
+    mount, open, read, umount, write,
+};
+
+const SCRATCH_DISK: &str = "/dev/vdb\0";
+const MOUNT_POINT: &str = "/mnt\0";
+const FILE: &str = "/mnt/mount_test\0";
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    assert_eq!(mount("/dev/vdz\0", MOUNT_POINT), -ENOENT);
+    assert_eq!(mount(SCRATCH_DISK, MOUNT_POINT), 0);
+    assert_eq!(mount(SCRATCH_DISK, MOUNT_POINT), -EBUSY);
+
+    let test_str = "Hello, scratch disk!";
+    let fd = open(
+        FILE,
+        OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC,
+    );
+    assert!(fd > 0);
+    let fd = fd as usize;
+    write(fd, test_str.as_bytes());
+    // the open file keeps the filesystem mounted
+    assert_eq!(umount(MOUNT_POINT), -EBUSY);
+    close(fd);
+    assert_eq!(umount(MOUNT_POINT), 0);
+    assert_eq!(umount(MOUNT_POINT), -EINVAL);
+
+    // the file is on the scratch disk, not on the boot image
+    assert!(open(FILE, OpenFlags::RDONLY) < 0);
+    assert_eq!(mount(SCRATCH_DISK, MOUNT_POINT), 0);
+    let fd = open(FILE, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut buffer = [0u8; 100];
+    let read_len = read(fd, &mut buffer) as usize;
+    close(fd);
+    assert_eq!(test_str, core::str::from_utf8(&buffer[..read_len]).unwrap());
+    assert_eq!(umount(MOUNT_POINT), 0);
+
+    println!("mount_test passed!");
+    0
+}
@@ -0,0 +1,110 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    OpenFlags, close,
+    errno::{EAGAIN, EBADF, EEXIST, EINVAL, EMSGSIZE, ENOENT},
+    exit, fork, mq_getattr, mq_open, mq_receive, mq_send, mq_setattr, mq_unlink,
+    mqueue::MqAttr,
+    poll::PollEvents,
+    poll_events, wait_exit_code,
+};
+
+const NAME: &str = "/mq_test\0";
+const MAXMSG: usize = 4;
+const MSGSIZE: usize = 32;
+/// Messages sent by the producer, more than the queue holds.
+const MESSAGES: u32 = 16;
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    let mut buffer = [0u8; MSGSIZE];
+    let mut priority = 0;
+    let attr = MqAttr {
+        maxmsg: MAXMSG as i64,
+        msgsize: MSGSIZE as i64,
+        ..Default::default()
+    };
+
+    // creating and opening by name
+    assert_eq!(mq_open("mq_test\0", OpenFlags::RDWR, None), -EINVAL);
+    assert_eq!(mq_open(NAME, OpenFlags::RDWR, None), -ENOENT);
+    let too_large = MqAttr {
+        maxmsg: 1 << 20,
+        ..attr
+    };
+    assert_eq!(
+        mq_open(NAME, OpenFlags::RDWR | OpenFlags::CREATE, Some(&too_large)),
+        -EINVAL
+    );
+    let flags = OpenFlags::RDWR | OpenFlags::CREATE | OpenFlags::NONBLOCK;
+    let fd = mq_open(NAME, flags, Some(&attr));
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    assert_eq!(mq_open(NAME, flags | OpenFlags::EXCL, Some(&attr)), -EEXIST);
+    let mut got = MqAttr::default();
+    assert_eq!(mq_getattr(fd, &mut got), 0);
+    assert_eq!(
+        (got.flags, got.maxmsg, got.msgsize, got.curmsgs),
+        (OpenFlags::NONBLOCK.bits() as i64, 4, 32, 0)
+    );
+
+    // limits, and messages received by decreasing priority
+    assert_eq!(
+        poll_events(fd, PollEvents::IN | PollEvents::OUT),
+        PollEvents::OUT
+    );
+    assert_eq!(mq_receive(fd, &mut buffer, &mut priority), -EAGAIN);
+    assert_eq!(mq_send(fd, &[0; MSGSIZE + 1], 0), -EMSGSIZE);
+    for (msg, priority) in [(&b"low"[..], 1), (b"high", 5), (b"low again", 1), (b"", 3)] {
+        assert_eq!(mq_send(fd, msg, priority), 0);
+    }
+    assert_eq!(mq_send(fd, b"full", 9), -EAGAIN);
+    assert_eq!(
+        poll_events(fd, PollEvents::IN | PollEvents::OUT),
+        PollEvents::IN
+    );
+    assert_eq!(
+        mq_receive(fd, &mut buffer[..MSGSIZE - 1], &mut priority),
+        -EMSGSIZE
+    );
+    for (msg, expected) in [(&b"high"[..], 5), (b"", 3), (b"low", 1), (b"low again", 1)] {
+        let len = mq_receive(fd, &mut buffer, &mut priority);
+        assert_eq!((&buffer[..len as usize], priority), (msg, expected));
+    }
+    let reader = mq_open(NAME, OpenFlags::RDONLY, None) as usize;
+    assert_eq!(mq_send(reader, b"denied", 0), -EBADF);
+
+    // a producer blocking on the full queue, and a consumer receiving every message in order
+    assert_eq!(mq_setattr(fd, &MqAttr::default()), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(reader);
+        for i in 0..MESSAGES {
+            assert_eq!(mq_send(fd, &i.to_le_bytes(), 0), 0);
+        }
+        exit(0);
+    }
+    for i in 0..MESSAGES {
+        assert_eq!(mq_receive(reader, &mut buffer, &mut priority), 4);
+        assert_eq!(buffer[..4], i.to_le_bytes());
+    }
+    assert_eq!(wait_exit_code(pid as usize), 0);
+
+    // an unlinked queue stays open, and its name is free again
+    assert_eq!(mq_unlink(NAME), 0);
+    assert_eq!(mq_unlink(NAME), -ENOENT);
+    assert_eq!(mq_send(fd, b"still open", 0), 0);
+    assert_eq!(mq_receive(reader, &mut buffer, &mut priority), 10);
+    assert_eq!(mq_open(NAME, OpenFlags::RDWR, None), -ENOENT);
+    close(reader);
+    close(fd);
+
+    println!("mq_test passed!");
+    0
+}
@@ -0,0 +1,89 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::ptr::addr_of_mut;
+
+use user_lib::{close, exit, fcntl, fork, fs::F_SETPIPE_SZ, pipe, read, read_file, waitpid, write};
+
+/// The bytes moved by each `read` and `write`.
+const CHUNK: usize = 16 * 1024;
+const PIPE_SIZES: [usize; 3] = [4 * 1024, 64 * 1024, 1024 * 1024];
+
+static DATA: [u8; CHUNK] = [0x5a; CHUNK];
+static mut BUFFER: [u8; CHUNK] = [0; CHUNK];
+
+/// The time since boot in milliseconds, with the precision of `/proc/uptime`.
+fn uptime_ms() -> usize {
+    let uptime = read_file("/proc/uptime\0").expect("cannot read /proc/uptime");
+    let (sec, centisec) = uptime.trim().split_once('.').unwrap();
+    sec.parse::<usize>().unwrap() * 1000 + centisec.parse::<usize>().unwrap() * 10
+}
+
+/// Move `total` bytes through a pipe of `pipe_size` bytes from a child process, return the
+/// elapsed milliseconds.
+fn transfer(total: usize, pipe_size: usize) -> usize {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let [read_end, write_end] = fds;
+    assert_eq!(fcntl(read_end, F_SETPIPE_SZ, pipe_size), pipe_size as isize);
+    let start = uptime_ms();
+    let pid = fork();
+    if pid == 0 {
+        close(read_end);
+        let mut written = 0;
+        while written < total {
+            let len = write(write_end, &DATA[..CHUNK.min(total - written)]);
+            assert!(len > 0);
+            written += len as usize;
+        }
+        close(write_end);
+        exit(0);
+    }
+    close(write_end);
+    let buffer = unsafe { &mut *addr_of_mut!(BUFFER) };
+    let mut received = 0;
+    loop {
+        let len = read(read_end, buffer);
+        assert!(len >= 0);
+        if len == 0 {
+            break;
+        }
+        received += len as usize;
+    }
+    let elapsed = uptime_ms() - start;
+    close(read_end);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(received, total);
+    elapsed
+}
+
+/// Measure the throughput of pipes of several capacities, moving the MiB given, 16 by default.
+#[unsafe(no_mangle)]
+fn main(_argc: usize, argv: &[&str]) -> i32 {
+    let Some(mib) = argv
+        .get(1)
+        .map_or(Some(16), |arg| arg.parse::<usize>().ok())
+    else {
+        println!("usage: pipe_bench [MiB]");
+        return -1;
+    };
+    let total = mib * 1024 * 1024;
+    for pipe_size in PIPE_SIZES {
+        // at least the precision of the clock
+        let elapsed = transfer(total, pipe_size).max(10);
+        println!(
+            "pipe of {:>4} KiB: {} MiB in {} ms, {} KiB/s",
+            pipe_size / 1024,
+            mib,
+            elapsed,
+            total / 1024 * 1000 / elapsed
+        );
+    }
+    0
+}
@@ -0,0 +1,110 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::ptr::addr_of_mut;
+
+use user_lib::{
+    OpenFlags, close,
+    errno::{EAGAIN, EBUSY, EINVAL, EPERM},
+    exit, fcntl, fork,
+    fs::{F_GETPIPE_SZ, F_SETPIPE_SZ, PIPE_BUF},
+    open, pipe, pipe2, read, waitpid, write,
+};
+
+/// The writes of each writer of the atomicity check.
+const WRITES: usize = 32;
+
+static A: [u8; PIPE_BUF] = [b'a'; PIPE_BUF];
+static B: [u8; PIPE_BUF] = [b'b'; PIPE_BUF];
+static mut BUFFER: [u8; PIPE_BUF] = [0; PIPE_BUF];
+
+fn spawn_writer(read_end: usize, write_end: usize, data: &'static [u8]) -> usize {
+    let pid = fork();
+    if pid == 0 {
+        close(read_end);
+        for _ in 0..WRITES {
+            assert_eq!(write(write_end, data), data.len() as isize);
+        }
+        exit(0);
+    }
+    pid as usize
+}
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    let buffer = unsafe { &mut *addr_of_mut!(BUFFER) };
+
+    // the capacity is shared by the ends and rounded up to pages
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe2(&mut fds, OpenFlags::NONBLOCK), 0);
+    let [read_end, write_end] = fds;
+    assert_eq!(fcntl(read_end, F_GETPIPE_SZ, 0), 64 * 1024);
+    assert_eq!(fcntl(write_end, F_SETPIPE_SZ, 1), PIPE_BUF as isize);
+    assert_eq!(fcntl(read_end, F_GETPIPE_SZ, 0), PIPE_BUF as isize);
+    assert_eq!(fcntl(read_end, F_SETPIPE_SZ, usize::MAX), -EPERM);
+
+    // a full pipe takes nothing more, and a write of at most PIPE_BUF bytes is not split
+    assert_eq!(write(write_end, &A[..100]), 100);
+    assert_eq!(write(write_end, &B), -EAGAIN);
+    assert_eq!(
+        write(write_end, &B[..PIPE_BUF - 100]),
+        (PIPE_BUF - 100) as isize
+    );
+    assert_eq!(write(write_end, b"!"), -EAGAIN);
+
+    // the data is kept when the capacity changes, which cannot be less than the data
+    assert_eq!(
+        fcntl(write_end, F_SETPIPE_SZ, 2 * PIPE_BUF),
+        2 * PIPE_BUF as isize
+    );
+    assert_eq!(write(write_end, b"!"), 1);
+    assert_eq!(fcntl(write_end, F_SETPIPE_SZ, PIPE_BUF), -EBUSY);
+    assert_eq!(read(read_end, buffer), PIPE_BUF as isize);
+    assert!(buffer[..100].iter().all(|&byte| byte == b'a'));
+    assert!(buffer[100..].iter().all(|&byte| byte == b'b'));
+    assert_eq!(read(read_end, buffer), 1);
+    assert_eq!(buffer[0], b'!');
+    close(read_end);
+    close(write_end);
+
+    // only pipes have a capacity
+    let null = open("/dev/null\0", OpenFlags::RDONLY);
+    assert!(null > 0);
+    assert_eq!(fcntl(null as usize, F_GETPIPE_SZ, 0), -EINVAL);
+    close(null as usize);
+
+    // the writes of PIPE_BUF bytes of two processes are not interleaved
+    assert_eq!(pipe(&mut fds), 0);
+    let [read_end, write_end] = fds;
+    let writers = [
+        spawn_writer(read_end, write_end, &A),
+        spawn_writer(read_end, write_end, &B),
+    ];
+    close(write_end);
+    let mut counts = [0usize; 2];
+    loop {
+        // the writes are not split, so each read takes one of them
+        let len = read(read_end, buffer);
+        if len == 0 {
+            break;
+        }
+        assert_eq!(len, PIPE_BUF as isize);
+        let index = (buffer[0] - b'a') as usize;
+        assert!(buffer.iter().all(|&byte| byte == buffer[0]));
+        counts[index] += 1;
+    }
+    assert_eq!(counts, [WRITES; 2]);
+    close(read_end);
+    for pid in writers {
+        let mut exit_code = 0;
+        assert_eq!(waitpid(pid, &mut exit_code), pid as isize);
+        assert_eq!(exit_code, 0);
+    }
+    println!("pipe_size_test passed!");
+    0
+}
@@ -0,0 +1,163 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    OpenFlags, close,
+    errno::{EAGAIN, EBADF},
+    fcntl, fork,
+    fs::{F_GETFL, F_SETFL},
+    open, pipe, pipe2, poll,
+    poll::{FdSet, PollEvents, PollFd},
+    read, select,
+    time::TimeSpec,
+    waitpid, write,
+};
+
+fn poll_fd(fd: usize, events: PollEvents) -> PollFd {
+    PollFd {
+        fd: fd as i32,
+        events,
+        revents: PollEvents::empty(),
+    }
+}
+
+/// Sleep by polling nothing.
+fn sleep_ms(ms: isize) {
+    assert_eq!(poll(&mut [], ms), 0);
+}
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    // non-blocking ends return EAGAIN instead of blocking
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe2(&mut fds, OpenFlags::NONBLOCK), 0);
+    let [read_end, write_end] = fds;
+    let mut buffer = [0u8; 64];
+    assert_eq!(read(read_end, &mut buffer), -EAGAIN);
+    let mut written = 0;
+    loop {
+        let len = write(write_end, &[0x5a; 16]);
+        if len == -EAGAIN {
+            break;
+        }
+        assert!(len > 0);
+        written += len;
+    }
+    assert!(written > 0);
+    assert_eq!(read(read_end, &mut buffer), written.min(64));
+    close(read_end);
+    close(write_end);
+
+    // O_NONBLOCK can be set later by F_SETFL
+    assert_eq!(pipe(&mut fds), 0);
+    let [read_end, write_end] = fds;
+    let flags = fcntl(read_end, F_GETFL, 0) as u32 | OpenFlags::NONBLOCK.bits();
+    assert_eq!(fcntl(read_end, F_SETFL, flags as usize), 0);
+    assert_eq!(read(read_end, &mut buffer), -EAGAIN);
+
+    // an empty pipe is writable but not readable, and the timeout elapses
+    let mut poll_fds = [
+        poll_fd(read_end, PollEvents::IN),
+        poll_fd(write_end, PollEvents::OUT),
+    ];
+    assert_eq!(poll(&mut poll_fds, 0), 1);
+    assert_eq!(poll_fds[0].revents, PollEvents::empty());
+    assert_eq!(poll_fds[1].revents, PollEvents::OUT);
+    assert_eq!(poll(&mut poll_fds[..1], 50), 0);
+
+    // woken up by a write from another process
+    let mut other = [0usize; 2];
+    assert_eq!(pipe(&mut other), 0);
+    let mut done = [0usize; 2];
+    assert_eq!(pipe(&mut done), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(read_end);
+        close(other[0]);
+        close(done[1]);
+        sleep_ms(50);
+        assert_eq!(write(other[1], b"ping"), 4);
+        // the write end stays open until the parent has read
+        assert_eq!(read(done[0], &mut buffer), 0);
+        // the readers see a hangup once all write ends are closed
+        return 0;
+    }
+    close(other[1]);
+    close(done[0]);
+    let mut poll_fds = [
+        poll_fd(read_end, PollEvents::IN),
+        poll_fd(other[0], PollEvents::IN),
+        // ignored
+        PollFd {
+            fd: -1,
+            ..Default::default()
+        },
+    ];
+    assert_eq!(poll(&mut poll_fds, -1), 1);
+    assert_eq!(poll_fds[0].revents, PollEvents::empty());
+    assert_eq!(poll_fds[1].revents, PollEvents::IN);
+    // a read returns the data available without waiting to fill the buffer
+    assert_eq!(read(other[0], &mut buffer), 4);
+    close(done[1]);
+    assert_eq!(poll(&mut poll_fds[1..], -1), 1);
+    assert_eq!(poll_fds[1].revents, PollEvents::HUP);
+    assert_eq!(read(other[0], &mut buffer), 0);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    close(other[0]);
+
+    // a closed fd is reported right away
+    let mut poll_fds = [poll_fd(other[0], PollEvents::IN)];
+    assert_eq!(poll(&mut poll_fds, -1), 1);
+    assert_eq!(poll_fds[0].revents, PollEvents::NVAL);
+
+    // select reports the ready fds in the sets
+    let null = open("/dev/null\0", OpenFlags::RDONLY);
+    assert!(null > 0);
+    let null = null as usize;
+    let mut readfds = FdSet::new();
+    readfds.insert(read_end);
+    readfds.insert(null);
+    let mut writefds = FdSet::new();
+    writefds.insert(write_end);
+    let nfds = read_end.max(write_end).max(null) + 1;
+    let timeout = TimeSpec::from_ms(0);
+    assert_eq!(
+        select(
+            nfds,
+            Some(&mut readfds),
+            Some(&mut writefds),
+            None,
+            Some(&timeout)
+        ),
+        2
+    );
+    assert!(!readfds.contains(read_end) && readfds.contains(null));
+    assert!(writefds.contains(write_end));
+    let mut readfds = FdSet::new();
+    readfds.insert(read_end);
+    let timeout = TimeSpec::from_ms(50);
+    assert_eq!(
+        select(nfds, Some(&mut readfds), None, None, Some(&timeout)),
+        0
+    );
+    assert!(!readfds.contains(read_end));
+    write(write_end, b"!");
+    readfds.insert(read_end);
+    assert_eq!(select(nfds, Some(&mut readfds), None, None, None), 1);
+    assert!(readfds.contains(read_end));
+    close(null);
+    readfds.insert(null);
+    assert_eq!(select(nfds, Some(&mut readfds), None, None, None), -EBADF);
+
+    close(read_end);
+    close(write_end);
+    println!("poll_test passed!");
+    0
+}
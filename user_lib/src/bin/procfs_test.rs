@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::format;
+use user_lib::{
+    OpenFlags, close, exit, fork,
+    fs::{FileType, Stat},
+    fstat, open, pipe, read, read_file, waitpid, write,
+};
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    let meminfo = read_file("/proc/meminfo\0").unwrap();
+    assert!(meminfo.starts_with("MemTotal:"));
+    assert!(read_file("/proc/uptime\0").unwrap().contains('.'));
+    let mounts = read_file("/proc/mounts\0").unwrap();
+    assert!(mounts.lines().any(|line| line == "/dev/vda / easy_fs"));
+    assert!(mounts.lines().any(|line| line == "proc /proc procfs"));
+
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let pid = fork();
+    if pid == 0 {
+        // wait for the parent to look at us
+        let mut byte = [0u8];
+        read(fds[0], &mut byte);
+        exit(0);
+    }
+    let status = read_file(&format!("/proc/{pid}/status\0")).unwrap();
+    assert!(status.lines().any(|line| line == format!("Pid:\t{pid}")));
+    assert!(status.contains("procfs_test"));
+    let cmdline = read_file(&format!("/proc/{pid}/cmdline\0")).unwrap();
+    assert!(cmdline.contains("procfs_test") && cmdline.ends_with('\0'));
+    let maps = read_file(&format!("/proc/{pid}/maps\0")).unwrap();
+    assert!(maps.lines().any(|line| line.ends_with("r-xu")));
+
+    // an entry of the fd directory opens the same file, the pipe wakes up the child
+    let fd = open(&format!("/proc/{pid}/fd/{}\0", fds[1]), OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let mut stat = Stat::default();
+    assert_eq!(fstat(fd as usize, &mut stat), 0);
+    assert_eq!(stat.file_type(), Some(FileType::Fifo));
+    close(fds[0]);
+    close(fds[1]);
+    write(fd as usize, b"x");
+    close(fd as usize);
+
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert!(open(&format!("/proc/{pid}/status\0"), OpenFlags::RDONLY) < 0);
+
+    println!("procfs_test passed!");
+    0
+}
@@ -0,0 +1,72 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::{format, string::String, vec::Vec};
+use user_lib::{
+    OpenFlags, close,
+    fs::{DIRENT64_NAME_OFFSET, Dirent64},
+    getdents, open, read_file,
+};
+
+/// Return the pids of the processes listed in `/proc`.
+fn pids() -> Vec<usize> {
+    let fd = open("/proc\0", OpenFlags::RDONLY);
+    assert!(fd >= 0, "ps: cannot open /proc");
+    let fd = fd as usize;
+    let mut pids = Vec::new();
+    let mut buf = [0u8; 256];
+    loop {
+        let len = getdents(fd, &mut buf);
+        if len <= 0 {
+            break;
+        }
+        let mut offset = 0;
+        while offset < len as usize {
+            let dirent = unsafe { (buf.as_ptr().add(offset) as *const Dirent64).read_unaligned() };
+            let name = &buf[offset + DIRENT64_NAME_OFFSET..offset + dirent.reclen as usize];
+            let name = name.split(|&b| b == 0).next().unwrap();
+            if let Some(pid) = core::str::from_utf8(name).ok().and_then(|n| n.parse().ok()) {
+                pids.push(pid);
+            }
+            offset += dirent.reclen as usize;
+        }
+    }
+    close(fd);
+    pids
+}
+
+/// Return the value of `field` in the status of a process.
+fn field<'a>(status: &'a str, field: &str) -> &'a str {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix(field)?.strip_prefix(':'))
+        .map_or("?", str::trim)
+}
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    println!("{:>5} {:>5} S CMD", "PID", "PPID");
+    for pid in pids() {
+        // the process may have exited meanwhile
+        let Some(status) = read_file(&format!("/proc/{pid}/status\0")) else {
+            continue;
+        };
+        let cmdline = read_file(&format!("/proc/{pid}/cmdline\0")).unwrap_or_default();
+        let cmd: String = cmdline.split_terminator('\0').collect::<Vec<_>>().join(" ");
+        println!(
+            "{:>5} {:>5} {} {}",
+            pid,
+            field(&status, "PPid"),
+            field(&status, "State").chars().next().unwrap_or('?'),
+            cmd,
+        );
+    }
+    0
+}
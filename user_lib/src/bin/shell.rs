@@ -3,17 +3,14 @@
 #![feature(custom_test_frameworks)]
 #![test_runner(user_lib::test_utils::test_runner)]
 
-use alloc::{format, string::String, vec::Vec};
-use user_lib::{OpenFlags, close, console::getchar, exec, fork, open, waitpid};
+use alloc::{format, string::String, vec, vec::Vec};
+use user_lib::{OpenFlags, close, dup2, exec, fork, open, pipe2, read, tcsetpgrp, waitpid};
 
 #[macro_use]
 extern crate user_lib;
 extern crate alloc;
 
-const LF: u8 = 0x0au8;
-const CR: u8 = 0x0du8;
-const DL: u8 = 0x7fu8;
-const BS: u8 = 0x08u8;
+const STDIN: usize = 0;
 
 fn parse_cmd(line: &str) -> (Vec<String>, Option<String>, Option<String>) {
     let mut args = Vec::new();
@@ -42,61 +39,116 @@ fn get_args_addr(args: &[String]) -> Vec<*const u8> {
     args_addr
 }
 
+/// Read a line edited by the terminal, without the newline. Return `None` on EOF.
+fn read_line() -> Option<String> {
+    let mut line = Vec::new();
+    let mut buf = [0u8; 128];
+    loop {
+        let len = read(STDIN, &mut buf);
+        if len <= 0 {
+            return (!line.is_empty()).then(|| String::from_utf8_lossy(&line).into_owned());
+        }
+        line.extend_from_slice(&buf[..len as usize]);
+        if line.last() == Some(&b'\n') {
+            line.pop();
+            return Some(String::from_utf8_lossy(&line).into_owned());
+        }
+    }
+}
+
+/// Run `args` with its standard input and output connected to the pipe ends given, then
+/// redirected to the files given.
+fn run_command(
+    args: &[String],
+    stdin: Option<usize>,
+    stdout: Option<usize>,
+    input_file: Option<String>,
+    output_file: Option<String>,
+) -> i32 {
+    for (fd, target_fd) in [(stdin, 0), (stdout, 1)] {
+        if let Some(fd) = fd {
+            dup2(fd, target_fd);
+        }
+    }
+    let redirections = [
+        (input_file, OpenFlags::RDONLY, 0),
+        (
+            output_file,
+            OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC,
+            1,
+        ),
+    ];
+    for (file, flags, target_fd) in redirections {
+        let Some(file) = file else {
+            continue;
+        };
+        let fd = open(file.as_str(), flags);
+        if fd < 0 {
+            println!("Error when opening {}!", file);
+            return -4;
+        }
+        dup2(fd as usize, target_fd);
+        close(fd as usize);
+    }
+
+    let args_addr = get_args_addr(args);
+    if exec(args[0].as_str(), &args_addr) == -1 {
+        println!("Error when executing!");
+        return -4;
+    }
+
+    unreachable!();
+}
+
 #[unsafe(no_mangle)]
 pub fn main() -> i32 {
     println!("Rust user shell");
-    let mut line: String = String::new();
-    print!(">> ");
     loop {
-        let c = getchar();
-        match c {
-            LF | CR => {
-                println!("");
-                if !line.is_empty() {
-                    let (args, input_file, output_file) = parse_cmd(&line);
-                    let args_addr = get_args_addr(&args);
-                    let pid = fork();
-                    if pid == 0 {
-                        // child process
-                        if let Some(input_file) = input_file {
-                            close(0);
-                            let fd = open(input_file.as_str(), OpenFlags::RDONLY);
-                            debug_assert_eq!(fd, 0);
-                        }
-                        if let Some(output_file) = output_file {
-                            close(1);
-                            let fd =
-                                open(output_file.as_str(), OpenFlags::CREATE | OpenFlags::WRONLY);
-                            debug_assert_eq!(fd, 1);
-                        }
-
-                        if exec(args[0].as_str(), &args_addr) == -1 {
-                            println!("Error when executing!");
-                            return -4;
-                        }
-
-                        unreachable!();
-                    } else {
-                        let mut exit_code: i32 = 0;
-                        let exit_pid = waitpid(pid as usize, &mut exit_code);
-                        assert_eq!(pid, exit_pid);
-                    }
-                    line.clear();
-                }
-                print!(">> ");
-            }
-            BS | DL => {
-                if !line.is_empty() {
-                    print!("{}", BS as char);
-                    print!(" ");
-                    print!("{}", BS as char);
-                    line.pop();
-                }
-            }
-            _ => {
-                print!("{}", c as char);
-                line.push(c as char);
+        print!(">> ");
+        // the shell keeps running on EOF as it is the only one
+        let Some(line) = read_line() else {
+            println!("");
+            continue;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let commands: Vec<_> = line.split('|').map(parse_cmd).collect();
+        if commands.iter().any(|(args, ..)| args.is_empty()) {
+            println!("Error: empty command in the pipeline!");
+            continue;
+        }
+        // the standard input and output of each command, the pipes between them are closed by
+        // exec once duplicated
+        let mut stdio = vec![(None, None); commands.len()];
+        for i in 1..commands.len() {
+            let mut pipe_fd = [0usize; 2];
+            pipe2(&mut pipe_fd, OpenFlags::CLOEXEC);
+            stdio[i - 1].1 = Some(pipe_fd[1]);
+            stdio[i].0 = Some(pipe_fd[0]);
+        }
+        let mut pids = Vec::new();
+        for ((args, input_file, output_file), &(stdin, stdout)) in commands.into_iter().zip(&stdio)
+        {
+            let pid = fork();
+            if pid == 0 {
+                // child process
+                return run_command(&args, stdin, stdout, input_file, output_file);
             }
+            pids.push(pid);
+        }
+        for fd in stdio
+            .iter()
+            .flat_map(|&(stdin, stdout)| stdin.into_iter().chain(stdout))
+        {
+            close(fd);
+        }
+        // ^C goes to the last command, the others see a broken pipe once it exits
+        tcsetpgrp(STDIN, *pids.last().unwrap() as usize);
+        for pid in pids {
+            let mut exit_code: i32 = 0;
+            let exit_pid = waitpid(pid as usize, &mut exit_code);
+            assert_eq!(pid, exit_pid);
         }
     }
 }
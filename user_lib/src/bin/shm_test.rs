@@ -0,0 +1,102 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    errno::{EEXIST, EINVAL, ENOENT, ENOMEM},
+    exit, fork,
+    ipc::{IPC_CREAT, IPC_EXCL, IPC_PRIVATE, IPC_RMID, IPC_SET, IPC_STAT, ShmIdDs},
+    shmat, shmctl, shmdt, shmget, wait_exit_code,
+};
+
+const KEY: i32 = 0x5348_4d54;
+const SIZE: usize = 8192;
+
+fn stat(id: usize) -> ShmIdDs {
+    let mut ds = ShmIdDs::default();
+    assert_eq!(shmctl(id, IPC_STAT, &mut ds), 0);
+    ds
+}
+
+fn attach(id: usize) -> &'static mut [u8] {
+    let addr = shmat(id, 0, 0);
+    assert!(addr > 0);
+    unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, SIZE) }
+}
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    // a keyed segment, found again by its key
+    assert_eq!(shmget(KEY, SIZE, 0), -ENOENT);
+    let id = shmget(KEY, SIZE, IPC_CREAT | 0o600);
+    assert!(id >= 0);
+    let id = id as usize;
+    assert_eq!(shmget(KEY, SIZE, 0), id as isize);
+    assert_eq!(shmget(KEY, SIZE, IPC_CREAT | IPC_EXCL), -EEXIST);
+    assert_eq!(shmget(KEY, SIZE + 1, 0), -EINVAL);
+    // a segment larger than the memory fails, one larger than the region is refused
+    assert_eq!(shmget(IPC_PRIVATE, 1 << 32, 0), -ENOMEM);
+    assert_eq!(shmget(IPC_PRIVATE, usize::MAX, 0), -EINVAL);
+    let private = shmget(IPC_PRIVATE, SIZE, 0);
+    assert!(private >= 0 && private as usize != id);
+    assert_eq!(
+        shmctl(private as usize, IPC_RMID, &mut ShmIdDs::default()),
+        0
+    );
+    let ds = stat(id);
+    assert_eq!(
+        (ds.key, ds.mode, ds.size, ds.nattch),
+        (KEY, 0o600, SIZE as u64, 0)
+    );
+    let mut ds = ShmIdDs {
+        mode: 0o644,
+        ..Default::default()
+    };
+    assert_eq!(shmctl(id, IPC_SET, &mut ds), 0);
+    assert_eq!(stat(id).mode, 0o644);
+
+    // a segment is zeroed, and shared with the children inheriting it or attaching it
+    let shared = attach(id);
+    assert!(shared.iter().all(|&byte| byte == 0));
+    assert_eq!(stat(id).nattch, 1);
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(stat(id).nattch, 2, "a child inherits the attachment");
+        shared[0] = 1;
+        let other = attach(shmget(KEY, 0, 0) as usize);
+        assert_eq!(other[0], 1, "attachments map the same frames");
+        other[SIZE - 1] = 2;
+        exit(0);
+    }
+    assert_eq!(wait_exit_code(pid as usize), 0);
+    assert_eq!((shared[0], shared[SIZE - 1]), (1, 2));
+    assert_eq!(stat(id).nattch, 1, "the child detaches on exit");
+
+    // a removed segment stays attached, but its key is free again
+    let addr = shared.as_ptr() as usize;
+    assert_eq!(shmdt(addr + 1), -EINVAL);
+    assert_eq!(shmctl(id, IPC_RMID, &mut ShmIdDs::default()), 0);
+    let mut ds = ShmIdDs::default();
+    assert_eq!(shmctl(id, IPC_STAT, &mut ds), -EINVAL);
+    shared[1] = 3;
+    assert_eq!(shared[1], 3);
+    let new_id = shmget(KEY, SIZE, IPC_CREAT);
+    assert!(new_id >= 0 && new_id as usize != id);
+    assert_eq!(attach(new_id as usize)[0], 0);
+    // the trampoline, a trap frame and the program are not for segments
+    let trampoline = usize::MAX - 0xfff;
+    for addr in [trampoline, trampoline - 0x1000, 0x1000] {
+        assert_eq!(shmat(new_id as usize, addr, 0), -EINVAL);
+    }
+    assert_eq!(shmdt(addr), 0);
+    assert_eq!(shmdt(addr), -EINVAL);
+    assert_eq!(shmat(id, 0, 0), -EINVAL);
+    assert_eq!(shmctl(new_id as usize, IPC_RMID, &mut ds), 0);
+
+    println!("shm_test passed!");
+    0
+}
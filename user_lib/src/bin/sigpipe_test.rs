@@ -0,0 +1,144 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    OpenFlags, close, dup, dup2, exec, exit, fcntl, fork,
+    fs::{F_SETPIPE_SZ, PIPE_BUF},
+    pipe, pipe2, poll,
+    poll::{PollEvents, PollFd},
+    read,
+    sig::SignalFlags,
+    wait_exit_code, write,
+};
+
+const STDIN: usize = 0;
+const STDOUT: usize = 1;
+
+/// Run `path` with its standard input and output connected to `stdin` and `stdout`, the other
+/// fds must be closed by exec.
+fn spawn(path: &str, args: &[*const u8], stdin: usize, stdout: usize) -> usize {
+    let pid = fork();
+    if pid == 0 {
+        dup2(stdin, STDIN);
+        dup2(stdout, STDOUT);
+        exec(path, args);
+        panic!("exec failed");
+    }
+    pid as usize
+}
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    let sigpipe = -SignalFlags::SIGPIPE.to_number();
+    let mut buffer = [0u8; 64];
+
+    // a write without readers kills the writer
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let [read_end, write_end] = fds;
+    close(read_end);
+    let mut poll_fds = [PollFd {
+        fd: write_end as i32,
+        events: PollEvents::OUT,
+        revents: PollEvents::empty(),
+    }];
+    assert_eq!(poll(&mut poll_fds, 0), 1);
+    assert!(poll_fds[0].revents.contains(PollEvents::ERR));
+    let pid = fork();
+    if pid == 0 {
+        write(write_end, b"lost");
+        exit(0);
+    }
+    close(write_end);
+    assert_eq!(wait_exit_code(pid as usize), sigpipe);
+
+    // a blocked writer is woken up when the last reader is gone
+    assert_eq!(pipe(&mut fds), 0);
+    let [read_end, write_end] = fds;
+    assert_eq!(fcntl(write_end, F_SETPIPE_SZ, PIPE_BUF), PIPE_BUF as isize);
+    let pid = fork();
+    if pid == 0 {
+        close(read_end);
+        loop {
+            write(write_end, &buffer);
+        }
+    }
+    close(write_end);
+    assert_eq!(read(read_end, &mut buffer), buffer.len() as isize);
+    close(read_end);
+    assert_eq!(wait_exit_code(pid as usize), sigpipe);
+
+    // EOF once every write end, duplicated or inherited, is closed
+    assert_eq!(pipe(&mut fds), 0);
+    let [read_end, write_end] = fds;
+    let other = dup(write_end);
+    assert!(other > 0);
+    assert_eq!(write(other as usize, b"first "), 6);
+    close(other as usize);
+    let pid = fork();
+    if pid == 0 {
+        close(read_end);
+        assert_eq!(write(write_end, b"last"), 4);
+        exit(0);
+    }
+    close(write_end);
+    let mut len = 0;
+    loop {
+        let n = read(read_end, &mut buffer[len..]);
+        assert!(n >= 0);
+        if n == 0 {
+            break;
+        }
+        len += n as usize;
+    }
+    assert_eq!(&buffer[..len], b"first last");
+    close(read_end);
+    assert_eq!(wait_exit_code(pid as usize), 0);
+
+    // yes | head -n 3, where yes only ends by SIGPIPE once head exits
+    let mut input = [0usize; 2];
+    let mut output = [0usize; 2];
+    assert_eq!(pipe2(&mut input, OpenFlags::CLOEXEC), 0);
+    assert_eq!(pipe2(&mut output, OpenFlags::CLOEXEC), 0);
+    let yes = spawn(
+        "yes\0",
+        &[c"yes".as_ptr().cast(), core::ptr::null()],
+        STDIN,
+        input[1],
+    );
+    let head = spawn(
+        "head\0",
+        &[
+            c"head".as_ptr().cast(),
+            c"-n".as_ptr().cast(),
+            c"3".as_ptr().cast(),
+            core::ptr::null(),
+        ],
+        input[0],
+        output[1],
+    );
+    close(input[0]);
+    close(input[1]);
+    close(output[1]);
+    let mut len = 0;
+    loop {
+        let n = read(output[0], &mut buffer[len..]);
+        assert!(n >= 0);
+        if n == 0 {
+            break;
+        }
+        len += n as usize;
+    }
+    close(output[0]);
+    assert_eq!(&buffer[..len], b"y\ny\ny\n");
+    assert_eq!(wait_exit_code(head), 0);
+    assert_eq!(wait_exit_code(yes), sigpipe);
+
+    println!("sigpipe_test passed!");
+    0
+}
@@ -0,0 +1,113 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{
+    condvar_create, condvar_signal, condvar_wait, errno::EPERM, exit, mutex_create, mutex_lock,
+    mutex_unlock, semaphore_create, semaphore_down, semaphore_up, thread_create, waittid, yield_,
+};
+
+const THREAD_COUNT: usize = 4;
+const ITERATIONS: usize = 100;
+const BUFFER_SIZE: usize = 4;
+
+static MUTEX: AtomicUsize = AtomicUsize::new(0);
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Increment the counter with a non-atomic read-modify-write protected by the mutex.
+fn mutex_worker(_: usize) -> ! {
+    let mutex = MUTEX.load(Ordering::Relaxed);
+    for _ in 0..ITERATIONS {
+        assert_eq!(mutex_lock(mutex), 0);
+        let value = COUNTER.load(Ordering::Relaxed);
+        // give other threads a chance to break the critical section
+        yield_();
+        COUNTER.store(value + 1, Ordering::Relaxed);
+        assert_eq!(mutex_unlock(mutex), 0);
+    }
+    exit(0);
+    unreachable!()
+}
+
+fn test_mutex() {
+    MUTEX.store(mutex_create() as usize, Ordering::Relaxed);
+    COUNTER.store(0, Ordering::Relaxed);
+    let tids: [usize; THREAD_COUNT] =
+        core::array::from_fn(|id| thread_create(mutex_worker as usize, id) as usize);
+    for tid in tids {
+        assert_eq!(waittid(tid), 0);
+    }
+    assert_eq!(COUNTER.load(Ordering::Relaxed), THREAD_COUNT * ITERATIONS);
+    println!("mutex test passed!");
+}
+
+static EMPTY: AtomicUsize = AtomicUsize::new(0);
+static FULL: AtomicUsize = AtomicUsize::new(0);
+static BUFFER: [AtomicUsize; BUFFER_SIZE] = [const { AtomicUsize::new(0) }; BUFFER_SIZE];
+
+fn producer(_: usize) -> ! {
+    for i in 0..ITERATIONS {
+        assert_eq!(semaphore_down(EMPTY.load(Ordering::Relaxed)), 0);
+        BUFFER[i % BUFFER_SIZE].store(i, Ordering::Relaxed);
+        assert_eq!(semaphore_up(FULL.load(Ordering::Relaxed)), 0);
+    }
+    exit(0);
+    unreachable!()
+}
+
+fn test_semaphore() {
+    EMPTY.store(semaphore_create(BUFFER_SIZE) as usize, Ordering::Relaxed);
+    FULL.store(semaphore_create(0) as usize, Ordering::Relaxed);
+    let tid = thread_create(producer as usize, 0) as usize;
+    // the consumer is the main thread, items must arrive in order
+    for i in 0..ITERATIONS {
+        assert_eq!(semaphore_down(FULL.load(Ordering::Relaxed)), 0);
+        assert_eq!(BUFFER[i % BUFFER_SIZE].load(Ordering::Relaxed), i);
+        assert_eq!(semaphore_up(EMPTY.load(Ordering::Relaxed)), 0);
+    }
+    assert_eq!(waittid(tid), 0);
+    println!("semaphore test passed!");
+}
+
+static CONDVAR: AtomicUsize = AtomicUsize::new(0);
+static READY: AtomicUsize = AtomicUsize::new(0);
+
+fn signaler(_: usize) -> ! {
+    let mutex = MUTEX.load(Ordering::Relaxed);
+    assert_eq!(mutex_lock(mutex), 0);
+    READY.store(1, Ordering::Relaxed);
+    assert_eq!(condvar_signal(CONDVAR.load(Ordering::Relaxed)), 0);
+    assert_eq!(mutex_unlock(mutex), 0);
+    exit(0);
+    unreachable!()
+}
+
+fn test_condvar() {
+    let mutex = MUTEX.load(Ordering::Relaxed);
+    CONDVAR.store(condvar_create() as usize, Ordering::Relaxed);
+    assert_eq!(mutex_lock(mutex), 0);
+    let tid = thread_create(signaler as usize, 0) as usize;
+    while READY.load(Ordering::Relaxed) == 0 {
+        assert_eq!(condvar_wait(CONDVAR.load(Ordering::Relaxed), mutex), 0);
+    }
+    assert_eq!(mutex_unlock(mutex), 0);
+    assert_eq!(waittid(tid), 0);
+    // the mutex is not held any more
+    assert_eq!(mutex_unlock(mutex), -EPERM);
+    assert_eq!(condvar_wait(CONDVAR.load(Ordering::Relaxed), mutex), -EPERM);
+    println!("condvar test passed!");
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    test_mutex();
+    test_semaphore();
+    test_condvar();
+    println!("sync_test passed!");
+    0
+}
@@ -0,0 +1,93 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use user_lib::{errno::EINVAL, exit, fork, gettid, thread_create, waitpid, waittid, yield_};
+
+const THREAD_COUNT: usize = 4;
+const ITERATIONS: usize = 1000;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+static RELEASED: AtomicBool = AtomicBool::new(false);
+
+fn worker(id: usize) -> ! {
+    for i in 0..ITERATIONS {
+        COUNTER.fetch_add(1, Ordering::Relaxed);
+        if i % 100 == 0 {
+            yield_();
+        }
+    }
+    println!("thread {} (tid = {}) done", id, gettid());
+    exit(id as i32 + 100);
+    unreachable!()
+}
+
+/// Run until released by the main thread.
+fn waiter(_arg: usize) -> ! {
+    while !RELEASED.load(Ordering::Acquire) {
+        yield_();
+    }
+    exit(0);
+    unreachable!()
+}
+
+fn idle(_arg: usize) -> ! {
+    exit(0);
+    unreachable!()
+}
+
+/// Fork only succeeds once the other threads have exited, joined or not.
+fn test_fork() {
+    let tid = thread_create(waiter as usize, 0);
+    assert!(tid > 0);
+    assert_eq!(fork(), -EINVAL);
+    RELEASED.store(true, Ordering::Release);
+    while fork_after_exit(tid as usize).is_none() {
+        yield_();
+    }
+    assert_eq!(waittid(tid as usize), 0);
+}
+
+/// Fork with the exited thread `tid` not joined, return `None` if it is still running.
+fn fork_after_exit(tid: usize) -> Option<()> {
+    let pid = fork();
+    if pid == -EINVAL {
+        return None;
+    }
+    if pid == 0 {
+        // the child reuses the tid of the thread left behind, whose stack is not copied
+        let child_tid = thread_create(idle as usize, 0);
+        assert_eq!(child_tid, tid as isize);
+        assert_eq!(waittid(child_tid as usize), 0);
+        exit(0);
+    }
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    Some(())
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    assert_eq!(gettid(), 0);
+    test_fork();
+    let mut tids = [0usize; THREAD_COUNT];
+    for (id, tid) in tids.iter_mut().enumerate() {
+        let ret = thread_create(worker as usize, id);
+        assert!(ret > 0);
+        *tid = ret as usize;
+    }
+    for (id, tid) in tids.iter().enumerate() {
+        assert_eq!(waittid(*tid), id as isize + 100);
+    }
+    // joined threads cannot be waited again
+    assert_eq!(waittid(tids[0]), -1);
+    assert_eq!(COUNTER.load(Ordering::Relaxed), THREAD_COUNT * ITERATIONS);
+    println!("thread_test passed!");
+    0
+}
@@ -0,0 +1,97 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    OpenFlags, close,
+    errno::ENOTEMPTY,
+    fs::{FileType, Stat},
+    fstat, mkdir, open, read, rmdir, unlink, write,
+};
+
+const DIR: &str = "/tmp/tmpfs_test\0";
+const FILE: &str = "/tmp/tmpfs_test/file\0";
+const BIG_FILE: &str = "/tmp/big\0";
+
+fn stat(path: &str) -> Stat {
+    let fd = open(path, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut stat = Stat::default();
+    assert_eq!(fstat(fd as usize, &mut stat), 0);
+    close(fd as usize);
+    stat
+}
+
+/// Write to `fd` until the filesystem is full, return the number of bytes written.
+fn fill(fd: usize) -> usize {
+    let buffer = [0x5au8; 2048];
+    let mut total = 0;
+    loop {
+        let len = write(fd, &buffer);
+        assert!(len >= 0);
+        total += len as usize;
+        if (len as usize) < buffer.len() {
+            return total;
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    // /tmp is a different filesystem from the root
+    assert_ne!(stat("/tmp\0").dev, stat("/\0").dev);
+
+    assert_eq!(mkdir(DIR), 0);
+    let fd = open(FILE, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let test_str = "Hello, tmpfs!";
+    write(fd as usize, test_str.as_bytes());
+    close(fd as usize);
+    let file_stat = stat(FILE);
+    assert_eq!(file_stat.file_type(), Some(FileType::Regular));
+    assert_eq!(file_stat.size, test_str.len() as u64);
+
+    // a removed file is readable until closed
+    let fd = open(FILE, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(rmdir(DIR), -ENOTEMPTY);
+    assert_eq!(unlink(FILE), 0);
+    assert!(open(FILE, OpenFlags::RDONLY) < 0);
+    assert_eq!(rmdir(DIR), 0);
+    let mut buffer = [0u8; 32];
+    let len = read(fd, &mut buffer) as usize;
+    close(fd);
+    assert_eq!(test_str, core::str::from_utf8(&buffer[..len]).unwrap());
+
+    // the size is limited, and the space comes back when the file is removed
+    let fd = open(BIG_FILE, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let size = fill(fd as usize);
+    close(fd as usize);
+    assert!(size > 0);
+    assert_eq!(unlink(BIG_FILE), 0);
+    let fd = open(BIG_FILE, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(fill(fd as usize), size);
+    close(fd as usize);
+    assert_eq!(unlink(BIG_FILE), 0);
+
+    // truncated when opened with TRUNC
+    let fd = open(BIG_FILE, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    write(fd as usize, test_str.as_bytes());
+    close(fd as usize);
+    let fd = open(BIG_FILE, OpenFlags::WRONLY | OpenFlags::TRUNC);
+    assert!(fd > 0);
+    close(fd as usize);
+    assert_eq!(stat(BIG_FILE).size, 0);
+    assert_eq!(unlink(BIG_FILE), 0);
+
+    println!("tmpfs_test passed!");
+    0
+}
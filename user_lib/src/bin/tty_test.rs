@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    errno::{ENOTTY, EPERM},
+    get_winsize, ioctl, pipe, tcgetattr, tcsetattr, tcsetpgrp,
+    termios::{LocalModes, TCGETS, Termios, WinSize},
+};
+
+const STDIN: usize = 0;
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    let mut cooked = Termios::default();
+    assert_eq!(tcgetattr(STDIN, &mut cooked), 0);
+    assert!(cooked.lflag.contains(LocalModes::ICANON | LocalModes::ECHO));
+
+    // switch to raw mode and back
+    let mut raw = cooked;
+    raw.make_raw();
+    assert_eq!(tcsetattr(STDIN, &raw), 0);
+    let mut termios = Termios::default();
+    assert_eq!(tcgetattr(STDIN, &mut termios), 0);
+    assert_eq!(termios, raw);
+    assert_eq!(tcsetattr(STDIN, &cooked), 0);
+    assert_eq!(tcgetattr(STDIN, &mut termios), 0);
+    assert_eq!(termios, cooked);
+
+    let mut winsize = WinSize::default();
+    assert_eq!(get_winsize(STDIN, &mut winsize), 0);
+    assert!(winsize.row > 0 && winsize.col > 0);
+
+    // only the caller and its children can be the foreground
+    assert_eq!(tcsetpgrp(STDIN, usize::MAX >> 1), -EPERM);
+
+    // pipes are not terminals
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert_eq!(
+        ioctl(fds[0], TCGETS, &mut termios as *mut _ as usize),
+        -ENOTTY
+    );
+
+    println!("tty_test passed!");
+    0
+}
@@ -0,0 +1,127 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    accept, bind, close, connect,
+    errno::{EADDRINUSE, EAGAIN, ECONNREFUSED, ENOENT, ENOTSOCK, EPIPE},
+    exit, fork, listen, pipe, poll,
+    poll::{PollEvents, PollFd},
+    poll_events, read, recv, recvfrom, send, sendto, socket,
+    socket::{AF_UNIX, MsgFlags, SOCK_DGRAM, SOCK_NONBLOCK, SOCK_STREAM, SockAddrUn},
+    socketpair, unlink, wait_exit_code, write,
+};
+
+const PATH: &str = "/unix_socket_test\0";
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    let mut buffer = [0u8; 64];
+
+    // a pair of streams, each end reads and writes
+    let mut sv = [0usize; 2];
+    assert_eq!(socketpair(AF_UNIX, SOCK_STREAM, &mut sv), 0);
+    assert_eq!(poll_events(sv[0], PollEvents::IN), PollEvents::empty());
+    assert_eq!(write(sv[0], b"ping"), 4);
+    assert_eq!(poll_events(sv[1], PollEvents::IN), PollEvents::IN);
+    assert_eq!(read(sv[1], &mut buffer), 4);
+    assert_eq!(&buffer[..4], b"ping");
+    assert_eq!(send(sv[1], b"pong", MsgFlags::empty()), 4);
+    assert_eq!(
+        recv(sv[0], &mut buffer, MsgFlags::DONTWAIT),
+        4,
+        "a stream receives the bytes available"
+    );
+    assert_eq!(&buffer[..4], b"pong");
+    assert_eq!(recv(sv[0], &mut buffer, MsgFlags::DONTWAIT), -EAGAIN);
+    // EOF once the peer is closed, and EPIPE writing to it
+    close(sv[1]);
+    assert!(poll_events(sv[0], PollEvents::IN).contains(PollEvents::HUP));
+    assert_eq!(read(sv[0], &mut buffer), 0);
+    assert_eq!(send(sv[0], b"lost", MsgFlags::NOSIGNAL), -EPIPE);
+    close(sv[0]);
+
+    // a listener bound to a socket file, with a client in another process
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert_eq!(bind(fds[0], &SockAddrUn::new("/pipe")), -ENOTSOCK);
+    close(fds[0]);
+    close(fds[1]);
+    let addr = SockAddrUn::new(PATH.trim_end_matches('\0'));
+    let listener = socket(AF_UNIX, SOCK_STREAM | SOCK_NONBLOCK);
+    assert!(listener >= 0);
+    let listener = listener as usize;
+    assert_eq!(connect(listener, &addr), -ENOENT);
+    assert_eq!(bind(listener, &addr), 0);
+    assert_eq!(listen(listener, 4), 0);
+    assert_eq!(accept(listener), -EAGAIN);
+    let other = socket(AF_UNIX, SOCK_STREAM) as usize;
+    assert_eq!(bind(other, &addr), -EADDRINUSE);
+    close(other);
+    let pid = fork();
+    if pid == 0 {
+        close(listener);
+        let client = socket(AF_UNIX, SOCK_STREAM) as usize;
+        assert_eq!(connect(client, &addr), 0);
+        assert_eq!(write(client, b"hello"), 5);
+        let mut buffer = [0u8; 16];
+        assert_eq!(read(client, &mut buffer), 5);
+        assert_eq!(&buffer[..5], b"HELLO");
+        exit(0);
+    }
+    let mut fds = [PollFd {
+        fd: listener as i32,
+        events: PollEvents::IN,
+        revents: PollEvents::empty(),
+    }];
+    assert_eq!(poll(&mut fds, -1), 1);
+    let server = accept(listener);
+    assert!(server >= 0);
+    let server = server as usize;
+    let len = read(server, &mut buffer);
+    assert_eq!(len, 5);
+    buffer[..5].make_ascii_uppercase();
+    assert_eq!(write(server, &buffer[..5]), 5);
+    assert_eq!(wait_exit_code(pid as usize), 0);
+    assert_eq!(read(server, &mut buffer), 0);
+    close(server);
+    close(listener);
+    assert_eq!(unlink(PATH), 0);
+
+    // datagrams in the abstract namespace, received with the name of their sender
+    let receiver = socket(AF_UNIX, SOCK_DGRAM) as usize;
+    let sender = socket(AF_UNIX, SOCK_DGRAM) as usize;
+    let receiver_addr = SockAddrUn::new_abstract("unix_socket_test.receiver");
+    assert_eq!(bind(receiver, &receiver_addr), 0);
+    assert_eq!(
+        bind(sender, &SockAddrUn::new_abstract("unix_socket_test.sender")),
+        0
+    );
+    assert_eq!(
+        sendto(sender, b"first", MsgFlags::empty(), &receiver_addr),
+        5
+    );
+    assert_eq!(connect(sender, &receiver_addr), 0);
+    assert_eq!(send(sender, b"second", MsgFlags::empty()), 6);
+    let mut from = SockAddrUn::default();
+    assert_eq!(
+        recvfrom(receiver, &mut buffer[..3], MsgFlags::empty(), &mut from),
+        3,
+        "a datagram is truncated to the buffer"
+    );
+    assert_eq!(&buffer[..3], b"fir");
+    assert_eq!(&from.path[..24], b"\0unix_socket_test.sender");
+    assert_eq!(recv(receiver, &mut buffer, MsgFlags::empty()), 6);
+    assert_eq!(&buffer[..6], b"second");
+    assert_eq!(recv(receiver, &mut buffer, MsgFlags::DONTWAIT), -EAGAIN);
+    close(receiver);
+    assert_eq!(send(sender, b"lost", MsgFlags::empty()), -ECONNREFUSED);
+    close(sender);
+
+    println!("unix_socket_test passed!");
+    0
+}
@@ -0,0 +1,26 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::read_file;
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    let Some(seconds) = read_file("/proc/uptime\0")
+        .and_then(|uptime| uptime.split('.').next()?.trim().parse::<usize>().ok())
+    else {
+        println!("uptime: cannot read /proc/uptime");
+        return -1;
+    };
+    println!(
+        "up {}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    0
+}
@@ -0,0 +1,90 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::{string::String, vec::Vec};
+use user_lib::{
+    OpenFlags, close,
+    errno::{EBUSY, EEXIST, EISDIR, ENOENT, ENOTDIR, ENOTEMPTY},
+    fs::{DIRENT64_NAME_OFFSET, Dirent64, FileType, Stat},
+    fstat, getdents, mkdir, mount, open, rmdir, umount, unlink, write,
+};
+
+const DIR: &str = "/vfs_test\0";
+const FILE: &str = "/vfs_test/file\0";
+
+/// Return the names and types of the entries of directory `fd`.
+fn list(fd: usize) -> Vec<(String, FileType)> {
+    let mut entries = Vec::new();
+    let mut buf = [0u8; 64];
+    loop {
+        let len = getdents(fd, &mut buf);
+        assert!(len >= 0);
+        if len == 0 {
+            return entries;
+        }
+        let mut offset = 0;
+        while offset < len as usize {
+            let dirent = unsafe { (buf.as_ptr().add(offset) as *const Dirent64).read_unaligned() };
+            let name = &buf[offset + DIRENT64_NAME_OFFSET..offset + dirent.reclen as usize];
+            let name = name.split(|&b| b == 0).next().unwrap();
+            entries.push((
+                String::from_utf8(name.into()).unwrap(),
+                FileType::from_dtype(dirent.dtype).unwrap(),
+            ));
+            offset += dirent.reclen as usize;
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    assert_eq!(mkdir(DIR), 0);
+    assert_eq!(mkdir(DIR), -EEXIST);
+    assert_eq!(mkdir("/vfs_test/a/b\0"), -ENOENT);
+
+    let fd = open(FILE, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let test_str = "Hello, VFS!";
+    write(fd, test_str.as_bytes());
+    let mut stat = Stat::default();
+    assert_eq!(fstat(fd, &mut stat), 0);
+    assert_eq!(stat.file_type(), Some(FileType::Regular));
+    assert_eq!(stat.size, test_str.len() as u64);
+    close(fd);
+
+    let fd = open(DIR, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(fstat(fd, &mut stat), 0);
+    assert_eq!(stat.file_type(), Some(FileType::Directory));
+    let entries = list(fd);
+    close(fd);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].0, "file");
+    assert_eq!(entries[0].1, FileType::Regular);
+    assert!(open(DIR, OpenFlags::WRONLY) < 0);
+
+    assert_eq!(rmdir(DIR), -ENOTEMPTY);
+    assert_eq!(unlink(DIR), -EISDIR);
+    assert_eq!(rmdir(FILE), -ENOTDIR);
+    assert_eq!(unlink(FILE), 0);
+    assert_eq!(unlink(FILE), -ENOENT);
+    assert_eq!(rmdir(DIR), 0);
+    assert!(open(DIR, OpenFlags::RDONLY) < 0);
+
+    // a mount point is in use while mounted
+    assert_eq!(mount("/dev/vdb\0", "/mnt\0"), 0);
+    assert_eq!(rmdir("/mnt\0"), -EBUSY);
+    assert_eq!(umount("/mnt\0"), 0);
+
+    println!("vfs_test passed!");
+    0
+}
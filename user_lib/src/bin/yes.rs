@@ -0,0 +1,24 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(user_lib::test_utils::test_runner)]
+
+extern crate alloc;
+
+use alloc::{format, string::String};
+use user_lib::write;
+
+const STDOUT: usize = 1;
+
+/// Print the arguments given, or `y`, on a line forever, until the output is closed.
+#[unsafe(no_mangle)]
+fn main(_argc: usize, argv: &[&str]) -> i32 {
+    let line = if argv.len() > 1 {
+        format!("{}\n", argv[1..].join(" "))
+    } else {
+        String::from("y\n")
+    };
+    // killed by SIGPIPE once the reader is gone
+    while write(STDOUT, line.as_bytes()) > 0 {}
+    -1
+}
@@ -5,18 +5,32 @@
 
 extern crate alloc;
 
-use core::ptr::addr_of_mut;
+use core::{ptr::addr_of_mut, sync::atomic::AtomicU32};
 
-use alloc::vec::Vec;
+use ::common::{
+    fs::{AT_FDCWD, AT_REMOVEDIR, FileType, Stat},
+    futex::{FUTEX_WAIT, FUTEX_WAKE},
+    ipc::ShmIdDs,
+    mqueue::MqAttr,
+    poll::{FdSet, PollEvents, PollFd},
+    sig::SignalFlags,
+    socket::{MsgFlags, SockAddr},
+    termios::{TCGETS, TCSETS, TIOCGWINSZ, TIOCSPGRP, Termios, WinSize},
+    time::{ItimerSpec, TimeSpec},
+};
+use alloc::{string::String, vec::Vec};
 use bitflags::bitflags;
 use buddy_system_allocator::LockedHeap;
 
 #[macro_use]
 pub mod console;
 mod common;
+pub mod sync;
 mod syscall;
 pub mod test_utils;
 
+pub use ::common::{errno, fs, ipc, mqueue, poll, sig, socket, termios, time};
+
 const USER_HEAP_SIZE: usize = 4096 * 4;
 
 static mut HEAP_SPACE: [u8; USER_HEAP_SIZE] = [0; USER_HEAP_SIZE];
@@ -63,6 +77,10 @@ pub fn exit(exit_code: i32) -> isize {
     syscall::sys_exit(exit_code)
 }
 
+pub fn exit_group(exit_code: i32) -> isize {
+    syscall::sys_exit_group(exit_code)
+}
+
 pub fn fork() -> isize {
     syscall::sys_fork()
 }
@@ -99,13 +117,105 @@ pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
     }
 }
 
+/// Wait for the child `pid` to exit and return its exit code.
+pub fn wait_exit_code(pid: usize) -> i32 {
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid as isize);
+    exit_code
+}
+
+/// Create a thread running `entry(arg)`, the thread must end by calling [`exit`].
+pub fn thread_create(entry: usize, arg: usize) -> isize {
+    syscall::sys_thread_create(entry, arg)
+}
+
+pub fn gettid() -> isize {
+    syscall::sys_gettid()
+}
+
+pub fn waittid(tid: usize) -> isize {
+    loop {
+        match syscall::sys_waittid(tid) {
+            -2 => {
+                yield_();
+            }
+            // -1 or the exit code of the thread
+            exit_code => return exit_code,
+        }
+    }
+}
+
+pub fn mutex_create() -> isize {
+    syscall::sys_mutex_create()
+}
+
+pub fn mutex_lock(mutex_id: usize) -> isize {
+    syscall::sys_mutex_lock(mutex_id)
+}
+
+pub fn mutex_unlock(mutex_id: usize) -> isize {
+    syscall::sys_mutex_unlock(mutex_id)
+}
+
+pub fn semaphore_create(res_count: usize) -> isize {
+    syscall::sys_semaphore_create(res_count)
+}
+
+pub fn semaphore_up(sem_id: usize) -> isize {
+    syscall::sys_semaphore_up(sem_id)
+}
+
+pub fn semaphore_down(sem_id: usize) -> isize {
+    syscall::sys_semaphore_down(sem_id)
+}
+
+pub fn condvar_create() -> isize {
+    syscall::sys_condvar_create()
+}
+
+pub fn condvar_signal(condvar_id: usize) -> isize {
+    syscall::sys_condvar_signal(condvar_id)
+}
+
+/// Release the mutex and wait until the condition variable is signaled, the mutex is locked
+/// again before returning.
+pub fn condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+    syscall::sys_condvar_wait(condvar_id, mutex_id)
+}
+
+/// Block while `futex` holds `val` until woken up by [`futex_wake`] or `timeout` has elapsed.
+///
+/// Return 0 if woken up, `-EAGAIN` if `futex` does not hold `val` or `-ETIMEDOUT` on timeout.
+pub fn futex_wait(futex: &AtomicU32, val: u32, timeout: Option<&TimeSpec>) -> isize {
+    let timeout = timeout.map_or(core::ptr::null(), |t| t as *const _);
+    syscall::sys_futex(futex.as_ptr(), FUTEX_WAIT, val, timeout)
+}
+
+/// Wake up at most `count` threads waiting on `futex`, return the number of woken threads.
+pub fn futex_wake(futex: &AtomicU32, count: u32) -> isize {
+    syscall::sys_futex(futex.as_ptr(), FUTEX_WAKE, count, core::ptr::null())
+}
+
+/// Enable or disable the deadlock detection, when enabled a [`mutex_lock`] or
+/// [`semaphore_down`] which would deadlock returns `-EDEADLK` instead of blocking.
+pub fn enable_deadlock_detect(enabled: bool) -> isize {
+    syscall::sys_enable_deadlock_detect(enabled as usize)
+}
+
 bitflags! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
     pub struct OpenFlags: u32 {
         const RDONLY = 0;
         const WRONLY = 1 << 0;
         const RDWR = 1 << 1;
+        /// With `CREATE`, fail if the file exists.
+        const EXCL = 1 << 7;
         const CREATE = 1 << 9;
         const TRUNC = 1 << 10;
+        const NONBLOCK = 1 << 11;
+        /// Not the value of Linux.
+        const APPEND = 1 << 12;
+        const CLOEXEC = 1 << 19;
     }
 }
 
@@ -113,10 +223,355 @@ pub fn open(path: &str, flags: OpenFlags) -> isize {
     syscall::sys_open(path, flags.bits())
 }
 
+/// Read the whole file at `path`, return `None` if it cannot be opened or is not UTF-8.
+pub fn read_file(path: &str) -> Option<String> {
+    let fd = open(path, OpenFlags::RDONLY);
+    if fd < 0 {
+        return None;
+    }
+    let mut data = Vec::new();
+    let mut buffer = [0u8; 256];
+    loop {
+        let len = read(fd as usize, &mut buffer);
+        if len <= 0 {
+            break;
+        }
+        data.extend_from_slice(&buffer[..len as usize]);
+    }
+    close(fd as usize);
+    String::from_utf8(data).ok()
+}
+
+pub fn fstat(fd: usize, stat: &mut Stat) -> isize {
+    syscall::sys_fstat(fd, stat)
+}
+
+/// Read the entries of directory `fd` into `buf` as [`fs::Dirent64`] records, return the
+/// number of bytes read, 0 at the end.
+pub fn getdents(fd: usize, buf: &mut [u8]) -> isize {
+    syscall::sys_getdents64(fd, buf)
+}
+
+pub fn mkdir(path: &str) -> isize {
+    syscall::sys_mkdirat(AT_FDCWD, path, 0o755)
+}
+
+/// Create the FIFO `path`, opened like a file and read and written like a pipe.
+pub fn mkfifo(path: &str) -> isize {
+    syscall::sys_mknodat(AT_FDCWD, path, FileType::Fifo.mode() | 0o644, 0)
+}
+
+pub fn unlink(path: &str) -> isize {
+    syscall::sys_unlinkat(AT_FDCWD, path, 0)
+}
+
+pub fn rmdir(path: &str) -> isize {
+    syscall::sys_unlinkat(AT_FDCWD, path, AT_REMOVEDIR)
+}
+
+/// Mount the easy_fs on block device `source` at directory `target`.
+pub fn mount(source: &str, target: &str) -> isize {
+    syscall::sys_mount(source, target, "easy_fs\0")
+}
+
+pub fn umount(target: &str) -> isize {
+    syscall::sys_umount2(target, 0)
+}
+
 pub fn close(fd: usize) -> isize {
     syscall::sys_close(fd)
 }
 
 pub fn pipe(pipe: &mut [usize]) -> isize {
-    syscall::sys_pipe(pipe)
+    pipe2(pipe, OpenFlags::empty())
+}
+
+/// Like [`pipe`], `flags` may be [`OpenFlags::CLOEXEC`] and [`OpenFlags::NONBLOCK`].
+pub fn pipe2(pipe: &mut [usize], flags: OpenFlags) -> isize {
+    syscall::sys_pipe2(pipe, flags.bits())
+}
+
+/// Duplicate `fd` to the lowest free fd.
+pub fn dup(fd: usize) -> isize {
+    syscall::sys_dup(fd)
+}
+
+/// Duplicate `old_fd` to `new_fd`, closing the file open there first.
+pub fn dup2(old_fd: usize, new_fd: usize) -> isize {
+    if old_fd == new_fd {
+        // dup3 fails on the same fds, check that `old_fd` is open
+        return if fcntl(old_fd, fs::F_GETFD, 0) < 0 {
+            -errno::EBADF
+        } else {
+            new_fd as isize
+        };
+    }
+    dup3(old_fd, new_fd, OpenFlags::empty())
+}
+
+/// Like [`dup2`], `flags` may be [`OpenFlags::CLOEXEC`].
+pub fn dup3(old_fd: usize, new_fd: usize, flags: OpenFlags) -> isize {
+    syscall::sys_dup3(old_fd, new_fd, flags.bits())
+}
+
+/// Perform `cmd`, one of the `F_*` commands of [`fs`], on `fd`.
+pub fn fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    syscall::sys_fcntl(fd, cmd, arg)
+}
+
+/// Wait until one of `fds` is ready for its events, or until `timeout` has elapsed. Return the
+/// number of fds with events returned in `revents`, 0 on timeout.
+pub fn ppoll(fds: &mut [PollFd], timeout: Option<&TimeSpec>) -> isize {
+    let timeout = timeout.map_or(core::ptr::null(), |t| t as *const _);
+    syscall::sys_ppoll(fds, timeout)
+}
+
+/// Like [`ppoll`], waiting forever if `timeout_ms` is negative.
+pub fn poll(fds: &mut [PollFd], timeout_ms: isize) -> isize {
+    let timeout = usize::try_from(timeout_ms).ok().map(TimeSpec::from_ms);
+    ppoll(fds, timeout.as_ref())
+}
+
+/// Return the events among `events` which `fd` has now, without waiting.
+pub fn poll_events(fd: usize, events: PollEvents) -> PollEvents {
+    let mut fds = [PollFd {
+        fd: fd as i32,
+        events,
+        revents: PollEvents::empty(),
+    }];
+    assert!(poll(&mut fds, 0) >= 0);
+    fds[0].revents
+}
+
+/// Wait until one of the fds below `nfds` in `readfds` is readable or one in `writefds` is
+/// writable, or until `timeout` has elapsed. The sets are replaced by the ready fds, whose
+/// number is returned.
+pub fn select(
+    nfds: usize,
+    readfds: Option<&mut FdSet>,
+    writefds: Option<&mut FdSet>,
+    exceptfds: Option<&mut FdSet>,
+    timeout: Option<&TimeSpec>,
+) -> isize {
+    let set = |set: Option<&mut FdSet>| set.map_or(core::ptr::null_mut(), |set| set as *mut _);
+    let timeout = timeout.map_or(core::ptr::null(), |t| t as *const _);
+    syscall::sys_pselect6(nfds, set(readfds), set(writefds), set(exceptfds), timeout)
+}
+
+/// Create an eventfd whose counter starts at `initval`, read and written as a `u64`. `flags`
+/// may have [`fs::EFD_SEMAPHORE`] and the bits of [`OpenFlags::CLOEXEC`] and
+/// [`OpenFlags::NONBLOCK`].
+pub fn eventfd(initval: u32, flags: u32) -> isize {
+    syscall::sys_eventfd2(initval, flags)
+}
+
+/// Create a disarmed timerfd of `clockid`, [`time::CLOCK_MONOTONIC`] or
+/// [`time::CLOCK_REALTIME`], which both count from boot.
+pub fn timerfd_create(clockid: usize, flags: OpenFlags) -> isize {
+    syscall::sys_timerfd_create(clockid, flags.bits())
+}
+
+/// Arm timerfd `fd` with `new_value`, which is absolute with [`time::TFD_TIMER_ABSTIME`], and
+/// store the previous setting in `old_value`. Reads return the number of expirations as a `u64`.
+pub fn timerfd_settime(
+    fd: usize,
+    flags: u32,
+    new_value: &ItimerSpec,
+    old_value: Option<&mut ItimerSpec>,
+) -> isize {
+    let old_value = old_value.map_or(core::ptr::null_mut(), |old| old as *mut _);
+    syscall::sys_timerfd_settime(fd, flags, new_value, old_value)
+}
+
+pub fn timerfd_gettime(fd: usize, curr_value: &mut ItimerSpec) -> isize {
+    syscall::sys_timerfd_gettime(fd, curr_value)
+}
+
+/// Create a signalfd reading the pending signals of `mask` as [`sig::SignalfdSiginfo`] records
+/// if `fd` is -1, or change the mask of signalfd `fd`. The signals should be blocked with
+/// [`sigprocmask`] so that they do not terminate the process.
+pub fn signalfd(fd: isize, mask: SignalFlags, flags: OpenFlags) -> isize {
+    syscall::sys_signalfd4(fd, &mask.bits(), flags.bits())
+}
+
+/// Change the blocked signals by `how`, one of [`sig::SIG_BLOCK`], [`sig::SIG_UNBLOCK`] and
+/// [`sig::SIG_SETMASK`], with `set` if any, and store the previous ones in `oldset`.
+pub fn sigprocmask(
+    how: usize,
+    set: Option<SignalFlags>,
+    oldset: Option<&mut SignalFlags>,
+) -> isize {
+    let set = set.map(|set| set.bits());
+    let set = set
+        .as_ref()
+        .map_or(core::ptr::null(), |set| set as *const _);
+    let mut old = 0;
+    let ret = syscall::sys_rt_sigprocmask(how, set, &mut old);
+    if let Some(oldset) = oldset {
+        *oldset = SignalFlags::from_bits_truncate(old);
+    }
+    ret
+}
+
+/// Open the message queue `name`, which starts with a slash, created with `attr` or the default
+/// capacity if none with [`OpenFlags::CREATE`]. Return its fd.
+pub fn mq_open(name: &str, flags: OpenFlags, attr: Option<&MqAttr>) -> isize {
+    let Some(name) = name.strip_prefix('/') else {
+        return -errno::EINVAL;
+    };
+    let attr = attr.map_or(core::ptr::null(), |attr| attr as *const _);
+    syscall::sys_mq_open(name, flags.bits(), 0o600, attr)
+}
+
+pub fn mq_unlink(name: &str) -> isize {
+    let Some(name) = name.strip_prefix('/') else {
+        return -errno::EINVAL;
+    };
+    syscall::sys_mq_unlink(name)
+}
+
+/// Send `msg` with `priority`, messages of higher priorities are received first.
+pub fn mq_send(fd: usize, msg: &[u8], priority: u32) -> isize {
+    syscall::sys_mq_timedsend(fd, msg, priority, core::ptr::null())
+}
+
+/// Receive the oldest message of the highest priority into `buf`, which must hold the largest
+/// message of the queue. Return its length and set `priority` to its priority.
+pub fn mq_receive(fd: usize, buf: &mut [u8], priority: &mut u32) -> isize {
+    syscall::sys_mq_timedreceive(fd, buf, priority, core::ptr::null())
+}
+
+pub fn mq_getattr(fd: usize, attr: &mut MqAttr) -> isize {
+    syscall::sys_mq_getsetattr(fd, core::ptr::null(), attr)
+}
+
+/// Set the flags of the queue from `attr`, only [`OpenFlags::NONBLOCK`] can be set.
+pub fn mq_setattr(fd: usize, attr: &MqAttr) -> isize {
+    syscall::sys_mq_getsetattr(fd, attr, core::ptr::null_mut())
+}
+
+/// Return the id of the shared memory segment of `key`, created with `size` bytes with
+/// [`ipc::IPC_CREAT`].
+pub fn shmget(key: i32, size: usize, flags: u32) -> isize {
+    syscall::sys_shmget(key, size, flags)
+}
+
+/// Attach segment `id` at `addr`, or wherever the kernel chooses if 0, and return the address.
+pub fn shmat(id: usize, addr: usize, flags: u32) -> isize {
+    syscall::sys_shmat(id, addr, flags)
+}
+
+/// Detach the segment attached at `addr`.
+pub fn shmdt(addr: usize) -> isize {
+    syscall::sys_shmdt(addr)
+}
+
+/// Run `cmd` of [`ipc::IPC_STAT`], [`ipc::IPC_SET`] and [`ipc::IPC_RMID`] on segment `id`.
+pub fn shmctl(id: usize, cmd: usize, buf: &mut ShmIdDs) -> isize {
+    syscall::sys_shmctl(id, cmd, buf)
+}
+
+/// Create a socket of `domain` and `type_`, which may be ORed with
+/// [`socket::SOCK_NONBLOCK`] and [`socket::SOCK_CLOEXEC`].
+pub fn socket(domain: u16, type_: u32) -> isize {
+    syscall::sys_socket(domain, type_, 0)
+}
+
+/// Create two sockets connected to each other, like [`pipe`] but both ends read and write.
+pub fn socketpair(domain: u16, type_: u32, sv: &mut [usize]) -> isize {
+    syscall::sys_socketpair(domain, type_, 0, sv)
+}
+
+pub fn bind(fd: usize, addr: &impl SockAddr) -> isize {
+    syscall::sys_bind(fd, core::ptr::from_ref(addr).cast(), addr.addr_len())
+}
+
+/// Accept connections to `fd`, at most `backlog` of them waiting to be accepted.
+pub fn listen(fd: usize, backlog: usize) -> isize {
+    syscall::sys_listen(fd, backlog)
+}
+
+/// Return a new fd connected to the next peer of the listening socket `fd`.
+pub fn accept(fd: usize) -> isize {
+    accept4(fd, core::ptr::null_mut(), core::ptr::null_mut(), 0)
+}
+
+/// Like [`accept`], writing the address of the peer to `addr`, whose size is at `addr_len`
+/// and which is replaced by the size of the address. `flags` may be
+/// [`socket::SOCK_NONBLOCK`] and [`socket::SOCK_CLOEXEC`].
+pub fn accept4(fd: usize, addr: *mut u8, addr_len: *mut u32, flags: u32) -> isize {
+    syscall::sys_accept4(fd, addr, addr_len, flags)
+}
+
+/// Connect a stream to the listener at `addr`, or set the default destination of a datagram
+/// socket.
+pub fn connect(fd: usize, addr: &impl SockAddr) -> isize {
+    syscall::sys_connect(fd, core::ptr::from_ref(addr).cast(), addr.addr_len())
+}
+
+/// Send `buf` to the peer of `fd`, return the number of bytes sent.
+pub fn send(fd: usize, buf: &[u8], flags: MsgFlags) -> isize {
+    syscall::sys_sendto(fd, buf, flags.bits(), core::ptr::null(), 0)
+}
+
+/// Receive to `buf` from the peer of `fd`, return the number of bytes received, 0 at EOF.
+pub fn recv(fd: usize, buf: &mut [u8], flags: MsgFlags) -> isize {
+    syscall::sys_recvfrom(
+        fd,
+        buf,
+        flags.bits(),
+        core::ptr::null_mut(),
+        core::ptr::null_mut(),
+    )
+}
+
+/// Send the datagram `buf` to `addr`.
+pub fn sendto(fd: usize, buf: &[u8], flags: MsgFlags, addr: &impl SockAddr) -> isize {
+    syscall::sys_sendto(
+        fd,
+        buf,
+        flags.bits(),
+        core::ptr::from_ref(addr).cast(),
+        addr.addr_len(),
+    )
+}
+
+/// Like [`recv`], writing the address of the sender to `addr`.
+pub fn recvfrom<A: SockAddr + Default>(
+    fd: usize,
+    buf: &mut [u8],
+    flags: MsgFlags,
+    addr: &mut A,
+) -> isize {
+    let mut addr_len = size_of::<A>() as u32;
+    *addr = A::default();
+    syscall::sys_recvfrom(
+        fd,
+        buf,
+        flags.bits(),
+        core::ptr::from_mut(addr).cast(),
+        &mut addr_len,
+    )
+}
+
+pub fn ioctl(fd: usize, request: usize, arg: usize) -> isize {
+    syscall::sys_ioctl(fd, request, arg)
+}
+
+pub fn tcgetattr(fd: usize, termios: &mut Termios) -> isize {
+    ioctl(fd, TCGETS, termios as *mut _ as usize)
+}
+
+pub fn tcsetattr(fd: usize, termios: &Termios) -> isize {
+    ioctl(fd, TCSETS, termios as *const _ as usize)
+}
+
+/// Make process `pid`, the caller or one of its children, receive the signals of the terminal.
+pub fn tcsetpgrp(fd: usize, pid: usize) -> isize {
+    let pid = pid as i32;
+    ioctl(fd, TIOCSPGRP, &pid as *const _ as usize)
+}
+
+pub fn get_winsize(fd: usize, winsize: &mut WinSize) -> isize {
+    ioctl(fd, TIOCGWINSZ, winsize as *mut _ as usize)
 }
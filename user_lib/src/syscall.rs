@@ -1,10 +1,18 @@
-use common::syscall_id::*;
+use common::{
+    fs::Stat,
+    ipc::ShmIdDs,
+    mqueue::MqAttr,
+    poll::{FdSet, PollFd},
+    sig::SignalFlags,
+    syscall_id::*,
+    time::{ItimerSpec, TimeSpec},
+};
 
 macro_rules! syscall {
     ($id:expr $(, $arg:expr)* ) => {{
-        let mut args = [0usize; 3];
+        let mut args = [0usize; 6];
         let _arg_slice = [$($arg as usize),*];
-        for i in 0..3.min(_arg_slice.len()) {
+        for i in 0..6.min(_arg_slice.len()) {
             args[i] = _arg_slice[i];
         }
         let mut ret: isize;
@@ -14,6 +22,9 @@ macro_rules! syscall {
                 inlateout("x10") args[0] => ret,
                 in("x11") args[1],
                 in("x12") args[2],
+                in("x13") args[3],
+                in("x14") args[4],
+                in("x15") args[5],
                 in("x17") $id,
             );
         }
@@ -21,10 +32,87 @@ macro_rules! syscall {
     }};
 }
 
+pub fn sys_dup(fd: usize) -> isize {
+    syscall!(SYSCALL_DUP, fd)
+}
+
+pub fn sys_dup3(old_fd: usize, new_fd: usize, flags: u32) -> isize {
+    syscall!(SYSCALL_DUP3, old_fd, new_fd, flags)
+}
+
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    syscall!(SYSCALL_FCNTL, fd, cmd, arg)
+}
+
+pub fn sys_ppoll(fds: &mut [PollFd], timeout: *const TimeSpec) -> isize {
+    syscall!(
+        SYSCALL_PPOLL,
+        fds.as_mut_ptr() as usize,
+        fds.len(),
+        timeout as usize,
+        0
+    )
+}
+
+pub fn sys_pselect6(
+    nfds: usize,
+    readfds: *mut FdSet,
+    writefds: *mut FdSet,
+    exceptfds: *mut FdSet,
+    timeout: *const TimeSpec,
+) -> isize {
+    syscall!(
+        SYSCALL_PSELECT6,
+        nfds,
+        readfds as usize,
+        writefds as usize,
+        exceptfds as usize,
+        timeout as usize,
+        0
+    )
+}
+
+pub fn sys_ioctl(fd: usize, request: usize, arg: usize) -> isize {
+    syscall!(SYSCALL_IOCTL, fd, request, arg)
+}
+
+pub fn sys_mknodat(dirfd: isize, path: &str, mode: u32, dev: usize) -> isize {
+    syscall!(SYSCALL_MKNODAT, dirfd, path.as_ptr() as usize, mode, dev)
+}
+
+pub fn sys_mkdirat(dirfd: isize, path: &str, mode: u32) -> isize {
+    syscall!(SYSCALL_MKDIRAT, dirfd, path.as_ptr() as usize, mode)
+}
+
+pub fn sys_unlinkat(dirfd: isize, path: &str, flags: u32) -> isize {
+    syscall!(SYSCALL_UNLINKAT, dirfd, path.as_ptr() as usize, flags)
+}
+
+pub fn sys_umount2(target: &str, flags: u32) -> isize {
+    syscall!(SYSCALL_UMOUNT2, target.as_ptr() as usize, flags)
+}
+
+pub fn sys_mount(source: &str, target: &str, fstype: &str) -> isize {
+    syscall!(
+        SYSCALL_MOUNT,
+        source.as_ptr() as usize,
+        target.as_ptr() as usize,
+        fstype.as_ptr() as usize
+    )
+}
+
 pub fn sys_open(path: &str, flags: u32) -> isize {
     syscall!(SYSCALL_OPEN, path.as_ptr() as usize, flags)
 }
 
+pub fn sys_getdents64(fd: usize, buf: &mut [u8]) -> isize {
+    syscall!(SYSCALL_GETDENTS64, fd, buf.as_mut_ptr() as usize, buf.len())
+}
+
+pub fn sys_fstat(fd: usize, stat: *mut Stat) -> isize {
+    syscall!(SYSCALL_FSTAT, fd, stat as usize)
+}
+
 pub fn sys_close(fd: usize) -> isize {
     syscall!(SYSCALL_CLOSE, fd)
 }
@@ -41,6 +129,10 @@ pub fn sys_exit(exit_code: i32) -> isize {
     syscall!(SYSCALL_EXIT, exit_code as usize)
 }
 
+pub fn sys_exit_group(exit_code: i32) -> isize {
+    syscall!(SYSCALL_EXIT_GROUP, exit_code as usize)
+}
+
 pub fn sys_fork() -> isize {
     syscall!(SYSCALL_FORK)
 }
@@ -57,6 +149,240 @@ pub fn sys_exec(path: &str, args: &[*const u8]) -> isize {
     syscall!(SYSCALL_EXEC, path.as_ptr() as usize, args.as_ptr() as usize)
 }
 
-pub fn sys_pipe(pipe: &mut [usize]) -> isize {
-    syscall!(SYSCALL_PIPE, pipe.as_mut_ptr() as usize)
+pub fn sys_pipe2(pipe: &mut [usize], flags: u32) -> isize {
+    syscall!(SYSCALL_PIPE2, pipe.as_mut_ptr() as usize, flags)
+}
+
+pub fn sys_eventfd2(initval: u32, flags: u32) -> isize {
+    syscall!(SYSCALL_EVENTFD2, initval, flags)
+}
+
+pub fn sys_timerfd_create(clockid: usize, flags: u32) -> isize {
+    syscall!(SYSCALL_TIMERFD_CREATE, clockid, flags)
+}
+
+pub fn sys_timerfd_settime(
+    fd: usize,
+    flags: u32,
+    new_value: *const ItimerSpec,
+    old_value: *mut ItimerSpec,
+) -> isize {
+    syscall!(
+        SYSCALL_TIMERFD_SETTIME,
+        fd,
+        flags,
+        new_value as usize,
+        old_value as usize
+    )
+}
+
+pub fn sys_timerfd_gettime(fd: usize, curr_value: *mut ItimerSpec) -> isize {
+    syscall!(SYSCALL_TIMERFD_GETTIME, fd, curr_value as usize)
+}
+
+pub fn sys_signalfd4(fd: isize, mask: *const i32, flags: u32) -> isize {
+    syscall!(
+        SYSCALL_SIGNALFD4,
+        fd,
+        mask as usize,
+        size_of::<SignalFlags>(),
+        flags
+    )
+}
+
+pub fn sys_rt_sigprocmask(how: usize, set: *const i32, oldset: *mut i32) -> isize {
+    syscall!(
+        SYSCALL_RT_SIGPROCMASK,
+        how,
+        set as usize,
+        oldset as usize,
+        size_of::<SignalFlags>()
+    )
+}
+
+pub fn sys_mq_open(name: &str, flags: u32, mode: u32, attr: *const MqAttr) -> isize {
+    syscall!(
+        SYSCALL_MQ_OPEN,
+        name.as_ptr() as usize,
+        flags,
+        mode,
+        attr as usize
+    )
+}
+
+pub fn sys_mq_unlink(name: &str) -> isize {
+    syscall!(SYSCALL_MQ_UNLINK, name.as_ptr() as usize)
+}
+
+pub fn sys_mq_timedsend(
+    fd: usize,
+    msg: &[u8],
+    priority: u32,
+    abs_timeout: *const TimeSpec,
+) -> isize {
+    syscall!(
+        SYSCALL_MQ_TIMEDSEND,
+        fd,
+        msg.as_ptr() as usize,
+        msg.len(),
+        priority,
+        abs_timeout as usize
+    )
+}
+
+pub fn sys_mq_timedreceive(
+    fd: usize,
+    buf: &mut [u8],
+    priority: *mut u32,
+    abs_timeout: *const TimeSpec,
+) -> isize {
+    syscall!(
+        SYSCALL_MQ_TIMEDRECEIVE,
+        fd,
+        buf.as_mut_ptr() as usize,
+        buf.len(),
+        priority as usize,
+        abs_timeout as usize
+    )
+}
+
+pub fn sys_mq_getsetattr(fd: usize, new_attr: *const MqAttr, old_attr: *mut MqAttr) -> isize {
+    syscall!(
+        SYSCALL_MQ_GETSETATTR,
+        fd,
+        new_attr as usize,
+        old_attr as usize
+    )
+}
+
+pub fn sys_shmget(key: i32, size: usize, flags: u32) -> isize {
+    syscall!(SYSCALL_SHMGET, key, size, flags)
+}
+
+pub fn sys_shmctl(id: usize, cmd: usize, buf: *mut ShmIdDs) -> isize {
+    syscall!(SYSCALL_SHMCTL, id, cmd, buf as usize)
+}
+
+pub fn sys_shmat(id: usize, addr: usize, flags: u32) -> isize {
+    syscall!(SYSCALL_SHMAT, id, addr, flags)
+}
+
+pub fn sys_shmdt(addr: usize) -> isize {
+    syscall!(SYSCALL_SHMDT, addr)
+}
+
+pub fn sys_socket(domain: u16, type_: u32, protocol: usize) -> isize {
+    syscall!(SYSCALL_SOCKET, domain, type_, protocol)
+}
+
+pub fn sys_socketpair(domain: u16, type_: u32, protocol: usize, sv: &mut [usize]) -> isize {
+    syscall!(
+        SYSCALL_SOCKETPAIR,
+        domain,
+        type_,
+        protocol,
+        sv.as_mut_ptr() as usize
+    )
+}
+
+pub fn sys_bind(fd: usize, addr: *const u8, addr_len: usize) -> isize {
+    syscall!(SYSCALL_BIND, fd, addr as usize, addr_len)
+}
+
+pub fn sys_listen(fd: usize, backlog: usize) -> isize {
+    syscall!(SYSCALL_LISTEN, fd, backlog)
+}
+
+pub fn sys_accept4(fd: usize, addr: *mut u8, addr_len: *mut u32, flags: u32) -> isize {
+    syscall!(SYSCALL_ACCEPT4, fd, addr as usize, addr_len as usize, flags)
+}
+
+pub fn sys_connect(fd: usize, addr: *const u8, addr_len: usize) -> isize {
+    syscall!(SYSCALL_CONNECT, fd, addr as usize, addr_len)
+}
+
+pub fn sys_sendto(fd: usize, buf: &[u8], flags: u32, addr: *const u8, addr_len: usize) -> isize {
+    syscall!(
+        SYSCALL_SENDTO,
+        fd,
+        buf.as_ptr() as usize,
+        buf.len(),
+        flags,
+        addr as usize,
+        addr_len
+    )
+}
+
+pub fn sys_recvfrom(
+    fd: usize,
+    buf: &mut [u8],
+    flags: u32,
+    addr: *mut u8,
+    addr_len: *mut u32,
+) -> isize {
+    syscall!(
+        SYSCALL_RECVFROM,
+        fd,
+        buf.as_mut_ptr() as usize,
+        buf.len(),
+        flags,
+        addr as usize,
+        addr_len as usize
+    )
+}
+
+pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
+    syscall!(SYSCALL_THREAD_CREATE, entry, arg)
+}
+
+pub fn sys_gettid() -> isize {
+    syscall!(SYSCALL_GETTID)
+}
+
+pub fn sys_waittid(tid: usize) -> isize {
+    syscall!(SYSCALL_WAITTID, tid)
+}
+
+pub fn sys_mutex_create() -> isize {
+    syscall!(SYSCALL_MUTEX_CREATE)
+}
+
+pub fn sys_mutex_lock(mutex_id: usize) -> isize {
+    syscall!(SYSCALL_MUTEX_LOCK, mutex_id)
+}
+
+pub fn sys_mutex_unlock(mutex_id: usize) -> isize {
+    syscall!(SYSCALL_MUTEX_UNLOCK, mutex_id)
+}
+
+pub fn sys_semaphore_create(res_count: usize) -> isize {
+    syscall!(SYSCALL_SEMAPHORE_CREATE, res_count)
+}
+
+pub fn sys_semaphore_up(sem_id: usize) -> isize {
+    syscall!(SYSCALL_SEMAPHORE_UP, sem_id)
+}
+
+pub fn sys_semaphore_down(sem_id: usize) -> isize {
+    syscall!(SYSCALL_SEMAPHORE_DOWN, sem_id)
+}
+
+pub fn sys_condvar_create() -> isize {
+    syscall!(SYSCALL_CONDVAR_CREATE)
+}
+
+pub fn sys_condvar_signal(condvar_id: usize) -> isize {
+    syscall!(SYSCALL_CONDVAR_SIGNAL, condvar_id)
+}
+
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+    syscall!(SYSCALL_CONDVAR_WAIT, condvar_id, mutex_id)
+}
+
+pub fn sys_enable_deadlock_detect(enabled: usize) -> isize {
+    syscall!(SYSCALL_ENABLE_DEADLOCK_DETECT, enabled)
+}
+
+pub fn sys_futex(uaddr: *const u32, op: usize, val: u32, timeout: *const TimeSpec) -> isize {
+    syscall!(SYSCALL_FUTEX, uaddr as usize, op, val, timeout as usize)
 }